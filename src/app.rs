@@ -1,17 +1,22 @@
+use crate::data::action::Action;
 use crate::data::edit_state::EditState;
 use crate::data::mode::Mode;
 use crate::data::settings::Settings;
-use crate::data::state::State;
+use crate::data::state::{State, StateRepairs};
 use crate::instance::IpcListener;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::ui::tileset_textures::TextureSource;
 use crate::ui::tileset_textures::TilesetTextures;
 use crate::undo::{RevisionIndex, Undo};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::watcher::FileWatcher;
 use camino::Utf8PathBuf;
 use egui_notify::Toasts;
 use std::collections::VecDeque;
 
 mod actions;
 mod eframe_app;
-mod export_workspace;
+pub mod export_workspace;
 pub mod files;
 mod init;
 mod ipc;
@@ -26,6 +31,8 @@ mod stamp;
 mod tiled;
 mod tilesets;
 mod undoredo;
+#[cfg(not(target_arch = "wasm32"))]
+mod watch;
 
 pub const UNIQUE_ID: &str = "com.mountainlizard.mountain-tiles";
 pub const USE_STORAGE: bool = true;
@@ -66,6 +73,10 @@ pub struct App {
     #[serde(skip)]
     pub saved_revision: Option<RevisionIndex>,
 
+    /// The most recent repeatable [`Action`], see [`App::repeat_last_action`]
+    #[serde(skip)]
+    last_action: Option<Action>,
+
     /// Provide textures to draw tilesets - this is updated with the base dir
     /// where data is saved, to allow resolving relative image paths
     #[serde(skip)]
@@ -75,6 +86,19 @@ pub struct App {
     #[serde(skip)]
     pub ipc_listener: Option<IpcListener>,
 
+    /// Watches tileset image files while
+    /// [`Settings::watch_tileset_images`] is enabled
+    #[cfg(not(target_arch = "wasm32"))]
+    #[serde(skip)]
+    pub tileset_watcher: Option<FileWatcher>,
+
+    /// The texture source and tileset image paths, as stored in the tilesets, that
+    /// [`App::tileset_watcher`] is watching, so the watched paths are only resolved
+    /// and updated when these change
+    #[cfg(not(target_arch = "wasm32"))]
+    #[serde(skip)]
+    pub tileset_watcher_paths: Option<(TextureSource, Vec<Utf8PathBuf>)>,
+
     /// Display notifications
     #[serde(skip)]
     pub toasts: Toasts,
@@ -85,20 +109,61 @@ impl App {
         self.textures.update_base_dir_from_file_path(path);
     }
 
+    /// Repair the state, see [`State::validate_and_repair`], and reset any map
+    /// scene rects that can't be displayed. Returns a summary of the repairs made.
+    pub fn validate_and_repair(&mut self) -> StateRepairs {
+        let mut repairs = self.state.validate_and_repair();
+        repairs.scene_rects = self.edit.repair_scene_rects();
+        repairs
+    }
+
     fn apply_invariants(&mut self) {
         self.apply_default_tileset_selection();
         self.apply_default_tileset_tile_selection_by_id();
         self.apply_default_map_selection();
         self.apply_default_map_edit_state_by_id();
+        self.apply_default_map_background();
     }
 
     pub fn select_mode(&mut self) {
-        self.edit.mode = Mode::Select;
+        self.set_mode(Mode::Select);
     }
     pub fn erase_mode(&mut self) {
-        self.edit.mode = Mode::Erase;
+        self.set_mode(Mode::Erase);
     }
     pub fn draw_mode(&mut self) {
-        self.edit.mode = Mode::Draw;
+        self.set_mode(Mode::Draw);
+    }
+    pub fn line_mode(&mut self) {
+        self.set_mode(Mode::Line);
+    }
+    pub fn ellipse_mode(&mut self) {
+        self.set_mode(Mode::Ellipse);
+    }
+    fn set_mode(&mut self, mode: Mode) {
+        if self.edit.mode != mode {
+            self.reset_transform_on_change();
+            self.edit.mode = mode;
+        }
+    }
+    /// Repeat the most recent repeatable action on the selected map, at the
+    /// hovered position for actions that apply at a position
+    pub fn repeat_last_action(&mut self) {
+        let pos = self
+            .selected_map_edit_state()
+            .and_then(|edit| edit.map_hovered)
+            .map(|pos| pos.into());
+        if let Some(action) = self
+            .last_action
+            .as_ref()
+            .zip(self.edit.selected_map_id)
+            .and_then(|(action, map_id)| action.repeated(map_id, pos))
+        {
+            self.act(action);
+        }
+    }
+
+    pub fn cycle_mode(&mut self) {
+        self.set_mode(self.edit.mode.next());
     }
 }
@@ -8,6 +8,10 @@ use crate::{
 
 impl App {
     pub fn act(&mut self, action: Action) {
+        if action.is_repeatable() {
+            self.last_action = Some(action.clone());
+        }
+
         let result = match action {
             Action::Draw {
                 map_id,
@@ -17,6 +21,18 @@ impl App {
                 let change = self.draw(map_id, pos);
                 ActionResult::new(change, complete)
             }
+            Action::DrawLine { map_id, start, end } => {
+                let change = self.draw_line(map_id, start, end);
+                ActionResult::new(change, true)
+            }
+            Action::DrawEllipse {
+                map_id,
+                rect,
+                filled,
+            } => {
+                let change = self.draw_ellipse(map_id, rect, filled);
+                ActionResult::new(change, true)
+            }
             Action::Erase {
                 map_id,
                 pos,
@@ -39,8 +55,44 @@ impl App {
                     ActionResult::NONE
                 }
             }
-            Action::UpdateTileset { id, tileset } => {
-                let change = self.update_tileset(id, tileset);
+            Action::StrokeSelection { map_id, tile } => {
+                if let Some(mut me) = self.map_editing_mut(map_id) {
+                    let change = me.stroke_selection(tile);
+                    ActionResult::new(change, true)
+                } else {
+                    ActionResult::NONE
+                }
+            }
+            Action::AutotileSelection { map_id } => {
+                if let Some(mut me) = self.map_editing_mut(map_id) {
+                    let change = me.autotile_selection();
+                    ActionResult::new(change, true)
+                } else {
+                    ActionResult::NONE
+                }
+            }
+            Action::ClearTransformsInSelection { map_id } => {
+                if let Some(mut me) = self.map_editing_mut(map_id) {
+                    let change = me.clear_transforms_in_selection();
+                    ActionResult::new(change, true)
+                } else {
+                    ActionResult::NONE
+                }
+            }
+            Action::RecolorSelection { map_id, index } => {
+                if let Some(mut me) = self.map_editing_mut(map_id) {
+                    let change = me.recolor_selection(index);
+                    ActionResult::new(change, true)
+                } else {
+                    ActionResult::NONE
+                }
+            }
+            Action::UpdateTileset {
+                id,
+                tileset,
+                remap_columns,
+            } => {
+                let change = self.update_tileset(id, tileset, remap_columns);
                 ActionResult::new(change, true)
             }
             Action::AddTileset { tileset } => {
@@ -48,27 +100,71 @@ impl App {
                 self.add_tileset(tileset);
                 ActionResult::CHANGE_AND_REVISION
             }
+            Action::AddSplitTilesets { tilesets } => {
+                let change = self.add_split_tilesets(tilesets);
+                ActionResult::new(change, true)
+            }
             Action::DeleteTileset { id } => {
                 let change = self.delete_tileset_by_id(id);
                 ActionResult::new(change, true)
             }
-            Action::ReplacePalette { palette } => {
-                let change = self.replace_palette(palette);
+            Action::MergeTilesets {
+                src,
+                dst,
+                index_map,
+            } => {
+                let change = self.merge_tilesets(src, dst, &index_map);
                 ActionResult::new(change, true)
             }
-            Action::AddMap { map } => {
-                // Always succeeds
-                self.add_map(map);
-                ActionResult::CHANGE_AND_REVISION
+            Action::ReplacePalette {
+                palette,
+                remap_to_nearest,
+            } => {
+                let change = self.replace_palette(palette, remap_to_nearest);
+                ActionResult::new(change, true)
+            }
+            Action::CompactPalette => {
+                let change = self.compact_palette();
+                ActionResult::new(change, true)
             }
+            Action::AddMap { map } => match self.add_map(map) {
+                Ok(()) => ActionResult::CHANGE_AND_REVISION,
+                Err(e) => {
+                    self.show_error_modal(&e.to_string());
+                    ActionResult::NONE
+                }
+            },
             Action::AppendTiledMap {
                 tiled,
                 squash_layers,
                 prefer_relative_path,
+                use_layer_properties,
+                into_map,
             } => {
                 // We'll append to a clone, since if the process fails it may partially modify the state
                 let mut new_state = self.state.clone();
-                match tiled.append_to_state(&mut new_state, squash_layers, prefer_relative_path) {
+                let result = match into_map {
+                    Some(map_id) => tiled
+                        .append_layers_to_map(
+                            &mut new_state,
+                            &self.settings,
+                            self.textures.base_dir().map(|dir| dir.as_path()),
+                            map_id,
+                            squash_layers,
+                            prefer_relative_path,
+                            use_layer_properties,
+                        )
+                        .map(|_| map_id),
+                    None => tiled.append_to_state(
+                        &mut new_state,
+                        &self.settings,
+                        self.textures.base_dir().map(|dir| dir.as_path()),
+                        squash_layers,
+                        prefer_relative_path,
+                        use_layer_properties,
+                    ),
+                };
+                match result {
                     Ok(map_id) => {
                         self.state = new_state;
                         self.edit.selected_map_id = Some(map_id);
@@ -80,14 +176,38 @@ impl App {
                     }
                 }
             }
+            Action::ResizeAllMaps { map_size, anchor } => {
+                match self.resize_all_maps(map_size, anchor) {
+                    Ok(change) => ActionResult::new(change, true),
+                    Err(e) => {
+                        self.show_error_modal(&e.to_string());
+                        ActionResult::NONE
+                    }
+                }
+            }
             Action::UpdateMap {
                 map_id,
                 name,
                 map_size,
                 tile_size,
                 background_color,
-            } => {
-                let change = self.update_map(map_id, name, map_size, tile_size, background_color);
+                default_tile,
+            } => match self.update_map(
+                map_id,
+                name,
+                map_size,
+                tile_size,
+                background_color,
+                default_tile,
+            ) {
+                Ok(change) => ActionResult::new(change, true),
+                Err(e) => {
+                    self.show_error_modal(&e.to_string());
+                    ActionResult::NONE
+                }
+            },
+            Action::SetMapMetadata { map_id, metadata } => {
+                let change = self.set_map_metadata(map_id, metadata);
                 ActionResult::new(change, true)
             }
             Action::DeleteMap { id } => {
@@ -118,15 +238,34 @@ impl App {
                     ActionResult::NONE
                 }
             }
+            Action::SetLayerOpacity {
+                map_id,
+                layer_id,
+                opacity,
+                complete,
+            } => {
+                if let Some(map) = self.state.maps.get_by_id_mut(map_id) {
+                    let change = map.tiles.set_layer_opacity(layer_id, opacity);
+                    ActionResult::new(change, complete)
+                } else {
+                    ActionResult::NONE
+                }
+            }
             Action::EditLayer {
                 map_id,
                 layer_id,
                 name,
                 opacity,
+                tint,
+                kind,
+                sparse,
             } => {
                 if let Some(map) = self.state.maps.get_by_id_mut(map_id) {
                     let mut change = map.tiles.set_layer_name(layer_id, name);
                     change |= map.tiles.set_layer_opacity(layer_id, opacity);
+                    change |= map.tiles.set_layer_tint(layer_id, tint);
+                    change |= map.tiles.set_layer_kind(layer_id, kind);
+                    change |= map.tiles.set_layer_sparse(layer_id, sparse);
                     ActionResult::new(change, true)
                 } else {
                     ActionResult::NONE
@@ -142,6 +281,23 @@ impl App {
                     ActionResult::NONE
                 }
             }
+            Action::ImportLayerCsv {
+                map_id,
+                path,
+                tileset_id,
+            } => {
+                let result = self
+                    .map_editing_mut(map_id)
+                    .map(|mut me| me.import_layer_csv(&path, tileset_id));
+                match result {
+                    Some(Ok(())) => ActionResult::CHANGE_AND_REVISION,
+                    Some(Err(e)) => {
+                        self.show_error_modal(&e.to_string());
+                        ActionResult::NONE
+                    }
+                    None => ActionResult::NONE,
+                }
+            }
             Action::DeleteSelectedLayers { map_id } => {
                 if let Some(mut me) = self.map_editing_mut(map_id) {
                     let change = me.delete_selected_layers();
@@ -159,6 +315,14 @@ impl App {
                     ActionResult::NONE
                 }
             }
+            Action::MergeDown { map_id } => {
+                if let Some(mut me) = self.map_editing_mut(map_id) {
+                    let change = me.merge_down();
+                    ActionResult::new(change, true)
+                } else {
+                    ActionResult::NONE
+                }
+            }
 
             Action::MoveSelectedLayersHigher { map_id } => {
                 if let Some(mut me) = self.map_editing_mut(map_id) {
@@ -176,6 +340,14 @@ impl App {
                     ActionResult::NONE
                 }
             }
+            Action::SwapLayers { map_id, a, b } => {
+                if let Some(mut me) = self.map_editing_mut(map_id) {
+                    let change = me.swap_layers(a, b);
+                    ActionResult::new(change, true)
+                } else {
+                    ActionResult::NONE
+                }
+            }
 
             Action::OnSave { path } => {
                 self.state.on_save(path);
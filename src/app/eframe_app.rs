@@ -8,15 +8,19 @@ use crate::{
         maps::maps_ui,
         menu::menu_ui,
         modal::{
-            data_loss_modal::data_loss_modal_ui, error_modal::error_modal_ui,
-            export_png_modal::export_png_modal_ui, export_tiled_modal::export_tiled_modal_ui,
-            help_modal::help_modal_ui, import_tiled_modal::import_tiled_modal_ui,
-            layer_modal::layer_modal_ui, map_modal::map_modal_ui, palette_modal::palette_modal_ui,
-            settings_modal::settings_modal_ui, tileset_modal::tileset_modal_ui,
+            compare_maps_modal::compare_maps_modal_ui, data_loss_modal::data_loss_modal_ui,
+            error_modal::error_modal_ui, export_png_modal::export_png_modal_ui,
+            export_tiled_modal::export_tiled_modal_ui, help_modal::help_modal_ui,
+            import_tiled_modal::import_tiled_modal_ui, layer_modal::layer_modal_ui,
+            map_modal::map_modal_ui, merge_tilesets_modal::merge_tilesets_modal_ui,
+            missing_tileset_images_modal::missing_tileset_images_modal_ui,
+            palette_modal::palette_modal_ui, project_repaired_modal::project_repaired_modal_ui,
+            resize_all_maps_modal::resize_all_maps_modal_ui, settings_modal::settings_modal_ui,
+            split_tileset_modal::split_tileset_modal_ui, tileset_modal::tileset_modal_ui,
         },
         palette::palette_ui,
         shortcuts::consume_shortcuts,
-        theme::DEFAULT_THEME,
+        theme::Theme,
         tileset::tileset_ui,
     },
 };
@@ -42,7 +46,11 @@ impl eframe::App for App {
 
         self.poll_and_handle_all_ipc_messages(ctx);
 
-        let menu_frame = DEFAULT_THEME.base_100_frame(2);
+        #[cfg(not(target_arch = "wasm32"))]
+        self.update_tileset_watcher(ctx);
+
+        let theme = Theme::current(ctx);
+        let menu_frame = theme.base_100_frame(2);
 
         egui::TopBottomPanel::top("top_panel")
             .frame(menu_frame)
@@ -50,7 +58,7 @@ impl eframe::App for App {
                 menu_ui(ui, self);
             });
 
-        let side_frame = DEFAULT_THEME.base_100_frame(16);
+        let side_frame = theme.base_100_frame(16);
 
         egui::SidePanel::left("left_panel")
             .frame(side_frame)
@@ -74,7 +82,7 @@ impl eframe::App for App {
                 layers_ui(ui, self);
             });
 
-        let centre_frame = DEFAULT_THEME.base_200_frame(0);
+        let centre_frame = theme.base_200_frame(0);
 
         egui::CentralPanel::default()
             .frame(centre_frame)
@@ -85,6 +93,7 @@ impl eframe::App for App {
                     ModalState::None => consume_shortcuts(ctx, self),
                     ModalState::Map { .. } => map_modal_ui(ui, self),
                     ModalState::Tileset { .. } => tileset_modal_ui(ui, self),
+                    ModalState::MergeTilesets { .. } => merge_tilesets_modal_ui(ui, self),
                     ModalState::Layer { .. } => layer_modal_ui(ui, self),
                     ModalState::ImportTiled { .. } => import_tiled_modal_ui(ui, self),
                     ModalState::Error { .. } => error_modal_ui(ui, self),
@@ -94,10 +103,17 @@ impl eframe::App for App {
                     ModalState::ExportPng { .. } => export_png_modal_ui(ui, self),
                     ModalState::ExportTiled { .. } => export_tiled_modal_ui(ui, self),
                     ModalState::Help { .. } => help_modal_ui(ui, self),
+                    ModalState::CompareMaps { .. } => compare_maps_modal_ui(ui, self),
+                    ModalState::ResizeAllMaps { .. } => resize_all_maps_modal_ui(ui, self),
+                    ModalState::SplitTileset { .. } => split_tileset_modal_ui(ui, self),
+                    ModalState::ProjectRepaired { .. } => project_repaired_modal_ui(ui, self),
+                    ModalState::MissingTilesetImages { .. } => {
+                        missing_tileset_images_modal_ui(ui, self)
+                    }
                 }
             });
 
-        self.feed_undo();
+        self.feed_undo(ctx.input(|i| i.time));
 
         self.toasts.show(ctx);
     }
@@ -1,59 +1,138 @@
 use crate::render::render_tiles;
 use crate::{
     app::App,
+    data::state::State,
     data::{
-        config::workspace::{self, Export, Project, Workspace},
+        config::workspace::{self, Export, Project, TileFormat, Workspace},
+        maps::Map,
+        palette::Palette,
         png::PngExportSettings,
         tiles::layer_tiles::LayerTiles,
         tiles::tileset_stacked_tiles::TilesetStackedTiles,
-        tiles::{Tile, Tiles, layer_tiles::Layer, tile_color::TileColor},
+        tiles::{
+            Tile, Tiles,
+            layer_tiles::Layer,
+            tile_color::{TileColor, UserColor},
+        },
         tilesets::{TilesetId, Tilesets},
     },
+    geom::u32size2::U32Size2,
+    ui::tileset_textures::TilesetTextures,
 };
 use bitbuffer::{BigEndian, BitWriteStream, Endianness, LittleEndian};
 use camino::Utf8PathBuf;
 use convert_case::ccase;
-use egui::ahash::{HashMap, HashMapExt};
+use egui::ahash::{HashMap, HashMapExt, HashSet};
 use eyre::{bail, eyre};
 use image::{ImageBuffer, Rgba};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs::{self, File};
 use std::io::{BufWriter, Write};
 
-fn tile_option_to_u32(
-    tile: &Option<Tile>,
-    firstgids: &HashMap<TilesetId, u32>,
-) -> eyre::Result<u32> {
-    if let Some(tile) = tile {
-        let palette_index = match tile.color {
-            TileColor::Default => 0,
-            TileColor::Palette { index } if index.index() <= 255 => index.index() & 0xff,
-            TileColor::Palette { .. } => {
-                bail!("Export to rust codegen only supports palette indices <= 255")
-            }
-            TileColor::UserColor(_user_color) => {
-                bail!("Export to rust codegen only supports default and palette colors")
+/// The index used for each tile in exported module data
+struct ExportGids {
+    /// The first gid of each tileset, see [`tileset_firstgids`]
+    firstgids: HashMap<TilesetId, u32>,
+    /// If tile indices are compacted, the compact index of each used flat tile
+    /// index, see [`Export::compact_tile_indices`]
+    compact: Option<HashMap<u32, u32>>,
+}
+
+impl ExportGids {
+    fn from_state(state: &State, export: &Export) -> ExportGids {
+        let firstgids = tileset_firstgids(&state.resources.tilesets);
+        let compact = export.compact_tile_indices.unwrap_or(false).then(|| {
+            used_flat_tile_indices(state, export, &firstgids)
+                .into_iter()
+                .zip(1..)
+                .collect()
+        });
+        ExportGids { firstgids, compact }
+    }
+
+    /// The index of `tile` in exported data, starting from 1. This is the flat tile
+    /// index, or its compact index if tile indices are compacted.
+    fn gid(&self, tile: &Tile) -> Option<u32> {
+        let flat_tile_index = flat_tile_index(tile, &self.firstgids)?;
+        match &self.compact {
+            Some(compact) => compact.get(&flat_tile_index).copied(),
+            None => Some(flat_tile_index),
+        }
+    }
+
+    /// The flat tile index for each compact index, with 0 for the empty tile at
+    /// index 0, or [`None`] if tile indices aren't compacted
+    fn compact_table(&self) -> Option<Vec<u32>> {
+        self.compact.as_ref().map(|compact| {
+            let mut table = vec![0; compact.len() + 1];
+            for (flat_tile_index, compact_index) in compact.iter() {
+                if let Some(entry) = table.get_mut(*compact_index as usize) {
+                    *entry = *flat_tile_index;
+                }
             }
-        };
+            table
+        })
+    }
+}
+
+/// The flat tile indices (see [`flat_tile_index`]) of all tiles placed in exported
+/// maps, in increasing order
+fn used_flat_tile_indices(
+    state: &State,
+    export: &Export,
+    firstgids: &HashMap<TilesetId, u32>,
+) -> Vec<u32> {
+    let mut used = BTreeSet::new();
+    for map in state.maps.iter().filter(|map| exports_map(export, map)) {
+        let tiles = map.tiles.exported_tiles();
+        used.extend(
+            tiles
+                .layers()
+                .flat_map(|layer| layer.tiles_iter().flatten())
+                .filter_map(|tile| flat_tile_index(&tile, firstgids)),
+        );
+    }
+    used.into_iter().collect()
+}
 
-        let firstgid = firstgids
-            .get(&tile.source.tileset_id)
-            .ok_or(eyre!("Missing gid for tileset id"))?;
+/// The palette index and index (see [`ExportGids::gid`]) of a tile for export
+fn tile_export_indices(tile: &Tile, gids: &ExportGids) -> eyre::Result<(u32, u32)> {
+    let palette_index = match tile.color {
+        TileColor::Default => 0,
+        TileColor::Palette { index } => index.index(),
+        TileColor::UserColor(_user_color) => {
+            bail!("Export to rust codegen only supports default and palette colors");
+        }
+    };
+
+    let flat_tile_index = gids.gid(tile).ok_or(eyre!("Missing gid for tileset id"))?;
 
-        // Flatten the tile index, to work as one tileset containing tiles from all
-        // input tilesets, starting from index 1 so we can use 0 for an empty tile,
-        // as in Tiled
-        let flat_tile_index = tile.source.tile_index.index() + *firstgid;
+    Ok((palette_index, flat_tile_index))
+}
+
+fn tile_option_to_u32(tile: &Option<Tile>, gids: &ExportGids) -> eyre::Result<u32> {
+    if let Some(tile) = tile {
+        let (palette_index, flat_tile_index) = tile_export_indices(tile, gids)?;
+
+        if palette_index > 255 {
+            bail!(
+                "Export to rust codegen only supports palette indices <= 255 in the compact tile format, set tile-format = \"Wide\" in the workspace file to export larger indices"
+            );
+        }
 
         // We will include the palette index using bits 16 to 24 (0-based) so
         // we can only support flat_tile_index <= 65535, from an internal
         // tile index <= 65534
         if flat_tile_index > 65535 {
-            bail!("Export to rust codegen only supports tile indices <= 65534")
+            bail!(
+                "Export to rust codegen only supports tile indices <= 65534 in the compact tile format, set tile-format = \"Wide\" or compact-tile-indices = true in the workspace file to export larger indices"
+            );
         }
 
         // Use a u32 format based on Tiled, but with the palette index included
         let tile_and_palette_index =
-            flat_tile_index | ((palette_index as u32) << 16) | tile.transform.as_tiled_flip_bits();
+            flat_tile_index | (palette_index << 16) | tile.transform.as_tiled_flip_bits();
 
         Ok(tile_and_palette_index)
     } else {
@@ -62,19 +141,63 @@ fn tile_option_to_u32(
     }
 }
 
-fn layer_to_raw(layer: &Layer, tilesets: &Tilesets) -> eyre::Result<Vec<u32>> {
-    // Build tileset first gids
+/// The largest flat tile index supported by [`TileFormat::Wide`], leaving
+/// bits 29 to 31 for the Tiled flip bits
+const WIDE_MAX_FLAT_TILE_INDEX: u32 = (1 << 29) - 1;
+
+fn tile_option_to_u64(tile: &Option<Tile>, gids: &ExportGids) -> eyre::Result<u64> {
+    if let Some(tile) = tile {
+        let (palette_index, flat_tile_index) = tile_export_indices(tile, gids)?;
+
+        if flat_tile_index > WIDE_MAX_FLAT_TILE_INDEX {
+            bail!(
+                "Export to rust codegen only supports tile indices < {} in the wide tile format",
+                WIDE_MAX_FLAT_TILE_INDEX
+            );
+        }
+
+        // The low 32 bits are exactly the Tiled gid, with the palette index above
+        let gid = flat_tile_index | tile.transform.as_tiled_flip_bits();
+        Ok(((palette_index as u64) << 32) | gid as u64)
+    } else {
+        // Empty tile is 0 as in Tiled
+        Ok(0)
+    }
+}
+
+/// Flatten the tile index, to work as one tileset containing tiles from all
+/// input tilesets, starting from index 1 so we can use 0 for an empty tile,
+/// as in Tiled. This is the index in the low bits of exported tiles, see
+/// `layer_to_raw`. [`None`] if the tile's tileset isn't in `firstgids`.
+pub fn flat_tile_index(tile: &Tile, firstgids: &HashMap<TilesetId, u32>) -> Option<u32> {
+    firstgids
+        .get(&tile.source.tileset_id)
+        .map(|firstgid| tile.source.tile_index.index() + *firstgid)
+}
+
+/// The first gid of each tileset, used to flatten tile indices so that they work as
+/// one tileset containing tiles from all tilesets, in order
+pub fn tileset_firstgids(tilesets: &Tilesets) -> HashMap<TilesetId, u32> {
     let mut firstgid = 1;
     let mut firstgids = HashMap::new();
 
     for tileset in tilesets.iter() {
         firstgids.insert(tileset.id(), firstgid);
-        firstgid += tileset.size_in_tiles.area();
+        firstgid += tileset.tile_count();
     }
 
+    firstgids
+}
+
+/// The raw value of each tile in the layer, using `format`. Values for
+/// [`TileFormat::Compact`] always fit in a `u32`.
+fn layer_to_raw(layer: &Layer, gids: &ExportGids, format: TileFormat) -> eyre::Result<Vec<u64>> {
     let combined = layer
         .tiles_iter()
-        .map(|t| tile_option_to_u32(t, &firstgids))
+        .map(|t| match format {
+            TileFormat::Compact => tile_option_to_u32(&t, gids).map(u64::from),
+            TileFormat::Wide => tile_option_to_u64(&t, gids),
+        })
         .collect::<Result<Vec<_>, _>>()?;
 
     Ok(combined)
@@ -141,135 +264,501 @@ fn save_image_as_png(
     Ok(())
 }
 
-impl App {
-    fn export_tileset(&self, self_dir: &Utf8PathBuf, export: &Export) -> eyre::Result<()> {
-        if export.exports_tileset() {
-            let tileset_image = self.tilesets_to_image()?;
-
-            if let Some(rel_path) = &export.tileset_1bit_path {
-                let mut path = self_dir.clone();
-                path.push(rel_path);
-                match export
-                    .tileset_1bit_endianness
-                    .as_ref()
-                    .unwrap_or(&workspace::Endianness::Little)
-                {
-                    workspace::Endianness::Big => {
-                        save_image_as_raw_1bit(&tileset_image, path, BigEndian)?
-                    }
-                    workspace::Endianness::Little => {
-                        save_image_as_raw_1bit(&tileset_image, path, LittleEndian)?
-                    }
+fn export_tileset(
+    state: &State,
+    textures: &TilesetTextures,
+    self_dir: &Utf8PathBuf,
+    export: &Export,
+) -> eyre::Result<()> {
+    if export.exports_tileset() {
+        let tileset_image = tilesets_to_image(state, textures, export)?;
+
+        if let Some(rel_path) = &export.tileset_1bit_path {
+            let mut path = self_dir.clone();
+            path.push(rel_path);
+            match export
+                .tileset_1bit_endianness
+                .as_ref()
+                .unwrap_or(&workspace::Endianness::Little)
+            {
+                workspace::Endianness::Big => {
+                    save_image_as_raw_1bit(&tileset_image, path, BigEndian)?
+                }
+                workspace::Endianness::Little => {
+                    save_image_as_raw_1bit(&tileset_image, path, LittleEndian)?
                 }
             }
+        }
 
-            if let Some(rel_path) = &export.tileset_png_path {
-                let mut path = self_dir.clone();
-                path.push(rel_path);
-                save_image_as_png(&tileset_image, path)?;
-            }
+        if let Some(rel_path) = &export.tileset_png_path {
+            let mut path = self_dir.clone();
+            path.push(rel_path);
+            save_image_as_png(&tileset_image, path)?;
         }
+    }
 
-        Ok(())
+    Ok(())
+}
+
+/// The width of the palette lookup image, one pixel for each palette index that can
+/// be exported in tile data, see [`tile_option_to_u32`]
+const PALETTE_LOOKUP_WIDTH: u32 = 256;
+
+/// An image [`PALETTE_LOOKUP_WIDTH`] pixels wide and 1 high, where pixel `i` is palette
+/// color `i`, see [`Export::palette_lookup_image_path`]
+fn palette_lookup_image(palette: &Palette) -> eyre::Result<ImageBuffer<Rgba<u8>, Vec<u8>>> {
+    if palette.len() > PALETTE_LOOKUP_WIDTH {
+        return Err(eyre!(
+            "Palette lookup image only supports palettes with up to {} colors, palette has {}",
+            PALETTE_LOOKUP_WIDTH,
+            palette.len()
+        ));
+    }
+    let mut image = ImageBuffer::new(PALETTE_LOOKUP_WIDTH, 1);
+    for (x, color) in (0..).zip(palette.colors()) {
+        image.put_pixel(x, 0, color.into());
     }
+    Ok(image)
+}
 
-    fn export_palette(&self, self_dir: &Utf8PathBuf, export: &Export) -> eyre::Result<()> {
-        if let Some(rel_path) = &export.palette_json_path {
-            let mut path = self_dir.clone();
-            path.push(rel_path);
+fn export_palette(state: &State, self_dir: &Utf8PathBuf, export: &Export) -> eyre::Result<()> {
+    if let Some(rel_path) = &export.palette_json_path {
+        let mut path = self_dir.clone();
+        path.push(rel_path);
 
-            self.state
-                .resources
-                .palette()
-                .write_to_json_by_path(path.clone())
-                .map_err(|e| {
-                    eyre!(
-                        "Failed to write JSON palette data to:\n\n{}\n\nError:\n{}",
-                        path,
-                        e
-                    )
-                })?;
-        }
+        state
+            .resources
+            .palette()
+            .write_to_json_by_path(path.clone())
+            .map_err(|e| {
+                eyre!(
+                    "Failed to write JSON palette data to:\n\n{}\n\nError:\n{}",
+                    path,
+                    e
+                )
+            })?;
+    }
 
-        if let Some(rel_path) = &export.palette_image_path {
-            let mut path = self_dir.clone();
-            path.push(rel_path);
+    if let Some(rel_path) = &export.palette_image_path {
+        let mut path = self_dir.clone();
+        path.push(rel_path);
 
-            self.state
-                .resources
-                .palette()
-                .write_to_image_by_path(path.clone())
-                .map_err(|e| {
-                    eyre!(
-                        "Failed to write palette as image to:\n\n{}\n\nError:\n{}",
-                        path,
-                        e
-                    )
-                })?;
-        }
+        state
+            .resources
+            .palette()
+            .write_to_image_by_path(path.clone())
+            .map_err(|e| {
+                eyre!(
+                    "Failed to write palette as image to:\n\n{}\n\nError:\n{}",
+                    path,
+                    e
+                )
+            })?;
+    }
 
-        Ok(())
+    if let Some(rel_path) = &export.palette_hex_path {
+        let mut path = self_dir.clone();
+        path.push(rel_path);
+
+        state
+            .resources
+            .palette()
+            .write_to_paintnet_by_path(path.clone())
+            .map_err(|e| {
+                eyre!(
+                    "Failed to write palette as Paint.NET hex list to:\n\n{}\n\nError:\n{}",
+                    path,
+                    e
+                )
+            })?;
     }
 
-    fn export_module(&self, self_dir: &Utf8PathBuf, export: &Export) -> eyre::Result<()> {
-        if let Some(module_path) = &export.module_path {
-            let mut path = self_dir.clone();
-            path.push(module_path);
+    if let Some(rel_path) = &export.palette_lookup_image_path {
+        let mut path = self_dir.clone();
+        path.push(rel_path);
 
-            let mut f = BufWriter::new(
-                File::create(path.clone())
-                    .map_err(|e| eyre!("Failed to open module file '{}': {}", path, e))?,
-            );
+        palette_lookup_image(state.resources.palette())?
+            .save(path.clone())
+            .map_err(|e| {
+                eyre!(
+                    "Failed to write palette lookup image to:\n\n{}\n\nError:\n{}",
+                    path,
+                    e
+                )
+            })?;
+    }
+
+    Ok(())
+}
 
-            // TODO: Keep map of name to count, use to append numbers on duplicate names
-
-            for map in self.state.maps.iter() {
-                let export = export
-                    .skip_maps_with_prefix
-                    .as_ref()
-                    .map(|skip| !map.name.starts_with(skip))
-                    .unwrap_or(true);
-                if export {
-                    Self::export_map_module(
-                        &map.name,
-                        &map.tiles,
-                        &self.state.resources.tilesets,
-                        &mut f,
-                    )?;
+/// True if `map` should be exported, i.e. it isn't skipped due to its name prefix
+fn exports_map(export: &Export, map: &Map) -> bool {
+    export
+        .skip_maps_with_prefix
+        .as_ref()
+        .map(|skip| !map.name.starts_with(skip))
+        .unwrap_or(true)
+}
+
+/// Description of the exported maps, see [`Export::metadata_json_path`]
+#[derive(Serialize, Debug, Clone, PartialEq)]
+struct MetadataJson {
+    /// The number of columns of tiles in the exported tileset image, the tile
+    /// with a given `gid` is at column `(gid - 1) % tileset_columns` and row
+    /// `(gid - 1) / tileset_columns`
+    tileset_columns: u32,
+    maps: Vec<MapMetadataJson>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+struct MapMetadataJson {
+    name: String,
+    map_size: U32Size2,
+    tile_size: U32Size2,
+    /// The background color as hex, using the default map background for maps that
+    /// don't have their own background
+    background: String,
+    layers: Vec<LayerMetadataJson>,
+    tilesets: Vec<TilesetMetadataJson>,
+    /// The user metadata of the map, see [`Map::metadata`]
+    metadata: BTreeMap<String, String>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+struct LayerMetadataJson {
+    name: String,
+    visible: bool,
+    opacity: Option<f32>,
+}
+
+/// A tileset used by a map, with the `firstgid` of its tiles in the exported data
+#[derive(Serialize, Debug, Clone, PartialEq)]
+struct TilesetMetadataJson {
+    name: String,
+    firstgid: u32,
+    tile_count: u32,
+}
+
+impl MetadataJson {
+    fn from_state(state: &State, export: &Export) -> MetadataJson {
+        let tilesets = &state.resources.tilesets;
+        let firstgids = tileset_firstgids(tilesets);
+
+        let maps = state
+            .maps
+            .iter()
+            .filter(|map| exports_map(export, map))
+            .map(|map| {
+                let tiles = map.tiles.exported_tiles();
+                let used_tileset_ids: HashSet<TilesetId> = tiles
+                    .layers()
+                    .flat_map(|layer| layer.tiles_iter().flatten())
+                    .map(|tile| tile.source.tileset_id)
+                    .collect();
+
+                MapMetadataJson {
+                    name: map.name(),
+                    map_size: map.tiles.map_size(),
+                    tile_size: map.tiles.tile_size(),
+                    background: map.tiles.background().as_hex_string(),
+                    layers: tiles
+                        .layers()
+                        .map(|layer| LayerMetadataJson {
+                            name: layer.name(),
+                            visible: layer.visible(),
+                            opacity: layer.opacity(),
+                        })
+                        .collect(),
+                    tilesets: tilesets
+                        .iter()
+                        .filter(|tileset| used_tileset_ids.contains(&tileset.id()))
+                        .filter_map(|tileset| {
+                            firstgids
+                                .get(&tileset.id())
+                                .map(|firstgid| TilesetMetadataJson {
+                                    name: tileset.name.clone(),
+                                    firstgid: *firstgid,
+                                    tile_count: tileset.tile_count(),
+                                })
+                        })
+                        .collect(),
+                    metadata: map.metadata.clone(),
                 }
-            }
+            })
+            .collect();
+
+        MetadataJson {
+            tileset_columns: tileset_columns(export),
+            maps,
+        }
+    }
+}
+
+fn export_metadata(state: &State, self_dir: &Utf8PathBuf, export: &Export) -> eyre::Result<()> {
+    if let Some(rel_path) = &export.metadata_json_path {
+        let mut path = self_dir.clone();
+        path.push(rel_path);
 
-            f.flush()?;
+        let metadata = MetadataJson::from_state(state, export);
+        let json = serde_json::to_string_pretty(&metadata)?;
+        fs::write(path.clone(), json).map_err(|e| {
+            eyre!(
+                "Failed to write map metadata JSON to:\n\n{}\n\nError:\n{}",
+                path,
+                e
+            )
+        })?;
+    }
+
+    Ok(())
+}
+
+fn export_module(state: &State, self_dir: &Utf8PathBuf, export: &Export) -> eyre::Result<()> {
+    if let Some(module_path) = &export.module_path {
+        let mut path = self_dir.clone();
+        path.push(module_path);
+
+        let mut f = BufWriter::new(
+            File::create(path.clone())
+                .map_err(|e| eyre!("Failed to open module file '{}': {}", path, e))?,
+        );
+
+        write_module(state, export, &mut f)?;
+    }
+    Ok(())
+}
+
+/// Write the module for all exported maps, see [`Export::module_path`]
+fn write_module<W: Write>(state: &State, export: &Export, f: &mut W) -> eyre::Result<()> {
+    let gids = ExportGids::from_state(state, export);
+    let format = export.tile_format.unwrap_or_default();
+
+    if format == TileFormat::Wide {
+        writeln!(f, "{}", WIDE_TILE_MODULE)?;
+    }
+
+    if let Some(table) = gids.compact_table() {
+        writeln!(
+            f,
+            "/// The flat tile index in the exported tileset image for each compact tile index,"
+        )?;
+        writeln!(f, "/// where 0 is the empty tile")?;
+        writeln!(f, "pub const COMPACT_TILE_GIDS: [u32; {}] = [", table.len())?;
+        for flat_tile_index in table.iter() {
+            writeln!(f, "    {},", flat_tile_index)?;
+        }
+        writeln!(f, "];")?;
+        writeln!(f)?;
+    }
+
+    // TODO: Keep map of name to count, use to append numbers on duplicate names
+
+    for map in state.maps.iter() {
+        if exports_map(export, map) {
+            export_map_module(&map.name, &map.tiles, &gids, format, f)?;
         }
+    }
+
+    f.flush()?;
+    Ok(())
+}
+
+/// Check that all tilesets and exported maps have the same tile size, when exporting data
+/// that relies on this, i.e. a module or tileset image, unless `export` allows mixed sizes.
+fn check_tile_sizes(state: &State, export: &Export) -> eyre::Result<()> {
+    let needs_shared_tile_size = export.module_path.is_some() || export.exports_tileset();
+    if !needs_shared_tile_size || export.allow_mixed_tile_sizes.unwrap_or(false) {
+        return Ok(());
+    }
+
+    let Some(tile_size) = state.resources.tilesets.iter().next().map(|t| t.tile_size) else {
+        return Ok(());
+    };
+
+    let mismatched_tilesets: Vec<String> = state
+        .resources
+        .tilesets
+        .iter()
+        .filter(|tileset| tileset.tile_size != tile_size)
+        .map(|tileset| {
+            format!(
+                "Tileset '{}' ({}x{})",
+                tileset.name, tileset.tile_size.w, tileset.tile_size.h
+            )
+        })
+        .collect();
+    let mismatched_maps: Vec<String> = state
+        .maps
+        .iter()
+        .filter(|map| exports_map(export, map) && map.tiles.tile_size() != tile_size)
+        .map(|map| {
+            let size = map.tiles.tile_size();
+            format!("Map '{}' ({}x{})", map.name, size.w, size.h)
+        })
+        .collect();
+
+    if mismatched_tilesets.is_empty() && mismatched_maps.is_empty() {
         Ok(())
+    } else {
+        Err(eyre!(
+            "Export requires all tilesets and maps to use the same tile size as the first tileset ({}x{}), but these differ:\n\n{}\n\nTo export anyway, set allow-mixed-tile-sizes = true in the workspace file.",
+            tile_size.w,
+            tile_size.h,
+            mismatched_tilesets
+                .into_iter()
+                .chain(mismatched_maps)
+                .collect::<Vec<_>>()
+                .join("\n")
+        ))
     }
+}
 
-    pub fn export_from_workspace_error(&mut self) -> eyre::Result<()> {
-        let self_path = self
-            .save_path
-            .as_ref()
-            .ok_or(eyre!("Please save the project before exporting."))?;
+/// The number of columns of tiles in the exported tileset image
+fn tileset_columns(export: &Export) -> u32 {
+    export.tileset_columns.unwrap_or(1).max(1)
+}
 
-        let project = Project::from_project_path(self_path.clone())?;
+fn tilesets_to_image(
+    state: &State,
+    textures: &TilesetTextures,
+    export: &Export,
+) -> eyre::Result<ImageBuffer<Rgba<u8>, Vec<u8>>> {
+    let tiles =
+        &TilesetStackedTiles::with_columns(&state.resources.tilesets, tileset_columns(export));
+    let palette = &state.resources.palette;
+    let tilesets = &state.resources.tilesets;
+    let settings = &PngExportSettings {
+        scale: 1,
+        transparent: true,
+        fit_within: None,
+    };
+    let image = render_tiles(tiles, palette, tilesets, textures, settings)?;
 
-        if !project.export_has_effect() {
-            let workspace_path = Workspace::workspace_path_from_project_path(self_path.clone())?;
+    if export.tileset_pad_to_pow2.unwrap_or(false) {
+        let mut padded = ImageBuffer::new(
+            image.width().next_power_of_two(),
+            image.height().next_power_of_two(),
+        );
+        image::imageops::replace(&mut padded, &image, 0, 0);
+        Ok(padded)
+    } else {
+        Ok(image)
+    }
+}
+
+/// Description of the exported tileset image as an array texture,
+/// see [`Export::tileset_layout_json_path`]
+#[derive(Serialize, Debug, Clone, PartialEq)]
+struct TilesetLayoutJson {
+    tile_size: U32Size2,
+    layer_count: u32,
+    array_layout: ArrayLayoutJson,
+}
+
+/// Matches the serialized form of Bevy's `ImageArrayLayout`
+#[derive(Serialize, Debug, Clone, PartialEq)]
+enum ArrayLayoutJson {
+    RowCount { rows: u32 },
+}
+
+impl TilesetLayoutJson {
+    fn from_state(state: &State, export: &Export) -> eyre::Result<TilesetLayoutJson> {
+        if tileset_columns(export) != 1 || export.tileset_pad_to_pow2.unwrap_or(false) {
             bail!(
-                "Export settings do not export any files\nAdd some settings to workspace file at:\n{}\nSee example data for supported settings.",
-                workspace_path
+                "tileset-layout-json-path requires the tileset image to be a single column with no padding, please remove tileset-columns and tileset-pad-to-pow2 from the workspace file."
             );
         }
+        let tilesets = &state.resources.tilesets;
+        let tile_size = tilesets
+            .first()
+            .map(|tileset| tileset.tile_size)
+            .ok_or(eyre!("Project has no tilesets to describe"))?;
+        let layer_count = tilesets.iter().map(|tileset| tileset.tile_count()).sum();
+        Ok(TilesetLayoutJson {
+            tile_size,
+            layer_count,
+            array_layout: ArrayLayoutJson::RowCount { rows: layer_count },
+        })
+    }
+}
 
-        if let Some(export) = project.export.as_ref() {
-            let mut self_dir = self_path.clone();
-            self_dir.pop();
+fn export_tileset_layout(
+    state: &State,
+    self_dir: &Utf8PathBuf,
+    export: &Export,
+) -> eyre::Result<()> {
+    if let Some(rel_path) = &export.tileset_layout_json_path {
+        let mut path = self_dir.clone();
+        path.push(rel_path);
 
-            self.export_tileset(&self_dir, export)?;
-            self.export_palette(&self_dir, export)?;
-            self.export_module(&self_dir, export)?;
-        }
+        let layout = TilesetLayoutJson::from_state(state, export)?;
+        let json = serde_json::to_string_pretty(&layout)?;
+        fs::write(path.clone(), json).map_err(|e| {
+            eyre!(
+                "Failed to write tileset layout JSON to:\n\n{}\n\nError:\n{}",
+                path,
+                e
+            )
+        })?;
+    }
 
-        Ok(())
+    Ok(())
+}
+
+/// Export files as specified by the workspace settings for the project saved
+/// at `project_path`, using `state` as the project data, with `default_map_background`
+/// for maps that don't have their own background.
+/// Tileset images are loaded directly from files using `textures`, so this
+/// doesn't require an egui context, and can be used without opening a window.
+pub fn export_project_from_workspace(
+    state: &State,
+    textures: &TilesetTextures,
+    project_path: &Utf8PathBuf,
+    default_map_background: UserColor,
+) -> eyre::Result<()> {
+    let mut state = state.clone();
+    state.set_inherited_backgrounds(default_map_background);
+    let state = &state;
+
+    let project = Project::from_project_path(project_path.clone())?;
+
+    if !project.export_has_effect() {
+        let workspace_path = Workspace::workspace_path_from_project_path(project_path.clone())?;
+        bail!(
+            "Export settings do not export any files\nAdd some settings to workspace file at:\n{}\nSee example data for supported settings.",
+            workspace_path
+        );
+    }
+
+    if let Some(export) = project.export.as_ref() {
+        let mut self_dir = project_path.clone();
+        self_dir.pop();
+
+        check_tile_sizes(state, export)?;
+
+        export_tileset(state, textures, &self_dir, export)?;
+        export_tileset_layout(state, &self_dir, export)?;
+        export_palette(state, &self_dir, export)?;
+        export_module(state, &self_dir, export)?;
+        export_metadata(state, &self_dir, export)?;
+    }
+
+    Ok(())
+}
+
+impl App {
+    pub fn export_from_workspace_error(&mut self) -> eyre::Result<()> {
+        let self_path = self
+            .save_path
+            .as_ref()
+            .ok_or(eyre!("Please save the project before exporting."))?;
+
+        export_project_from_workspace(
+            &self.state,
+            &self.textures,
+            self_path,
+            self.settings.default_map_background,
+        )
     }
 
     pub fn export_from_workspace(&mut self) {
@@ -279,100 +768,553 @@ impl App {
             self.success("Exported workspace");
         }
     }
+}
 
-    fn tilesets_to_image(&self) -> eyre::Result<ImageBuffer<Rgba<u8>, Vec<u8>>> {
-        let tiles = &TilesetStackedTiles::new(&self.state.resources.tilesets);
-        let palette = &self.state.resources.palette;
-        let tilesets = &self.state.resources.tilesets;
-        let textures = &self.textures;
-        let settings = &PngExportSettings {
-            scale: 1,
-            transparent: true,
-        };
-        let image = render_tiles(tiles, palette, tilesets, textures, settings)?;
-        Ok(image)
+/// Definitions used by modules exported with [`TileFormat::Wide`], written at the start
+/// of the module, so that the module doesn't depend on any other crate for them.
+/// `Size` mirrors `embedded_graphics_core::prelude::Size`, as used by the compact format.
+const WIDE_TILE_MODULE: &str = r#"pub mod wide_tile {
+    /// A size, in tiles
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Size {
+        pub width: u32,
+        pub height: u32,
     }
 
-    fn export_map_module<W: Write>(
-        map_name: &str,
-        tiles: &LayerTiles,
-        tilesets: &Tilesets,
-        f: &mut W,
-    ) -> eyre::Result<()> {
-        // If there are no layers, there's nothing to export
-        if let Some(layer_tile_count) = tiles.first_layer().map(|layer| layer.tiles_iter().len()) {
-            writeln!(f, "pub mod {} {{", ccase!(snake, map_name))?;
-
-            writeln!(f, "    pub mod layers {{")?;
-            writeln!(f)?;
-            writeln!(f, "        use embedded_graphics_core::prelude::Size;")?;
-            writeln!(f, "        use tili::tile::{{Tile, LayerData}};")?;
-            writeln!(f)?;
-
-            for layer in tiles.layers() {
-                let combined = layer_to_raw(layer, tilesets)?;
-
-                writeln!(
-                    f,
-                    "        pub const {}: LayerData<{}> = LayerData {{",
-                    ccase!(constant, layer.name()),
-                    layer_tile_count,
-                )?;
-
-                writeln!(f, "            name: \"{}\",", layer.name())?;
-                writeln!(f, "            visible: {},", layer.visible())?;
-                writeln!(
-                    f,
-                    "            size: Size::new({}, {}),",
-                    tiles.map_size().w,
-                    tiles.map_size().h
-                )?;
-
-                writeln!(f, "            tiles: [")?;
-
-                for tile in combined.iter() {
-                    writeln!(f, "                Tile::raw({}),", tile)?;
-                }
+    impl Size {
+        pub const fn new(width: u32, height: u32) -> Self {
+            Self { width, height }
+        }
+    }
 
-                writeln!(f, "            ],")?;
-                writeln!(f, "            opacity: {:?},", layer.opacity())?;
-                writeln!(f, "        }};")?;
-            }
+    /// A tile, with the Tiled gid (flat tile index, with flip bits in bits 29 to 31)
+    /// in the low 32 bits, and the palette index in the high 32 bits
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct WideTile(pub u64);
+
+    impl WideTile {
+        /// The Tiled gid, including flip bits
+        pub const fn gid(&self) -> u32 {
+            self.0 as u32
+        }
+
+        /// The flat tile index, where 0 is the empty tile
+        pub const fn flat_tile_index(&self) -> u32 {
+            self.gid() & 0x1FFF_FFFF
+        }
+
+        /// The Tiled flip bits, moved to the low 3 bits
+        pub const fn flip_bits(&self) -> u32 {
+            self.gid() >> 29
+        }
+
+        pub const fn palette_index(&self) -> u32 {
+            (self.0 >> 32) as u32
+        }
+
+        pub const fn is_empty(&self) -> bool {
+            self.flat_tile_index() == 0
+        }
+    }
+
+    pub struct WideLayerData<const N: usize> {
+        pub name: &'static str,
+        pub visible: bool,
+        pub size: Size,
+        pub tiles: [WideTile; N],
+        pub opacity: Option<f32>,
+    }
+
+    pub struct WideMapData<const L: usize, const N: usize> {
+        pub name: &'static str,
+        pub size: Size,
+        pub layers: [&'static WideLayerData<N>; L],
+    }
+}
+"#;
+
+/// The names used in an exported map module for each [`TileFormat`]
+struct ModuleNames {
+    layer_uses: &'static [&'static str],
+    map_uses: &'static [&'static str],
+    layer_data: &'static str,
+    map_data: &'static str,
+    tile_constructor: &'static str,
+}
+
+impl ModuleNames {
+    fn for_format(format: TileFormat) -> ModuleNames {
+        match format {
+            TileFormat::Compact => ModuleNames {
+                layer_uses: &[
+                    "embedded_graphics_core::prelude::Size",
+                    "tili::tile::{Tile, LayerData}",
+                ],
+                map_uses: &[
+                    "embedded_graphics_core::prelude::Size",
+                    "tili::tile::MapData",
+                ],
+                layer_data: "LayerData",
+                map_data: "MapData",
+                tile_constructor: "Tile::raw",
+            },
+            TileFormat::Wide => ModuleNames {
+                layer_uses: &["super::super::wide_tile::{Size, WideLayerData, WideTile}"],
+                map_uses: &["super::wide_tile::{Size, WideMapData}"],
+                layer_data: "WideLayerData",
+                map_data: "WideMapData",
+                tile_constructor: "WideTile",
+            },
+        }
+    }
+}
+
+fn export_map_module<W: Write>(
+    map_name: &str,
+    tiles: &LayerTiles,
+    gids: &ExportGids,
+    format: TileFormat,
+    f: &mut W,
+) -> eyre::Result<()> {
+    let tiles = &tiles.exported_tiles();
+    let names = ModuleNames::for_format(format);
 
-            writeln!(f, "    }}")?;
-            writeln!(f)?;
-            writeln!(f, "    use embedded_graphics_core::prelude::Size;")?;
-            writeln!(f, "    use tili::tile::MapData;")?;
-            writeln!(f)?;
+    // If there are no layers, there's nothing to export
+    if let Some(layer_tile_count) = tiles.first_layer().map(|layer| layer.cell_count()) {
+        writeln!(f, "pub mod {} {{", ccase!(snake, map_name))?;
+
+        writeln!(f, "    pub mod layers {{")?;
+        writeln!(f)?;
+        for layer_use in names.layer_uses {
+            writeln!(f, "        use {};", layer_use)?;
+        }
+        writeln!(f)?;
+
+        for layer in tiles.layers() {
+            let combined = layer_to_raw(layer, gids, format)?;
 
             writeln!(
                 f,
-                "    pub const MAP: MapData<{}, {}> = MapData {{",
-                tiles.layer_count(),
-                layer_tile_count
+                "        pub const {}: {}<{}> = {} {{",
+                ccase!(constant, layer.name()),
+                names.layer_data,
+                layer_tile_count,
+                names.layer_data,
             )?;
-            writeln!(f, "        name: \"{}\",", map_name)?;
+
+            writeln!(f, "            name: \"{}\",", layer.name())?;
+            writeln!(f, "            visible: {},", layer.visible())?;
             writeln!(
                 f,
-                "        size: Size::new({}, {}),",
+                "            size: Size::new({}, {}),",
                 tiles.map_size().w,
                 tiles.map_size().h
             )?;
 
-            writeln!(f, "        layers: [")?;
-            for layer in tiles.layers() {
-                writeln!(f, "            &layers::{}", ccase!(constant, layer.name()))?;
+            writeln!(f, "            tiles: [")?;
+
+            for tile in combined.iter() {
+                writeln!(f, "                {}({}),", names.tile_constructor, tile)?;
             }
-            writeln!(f, "        ]")?;
 
-            writeln!(f, "    }};")?;
+            writeln!(f, "            ],")?;
+            writeln!(f, "            opacity: {:?},", layer.opacity())?;
+            writeln!(f, "        }};")?;
+        }
 
-            writeln!(f, "}}")?;
+        writeln!(f, "    }}")?;
+        writeln!(f)?;
+        for map_use in names.map_uses {
+            writeln!(f, "    use {};", map_use)?;
+        }
+        writeln!(f)?;
 
-            // Flush to detect any errors
-            f.flush()?;
+        writeln!(
+            f,
+            "    pub const MAP: {}<{}, {}> = {} {{",
+            names.map_data,
+            tiles.layer_count(),
+            layer_tile_count,
+            names.map_data,
+        )?;
+        writeln!(f, "        name: \"{}\",", map_name)?;
+        writeln!(
+            f,
+            "        size: Size::new({}, {}),",
+            tiles.map_size().w,
+            tiles.map_size().h
+        )?;
+
+        writeln!(f, "        layers: [")?;
+        for layer in tiles.layers() {
+            writeln!(f, "            &layers::{}", ccase!(constant, layer.name()))?;
         }
+        writeln!(f, "        ]")?;
+
+        writeln!(f, "    }};")?;
+
+        writeln!(f, "}}")?;
+
+        // Flush to detect any errors
+        f.flush()?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{
+        data::{
+            tiles::{TileIndex, TileSource, tile_color::UserColor},
+            tilesets::Tileset,
+        },
+        geom::{transform::Transform, u32pos2::u32pos2, u32size2::u32size2},
+    };
+
+    use super::*;
+
+    #[test]
+    fn metadata_json_shape() -> eyre::Result<()> {
+        let mut state = State::default();
+        state.resources.tilesets = Tilesets::builtin_tilesets();
+        let mut second_tileset = Tileset::default();
+        second_tileset.name = "second".to_string();
+        second_tileset.size_in_tiles = u32size2(4, 2);
+        let second_tileset_id = state.resources.tilesets.push_tileset(second_tileset);
+
+        let mut map = Map::new_with_layer(
+            "First".to_string(),
+            u32size2(3, 2),
+            u32size2(8, 8),
+            UserColor::BLACK,
+        );
+        map.tiles.set_tile(
+            0,
+            u32pos2(1, 1),
+            Some(Tile {
+                source: TileSource {
+                    tileset_id: second_tileset_id,
+                    tile_index: TileIndex::new(2),
+                },
+                color: TileColor::Default,
+                transform: Transform::None,
+            }),
+        );
+        map.metadata
+            .insert("music".to_string(), "cave.ogg".to_string());
+        state.maps.push_map(map);
+        state.maps.push_map(Map::new_with_layer(
+            "skip-Second".to_string(),
+            u32size2(4, 4),
+            u32size2(8, 8),
+            UserColor::BLACK,
+        ));
+
+        let export = Export {
+            skip_maps_with_prefix: Some("skip-".to_string()),
+            ..Default::default()
+        };
+        let metadata = serde_json::to_value(MetadataJson::from_state(&state, &export))?;
 
+        // Only the used tileset is listed, with its firstgid following the
+        // 256 tiles of the builtin tileset
+        let expected = json!({
+            "tileset_columns": 1,
+            "maps": [{
+                "name": "First",
+                "map_size": { "w": 3, "h": 2 },
+                "tile_size": { "w": 8, "h": 8 },
+                "background": "000000",
+                "layers": [{ "name": "New Layer", "visible": true, "opacity": null }],
+                "tilesets": [{ "name": "second", "firstgid": 257, "tile_count": 8 }],
+                "metadata": { "music": "cave.ogg" },
+            }]
+        });
+        assert_eq!(metadata, expected);
         Ok(())
     }
+
+    #[test]
+    fn tileset_image_in_columns_padded_to_pow2() -> eyre::Result<()> {
+        let mut state = State::default();
+        state.resources.tilesets = Tilesets::builtin_tilesets();
+        let textures = TilesetTextures::builtin_tileset_textures();
+
+        // The 256 builtin 8x8 tiles in 10 columns need 26 rows
+        let mut export = Export {
+            tileset_columns: Some(10),
+            ..Default::default()
+        };
+        let image = tilesets_to_image(&state, &textures, &export)?;
+        assert_eq!((image.width(), image.height()), (80, 208));
+
+        export.tileset_pad_to_pow2 = Some(true);
+        let padded = tilesets_to_image(&state, &textures, &export)?;
+        assert_eq!((padded.width(), padded.height()), (128, 256));
+
+        // Tiles keep their positions, and padding is transparent
+        assert_eq!(padded.get_pixel(0, 0), image.get_pixel(0, 0));
+        assert_eq!(padded.get_pixel(79, 207), image.get_pixel(79, 207));
+        assert_eq!(padded.get_pixel(127, 255).0, [0, 0, 0, 0]);
+        Ok(())
+    }
+
+    #[test]
+    fn palette_lookup_image_is_256x1() -> eyre::Result<()> {
+        let red = UserColor::new(255, 0, 0, 255);
+        let palette = Palette::new(vec![UserColor::BLACK, red]);
+        let image = palette_lookup_image(&palette)?;
+        assert_eq!((image.width(), image.height()), (256, 1));
+
+        // Pixel i is palette color i, and the rest are transparent
+        assert_eq!(image.get_pixel(0, 0).0, [0, 0, 0, 255]);
+        assert_eq!(image.get_pixel(1, 0).0, [255, 0, 0, 255]);
+        assert_eq!(image.get_pixel(255, 0).0, [0, 0, 0, 0]);
+
+        // Indices past 255 can't be exported, so neither can their colors
+        assert!(palette_lookup_image(&Palette::new(vec![red; 256])).is_ok());
+        assert!(palette_lookup_image(&Palette::new(vec![red; 257])).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn tileset_layout_json_fixture() -> eyre::Result<()> {
+        let mut state = State::default();
+        state.resources.tilesets = Tilesets::builtin_tilesets();
+        let mut second_tileset = Tileset::default();
+        second_tileset.size_in_tiles = u32size2(4, 2);
+        state.resources.tilesets.push_tileset(second_tileset);
+
+        let mut export = Export::default();
+        let layout = serde_json::to_value(TilesetLayoutJson::from_state(&state, &export)?)?;
+        let expected = json!({
+            "tile_size": { "w": 8, "h": 8 },
+            "layer_count": 264,
+            "array_layout": { "RowCount": { "rows": 264 } },
+        });
+        assert_eq!(layout, expected);
+
+        // Array layers need a single column of tiles
+        export.tileset_columns = Some(2);
+        assert!(TilesetLayoutJson::from_state(&state, &export).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn flat_tile_index_matches_exported_tiles() -> eyre::Result<()> {
+        let mut tilesets = Tilesets::builtin_tilesets();
+        let second_tileset_id = tilesets.push_tileset(Tileset::default());
+        let tile = Tile {
+            source: TileSource {
+                tileset_id: second_tileset_id,
+                tile_index: TileIndex::new(2),
+            },
+            color: TileColor::Default,
+            transform: Transform::None,
+        };
+        let firstgids = tileset_firstgids(&tilesets);
+
+        // The 256 builtin tiles come first, starting from 1
+        assert_eq!(flat_tile_index(&tile, &firstgids), Some(259));
+        let gids = ExportGids {
+            firstgids: firstgids.clone(),
+            compact: None,
+        };
+        assert_eq!(tile_option_to_u32(&Some(tile), &gids)?, 259);
+
+        let unknown = Tile {
+            source: TileSource {
+                tileset_id: TilesetId::ONE,
+                tile_index: TileIndex::new(0),
+            },
+            ..tile
+        };
+        assert_eq!(flat_tile_index(&unknown, &HashMap::new()), None);
+        Ok(())
+    }
+
+    #[test]
+    fn wide_tile_format_exports_large_palette_indices() -> eyre::Result<()> {
+        use crate::data::palette::PaletteIndex;
+
+        let tilesets = Tilesets::builtin_tilesets();
+        let gids = ExportGids {
+            firstgids: tileset_firstgids(&tilesets),
+            compact: None,
+        };
+        let builtin_id = tilesets
+            .first()
+            .map(|t| t.id())
+            .ok_or(eyre!("No tileset"))?;
+        let mut map = Map::new_with_layer(
+            "Large Palette".to_string(),
+            u32size2(2, 1),
+            u32size2(8, 8),
+            UserColor::BLACK,
+        );
+        map.tiles.set_tile(
+            0,
+            u32pos2(1, 0),
+            Some(Tile {
+                source: TileSource {
+                    tileset_id: builtin_id,
+                    tile_index: TileIndex::new(3),
+                },
+                color: TileColor::Palette {
+                    index: PaletteIndex::new(299),
+                },
+                transform: Transform::MirrorX,
+            }),
+        );
+
+        // The compact format can't hold the palette index, and suggests the wide format
+        let mut compact = vec![];
+        let error = export_map_module(
+            &map.name,
+            &map.tiles,
+            &gids,
+            TileFormat::Compact,
+            &mut compact,
+        )
+        .err()
+        .map(|e| e.to_string())
+        .unwrap_or_default();
+        assert!(error.contains("tile-format"));
+
+        // The wide module matches the module compiled and checked by tests/wide_module.rs
+        let mut state = State::default();
+        state.resources.tilesets = tilesets;
+        state.maps.push_map(map);
+        let export = Export {
+            tile_format: Some(TileFormat::Wide),
+            ..Default::default()
+        };
+        let mut wide = vec![];
+        write_module(&state, &export, &mut wide)?;
+        let module = String::from_utf8(wide)?;
+        assert_eq!(module, include_str!("../../test-data/wide-module.rs"));
+        Ok(())
+    }
+
+    #[test]
+    fn compact_tile_indices_number_only_used_tiles() -> eyre::Result<()> {
+        let mut state = State::default();
+        state.resources.tilesets = Tilesets::builtin_tilesets();
+        let builtin_id = state
+            .resources
+            .tilesets
+            .first()
+            .map(|t| t.id())
+            .ok_or(eyre!("No tileset"))?;
+        // A large tileset, so its tiles have flat indices past the compact format limit
+        let mut large_tileset = Tileset::default();
+        large_tileset.size_in_tiles = u32size2(300, 300);
+        let large_id = state.resources.tilesets.push_tileset(large_tileset);
+
+        let tile = |tileset_id, index| Tile {
+            source: TileSource {
+                tileset_id,
+                tile_index: TileIndex::new(index),
+            },
+            color: TileColor::Default,
+            transform: Transform::None,
+        };
+        let mut map = Map::new_with_layer(
+            "Sparse".to_string(),
+            u32size2(3, 1),
+            u32size2(8, 8),
+            UserColor::BLACK,
+        );
+        map.tiles
+            .set_tile(0, u32pos2(0, 0), Some(tile(large_id, 80000)));
+        map.tiles
+            .set_tile(0, u32pos2(1, 0), Some(tile(builtin_id, 10)));
+        map.tiles
+            .set_tile(0, u32pos2(2, 0), Some(tile(large_id, 80000)));
+        state.maps.push_map(map);
+
+        // Tiles in skipped maps don't use a compact index
+        let mut skipped = Map::new_with_layer(
+            "skip-Other".to_string(),
+            u32size2(1, 1),
+            u32size2(8, 8),
+            UserColor::BLACK,
+        );
+        skipped
+            .tiles
+            .set_tile(0, u32pos2(0, 0), Some(tile(builtin_id, 20)));
+        state.maps.push_map(skipped);
+
+        let mut export = Export {
+            skip_maps_with_prefix: Some("skip-".to_string()),
+            ..Default::default()
+        };
+        assert!(write_module(&state, &export, &mut vec![]).is_err());
+
+        export.compact_tile_indices = Some(true);
+        let mut module = vec![];
+        write_module(&state, &export, &mut module)?;
+        let module = String::from_utf8(module)?;
+
+        // Flat indices 11 and 80257 (after the 256 builtin tiles) become 1 and 2
+        assert!(module.contains(
+            "pub const COMPACT_TILE_GIDS: [u32; 3] = [\n    0,\n    11,\n    80257,\n];"
+        ));
+        assert!(module.contains(
+            "Tile::raw(2),\n                Tile::raw(1),\n                Tile::raw(2),"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn mixed_tile_sizes_are_rejected() {
+        let mut state = State::default();
+        state.resources.tilesets = Tilesets::builtin_tilesets();
+        state.maps.push_map(Map::new_with_layer(
+            "Matching".to_string(),
+            u32size2(4, 4),
+            u32size2(8, 8),
+            UserColor::BLACK,
+        ));
+        let export = Export {
+            module_path: Some("maps.rs".into()),
+            ..Default::default()
+        };
+        assert!(check_tile_sizes(&state, &export).is_ok());
+
+        state.maps.push_map(Map::new_with_layer(
+            "Mismatched".to_string(),
+            u32size2(4, 4),
+            u32size2(16, 16),
+            UserColor::BLACK,
+        ));
+        let error = check_tile_sizes(&state, &export)
+            .err()
+            .map(|e| e.to_string())
+            .unwrap_or_default();
+        assert!(error.contains("Map 'Mismatched'"));
+        assert!(!error.contains("Map 'Matching'"));
+
+        // Allowed by override, or when skipped
+        let allowed = Export {
+            allow_mixed_tile_sizes: Some(true),
+            ..export.clone()
+        };
+        assert!(check_tile_sizes(&state, &allowed).is_ok());
+        let skipped = Export {
+            skip_maps_with_prefix: Some("Mis".to_string()),
+            ..export.clone()
+        };
+        assert!(check_tile_sizes(&state, &skipped).is_ok());
+
+        // Only checked when exporting data that relies on a shared tile size
+        let palette_only = Export {
+            palette_json_path: Some("palette.json".into()),
+            ..Default::default()
+        };
+        assert!(check_tile_sizes(&state, &palette_only).is_ok());
+    }
 }
@@ -2,8 +2,12 @@ use camino::Utf8PathBuf;
 
 use crate::{
     app::App,
-    data::{action::Action, modal::DataLossOperation, state::State},
-    ui::file_dialog,
+    data::{
+        action::Action,
+        modal::{DataLossOperation, ModalState},
+        state::State,
+    },
+    ui::{file_dialog, file_manager},
 };
 
 pub enum StateSource {
@@ -107,6 +111,7 @@ impl App {
             }
             StateSource::Open { file_path } => {
                 self.on_open(file_path.clone());
+                self.check_tileset_images();
             }
         }
     }
@@ -126,14 +131,20 @@ impl App {
     /// Attempt to open a document, display an error modal if this fails
     /// Returns true if a document was opened, false otherwise
     pub fn open_document(&mut self, path: Utf8PathBuf, context: OpenContext) -> bool {
-        match State::from_path(path.clone()) {
-            Ok(map) => {
+        match State::from_path_with_repairs(path.clone()) {
+            Ok((map, repairs)) => {
                 self.use_state(
                     map,
                     StateSource::Open {
                         file_path: path.clone(),
                     },
                 );
+                // This replaces any missing tileset images modal, which is shown once
+                // the repairs have been seen
+                if !repairs.is_empty() {
+                    self.edit
+                        .show_modal(ModalState::project_repaired(repairs.descriptions()));
+                }
                 true
             }
             Err(e) => {
@@ -201,10 +212,42 @@ impl App {
         }
     }
 
+    pub fn show_save_copy_document_modal(&mut self) {
+        match file_dialog::save_mnp_file(&self.save_path) {
+            Ok(Some(path)) => self.save_document_copy(path),
+            Ok(None) => {}
+            Err(e) => self.show_error_modal(&e.to_string()),
+        }
+    }
+
+    /// Save a copy of the document to `path`, leaving the save path and saved revision
+    /// unchanged, so File->Save still saves to the original file, and unsaved changes
+    /// are still tracked against it.
+    pub fn save_document_copy(&mut self, path: Utf8PathBuf) {
+        let mut state = self.state.clone();
+        state.rebase_tileset_paths(self.textures.base_dir(), &path);
+        match state.save_to_path(path.clone()) {
+            Ok(()) => self.success(format!("Saved a copy to {}", path)),
+            Err(e) => self.show_error_modal(&e.to_string()),
+        }
+    }
+
     pub fn save_document(&mut self, path: Utf8PathBuf) {
+        // When saving to a new directory, relative tileset paths need to be resolved
+        // from the current directory before being made relative to the new one.
+        // This is done on a copy, so the state is unchanged if saving fails.
+        let base_dir = self.textures.base_dir().cloned();
+        let mut state = self.state.clone();
+        state.rebase_tileset_paths(base_dir.as_ref(), &path);
         let saved_revision = self.undo.most_recent_revision_index();
-        match self.state.save_to_path(path.clone()) {
+        match state.save_to_path(path.clone()) {
             Ok(()) => {
+                // Like [`Action::OnSave`], this doesn't make a new revision. Undo revisions
+                // are rebased too, since their relative paths would otherwise be resolved
+                // from the new directory after undoing.
+                self.state = state;
+                self.undo
+                    .update_all_data(|state| state.rebase_tileset_paths(base_dir.as_ref(), &path));
                 self.saved_revision = saved_revision;
                 self.on_save(path);
             }
@@ -212,6 +255,18 @@ impl App {
         }
     }
 
+    /// Open the platform file manager at the folder containing the project file, if it
+    /// has been saved
+    pub fn open_project_folder(&mut self) {
+        let Some(dir) = self.save_path.as_ref().and_then(|path| path.parent()) else {
+            self.show_error_modal("The project hasn't been saved, so it has no folder.");
+            return;
+        };
+        if let Err(e) = file_manager::open_folder(dir) {
+            self.show_error_modal(&e.to_string());
+        }
+    }
+
     pub fn check_data_loss_then_quit(&mut self, ctx: &egui::Context) {
         if self.may_have_unsaved_changes() {
             self.show_data_loss_modal(DataLossOperation::Quit);
@@ -3,12 +3,12 @@ use crate::{
         App, UNIQUE_ID, USE_STORAGE,
         files::{OpenContext, StateSource},
     },
+    cli::file_argument,
     instance::create_ipc_listener,
     ui::egui_utils,
-    ui::theme::{self, DEFAULT_THEME},
+    ui::theme,
 };
-use camino::Utf8PathBuf;
-use egui::{ThemePreference, vec2};
+use egui::vec2;
 use egui_notify::Anchor;
 use std::env;
 
@@ -51,6 +51,15 @@ impl App {
             _ => Default::default(),
         };
 
+        // Restored data may come from an older or damaged version, so repair it before use
+        let repairs = data.validate_and_repair();
+        if !repairs.is_empty() {
+            log::warn!(
+                "Repaired stored application state: {}",
+                repairs.descriptions().join(", ")
+            );
+        }
+
         // Store ipc_listener to be polled on updates
         data.ipc_listener = ipc_listener;
 
@@ -64,12 +73,12 @@ impl App {
         data.update_texture_base_dir_from_file_path(data.save_path.clone());
 
         let args: Vec<String> = env::args().collect();
-        let filename = args.get(1);
 
-        // Try to open initial file, if we have one
-        let opened = if let Some(filename) = filename {
+        // Try to open initial file, if we have one. There's no open document yet,
+        // so there's no need to check for data loss before opening.
+        let opened = if let Some(path) = file_argument(&args) {
             // If we were called with a filename, try to open it
-            data.open_document(Utf8PathBuf::from(filename), OpenContext::FileArgument)
+            data.open_document(path, OpenContext::FileArgument)
         } else if let Some(save_path) = data.save_path.clone() {
             // If we have a save path from stored application state, try to load the project
             // Note this shows error modal if load fails, which is fine
@@ -88,8 +97,8 @@ impl App {
         // Note, loading data from storage also loads and applies egui theme settings,
         // so we need to set the custom theme afterwards - there may be a neater
         // way of doing this
-        theme::apply_theme(&cc.egui_ctx, DEFAULT_THEME);
-        cc.egui_ctx.set_theme(ThemePreference::Dark);
+        theme::apply_themes(&cc.egui_ctx);
+        cc.egui_ctx.set_theme(data.settings.theme.preference());
 
         egui_utils::replace_fonts(&cc.egui_ctx);
 
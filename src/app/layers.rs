@@ -1,6 +1,50 @@
-use crate::{app::App, selection::ShiftDirection};
+use crate::{app::App, data::action::Action, selection::ShiftDirection, ui::file_dialog};
 
 impl App {
+    /// Leave solo mode if it's active, otherwise make the first selected layer solo,
+    /// see [`crate::data::map_edit_state::MapEditState::toggle_solo_layer`]
+    pub fn toggle_solo_selected_layer(&mut self) {
+        if let Some(me) = self.selected_map_editing_mut() {
+            if me.edit.solo_layer.is_some() {
+                me.edit.solo_layer = None;
+            } else if let Some(layer_index) = me.selected_layer_indices().first() {
+                if let Some(id) = me.map.tiles().layer_id(*layer_index) {
+                    me.edit.toggle_solo_layer(me.map, id);
+                }
+            }
+        }
+    }
+
+    /// Toggle the visibility of the selected layers on the selected map. All selected
+    /// layers are set to the opposite of the first selected layer's current visibility,
+    /// so a mix of visible and hidden layers ends up consistent.
+    pub fn toggle_selected_layer_visible(&mut self) {
+        let Some(map_id) = self.edit.selected_map_id else {
+            return;
+        };
+        let Some(me) = self.selected_map_editing_mut() else {
+            return;
+        };
+        let selection = &me.edit.layer_selection;
+        let layers: Vec<_> = me
+            .map
+            .tiles()
+            .layers()
+            .filter(|layer| selection.is_selected(layer.id()))
+            .map(|layer| (layer.id(), layer.visible()))
+            .collect();
+        if let Some((_, first_visible)) = layers.first() {
+            let visible = !first_visible;
+            for (layer_id, _) in layers {
+                self.act(Action::SetLayerVisible {
+                    map_id,
+                    layer_id,
+                    visible,
+                });
+            }
+        }
+    }
+
     pub fn selected_map_toggle_select_all_layers(&mut self) {
         if let Some(me) = self.selected_map_editing_mut() {
             me.edit.layer_selection.toggle_select_all(me.map.tiles());
@@ -19,6 +63,31 @@ impl App {
         }
     }
 
+    /// Pick a CSV file of tile indices, and import it as a new layer on the selected map,
+    /// using the selected tileset
+    pub fn pick_layer_csv_file_to_import(&mut self) {
+        let Some(map_id) = self.edit.selected_map_id else {
+            self.show_error_modal("No map selected to import a layer into.");
+            return;
+        };
+        let Some(tileset_id) = self.edit.selected_tileset_id else {
+            self.show_error_modal("No tileset selected to use for the imported layer.");
+            return;
+        };
+        match file_dialog::pick_file_with_extension(
+            file_dialog::CSV_NAME,
+            file_dialog::CSV_EXTENSION,
+        ) {
+            Ok(Some(path)) => self.act(Action::ImportLayerCsv {
+                map_id,
+                path,
+                tileset_id,
+            }),
+            Ok(None) => {}
+            Err(e) => self.show_error_modal(&e.to_string()),
+        }
+    }
+
     pub fn select_first_layer(&mut self) {
         if let Some(me) = self.selected_map_editing_mut() {
             me.edit.layer_selection.clear();
@@ -5,15 +5,35 @@ use crate::{
         map_edit_state::MapEditState,
         maps::{Map, MapId},
         mode::Mode,
+        palette::PaletteIndex,
         resources::Resources,
         stamp::{Stamp, TileLocation},
-        tiles::{Tiles, tile_color::UserColor},
+        tiles::{
+            Tile, TileSource, Tiles,
+            layer_tiles::ResizeAnchor,
+            tile_color::{TileColor, UserColor},
+        },
+        tilesets::TilesetId,
+        wang_set::WangSet,
+    },
+    geom::{
+        ellipse::ellipse_positions,
+        i32pos2::{I32Pos2, i32pos2},
+        line::line_positions,
+        transform::Transform,
+        u32pos2::{U32Pos2, u32pos2},
+        u32rect::U32Rect,
+        u32size2::U32Size2,
     },
-    geom::{i32pos2::I32Pos2, u32pos2::u32pos2, u32size2::U32Size2},
     selection::{apply_default_selection, apply_default_value_per_selectable_id},
     ui::tileset_textures::TilesetTextures,
 };
-use egui::Response;
+use camino::Utf8Path;
+use egui::{
+    Response,
+    ahash::{HashSet, HashSetExt},
+};
+use std::collections::BTreeMap;
 
 pub struct MapEditing<'a> {
     pub map: &'a mut Map,
@@ -56,6 +76,15 @@ impl<'a> MapEditing<'a> {
         self.edit.layer_selection.select_only(layer_id);
     }
 
+    pub fn import_layer_csv(&mut self, path: &Utf8Path, tileset_id: TilesetId) -> eyre::Result<()> {
+        let layer_id =
+            self.map
+                .tiles
+                .import_layer_csv(path, tileset_id, self.resources.tilesets())?;
+        self.edit.layer_selection.select_only(layer_id);
+        Ok(())
+    }
+
     pub fn delete_selected_layers(&mut self) -> bool {
         self.edit
             .layer_selection
@@ -95,6 +124,44 @@ impl<'a> MapEditing<'a> {
         }
     }
 
+    /// Merge the single selected layer into the layer immediately below it, where
+    /// tiles on the selected layer replace those below. This uses the stored tiles,
+    /// so works the same whether or not the layers are visible. Returns false, making
+    /// no change, unless exactly one layer is selected and it isn't the bottom layer.
+    pub fn merge_down(&mut self) -> bool {
+        let Some(upper_index) = self.merge_down_layer_index() else {
+            return false;
+        };
+        let lower_index = upper_index + 1;
+        let tiles = self.map.tiles_mut();
+        for pos in tiles.map_positions() {
+            if let Ok(Some(tile)) = tiles.tile_checked(upper_index, pos) {
+                // Positions come from the map, so are in range for both layers
+                let _ = tiles.set_tile_checked(lower_index, pos, Some(tile));
+            }
+        }
+        let lower_layer_id = tiles.layer_id(lower_index);
+        tiles.remove_layer_by_index(upper_index);
+
+        // Select the layer we merged into
+        if let Some(id) = lower_layer_id {
+            self.edit.layer_selection.select_only(id);
+        }
+        true
+    }
+
+    /// The index of the layer to merge down, if [`MapEditing::merge_down`] can be used
+    fn merge_down_layer_index(&self) -> Option<usize> {
+        match self.selected_layer_indices().as_slice() {
+            [index] if index + 1 < self.map.tiles().layer_count() => Some(*index),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn can_merge_down(&self) -> bool {
+        self.merge_down_layer_index().is_some()
+    }
+
     pub fn can_move_selected_layers_higher(&self) -> bool {
         self.selected_layer_indices()
             .first()
@@ -196,10 +263,40 @@ impl<'a> MapEditing<'a> {
         // use map_hovered, converted to a signed position
         scene_hovered = scene_hovered.or(map_hovered.map(|p| p.into()));
 
+        // Moving the pointer switches back from the keyboard cursor to pointer hover,
+        // otherwise the keyboard cursor replaces the hover position
+        if scene_response.ctx.input(|i| i.pointer.is_moving()) {
+            self.edit.cursor = None;
+        }
+        if let Some(cursor) = self.edit.cursor {
+            map_hovered = Some(cursor);
+            scene_hovered = Some(cursor.into());
+        }
+
         self.edit.map_hovered = map_hovered;
         self.edit.map_scene_hovered = scene_hovered;
     }
 
+    /// Move the keyboard cursor by the specified shift, constrained to the map.
+    /// If the cursor is not in use, it starts from the hovered position if any,
+    /// or the top left of the map otherwise.
+    pub fn move_cursor(&mut self, shift: I32Pos2) {
+        let map_size = self.map.tiles().map_size();
+        if map_size.w > 0 && map_size.h > 0 {
+            let start: I32Pos2 = match self.edit.cursor.or(self.edit.map_hovered) {
+                Some(pos) => pos.into(),
+                None => i32pos2(0, 0) - shift,
+            };
+            let pos = start + shift;
+            let x = pos.x.clamp(0, map_size.w as i32 - 1) as u32;
+            let y = pos.y.clamp(0, map_size.h as i32 - 1) as u32;
+            let cursor = u32pos2(x, y);
+            self.edit.cursor = Some(cursor);
+            self.edit.map_hovered = Some(cursor);
+            self.edit.map_scene_hovered = Some(cursor.into());
+        }
+    }
+
     pub fn erase_selected_visible_tiles(&mut self) -> bool {
         let mut changed = false;
         for layer_index in self.selected_visible_layer_indices() {
@@ -212,6 +309,147 @@ impl<'a> MapEditing<'a> {
         changed
     }
 
+    /// Place the given tile on every selected cell at the boundary of the selection
+    /// (see [`TileSelection::boundary`]), on all selected, visible layers.
+    /// Returns true if any tiles were changed.
+    ///
+    /// [`TileSelection::boundary`]: crate::data::tiles::tile_selection::TileSelection::boundary
+    pub fn stroke_selection(&mut self, tile: Tile) -> bool {
+        let mut changed = false;
+        if self.resources.is_tile_valid(&tile) {
+            let boundary = self.edit.selection().boundary();
+            for layer_index in self.selected_visible_layer_indices() {
+                for pos in boundary.iter() {
+                    if self.map.tiles.set_tile(layer_index, *pos, Some(tile)) {
+                        changed = true;
+                    }
+                }
+            }
+        }
+        changed
+    }
+
+    /// Find the [`WangSet`] containing the tile, if any
+    fn wang_set_for_tile(&self, tile: &Tile) -> Option<&WangSet> {
+        self.resources
+            .tilesets()
+            .get_by_id(tile.source.tileset_id)
+            .and_then(|tileset| tileset.wang_set.as_deref())
+            .filter(|wang_set| wang_set.contains(tile.source.tile_index))
+    }
+
+    /// Auto-tile the specified positions and their neighbours, on selected,
+    /// visible layers. Each of these cells containing a tile from a [`WangSet`]
+    /// is replaced by the tile from that set matching its corners. The replacement
+    /// keeps the tile's color, but its transform is reset to [`Transform::None`],
+    /// since the corner tiles are only chosen to match in their untransformed form.
+    /// Cells outside the map are treated as terrain, so that terrain extending
+    /// to the edge of the map continues seamlessly.
+    /// Returns true if any tiles were changed.
+    pub fn autotile_positions(&mut self, positions: &[U32Pos2]) -> bool {
+        let map_size = self.map.tiles().map_size();
+
+        // Each position, and all cells around it, may need its tile changing
+        let mut targets = HashSet::new();
+        for pos in positions.iter() {
+            let ipos: I32Pos2 = pos.into();
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    if let Ok(target) = U32Pos2::try_from(ipos + i32pos2(dx, dy)) {
+                        if map_size.contains(target.into()) {
+                            targets.insert(target);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut changed = false;
+        for layer_index in self.selected_visible_layer_indices() {
+            // Find all changes first, so that results don't depend on the order
+            // we visit cells
+            let mut new_tiles = vec![];
+            let tiles = self.map.tiles();
+            for target in targets.iter() {
+                if let Some(tile) = tiles.tile(layer_index, *target) {
+                    if let Some(wang_set) = self.wang_set_for_tile(&tile) {
+                        let is_terrain = |pos: I32Pos2| match U32Pos2::try_from(pos) {
+                            Ok(upos) if map_size.contains(pos) => {
+                                tiles.tile(layer_index, upos).is_some_and(|t| {
+                                    t.source.tileset_id == tile.source.tileset_id
+                                        && wang_set.contains(t.source.tile_index)
+                                })
+                            }
+                            _ => true,
+                        };
+                        let corners = WangSet::corners_at(target.into(), is_terrain);
+                        if let Some(tile_index) = wang_set.tile_for_corners(corners) {
+                            let new_tile = Tile {
+                                source: TileSource {
+                                    tileset_id: tile.source.tileset_id,
+                                    tile_index,
+                                },
+                                color: tile.color,
+                                // A transformed corner tile would no longer match its corners
+                                transform: Transform::None,
+                            };
+                            new_tiles.push((*target, new_tile));
+                        }
+                    }
+                }
+            }
+
+            for (pos, tile) in new_tiles {
+                if self.map.tiles.set_tile(layer_index, pos, Some(tile)) {
+                    changed = true;
+                }
+            }
+        }
+        changed
+    }
+
+    /// Auto-tile the selection - see [`MapEditing::autotile_positions`]
+    pub fn autotile_selection(&mut self) -> bool {
+        let positions: Vec<U32Pos2> = self.edit.selection().iter().copied().collect();
+        self.autotile_positions(&positions)
+    }
+
+    /// Reset the transform of every tile in the selection to [`Transform::None`], on
+    /// selected, visible layers. Returns true if any tiles were changed.
+    pub fn clear_transforms_in_selection(&mut self) -> bool {
+        let mut changed = false;
+        for layer_index in self.selected_visible_layer_indices() {
+            for pos in self.edit.selection().iter() {
+                if let Some(tile) = self.map.tiles.tile(layer_index, *pos) {
+                    let tile = tile.with_exact_transform(Transform::None);
+                    if self.map.tiles.set_tile(layer_index, *pos, Some(tile)) {
+                        changed = true;
+                    }
+                }
+            }
+        }
+        changed
+    }
+
+    /// Change the color of every tile in the selection to the palette color at
+    /// `index`, on selected, visible layers, leaving empty cells empty. Returns true
+    /// if any tiles were changed.
+    pub fn recolor_selection(&mut self, index: PaletteIndex) -> bool {
+        let color = TileColor::from_palette_index(index);
+        let mut changed = false;
+        for layer_index in self.selected_visible_layer_indices() {
+            for pos in self.edit.selection().iter() {
+                if let Some(tile) = self.map.tiles.tile(layer_index, *pos) {
+                    let tile = tile.with_color(color);
+                    if self.map.tiles.set_tile(layer_index, *pos, Some(tile)) {
+                        changed = true;
+                    }
+                }
+            }
+        }
+        changed
+    }
+
     pub fn selection_as_stamp(&self) -> Stamp {
         let mut stamp = Stamp::new();
         let selection = self.edit.selection();
@@ -242,6 +480,64 @@ impl<'a> MapEditing<'a> {
         stamp
     }
 
+    /// Select exactly the cells containing a tile in the specified layer, e.g. to copy an
+    /// irregular region. The drag rect is set to the bounding box of the selected cells.
+    /// Returns true if any cells were selected.
+    pub fn select_nonempty(&mut self, layer_index: usize) -> bool {
+        let tiles = self.map.tiles();
+        let selection = self.edit.selection_mut();
+        selection.clear();
+        for pos in tiles.map_positions() {
+            if tiles.tile(layer_index, pos).is_some() {
+                selection.add_selection(pos);
+            }
+        }
+        selection.drag_rect = selection.range_rect();
+        !selection.is_empty()
+    }
+
+    /// Create a new [`Map`] from [`MapEditing::selection_as_stamp`], sized to the bounding box
+    /// of the selection, with a layer for each selected visible layer the tiles are taken from.
+    /// Returns [`None`] if there is no selection.
+    pub fn selection_as_map(&self) -> Option<Map> {
+        let selection_rect = self.edit.selection().range_rect()?;
+        let stamp = self.selection_as_stamp();
+        let layer_tiles = self.map.tiles();
+
+        let mut map = Map::new(
+            format!("{} selection", self.map.name),
+            selection_rect.size(),
+            layer_tiles.tile_size(),
+            layer_tiles.background(),
+        );
+        for (stamp_layer_index, layer_index) in
+            self.selected_visible_layer_indices().iter().enumerate()
+        {
+            map.tiles.insert_layer(
+                stamp_layer_index,
+                layer_tiles
+                    .layer_name(*layer_index)
+                    .map(|name| name.as_str())
+                    .unwrap_or_default(),
+                true,
+                layer_tiles.layer_opacity(*layer_index),
+            );
+        }
+
+        // Stamp positions are relative to the center of the selection, so offset
+        // them to be relative to the top left of the selection instead
+        let center: I32Pos2 = selection_rect.center().into();
+        let min: I32Pos2 = selection_rect.min.into();
+        for (location, tile) in stamp.tiles.iter() {
+            let position = location.position + center - min;
+            if let (Ok(x), Ok(y)) = (u32::try_from(position.x), u32::try_from(position.y)) {
+                map.tiles
+                    .set_tile(location.stamp_layer_index, u32pos2(x, y), Some(*tile));
+            }
+        }
+        Some(map)
+    }
+
     fn reset_zoom(&mut self) {
         self.edit.scene_rect = self.map.tiles.screen_rect();
     }
@@ -253,12 +549,27 @@ impl<'a> MapEditing<'a> {
     pub(crate) fn can_merge_selected_layers(&self) -> bool {
         self.selected_layer_indices().len() > 1
     }
+
+    /// If exactly two layers are selected, their indices
+    pub(crate) fn selected_layer_pair(&self) -> Option<(usize, usize)> {
+        match self.selected_layer_indices().as_slice() {
+            [a, b] => Some((*a, *b)),
+            _ => None,
+        }
+    }
+
+    pub fn swap_layers(&mut self, a: usize, b: usize) -> bool {
+        self.map.tiles.swap_layers(a, b)
+    }
 }
 
 impl App {
-    pub fn add_map(&mut self, map: Map) {
+    /// Add `map` and select it. Errors if the map is over the maximum map size in settings.
+    pub fn add_map(&mut self, map: Map) -> eyre::Result<()> {
+        self.settings.check_map_size(map.tiles.map_size())?;
         let id = self.state.maps.push_map(map);
         self.edit.selected_map_id = Some(id);
+        Ok(())
     }
 
     /// Call [`apply_default_selection`] on our map selection.
@@ -308,10 +619,14 @@ impl App {
 
     pub(super) fn draw(&mut self, map_id: MapId, pos: I32Pos2) -> bool {
         let mut change = false;
+        let autotile = self.edit.autotile;
         // If map doesn't exist or has no edit state, there's nothing to be done.
         // Note that missing edit state is interpreted as the default edit state, with no selection
-        if let Some(me) = self.map_editing_mut(map_id) {
+        if let Some(mut me) = self.map_editing_mut(map_id) {
             let stamp_layer_index_to_layer_index = me.stamp_layer_index_to_layer_index();
+            let empty_cells_only = me.edit.draw_empty_cells_only;
+            let pos = pos + me.stamp.anchor_offset(me.edit.stamp_anchor);
+            let mut drawn_positions = vec![];
 
             for (location, tile) in me.stamp.tiles.iter() {
                 if let Ok(upos) = (location.position + pos).try_into() {
@@ -320,14 +635,44 @@ impl App {
                     if let Some(layer_index) =
                         stamp_layer_index_to_layer_index.get(location.stamp_layer_index)
                     {
+                        // Tilesets are shared by all maps in the document, and the stamp is
+                        // cleared when a different document is used, so stamp tiles copied from
+                        // any map are valid here. The only invalid tiles are those from a
+                        // tileset deleted since the stamp was made - these have no tileset
+                        // data left to re-import, so they are skipped.
                         if me.resources.is_tile_valid(tile)
+                            && (!empty_cells_only
+                                || me.map.tiles.tile(*layer_index, upos).is_none())
                             && me.map.tiles.set_tile(*layer_index, upos, Some(*tile))
                         {
                             change = true;
+                            drawn_positions.push(upos);
                         }
                     }
                 }
             }
+
+            if autotile && !drawn_positions.is_empty() {
+                me.autotile_positions(&drawn_positions);
+            }
+        }
+        change
+    }
+
+    /// Draw the stamp at each position along a line, see [`line_positions`]
+    pub(super) fn draw_line(&mut self, map_id: MapId, start: I32Pos2, end: I32Pos2) -> bool {
+        let mut change = false;
+        for pos in line_positions(start, end) {
+            change |= self.draw(map_id, pos);
+        }
+        change
+    }
+
+    /// Draw the stamp at each cell of an ellipse, see [`ellipse_positions`]
+    pub(super) fn draw_ellipse(&mut self, map_id: MapId, rect: U32Rect, filled: bool) -> bool {
+        let mut change = false;
+        for pos in ellipse_positions(rect, filled) {
+            change |= self.draw(map_id, pos.into());
         }
         change
     }
@@ -346,42 +691,220 @@ impl App {
         changed
     }
 
+    /// Update the map's properties, resizing it if needed. Errors without changing
+    /// the map if `map_size` is over the maximum map size in settings.
     pub(super) fn update_map(
         &mut self,
         map_id: MapId,
         name: String,
         map_size: U32Size2,
         tile_size: U32Size2,
-        background_color: UserColor,
-    ) -> bool {
+        background_color: Option<UserColor>,
+        default_tile: Option<Tile>,
+    ) -> eyre::Result<bool> {
+        self.settings.check_map_size(map_size)?;
         if let Some(map) = self.state.maps.get_by_id_mut(map_id) {
             if map.name() != name
                 || map.tiles.map_size() != map_size
                 || map.tiles.tile_size() != tile_size
-                || map.tiles.background() != background_color
+                || map.tiles.background != background_color
+                || map.tiles.default_tile != default_tile
             {
                 map.name = name;
                 map.tiles.resize(map_size);
                 map.tiles.set_tile_size(tile_size);
                 map.tiles.background = background_color;
+                map.tiles.default_tile = default_tile;
 
                 if let Some(map_edit_state) = self.edit.map_edit_state_by_id.get_mut(&map_id) {
                     map_edit_state.selection_mut().clear();
                 }
 
-                true
+                Ok(true)
             } else {
-                false
+                Ok(false)
             }
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Resize every map to `map_size`. Errors without changing any map if `map_size`
+    /// is over the maximum map size in settings.
+    pub(super) fn resize_all_maps(
+        &mut self,
+        map_size: U32Size2,
+        anchor: ResizeAnchor,
+    ) -> eyre::Result<bool> {
+        self.settings.check_map_size(map_size)?;
+        let mut changed = false;
+        for map in self.state.maps.iter_mut() {
+            if map.tiles.map_size() != map_size {
+                map.tiles.resize_anchored(map_size, anchor);
+                if let Some(map_edit_state) = self.edit.map_edit_state_by_id.get_mut(&map.id()) {
+                    map_edit_state.selection_mut().clear();
+                }
+                changed = true;
+            }
+        }
+        Ok(changed)
+    }
+
+    pub(super) fn set_map_metadata(
+        &mut self,
+        map_id: MapId,
+        metadata: BTreeMap<String, String>,
+    ) -> bool {
+        if let Some(map) = self.state.maps.get_by_id_mut(map_id)
+            && map.metadata != metadata
+        {
+            map.metadata = metadata;
+            true
         } else {
             false
         }
     }
 
+    /// Make sure maps that inherit their background use the background from settings
+    pub(super) fn apply_default_map_background(&mut self) {
+        self.state
+            .set_inherited_backgrounds(self.settings.default_map_background);
+    }
+
     pub(super) fn delete_map(&mut self, id: MapId) -> bool {
         self.state.maps.delete_by_id(id)
     }
 
+    /// True if there is a selected map with a non-empty selection, and a tile
+    /// in the stamp to stroke the selection with
+    pub fn can_stroke_selection(&self) -> bool {
+        self.selected_map_edit_state()
+            .map(|edit| !edit.selection().is_empty())
+            .unwrap_or(false)
+            && self.edit.stamp.primary_tile().is_some()
+    }
+
+    /// Stroke the selection of the selected map (if any) with the primary tile
+    /// of the current stamp (if any)
+    pub fn stroke_selection(&mut self) {
+        if let (Some(map_id), Some(tile)) =
+            (self.edit.selected_map_id, self.edit.stamp.primary_tile())
+        {
+            self.act(Action::StrokeSelection { map_id, tile });
+        }
+    }
+
+    /// Move the keyboard cursor of the selected map (if any)
+    pub fn move_map_cursor(&mut self, shift: I32Pos2) {
+        if let Some(mut me) = self.selected_map_editing_mut() {
+            me.move_cursor(shift);
+        }
+    }
+
+    /// Select the palette color of the topmost displayed tile at the hovered position
+    /// (or keyboard cursor) of the selected map. If the tile uses a color that isn't
+    /// in the palette, it is added to the end of the palette and selected, as an
+    /// undoable change, with a notification saying so.
+    pub fn eyedrop_palette_color(&mut self) {
+        let Some(color) = self
+            .selected_map()
+            .zip(self.selected_map_edit_state())
+            .and_then(|(map, edit)| edit.displayed_tile_at_pointer(map))
+            .map(|tile| tile.color)
+        else {
+            return;
+        };
+
+        match color {
+            TileColor::Default => {}
+            TileColor::Palette { index } => {
+                if index.index() < self.state.resources.palette().len() {
+                    self.edit.select_palette_index(index);
+                }
+            }
+            TileColor::UserColor(user_color) => {
+                let palette = self.state.resources.palette();
+                if let Some(index) = palette.index_of_color(user_color) {
+                    self.edit.select_palette_index(index);
+                } else {
+                    let index = PaletteIndex::new(palette.len());
+                    let message = format!(
+                        "Added color #{} to the end of the palette, undo to remove it",
+                        user_color.as_hex_string()
+                    );
+                    let palette = palette.with_color_appended(user_color);
+                    self.act(Action::ReplacePalette {
+                        palette,
+                        remap_to_nearest: false,
+                    });
+                    self.edit.select_palette_index(index);
+                    self.success(message);
+                }
+            }
+        }
+    }
+
+    /// Apply the current mode at the keyboard cursor of the selected map (if the
+    /// cursor is in use) - drawing the stamp, erasing, or adding to the selection
+    pub fn apply_at_map_cursor(&mut self) {
+        let mode = self.edit.mode;
+        if let (Some(map_id), Some(cursor)) = (
+            self.edit.selected_map_id,
+            self.selected_map_edit_state().and_then(|edit| edit.cursor),
+        ) {
+            let pos = cursor.into();
+            match mode {
+                Mode::Draw => self.act(Action::Draw {
+                    map_id,
+                    pos,
+                    complete: true,
+                }),
+                Mode::Line => self.act(Action::DrawLine {
+                    map_id,
+                    start: pos,
+                    end: pos,
+                }),
+                Mode::Ellipse => self.act(Action::DrawEllipse {
+                    map_id,
+                    rect: U32Rect::from_pos(cursor),
+                    filled: true,
+                }),
+                Mode::Erase => self.act(Action::Erase {
+                    map_id,
+                    pos,
+                    complete: true,
+                }),
+                Mode::Select => {
+                    if let Some(edit) = self.selected_map_edit_state_mut() {
+                        edit.selection_mut().add_selection(cursor);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Auto-tile the selection of the selected map (if any)
+    pub fn autotile_selection(&mut self) {
+        if let Some(map_id) = self.edit.selected_map_id {
+            self.act(Action::AutotileSelection { map_id });
+        }
+    }
+
+    pub fn clear_transforms_in_selection(&mut self) {
+        if let Some(map_id) = self.edit.selected_map_id {
+            self.act(Action::ClearTransformsInSelection { map_id });
+        }
+    }
+
+    /// Recolor the selection of the selected map with the selected palette color,
+    /// see [`MapEditing::recolor_selection`]
+    pub fn recolor_selection(&mut self) {
+        if let Some(map_id) = self.edit.selected_map_id {
+            let index = self.edit.selected_palette_index();
+            self.act(Action::RecolorSelection { map_id, index });
+        }
+    }
+
     pub fn delete_selected_map(&mut self) {
         if let Some(id) = self.edit.selected_map_id {
             self.act(Action::DeleteMap { id });
@@ -431,3 +954,467 @@ impl App {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        data::{
+            tiles::{TileIndex, tile_color::TileColor},
+            tilesets::{TilesetId, Tilesets},
+        },
+        geom::{u32rect::U32Rect, u32size2::u32size2},
+    };
+
+    use super::*;
+
+    fn tile(index: u32) -> Tile {
+        Tile {
+            source: TileSource {
+                tileset_id: TilesetId::ONE,
+                tile_index: TileIndex::new(index),
+            },
+            color: TileColor::Default,
+            transform: Transform::None,
+        }
+    }
+
+    fn map_and_edit_state(selection: U32Rect) -> (Map, MapEditState) {
+        let map = Map::new_with_layer(
+            "Map".to_string(),
+            u32size2(8, 8),
+            u32size2(8, 8),
+            UserColor::BLACK,
+        );
+        let mut edit = MapEditState::default();
+        edit.apply_invariants(&map);
+        edit.selection_mut().add_rect_selection(selection);
+        (map, edit)
+    }
+
+    /// The parts of a [`MapEditing`] owned outside the map and its edit state
+    #[derive(Default)]
+    struct EditingParts {
+        stamp: Stamp,
+        resources: Resources,
+        textures: TilesetTextures,
+    }
+
+    fn map_editing<'a>(
+        map: &'a mut Map,
+        edit: &'a mut MapEditState,
+        mode: Mode,
+        parts: &'a mut EditingParts,
+    ) -> MapEditing<'a> {
+        MapEditing {
+            map,
+            edit,
+            mode,
+            stamp: &mut parts.stamp,
+            resources: &mut parts.resources,
+            textures: &parts.textures,
+        }
+    }
+
+    #[test]
+    fn stroke_selection_produces_hollow_rect() {
+        let (mut map, mut edit) =
+            map_and_edit_state(U32Rect::from_two_pos(u32pos2(1, 2), u32pos2(5, 6)));
+        let mut parts = EditingParts {
+            resources: Resources {
+                tilesets: Tilesets::builtin_tilesets(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let tile = tile(3);
+
+        let mut me = map_editing(&mut map, &mut edit, Mode::Select, &mut parts);
+
+        assert!(me.stroke_selection(tile));
+
+        // Repeating the stroke makes no further changes
+        assert!(!me.stroke_selection(tile));
+
+        for pos in map.tiles().map_positions() {
+            let in_rect = (1..=5).contains(&pos.x) && (2..=6).contains(&pos.y);
+            let on_edge = pos.x == 1 || pos.x == 5 || pos.y == 2 || pos.y == 6;
+            let expected = if in_rect && on_edge { Some(tile) } else { None };
+            assert_eq!(map.tiles().tile(0, pos), expected, "at {:?}", pos);
+        }
+    }
+
+    #[test]
+    fn merge_down_keeps_upper_tiles() -> eyre::Result<()> {
+        let (mut map, mut edit) = map_and_edit_state(U32Rect::from_pos(u32pos2(0, 0)));
+        // Layer 0 is the top layer, add an upper layer above the bottom layer
+        let bottom_id = map.tiles().layer_id(0);
+        let upper_id = map.tiles.insert_layer(0, "Upper", true, None);
+        map.tiles.set_tile(0, u32pos2(0, 0), Some(tile(1)));
+        map.tiles.set_tile(0, u32pos2(1, 0), Some(tile(2)));
+        map.tiles.set_tile(1, u32pos2(1, 0), Some(tile(3)));
+        map.tiles.set_tile(1, u32pos2(2, 0), Some(tile(4)));
+
+        let mut parts = EditingParts::default();
+        let mut me = map_editing(&mut map, &mut edit, Mode::Select, &mut parts);
+
+        // Bottom layer can't be merged down
+        if let Some(id) = bottom_id {
+            me.edit.layer_selection.select_only(id);
+        }
+        assert!(!me.merge_down());
+
+        me.edit.layer_selection.select_only(upper_id);
+        assert!(me.merge_down());
+        assert_eq!(me.map.tiles().layer_count(), 1);
+        assert_eq!(me.map.tiles().layer_id(0), bottom_id);
+        assert_eq!(
+            me.map.tiles().tile_checked(0, u32pos2(0, 0))?,
+            Some(tile(1))
+        );
+        assert_eq!(
+            me.map.tiles().tile_checked(0, u32pos2(1, 0))?,
+            Some(tile(2))
+        );
+        assert_eq!(
+            me.map.tiles().tile_checked(0, u32pos2(2, 0))?,
+            Some(tile(4))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn clear_transforms_in_selection_only_changes_selected_tiles() {
+        let (mut map, mut edit) =
+            map_and_edit_state(U32Rect::from_two_pos(u32pos2(0, 0), u32pos2(1, 0)));
+        let flipped = tile(1).with_exact_transform(Transform::MirrorX);
+        for x in 0..3 {
+            map.tiles.set_tile(0, u32pos2(x, 0), Some(flipped));
+        }
+
+        let mut parts = EditingParts::default();
+        let mut me = map_editing(&mut map, &mut edit, Mode::Select, &mut parts);
+
+        assert!(me.clear_transforms_in_selection());
+        assert!(!me.clear_transforms_in_selection());
+
+        assert_eq!(map.tiles().tile(0, u32pos2(0, 0)), Some(tile(1)));
+        assert_eq!(map.tiles().tile(0, u32pos2(1, 0)), Some(tile(1)));
+        assert_eq!(map.tiles().tile(0, u32pos2(2, 0)), Some(flipped));
+    }
+
+    #[test]
+    fn recolor_selection_keeps_sources_and_empty_cells() {
+        let (mut map, mut edit) =
+            map_and_edit_state(U32Rect::from_two_pos(u32pos2(0, 0), u32pos2(2, 0)));
+        let flipped = tile(1).with_exact_transform(Transform::MirrorX);
+        map.tiles.set_tile(0, u32pos2(0, 0), Some(flipped));
+        map.tiles.set_tile(0, u32pos2(1, 0), Some(tile(2)));
+        map.tiles.set_tile(0, u32pos2(3, 0), Some(tile(3)));
+
+        let mut parts = EditingParts::default();
+        let mut me = map_editing(&mut map, &mut edit, Mode::Select, &mut parts);
+
+        let index = PaletteIndex::new(4);
+        assert!(me.recolor_selection(index));
+        assert!(!me.recolor_selection(index));
+
+        let color = TileColor::from_palette_index(index);
+        let tiles = map.tiles();
+        assert_eq!(
+            tiles.tile(0, u32pos2(0, 0)),
+            Some(flipped.with_color(color))
+        );
+        assert_eq!(
+            tiles.tile(0, u32pos2(1, 0)),
+            Some(tile(2).with_color(color))
+        );
+        assert_eq!(tiles.tile(0, u32pos2(2, 0)), None);
+        assert_eq!(tiles.tile(0, u32pos2(3, 0)), Some(tile(3)));
+    }
+
+    #[test]
+    fn autotile_selection_uses_corner_tiles() {
+        // Select a 3x3 block and fill it with terrain
+        let (mut map, mut edit) =
+            map_and_edit_state(U32Rect::from_two_pos(u32pos2(2, 2), u32pos2(4, 4)));
+        for pos in edit.selection().iter() {
+            map.tiles.set_tile(0, *pos, Some(tile(15)));
+        }
+        // Transforms are reset, so the corners still match
+        map.tiles.set_tile(
+            0,
+            u32pos2(2, 2),
+            Some(tile(15).with_exact_transform(Transform::MirrorX)),
+        );
+
+        // Use a wang set where the tile index is the same as the corner mask
+        let mut tilesets = Tilesets::builtin_tilesets();
+        let mut tileset = tilesets.get(0).cloned().unwrap_or_default();
+        let mut wang_set = WangSet::new();
+        for (corners, tile_index) in wang_set.tiles.iter_mut().enumerate() {
+            *tile_index = Some(TileIndex::new(corners as u32));
+        }
+        tileset.wang_set = Some(Box::new(wang_set));
+        tilesets.update_tileset(TilesetId::ONE, tileset);
+
+        let mut parts = EditingParts {
+            resources: Resources {
+                tilesets,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut me = map_editing(&mut map, &mut edit, Mode::Select, &mut parts);
+
+        assert!(me.autotile_selection());
+
+        let expected = [[4, 12, 8], [6, 15, 9], [2, 3, 1]];
+        for (y, row) in expected.iter().enumerate() {
+            for (x, index) in row.iter().enumerate() {
+                let pos = u32pos2(x as u32 + 2, y as u32 + 2);
+                assert_eq!(map.tiles().tile(0, pos), Some(tile(*index)), "at {:?}", pos);
+            }
+        }
+    }
+
+    #[test]
+    fn select_nonempty_selects_scattered_tiles() {
+        let (mut map, mut edit) = map_and_edit_state(U32Rect::from_pos(u32pos2(7, 7)));
+        let scattered = [u32pos2(0, 0), u32pos2(3, 1), u32pos2(6, 2), u32pos2(1, 7)];
+        for pos in scattered {
+            map.tiles.set_tile(0, pos, Some(tile(1)));
+        }
+
+        let mut parts = EditingParts::default();
+        let mut me = map_editing(&mut map, &mut edit, Mode::Select, &mut parts);
+
+        // The previous selection is replaced
+        assert!(me.select_nonempty(0));
+        let mut selected: Vec<_> = me.edit.selection().iter().copied().collect();
+        selected.sort_by_key(|pos| (pos.y, pos.x));
+        assert_eq!(selected, scattered);
+        assert_eq!(
+            me.edit.selection().drag_rect,
+            Some(U32Rect::from_two_pos(u32pos2(0, 0), u32pos2(6, 7)))
+        );
+
+        me.map.tiles.insert_layer(0, "Empty", true, None);
+        assert!(!me.select_nonempty(0));
+        assert!(me.edit.selection().is_empty());
+        assert_eq!(me.edit.selection().drag_rect, None);
+    }
+
+    #[test]
+    fn selection_as_map_contains_selected_tiles() {
+        let (mut map, mut edit) =
+            map_and_edit_state(U32Rect::from_two_pos(u32pos2(2, 3), u32pos2(4, 4)));
+        map.tiles.set_tile(0, u32pos2(2, 3), Some(tile(1)));
+        map.tiles.set_tile(0, u32pos2(4, 4), Some(tile(2)));
+        // Outside the selection
+        map.tiles.set_tile(0, u32pos2(5, 5), Some(tile(3)));
+
+        let mut parts = EditingParts::default();
+        let me = map_editing(&mut map, &mut edit, Mode::Select, &mut parts);
+
+        let new_map = me.selection_as_map();
+        let new_tiles = new_map.as_ref().map(|map| map.tiles());
+        assert_eq!(new_tiles.map(|t| t.map_size()), Some(u32size2(3, 2)));
+        assert_eq!(new_tiles.map(|t| t.layer_count()), Some(1));
+        assert_eq!(
+            new_tiles.and_then(|t| t.layer_name(0).cloned()),
+            Some("New Layer".to_string())
+        );
+        assert_eq!(
+            new_tiles.and_then(|t| t.tile(0, u32pos2(0, 0))),
+            Some(tile(1))
+        );
+        assert_eq!(
+            new_tiles.and_then(|t| t.tile(0, u32pos2(2, 1))),
+            Some(tile(2))
+        );
+        assert_eq!(
+            new_tiles.map(|t| t.map_positions().filter_map(|p| t.tile(0, p)).count()),
+            Some(2)
+        );
+
+        // No map without a selection
+        me.edit.selection_mut().clear();
+        assert!(me.selection_as_map().is_none());
+    }
+
+    #[test]
+    fn move_cursor_is_constrained_to_map() {
+        let (mut map, mut edit) = map_and_edit_state(U32Rect::from_pos(u32pos2(0, 0)));
+        let mut parts = EditingParts::default();
+        let mut me = map_editing(&mut map, &mut edit, Mode::Draw, &mut parts);
+
+        // Cursor starts from hovered position
+        me.edit.map_hovered = Some(u32pos2(3, 3));
+        me.move_cursor(i32pos2(1, 0));
+        assert_eq!(me.edit.cursor, Some(u32pos2(4, 3)));
+        assert_eq!(me.edit.map_hovered, Some(u32pos2(4, 3)));
+
+        for _ in 0..10 {
+            me.move_cursor(i32pos2(1, 1));
+        }
+        assert_eq!(me.edit.cursor, Some(u32pos2(7, 7)));
+
+        for _ in 0..10 {
+            me.move_cursor(i32pos2(-1, 0));
+        }
+        assert_eq!(me.edit.cursor, Some(u32pos2(0, 7)));
+    }
+
+    #[test]
+    fn displayed_tile_at_pointer_skips_hidden_layers() {
+        let (mut map, mut edit) = map_and_edit_state(U32Rect::from_pos(u32pos2(0, 0)));
+        let pos = u32pos2(2, 3);
+        let top = map.tiles.insert_layer(0, "Top", true, None);
+        map.tiles.set_tile(0, pos, Some(tile(1)));
+        map.tiles.set_tile(1, pos, Some(tile(2)));
+
+        // No pointer, then the keyboard cursor, then the hovered position is used
+        assert_eq!(edit.displayed_tile_at_pointer(&map), None);
+        edit.cursor = Some(pos);
+        assert_eq!(edit.displayed_tile_at_pointer(&map), Some(tile(1)));
+        edit.map_hovered = Some(u32pos2(0, 0));
+        assert_eq!(edit.displayed_tile_at_pointer(&map), None);
+        edit.map_hovered = Some(pos);
+
+        // Hidden layers are skipped
+        if let Some(visible) = map.tiles.layer_visible_mut(0) {
+            *visible = false;
+        }
+        assert_eq!(edit.displayed_tile_at_pointer(&map), Some(tile(2)));
+
+        // Hidden layers can't be solo
+        edit.toggle_solo_layer(&map, top);
+        assert_eq!(edit.solo_layer, None);
+
+        // Only the solo layer is displayed
+        if let Some(visible) = map.tiles.layer_visible_mut(0) {
+            *visible = true;
+        }
+        if let Some(bottom) = map.tiles().layer_id(1) {
+            edit.toggle_solo_layer(&map, bottom);
+        }
+        assert_eq!(edit.displayed_tile_at_pointer(&map), Some(tile(2)));
+
+        // Hiding the solo layer leaves solo mode
+        if let Some(visible) = map.tiles.layer_visible_mut(1) {
+            *visible = false;
+        }
+        edit.apply_invariants(&map);
+        assert_eq!(edit.solo_layer, None);
+    }
+
+    #[test]
+    fn draw_empty_cells_only_keeps_existing_tiles() -> eyre::Result<()> {
+        let mut app = App::default();
+        app.state.resources.tilesets = Tilesets::builtin_tilesets();
+        let (mut map, _) = map_and_edit_state(U32Rect::from_pos(u32pos2(0, 0)));
+        map.tiles.set_tile(0, u32pos2(1, 0), Some(tile(1)));
+        let map_id = map.id();
+        app.add_map(map)?;
+        app.apply_invariants();
+        for x in 0..2 {
+            app.edit
+                .stamp
+                .insert(TileLocation::new(0, i32pos2(x, 0)), tile(2));
+        }
+
+        let drawn_tiles = |app: &App| {
+            app.state.maps.get_by_id(map_id).map(|map| {
+                [
+                    map.tiles().tile(0, u32pos2(0, 0)),
+                    map.tiles().tile(0, u32pos2(1, 0)),
+                ]
+            })
+        };
+
+        if let Some(edit) = app.selected_map_edit_state_mut() {
+            edit.draw_empty_cells_only = true;
+        }
+        assert!(app.draw(map_id, i32pos2(0, 0)));
+        assert_eq!(drawn_tiles(&app), Some([Some(tile(2)), Some(tile(1))]));
+
+        // Only the existing tile is left, so nothing more is drawn
+        assert!(!app.draw(map_id, i32pos2(0, 0)));
+
+        // Drawing normally replaces the existing tile
+        if let Some(edit) = app.selected_map_edit_state_mut() {
+            edit.draw_empty_cells_only = false;
+        }
+        assert!(app.draw(map_id, i32pos2(0, 0)));
+        assert_eq!(drawn_tiles(&app), Some([Some(tile(2)), Some(tile(2))]));
+        Ok(())
+    }
+
+    #[test]
+    fn eyedrop_adds_missing_color_to_palette() -> eyre::Result<()> {
+        let mut app = App::default();
+        let (mut map, _) = map_and_edit_state(U32Rect::from_pos(u32pos2(0, 0)));
+        let color = TileColor::UserColor(UserColor::new(1, 2, 3, 255));
+        map.tiles
+            .set_tile(0, u32pos2(1, 1), Some(tile(1).with_color(color)));
+        app.add_map(map)?;
+        app.apply_invariants();
+        if let Some(edit) = app.selected_map_edit_state_mut() {
+            edit.map_hovered = Some(u32pos2(1, 1));
+        }
+
+        let len = app.state.resources.palette().len();
+        app.eyedrop_palette_color();
+        assert_eq!(app.state.resources.palette().len(), len + 1);
+        assert_eq!(app.selected_palette_index(), PaletteIndex::new(len));
+
+        // Picking the color again selects it without adding it again
+        app.select_palette_index(PaletteIndex::new(0));
+        app.eyedrop_palette_color();
+        assert_eq!(app.state.resources.palette().len(), len + 1);
+        assert_eq!(app.selected_palette_index(), PaletteIndex::new(len));
+        Ok(())
+    }
+
+    #[test]
+    fn map_size_limit_is_enforced() -> eyre::Result<()> {
+        let mut app = App::default();
+        app.settings.max_map_tiles = 64;
+        let (map, _) = map_and_edit_state(U32Rect::from_pos(u32pos2(0, 0)));
+        let map_id = map.id();
+        app.add_map(map)?;
+
+        // Over the limit is refused without changing the map
+        assert!(
+            app.update_map(
+                map_id,
+                "Map".to_string(),
+                u32size2(9, 8),
+                u32size2(8, 8),
+                None,
+                None
+            )
+            .is_err()
+        );
+        assert!(
+            app.resize_all_maps(u32size2(8, 9), ResizeAnchor::default())
+                .is_err()
+        );
+        let map_size = |app: &App| app.state.maps.get_by_id(map_id).map(|m| m.tiles.map_size());
+        assert_eq!(map_size(&app), Some(u32size2(8, 8)));
+
+        // Within the limit is allowed
+        assert!(app.resize_all_maps(u32size2(4, 16), ResizeAnchor::default())?);
+        assert_eq!(map_size(&app), Some(u32size2(4, 16)));
+
+        let too_large = Map::new_with_layer(
+            "Large".to_string(),
+            u32size2(16, 16),
+            u32size2(8, 8),
+            UserColor::BLACK,
+        );
+        assert!(app.add_map(too_large).is_err());
+        assert_eq!(app.state.maps.iter().count(), 1);
+        Ok(())
+    }
+}
@@ -1,17 +1,24 @@
+use camino::Utf8PathBuf;
+use egui::Context;
+
 use crate::{
     app::App,
     data::palette::Palette,
     data::tiles::Tiles,
+    data::tiles::tile_color::UserColor,
+    data::tilesets::TilesetId,
     data::{
         maps::MapId,
         modal::{DataLossOperation, ModalResult, ModalState},
     },
+    geom::u32size2::u32size2,
     tiled::tiled_json::Tiled,
 };
 
 impl App {
     pub fn show_new_map_modal(&mut self) {
-        self.edit.show_modal(ModalState::new_map());
+        self.edit
+            .show_modal(ModalState::new_map(self.edit.stamp.primary_tile()));
     }
 
     pub fn show_help_modal(&mut self) {
@@ -25,16 +32,29 @@ impl App {
 
     pub fn show_layer_modal(&mut self, map_id: MapId, layer_index: usize) {
         if let Some(map) = self.state.maps.get_by_id(map_id) {
-            if let (Some(layer_id), Some(name), opacity) = (
+            if let (Some(layer_id), Some(name), opacity, tint, kind) = (
                 map.tiles().layer_id(layer_index),
                 map.tiles().layer_name(layer_index),
                 map.tiles().layer_opacity(layer_index),
+                map.tiles().layer_tint(layer_index),
+                map.tiles().layer_kind(layer_index),
             ) {
+                let default_tint = tint.unwrap_or(UserColor::WHITE);
+                let sparse = map
+                    .tiles
+                    .layers()
+                    .nth(layer_index)
+                    .is_some_and(|layer| layer.is_sparse());
                 self.edit.show_modal(ModalState::Layer {
                     map_id: map.id(),
                     layer_id,
                     name: name.clone(),
                     opacity,
+                    tint,
+                    default_tint,
+                    default_tint_as_text: default_tint.as_css_string(),
+                    kind,
+                    sparse,
                     result: ModalResult::Init,
                 });
             }
@@ -43,7 +63,55 @@ impl App {
 
     pub fn show_edit_selected_map_modal(&mut self) {
         if let Some(map) = self.selected_map() {
-            self.edit.show_modal(ModalState::edit_map(map));
+            let modal = ModalState::edit_map(map, self.edit.stamp.primary_tile());
+            self.edit.show_modal(modal);
+        }
+    }
+
+    /// Check the image for each tileset can be found, and if not show a modal listing
+    /// the missing images, so the user can locate them.
+    pub fn check_tileset_images(&mut self) {
+        let missing = self
+            .textures
+            .missing_tileset_images(self.state.resources.tilesets());
+        if !missing.is_empty() {
+            self.edit
+                .show_modal(ModalState::missing_tileset_images(missing));
+        }
+    }
+
+    /// Relocate the image for a tileset listed in the missing tileset images modal (see
+    /// [`App::relocate_tileset_image`]). The tileset is removed from the modal if its image
+    /// is now found, and the modal is closed when no missing images remain.
+    pub fn locate_missing_tileset_image(
+        &mut self,
+        ctx: &Context,
+        tileset_id: TilesetId,
+        path: Utf8PathBuf,
+    ) {
+        if !self.relocate_tileset_image(ctx, tileset_id, path) {
+            return;
+        }
+        let still_missing = self
+            .state
+            .resources
+            .tilesets()
+            .get_by_id(tileset_id)
+            .and_then(|tileset| self.textures.missing_tileset_image(tileset));
+
+        if let ModalState::MissingTilesetImages { missing, .. } = &mut self.edit.modal {
+            match still_missing {
+                Some(image) => {
+                    for existing in missing.iter_mut().filter(|m| m.tileset_id == tileset_id) {
+                        *existing = image.clone();
+                    }
+                }
+                None => missing.retain(|m| m.tileset_id != tileset_id),
+            }
+            if missing.is_empty() {
+                self.edit.hide_modal();
+                self.success("All tileset images found");
+            }
         }
     }
 
@@ -75,6 +143,28 @@ impl App {
         self.edit.show_modal(ModalState::export_png());
     }
 
+    /// Show a modal comparing the selected map with another map, defaulting
+    /// to the first other map in the project
+    pub fn show_compare_maps_modal(&mut self) {
+        let a = self.edit.selected_map_id;
+        let b = self
+            .state
+            .maps
+            .iter()
+            .map(|map| map.id())
+            .find(|id| Some(*id) != a);
+        self.edit.show_modal(ModalState::compare_maps(a, b));
+    }
+
+    /// Show a modal to resize all maps, defaulting to the size of the selected map
+    pub fn show_resize_all_maps_modal(&mut self) {
+        let size = self
+            .selected_map()
+            .map(|map| map.tiles.map_size())
+            .unwrap_or(u32size2(32, 32));
+        self.edit.show_modal(ModalState::resize_all_maps(size));
+    }
+
     /// Progress the state of a modal, based on it's [`ModalState::result`]:
     ///  - [`ModalResult::Init`]: Move the result on to [`ModalResult::Active`],
     ///    return [`None`]
@@ -10,4 +10,10 @@ impl App {
             .success(caption)
             .duration(Duration::from_secs(2));
     }
+
+    pub fn warning(&mut self, caption: impl Into<WidgetText>) {
+        self.toasts
+            .warning(caption)
+            .duration(Duration::from_secs(5));
+    }
 }
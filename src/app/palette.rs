@@ -3,7 +3,7 @@ use crate::{
     data::{
         action::Action,
         modal::DataLossOperation,
-        palette::{Palette, PaletteIndex},
+        palette::{Palette, PaletteIndex, palette_index},
         tiles::tile_color::TileColor,
     },
     ui::file_dialog::{self, JSON_EXTENSION, JSON_NAME},
@@ -28,9 +28,13 @@ impl App {
     /// Replace palette without checking for affected tiles
     /// This clears any tiles that were using a palette index outside the new palette,
     /// and also clears tileset selections and stamp
-    pub(super) fn replace_palette(&mut self, palette: Palette) -> bool {
+    pub(super) fn replace_palette(&mut self, palette: Palette, remap_to_nearest: bool) -> bool {
         if self.state.resources.palette != palette {
-            self.state.clear_tiles_outside_palette(&palette);
+            if remap_to_nearest {
+                self.state.remap_tiles_outside_palette(&palette);
+            } else {
+                self.state.clear_tiles_outside_palette(&palette);
+            }
             self.state.resources.palette = palette;
             self.edit.clear_tileset_tile_selections_and_stamp();
             true
@@ -39,6 +43,26 @@ impl App {
         }
     }
 
+    /// Remove palette colors not used by any tile, see [`crate::data::state::State::compact_palette`].
+    /// This keeps the selected color selected if it's still in the palette, and clears
+    /// tileset selections and stamp, as for [`App::replace_palette`].
+    pub(super) fn compact_palette(&mut self) -> bool {
+        let old_len = self.state.resources.palette().len();
+        let index_map = self.state.compact_palette();
+        let removed = old_len as usize - index_map.len();
+        if removed > 0 {
+            let selected = self.edit.selected_palette_index().index();
+            let new_selected = index_map.get(&selected).copied().unwrap_or(0);
+            self.edit.clear_tileset_tile_selections_and_stamp();
+            self.select_palette_index(palette_index(new_selected));
+            self.success(format!("Removed {} unused palette colors", removed));
+            true
+        } else {
+            self.success("No unused palette colors to remove");
+            false
+        }
+    }
+
     pub fn previous_palette_index(&mut self) {
         let i = self.selected_palette_index();
         self.select_palette_index(i.previous_within_palette(self.state.resources.palette()));
@@ -53,12 +77,18 @@ impl App {
     /// otherwise just replace.
     pub fn prompt_to_replace_palette(&mut self, palette: Palette) {
         if let Some(resource_use) = self.state.find_use_of_colors_outside_palette(&palette) {
+            let lost_indices = self.state.used_palette_indices_outside(&palette);
             self.show_data_loss_modal(DataLossOperation::ReplacePalette {
                 palette,
                 resource_use,
+                lost_indices,
+                remap_to_nearest: false,
             });
         } else {
-            self.act(Action::ReplacePalette { palette });
+            self.act(Action::ReplacePalette {
+                palette,
+                remap_to_nearest: false,
+            });
         }
     }
 
@@ -107,4 +137,21 @@ impl App {
             Err(e) => self.show_error_modal(&e.to_string()),
         }
     }
+
+    pub fn show_export_palette_paintnet_modal(&mut self) {
+        match file_dialog::save_paintnet_file(&None) {
+            Ok(Some(path)) => {
+                if let Err(e) = self
+                    .state
+                    .resources
+                    .palette()
+                    .write_to_paintnet_by_path(path)
+                {
+                    self.show_error_modal(&e.to_string());
+                }
+            }
+            Ok(None) => {}
+            Err(e) => self.show_error_modal(&e.to_string()),
+        }
+    }
 }
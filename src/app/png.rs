@@ -1,6 +1,6 @@
 use crate::{
     app::{App, maps::MapEditing},
-    data::png::PngExportSettings,
+    data::{png::PngExportSettings, tiles::Tiles},
     render::render_tiles,
     ui::file_dialog,
     utils::path_with_suffix_and_extension,
@@ -41,12 +41,14 @@ impl App {
         path: Utf8PathBuf,
         settings: &PngExportSettings,
     ) -> eyre::Result<()> {
+        let tiles = me.map.tiles().exported_tiles();
+        let settings = settings.resolved_for_size(tiles.pixel_size_unscaled());
         let image = render_tiles(
-            me.map.tiles(),
+            &tiles,
             me.resources.palette(),
             me.resources.tilesets(),
             me.textures,
-            settings,
+            &settings,
         )?;
         image.save(path)?;
         Ok(())
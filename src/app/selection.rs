@@ -1,10 +1,11 @@
 use crate::{
     app::App,
-    data::{action::Action, mode::Mode},
+    data::{action::Action, edit_state::LAYER_SHORTCUT_BANK_SIZE, mode::Mode, tiles::Tiles},
 };
 
 impl App {
     pub fn select_layer(&mut self, layer_index: usize) {
+        self.reset_transform_on_change();
         if let Some(map_editing) = self.selected_map_editing_mut() {
             map_editing
                 .edit
@@ -13,6 +14,22 @@ impl App {
         }
     }
 
+    /// Move the number key layer shortcuts on to the next nine layers of the selected
+    /// map, wrapping back to the first nine
+    pub fn next_layer_shortcut_bank(&mut self) {
+        let layer_count = self
+            .selected_map()
+            .map(|map| map.tiles().layer_count())
+            .unwrap_or(0);
+        self.edit.next_layer_shortcut_bank(layer_count);
+        let first = self.edit.layer_shortcut_index(0) + 1;
+        self.success(format!(
+            "Keys 1-9 select layers {}-{}",
+            first,
+            first + LAYER_SHORTCUT_BANK_SIZE - 1
+        ));
+    }
+
     pub fn cut(&mut self) {
         self.copy_optional_delete(true);
     }
@@ -28,6 +45,25 @@ impl App {
         }
     }
 
+    /// True if there is a selected layer to use for [`App::select_nonempty_on_selected_layer`]
+    pub fn can_select_nonempty(&self) -> bool {
+        match (self.selected_map(), self.selected_map_edit_state()) {
+            (Some(map), Some(edit)) => !edit.no_layers_selected(map),
+            _ => false,
+        }
+    }
+
+    /// Select the cells containing tiles on the first selected layer of the selected map,
+    /// and switch to select mode so the selection can be copied.
+    pub fn select_nonempty_on_selected_layer(&mut self) {
+        if let Some(mut me) = self.selected_map_editing_mut() {
+            if let Some(layer_index) = me.selected_layer_indices().first().copied() {
+                me.select_nonempty(layer_index);
+                self.select_mode();
+            }
+        }
+    }
+
     pub fn clear_selection(&mut self) {
         if let Some(map_edit_state) = self.selected_map_edit_state_mut() {
             map_edit_state.selection_mut().clear();
@@ -55,6 +91,17 @@ impl App {
         }
     }
 
+    /// Copy the selection into a new map, see [`crate::app::maps::MapEditing::selection_as_map`],
+    /// and select the new map.
+    pub fn selection_to_new_map(&mut self) {
+        if let Some(map) = self
+            .selected_map_editing_mut()
+            .and_then(|me| me.selection_as_map())
+        {
+            self.act(Action::AddMap { map });
+        }
+    }
+
     pub fn tileset_selected_tiles_from_stamp(&mut self) {
         // Make sure we have selections for all tilesets, and clear them
         self.apply_default_tileset_tile_selection_by_id();
@@ -63,7 +110,7 @@ impl App {
         // For each tileset, add the tiles used in the stamp to the associated selection
         for tileset in self.state.resources.tilesets().iter() {
             let id = tileset.id();
-            let tile_set_size = tileset.size_in_tiles;
+            let tile_set_size = tileset.grid_size();
             if let Some(selection) = self.edit.tileset_tile_selection_by_id.get_mut(&id) {
                 for source in self.edit.stamp.tile_sources.iter() {
                     if source.tileset_id == id {
@@ -1,10 +1,8 @@
-use crate::{
-    app::App, data::mode::Mode, data::stamp::Stamp, data::tiles::Tiles, geom::transform::Transform,
-};
+use crate::{app::App, data::stamp::Stamp, data::tiles::Tiles, geom::transform::Transform};
 
 impl App {
     pub fn transform(&mut self, transform: Transform) {
-        if self.edit.mode == Mode::Draw {
+        if self.edit.mode.draws_stamp() {
             self.edit.stamp = self.edit.stamp.with_transform(transform);
         }
     }
@@ -13,6 +11,17 @@ impl App {
         self.transform(self.edit.stamp.transform.inverse());
     }
 
+    /// Clear the stamp's transform in any mode, if
+    /// [`crate::data::settings::Settings::reset_transform_on_change`] is set
+    pub(super) fn reset_transform_on_change(&mut self) {
+        if self.settings.reset_transform_on_change {
+            self.edit.stamp = self
+                .edit
+                .stamp
+                .with_transform(self.edit.stamp.transform.inverse());
+        }
+    }
+
     pub fn stamp_from_tileset<T: Tiles>(&mut self, tiles: &T) {
         if let (Some(selected_tileset_id), Some(selection)) = (
             self.edit.selected_tileset_id,
@@ -1,7 +1,10 @@
 use crate::{
     app::{App, maps::MapEditing},
     data::{modal::DataLossOperation, tiled::TiledExportSettings},
-    tiled::{tiled_json::Tiled, tiled_xml::TiledXml},
+    tiled::{
+        tiled_json::Tiled,
+        tiled_xml::{TiledProjectXml, TiledXml},
+    },
     ui::file_dialog,
     utils::path_with_suffix_and_extension,
 };
@@ -53,6 +56,33 @@ impl App {
         }
     }
 
+    pub fn show_export_tiled_project_folder_modal(&mut self, settings: &TiledExportSettings) {
+        match file_dialog::pick_folder_with_default(&self.save_path) {
+            Ok(Some(dir)) => match self.export_tiled_project(dir, settings) {
+                Ok(count) => self.success(format!("Exported {} maps as Tiled", count)),
+                Err(e) => self.show_error_modal(&e.to_string()),
+            },
+            Ok(None) => {}
+            Err(e) => self.show_error_modal(&e.to_string()),
+        }
+    }
+
+    fn export_tiled_project(
+        &self,
+        dir: Utf8PathBuf,
+        settings: &TiledExportSettings,
+    ) -> eyre::Result<usize> {
+        let project = TiledProjectXml::from_project_parts(
+            dir.clone(),
+            settings,
+            &self.state.maps,
+            self.state.resources.palette(),
+            self.state.resources.tilesets(),
+        )?;
+        project.save(dir, settings)?;
+        Ok(project.maps.len())
+    }
+
     fn export_tiled(
         me: &MapEditing<'_>,
         path: Utf8PathBuf,
@@ -1,4 +1,6 @@
-use egui::Context;
+use camino::Utf8PathBuf;
+use egui::{Context, ahash::HashMap};
+use eyre::eyre;
 
 use crate::{
     app::App,
@@ -10,6 +12,7 @@ use crate::{
     geom::i32pos2::I32Pos2,
     geom::u32size2::u32size2,
     selection::{apply_default_selection, apply_default_value_per_selectable_id},
+    ui::file_manager,
 };
 
 impl App {
@@ -18,6 +21,17 @@ impl App {
         self.edit.selected_tileset_id = Some(id);
     }
 
+    pub(super) fn add_split_tilesets(&mut self, tilesets: Vec<Tileset>) -> bool {
+        let count = tilesets.len();
+        for tileset in tilesets {
+            self.add_tileset(tileset);
+        }
+        if count > 0 {
+            self.success(format!("Added {} tilesets", count));
+        }
+        count > 0
+    }
+
     pub(super) fn delete_tileset_by_id(&mut self, tileset_id: TilesetId) -> bool {
         let mut change = false;
         change |= self.state.clear_tiles_with_tileset(tileset_id);
@@ -28,13 +42,52 @@ impl App {
         change
     }
 
-    pub(super) fn update_tileset(&mut self, id: TilesetId, tileset: Tileset) -> bool {
+    pub(super) fn merge_tilesets(
+        &mut self,
+        src: TilesetId,
+        dst: TilesetId,
+        index_map: &HashMap<u32, u32>,
+    ) -> bool {
+        match self.state.merge_tilesets(src, dst, index_map) {
+            Ok(count) => {
+                self.edit.clear_tileset_tile_selections_and_stamp();
+                self.edit.selected_tileset_id = Some(dst);
+                self.success(format!("Merged tilesets, remapped {} tiles", count));
+                true
+            }
+            Err(e) => {
+                self.show_error_modal(&e.to_string());
+                false
+            }
+        }
+    }
+
+    pub(super) fn update_tileset(
+        &mut self,
+        id: TilesetId,
+        tileset: Tileset,
+        remap_columns: bool,
+    ) -> bool {
+        let old_columns = self
+            .state
+            .resources
+            .tilesets()
+            .get_by_id(id)
+            .map(|old| old.grid_size().w);
+        let new_columns = tileset.grid_size().w;
         if self
             .state
             .resources
             .tilesets_mut()
             .update_tileset(id, tileset)
         {
+            if remap_columns
+                && let Some(old_columns) = old_columns
+                && old_columns != new_columns
+            {
+                self.state
+                    .remap_tileset_columns(id, old_columns, new_columns);
+            }
             self.edit.clear_tileset_tile_selections_and_stamp();
             true
         } else {
@@ -42,6 +95,99 @@ impl App {
         }
     }
 
+    /// Update a tileset, first asking the user whether to keep tiles in place if this
+    /// changes the number of columns of a tileset used in maps, since that changes
+    /// which tile each tile index shows.
+    pub fn prompt_to_update_tileset(&mut self, id: TilesetId, tileset: Tileset) {
+        let old_columns = self
+            .state
+            .resources
+            .tilesets()
+            .get_by_id(id)
+            .map(|old| old.grid_size().w);
+        let new_columns = tileset.grid_size().w;
+        if let Some(old_columns) = old_columns
+            && old_columns != new_columns
+            && let Some(tileset_use) = self.state.find_use_of_tileset(id)
+        {
+            self.show_data_loss_modal(DataLossOperation::ChangeTilesetColumns {
+                tileset_id: id,
+                tileset,
+                tileset_use,
+                old_columns,
+                remap_columns: true,
+            });
+        } else {
+            self.act(Action::UpdateTileset {
+                id,
+                tileset,
+                remap_columns: false,
+            });
+        }
+    }
+
+    /// Change the image path for a tileset, e.g. after the project or image has been
+    /// moved, respecting [`Tileset::prefer_relative_path`]. The texture is refreshed, and
+    /// the size of the tileset in tiles is updated from the new image if it can be read.
+    /// If the tileset is used in maps, its number of columns is kept so tile indices still
+    /// show the same tiles: a wider image gets a [`Tileset::columns`] override, and a
+    /// narrower one is refused.
+    /// Returns true if the tileset was updated.
+    pub fn relocate_tileset_image(
+        &mut self,
+        ctx: &Context,
+        id: TilesetId,
+        new_path: Utf8PathBuf,
+    ) -> bool {
+        let Some(mut tileset) = self.state.resources.tilesets().get_by_id(id).cloned() else {
+            return false;
+        };
+        tileset.relocate(new_path, self.textures.base_dir());
+        if let Ok(size_in_tiles) = self.textures.tileset_image_size_in_tiles(&tileset) {
+            let old_columns = tileset.column_count();
+            tileset.size_in_tiles = size_in_tiles;
+            let new_columns = tileset.column_count();
+            if new_columns != old_columns && self.state.find_use_of_tileset(id).is_some() {
+                if new_columns < old_columns {
+                    self.show_error_modal(&format!(
+                        "The image has {} columns of tiles, but tileset '{}' uses {}, so tiles in maps would change.\nChoose another image, or change the tileset's columns in tileset settings.",
+                        new_columns, tileset.name, old_columns
+                    ));
+                    return false;
+                }
+                tileset.columns = Some(old_columns);
+                self.warning(format!(
+                    "The image is wider than before, so tileset '{}' now uses only the first {} columns",
+                    tileset.name, old_columns
+                ));
+            }
+        }
+        self.textures.refresh_tileset(ctx, &tileset);
+        self.act(Action::UpdateTileset {
+            id,
+            tileset,
+            remap_columns: false,
+        });
+        true
+    }
+
+    /// Open the platform file manager showing the image file of the selected tileset
+    pub fn reveal_selected_tileset_image(&mut self) {
+        let Some(tileset) = self.selected_tileset() else {
+            return;
+        };
+        let result = match self.textures.path_for_tileset(tileset) {
+            Some(path) => file_manager::reveal_file(&path),
+            None => Err(eyre!(
+                "Tileset '{}' is built in, so it has no image file.",
+                tileset.name
+            )),
+        };
+        if let Err(e) = result {
+            self.show_error_modal(&e.to_string());
+        }
+    }
+
     pub fn selected_tileset(&self) -> Option<&Tileset> {
         self.edit
             .selected_tileset_id
@@ -70,6 +216,14 @@ impl App {
         }
     }
 
+    /// Reload the images of all tilesets from their files, e.g. after they have been
+    /// created or edited in another application
+    pub fn reload_all_tilesets(&mut self, ctx: &Context) {
+        self.textures
+            .refresh_all_tilesets(ctx, self.state.resources.tilesets());
+        self.success("Reloaded tileset images");
+    }
+
     pub fn show_new_tileset_modal(&mut self) {
         let mut tileset = Tileset::default();
         // TODO: Use selected map's tile size as default, if there is one
@@ -88,6 +242,27 @@ impl App {
         }
     }
 
+    pub fn show_split_selected_tileset_modal(&mut self) {
+        if let Some(tileset) = self.selected_tileset() {
+            self.edit
+                .show_modal(ModalState::split_tileset(tileset.id()));
+        }
+    }
+
+    pub fn show_merge_selected_tileset_modal(&mut self) {
+        if let Some(tileset) = self.selected_tileset() {
+            let src = tileset.id();
+            let dst = self
+                .state
+                .resources
+                .tilesets()
+                .iter()
+                .find(|t| t.id() != src)
+                .map(|t| t.id());
+            self.edit.show_modal(ModalState::merge_tilesets(src, dst));
+        }
+    }
+
     pub fn delete_selected_tileset(&mut self) {
         if let Some(tileset) = self.selected_tileset() {
             if let Some(tileset_use) = self.state.find_use_of_tileset(tileset.id()) {
@@ -103,6 +278,7 @@ impl App {
     }
 
     pub fn shift_tileset_selection(&mut self, shift: I32Pos2) {
+        self.reset_transform_on_change();
         self.edit
             .stamp
             .shift(self.state.resources.tilesets(), shift);
@@ -113,6 +289,7 @@ impl App {
         if let Some(id) = self.edit.selected_tileset_id {
             if let Some(previous_tileset) = self.state.resources.tilesets().previous_by_id(id) {
                 self.edit.selected_tileset_id = Some(previous_tileset.id());
+                self.reset_transform_on_change();
             }
         }
     }
@@ -121,7 +298,85 @@ impl App {
         if let Some(id) = self.edit.selected_tileset_id {
             if let Some(next_tileset) = self.state.resources.tilesets().next_by_id(id) {
                 self.edit.selected_tileset_id = Some(next_tileset.id());
+                self.reset_transform_on_change();
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use image::RgbaImage;
+
+    use crate::{
+        data::{
+            maps::Map,
+            tiles::{
+                Tile, TileIndex, TileSource, Tiles,
+                tile_color::{TileColor, UserColor},
+            },
+            tilesets::TilesetMode,
+        },
+        geom::{transform::Transform, u32pos2::u32pos2},
+    };
+
+    use super::*;
+
+    #[test]
+    fn relocating_image_keeps_columns_of_used_tileset() -> eyre::Result<()> {
+        let temp = assert_fs::TempDir::new()?;
+        let dir = Utf8PathBuf::from_path_buf(temp.path().to_path_buf())
+            .map_err(|_| eyre!("Can't convert temp dir to utf8"))?;
+        // 8x8 pixel tiles, the tileset's image is 4 columns wide
+        RgbaImage::new(16, 16).save(dir.join("narrow.png"))?;
+        RgbaImage::new(48, 16).save(dir.join("wide.png"))?;
+
+        let mut app = App::default();
+        app.textures
+            .update_base_dir_from_file_path(Some(dir.join("project.mtp")));
+        let id = app
+            .state
+            .resources
+            .tilesets_mut()
+            .push_tileset(Tileset::new_with_default_id(
+                "sheet".to_string(),
+                "sheet.png".into(),
+                u32size2(8, 8),
+                u32size2(4, 2),
+                TilesetMode::Direct,
+                None,
+                None,
+                false,
+            ));
+        let mut map = Map::new_with_layer(
+            "Map".to_string(),
+            u32size2(4, 4),
+            u32size2(8, 8),
+            UserColor::BLACK,
+        );
+        let tile = Tile {
+            source: TileSource {
+                tileset_id: id,
+                tile_index: TileIndex::new(5),
+            },
+            color: TileColor::Default,
+            transform: Transform::None,
+        };
+        map.tiles.set_tile(0, u32pos2(0, 0), Some(tile));
+        app.add_map(map)?;
+        let ctx = Context::default();
+        let tileset = |app: &App| app.state.resources.tilesets().get_by_id(id).cloned();
+
+        // A narrower image would move tiles, so is refused
+        assert!(!app.relocate_tileset_image(&ctx, id, dir.join("narrow.png")));
+        assert_eq!(tileset(&app).map(|t| t.path), Some("sheet.png".into()));
+
+        // A wider image keeps the existing columns
+        assert!(app.relocate_tileset_image(&ctx, id, dir.join("wide.png")));
+        let relocated = tileset(&app).ok_or_else(|| eyre!("Expected tileset"))?;
+        assert_eq!(relocated.size_in_tiles, u32size2(6, 2));
+        assert_eq!(relocated.columns, Some(4));
+        assert_eq!(relocated.grid_size(), u32size2(4, 2));
+        Ok(())
+    }
+}
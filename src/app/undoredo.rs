@@ -1,4 +1,4 @@
-use crate::app::App;
+use crate::{app::App, undo};
 
 impl App {
     pub fn can_undo(&self) -> bool {
@@ -14,10 +14,28 @@ impl App {
         }
     }
 
+    /// The number of undos that can be performed, in succession
+    pub fn available_undos(&self) -> usize {
+        if self.can_undo() {
+            self.undo.available_undos(&self.state)
+        } else {
+            0
+        }
+    }
+
     pub fn can_redo(&self) -> bool {
         self.state.can_redo() && self.undo.has_redo(&self.state)
     }
 
+    /// The number of redos that can be performed, in succession
+    pub fn available_redos(&self) -> usize {
+        if self.can_redo() {
+            self.undo.available_redos(&self.state)
+        } else {
+            0
+        }
+    }
+
     pub fn redo(&mut self) {
         if self.can_redo() {
             if let Some((redo_state, redo_edit)) = self.undo.redo(&self.state) {
@@ -27,19 +45,132 @@ impl App {
         }
     }
 
-    /// Feed [`Undo`] with our current data and edit state
-    pub(super) fn feed_undo(&mut self) {
-        self.undo.feed_state(&self.state, &self.edit);
+    /// Feed [`Undo`] with our current data and edit state, at the current time in seconds
+    pub(super) fn feed_undo(&mut self, current_time: f64) {
+        self.undo.feed_state(current_time, &self.state, &self.edit);
     }
 
     pub fn may_have_unsaved_changes(&self) -> bool {
         self.undo
             .may_have_changed_from_revision_index(self.saved_revision, &self.state)
     }
+    /// The settings for [`App::undo`], from the app settings
+    fn undo_settings(&self) -> undo::Settings {
+        undo::Settings {
+            coalesce_seconds: self.settings.undo_coalesce_seconds,
+            ..Default::default()
+        }
+    }
+
+    /// Use the current app settings for undo, e.g. after they are changed
+    pub fn update_undo_settings(&mut self) {
+        self.undo.set_settings(self.undo_settings());
+    }
+
     // Create a new undo manager that will just have the current state,
     // with no undo states
     pub fn clear_undo(&mut self) {
-        self.undo = Default::default();
-        self.feed_undo();
+        self.undo = undo::Undo::with_settings(self.undo_settings());
+        // The first revision is never coalesced into, so the time doesn't matter here
+        self.feed_undo(0.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        data::{
+            action::Action,
+            palette::Palette,
+            tiles::tile_color::UserColor,
+            tilesets::{Tileset, TilesetMode},
+        },
+        geom::u32size2::u32size2,
+    };
+
+    use super::*;
+
+    fn tileset(name: &str) -> Tileset {
+        Tileset::new_with_default_id(
+            name.to_string(),
+            format!("{}.png", name).into(),
+            u32size2(8, 8),
+            u32size2(4, 4),
+            TilesetMode::Direct,
+            None,
+            None,
+            false,
+        )
+    }
+
+    /// Perform `action`, as on a frame of the app, checking that it can be undone in one step
+    fn act_with_one_revision(app: &mut App, action: Action) {
+        let undos = app.available_undos();
+        app.act(action);
+        app.feed_undo(0.0);
+        assert_eq!(app.available_undos(), undos + 1);
+    }
+
+    fn app() -> App {
+        let mut app = App::default();
+        app.clear_undo();
+        app
+    }
+
+    #[test]
+    fn undo_add_and_update_tileset_restores_tilesets() -> eyre::Result<()> {
+        let mut app = app();
+        let original_tilesets = app.state.resources.tilesets().clone();
+
+        act_with_one_revision(
+            &mut app,
+            Action::AddTileset {
+                tileset: tileset("new"),
+            },
+        );
+        let added_tilesets = app.state.resources.tilesets().clone();
+        assert_eq!(added_tilesets.len(), original_tilesets.len() + 1);
+        let mut updated = added_tilesets
+            .iter()
+            .find(|t| t.name == "new")
+            .cloned()
+            .ok_or(eyre::eyre!("Missing added tileset"))?;
+        updated.name = "renamed".to_string();
+        act_with_one_revision(
+            &mut app,
+            Action::UpdateTileset {
+                id: updated.id(),
+                tileset: updated,
+                remap_columns: false,
+            },
+        );
+
+        app.undo();
+        assert_eq!(app.state.resources.tilesets(), &added_tilesets);
+        app.undo();
+        assert_eq!(app.state.resources.tilesets(), &original_tilesets);
+        assert!(!app.can_undo());
+
+        app.redo();
+        assert_eq!(app.state.resources.tilesets(), &added_tilesets);
+        Ok(())
+    }
+
+    #[test]
+    fn undo_replace_palette_restores_palette() {
+        let mut app = app();
+        let original_palette = app.state.resources.palette().clone();
+
+        act_with_one_revision(
+            &mut app,
+            Action::ReplacePalette {
+                palette: Palette::new(vec![UserColor::BLACK, UserColor::WHITE]),
+                remap_to_nearest: false,
+            },
+        );
+        assert_ne!(app.state.resources.palette(), &original_palette);
+
+        app.undo();
+        assert_eq!(app.state.resources.palette(), &original_palette);
     }
 }
@@ -0,0 +1,56 @@
+use egui::Context;
+
+use crate::{app::App, watcher::create_file_watcher};
+
+impl App {
+    /// Start or stop watching tileset images as needed for
+    /// [`crate::data::settings::Settings::watch_tileset_images`], update the watched
+    /// paths to those of the current tilesets, and refresh the textures of any
+    /// tilesets whose images have changed
+    pub(super) fn update_tileset_watcher(&mut self, ctx: &Context) {
+        if !self.settings.watch_tileset_images {
+            self.tileset_watcher = None;
+            self.tileset_watcher_paths = None;
+            return;
+        }
+
+        let watcher = self
+            .tileset_watcher
+            .get_or_insert_with(|| create_file_watcher(ctx.clone()));
+
+        let tilesets = self.state.resources.tilesets();
+        let source = self.textures.source();
+        let unchanged =
+            self.tileset_watcher_paths
+                .as_ref()
+                .is_some_and(|(watched_source, paths)| {
+                    watched_source == source
+                        && paths
+                            .iter()
+                            .eq(tilesets.iter().map(|tileset| &tileset.path))
+                });
+        if !unchanged {
+            watcher.watch(
+                tilesets
+                    .iter()
+                    .filter_map(|tileset| self.textures.path_for_tileset(tileset))
+                    .collect(),
+            );
+            self.tileset_watcher_paths = Some((
+                source.clone(),
+                tilesets
+                    .iter()
+                    .map(|tileset| tileset.path.clone())
+                    .collect(),
+            ));
+        }
+
+        while let Some(path) = watcher.poll_recv() {
+            for tileset in tilesets.iter() {
+                if self.textures.path_for_tileset(tileset).as_ref() == Some(&path) {
+                    self.textures.refresh_tileset(ctx, tileset);
+                }
+            }
+        }
+    }
+}
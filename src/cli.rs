@@ -0,0 +1,229 @@
+use crate::{
+    app::export_workspace::export_project_from_workspace,
+    data::{settings::Settings, state::State, tiles::tile_color::UserColor},
+    ui::tileset_textures::TilesetTextures,
+};
+use camino::Utf8PathBuf;
+use eyre::eyre;
+
+pub const USAGE: &str = "Usage:
+  mountain-tiles [FILE]                    Open the application, optionally with a file
+  mountain-tiles export --project FILE     Export files as specified by the project's
+    [--background COLOR]                   workspace settings, without opening a window.
+                                           Maps without their own background use COLOR
+                                           as hex, defaulting to 000000 (black)";
+
+/// The file to open from the application's arguments (including the program name as
+/// the first argument), if any. This is the first argument that isn't an option, so
+/// options added by the platform, such as macOS's `-psn_...` process serial number,
+/// aren't treated as files. This is used both when this is the first instance of the
+/// application, to open the file on startup, and by later instances, to send the file
+/// to the first instance.
+pub fn file_argument(args: &[String]) -> Option<Utf8PathBuf> {
+    args.iter()
+        .skip(1)
+        .find(|arg| !arg.starts_with('-'))
+        .map(Utf8PathBuf::from)
+}
+
+/// A command to run from the command line, without opening the application window
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// Export from the workspace settings for a project, using `background` for maps
+    /// that inherit the default map background, or the default from [`Settings`] if
+    /// not specified, since application settings aren't available without the window
+    Export {
+        project: Utf8PathBuf,
+        background: Option<UserColor>,
+    },
+}
+
+impl Command {
+    /// Parse a command from the application's arguments (including the program
+    /// name as the first argument). Returns `None` if the arguments don't
+    /// specify a command, in which case the application should open as normal.
+    pub fn from_args(args: &[String]) -> eyre::Result<Option<Command>> {
+        let mut args = args.iter().skip(1);
+        match args.next().map(|arg| arg.as_str()) {
+            Some("export") => {
+                let mut project = None;
+                let mut background = None;
+                while let Some(arg) = args.next() {
+                    match arg.as_str() {
+                        "--project" => match args.next() {
+                            Some(path) => project = Some(Utf8PathBuf::from(path)),
+                            None => return Err(eyre!("Missing project file after --project")),
+                        },
+                        "--background" => match args.next() {
+                            Some(color) => {
+                                background = Some(
+                                    UserColor::from_hex(color.trim_start_matches('#')).map_err(
+                                        |e| eyre!("Invalid background color '{}': {}", color, e),
+                                    )?,
+                                )
+                            }
+                            None => return Err(eyre!("Missing color after --background")),
+                        },
+                        other => return Err(eyre!("Unexpected argument '{}'", other)),
+                    }
+                }
+                match project {
+                    Some(project) => Ok(Some(Command::Export {
+                        project,
+                        background,
+                    })),
+                    None => Err(eyre!(
+                        "Export requires a project file, using --project FILE"
+                    )),
+                }
+            }
+            _ => Ok(None),
+        }
+    }
+
+    pub fn run(&self) -> eyre::Result<()> {
+        match self {
+            Command::Export {
+                project,
+                background,
+            } => {
+                let state = State::from_path(project.clone())?;
+                let mut textures = TilesetTextures::default();
+                textures.update_base_dir_from_file_path(Some(project.clone()));
+                let background = background.unwrap_or(Settings::default().default_map_background);
+                export_project_from_workspace(&state, &textures, project, background)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_export_command() -> eyre::Result<()> {
+        assert_eq!(Command::from_args(&args(&["mountain-tiles"]))?, None);
+        assert_eq!(
+            Command::from_args(&args(&["mountain-tiles", "map.mnp"]))?,
+            None
+        );
+        assert_eq!(
+            Command::from_args(&args(&["mountain-tiles", "export", "--project", "map.mnp"]))?,
+            Some(Command::Export {
+                project: "map.mnp".into(),
+                background: None,
+            })
+        );
+        assert_eq!(
+            Command::from_args(&args(&[
+                "mountain-tiles",
+                "export",
+                "--project",
+                "map.mnp",
+                "--background",
+                "#102030"
+            ]))?,
+            Some(Command::Export {
+                project: "map.mnp".into(),
+                background: Some(UserColor::new(16, 32, 48, 255)),
+            })
+        );
+        assert!(
+            Command::from_args(&args(&["mountain-tiles", "export", "--background", "nope"]))
+                .is_err()
+        );
+        assert!(Command::from_args(&args(&["mountain-tiles", "export"])).is_err());
+        assert!(Command::from_args(&args(&["mountain-tiles", "export", "--project"])).is_err());
+        assert!(Command::from_args(&args(&["mountain-tiles", "export", "--other"])).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn export_resolves_inherited_backgrounds() -> eyre::Result<()> {
+        use crate::data::{config::workspace::Workspace, maps::Map};
+        use crate::geom::u32size2::u32size2;
+
+        let temp = assert_fs::TempDir::new()?;
+        let dir = Utf8PathBuf::try_from(temp.path().to_path_buf())?;
+        std::fs::write(
+            dir.join(Workspace::FILENAME),
+            "[default.export]\nmetadata-json-path = \"maps.json\"\n",
+        )?;
+
+        // One map inherits the default background, the other has its own
+        let mut state = State::default();
+        let red = UserColor::new(255, 0, 0, 255);
+        let mut inheriting = Map::new_with_layer(
+            "Inheriting".to_string(),
+            u32size2(2, 2),
+            u32size2(8, 8),
+            red,
+        );
+        inheriting.tiles.background = None;
+        state.maps.push_map(inheriting);
+        state.maps.push_map(Map::new_with_layer(
+            "Own".to_string(),
+            u32size2(2, 2),
+            u32size2(8, 8),
+            red,
+        ));
+        let project = dir.join("project.mnp");
+        state.save_to_path(project.clone())?;
+
+        let backgrounds = |command: Command| -> eyre::Result<Vec<String>> {
+            command.run()?;
+            let metadata: serde_json::Value =
+                serde_json::from_str(&std::fs::read_to_string(dir.join("maps.json"))?)?;
+            Ok(metadata
+                .get("maps")
+                .and_then(|maps| maps.as_array())
+                .into_iter()
+                .flatten()
+                .filter_map(|map| map.get("background").and_then(|b| b.as_str()))
+                .map(|background| background.to_string())
+                .collect())
+        };
+
+        assert_eq!(
+            backgrounds(Command::Export {
+                project: project.clone(),
+                background: None,
+            })?,
+            vec!["000000", "FF0000"]
+        );
+        assert_eq!(
+            backgrounds(Command::Export {
+                project,
+                background: Some(UserColor::new(16, 32, 48, 255)),
+            })?,
+            vec!["102030", "FF0000"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn file_argument_skips_program_and_options() {
+        assert_eq!(file_argument(&args(&["mountain-tiles"])), None);
+        assert_eq!(
+            file_argument(&args(&["mountain-tiles", "map.mnp"])),
+            Some("map.mnp".into())
+        );
+        assert_eq!(
+            file_argument(&args(&[
+                "mountain-tiles",
+                "-psn_0_12345",
+                "/maps/cave map.mnp"
+            ])),
+            Some("/maps/cave map.mnp".into())
+        );
+        assert_eq!(
+            file_argument(&args(&["mountain-tiles", "-psn_0_12345"])),
+            None
+        );
+    }
+}
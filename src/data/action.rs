@@ -1,15 +1,19 @@
 use camino::Utf8PathBuf;
+use egui::ahash::HashMap;
+use std::collections::BTreeMap;
 
 use crate::{
     data::maps::{Map, MapId},
-    data::palette::Palette,
-    data::tiles::layer_tiles::LayerId,
+    data::palette::{Palette, PaletteIndex},
+    data::tiles::Tile,
+    data::tiles::layer_tiles::{LayerId, LayerKind, ResizeAnchor},
     data::tiles::tile_color::UserColor,
     data::tilesets::{Tileset, TilesetId},
-    geom::{i32pos2::I32Pos2, u32size2::U32Size2},
+    geom::{i32pos2::I32Pos2, u32rect::U32Rect, u32size2::U32Size2},
     tiled::tiled_json::Tiled,
 };
 
+#[derive(Clone)]
 pub enum Action {
     /// Draw the current stamp at the specified center position
     Draw {
@@ -20,6 +24,22 @@ pub enum Action {
         complete: bool,
     },
 
+    /// Draw the current stamp centered at each position on a line between
+    /// `start` and `end`, inclusive
+    DrawLine {
+        map_id: MapId,
+        start: I32Pos2,
+        end: I32Pos2,
+    },
+
+    /// Draw the current stamp centered at each cell of an ellipse fitting `rect`,
+    /// either filled or just the outline
+    DrawEllipse {
+        map_id: MapId,
+        rect: U32Rect,
+        filled: bool,
+    },
+
     /// Erase the tile at the specified position (if it lies within the map)
     Erase {
         map_id: MapId,
@@ -32,8 +52,30 @@ pub enum Action {
     /// Erase selected visible tiles (i.e. tiles within the selected areas, on selected, visible layers)
     EraseSelectedVisibleTiles { map_id: MapId },
 
-    /// Update the tileset with specified id (if any) to be the specified tileset
-    UpdateTileset { id: TilesetId, tileset: Tileset },
+    /// Place a tile on each selected cell at the boundary of the selection
+    /// (i.e. selected cells with at least one 4-neighbour that is not selected),
+    /// on selected, visible layers.
+    StrokeSelection { map_id: MapId, tile: Tile },
+
+    /// Auto-tile the selected cells and their neighbours, on selected, visible layers,
+    /// using the [`crate::data::wang_set::WangSet`] of each tile's tileset
+    AutotileSelection { map_id: MapId },
+
+    /// Reset the transform of each selected tile, on selected, visible layers
+    ClearTransformsInSelection { map_id: MapId },
+
+    /// Change the color of each selected tile to a palette color, on selected, visible layers
+    RecolorSelection { map_id: MapId, index: PaletteIndex },
+
+    /// Update the tileset with specified id (if any) to be the specified tileset.
+    /// If `remap_columns` is true and the number of columns changes, tiles using the
+    /// tileset are changed to keep showing the same tiles, see
+    /// [`crate::data::state::State::remap_tileset_columns`].
+    UpdateTileset {
+        id: TilesetId,
+        tileset: Tileset,
+        remap_columns: bool,
+    },
 
     /// Add a new tileset
     AddTileset { tileset: Tileset },
@@ -41,8 +83,28 @@ pub enum Action {
     /// Delete a tileset
     DeleteTileset { id: TilesetId },
 
-    /// Replace the palette
-    ReplacePalette { palette: Palette },
+    /// Add new tilesets split from parts of an existing tileset's image, see [`Tileset::split`]
+    AddSplitTilesets { tilesets: Vec<Tileset> },
+
+    /// Merge the `src` tileset into the `dst` tileset, changing all tiles using `src` to
+    /// use `dst` with tile indices mapped by `index_map` (unmapped indices are unchanged),
+    /// then deleting `src`
+    MergeTilesets {
+        src: TilesetId,
+        dst: TilesetId,
+        index_map: HashMap<u32, u32>,
+    },
+
+    /// Replace the palette. Tiles using colors that aren't in the new palette are
+    /// changed to the nearest color if `remap_to_nearest` is true, otherwise removed.
+    ReplacePalette {
+        palette: Palette,
+        remap_to_nearest: bool,
+    },
+
+    /// Remove palette colors that aren't used by any tile, keeping the
+    /// appearance of all tiles
+    CompactPalette,
 
     /// Add a new map
     AddMap { map: Map },
@@ -53,6 +115,10 @@ pub enum Action {
         tiled: Tiled,
         squash_layers: bool,
         prefer_relative_path: bool,
+        use_layer_properties: bool,
+        /// If set, the Tiled map's layers are added to this existing map
+        /// instead of a new map
+        into_map: Option<MapId>,
     },
 
     /// Update a map
@@ -61,7 +127,22 @@ pub enum Action {
         name: String,
         map_size: U32Size2,
         tile_size: U32Size2,
-        background_color: UserColor,
+        /// The background color, or [`None`] to inherit the default background
+        background_color: Option<UserColor>,
+        default_tile: Option<Tile>,
+    },
+
+    /// Resize every map in the project to `map_size`, placing existing
+    /// contents according to `anchor`, as a single revision
+    ResizeAllMaps {
+        map_size: U32Size2,
+        anchor: ResizeAnchor,
+    },
+
+    /// Replace the user metadata of a map, see [`Map::metadata`]
+    SetMapMetadata {
+        map_id: MapId,
+        metadata: BTreeMap<String, String>,
     },
 
     /// Delete a map
@@ -74,6 +155,16 @@ pub enum Action {
         visible: bool,
     },
 
+    /// Set layer opacity
+    SetLayerOpacity {
+        map_id: MapId,
+        layer_id: LayerId,
+        opacity: Option<f32>,
+        /// True if this edit is complete (e.g. drag stopped),
+        /// false if still in progress (e.g. still dragging)
+        complete: bool,
+    },
+
     /// Rename layer
     SetLayerName {
         map_id: MapId,
@@ -81,17 +172,28 @@ pub enum Action {
         name: String,
     },
 
-    /// Rename and/or edit opacity of layer (if name and/or opacity match current values, no change will be made to data)
+    /// Rename and/or edit opacity, tint and kind of layer (if these match current values, no change will be made to data)
     EditLayer {
         map_id: MapId,
         layer_id: LayerId,
         name: String,
         opacity: Option<f32>,
+        tint: Option<UserColor>,
+        kind: LayerKind,
+        sparse: bool,
     },
 
     /// Add layer
     AddLayer { map_id: MapId },
 
+    /// Add a layer imported from a CSV file of tile indices, using the tileset
+    /// with id `tileset_id`, see [`crate::data::tiles::layer_tiles::LayerTiles::import_layer_csv`]
+    ImportLayerCsv {
+        map_id: MapId,
+        path: Utf8PathBuf,
+        tileset_id: TilesetId,
+    },
+
     /// Delete selected layers
     DeleteSelectedLayers { map_id: MapId },
 
@@ -104,16 +206,93 @@ pub enum Action {
     /// Has no effect if less than 2 layers are selected.
     MergeSelectedLayers { map_id: MapId },
 
+    /// Merge the single selected layer into the layer immediately
+    /// below it, where the selected layer's tiles replace those
+    /// below. The selected layer is then deleted.
+    /// Has no effect unless exactly one layer is selected, or if
+    /// it is the bottom layer.
+    MergeDown { map_id: MapId },
+
     /// Move selected layers higher
     MoveSelectedLayersHigher { map_id: MapId },
 
     /// Move selected layers lower
     MoveSelectedLayersLower { map_id: MapId },
 
+    /// Swap the positions of the layers at indices `a` and `b`.
+    /// Layers keep their ids, so the layer selection follows the layers.
+    SwapLayers { map_id: MapId, a: usize, b: usize },
+
     /// Update data on save (e.g. to make tileset paths relative if requested)
     OnSave { path: Utf8PathBuf },
 }
 
+impl Action {
+    /// True if the action can be repeated, see [`Action::repeated`]
+    pub fn is_repeatable(&self) -> bool {
+        matches!(
+            self,
+            Action::Draw { .. }
+                | Action::Erase { .. }
+                | Action::EraseSelectedVisibleTiles { .. }
+                | Action::StrokeSelection { .. }
+                | Action::AutotileSelection { .. }
+                | Action::ClearTransformsInSelection { .. }
+                | Action::RecolorSelection { .. }
+                | Action::AddLayer { .. }
+                | Action::DeleteSelectedLayers { .. }
+                | Action::MergeSelectedLayers { .. }
+                | Action::MergeDown { .. }
+                | Action::MoveSelectedLayersHigher { .. }
+                | Action::MoveSelectedLayersLower { .. }
+        )
+    }
+
+    /// The action to perform to repeat this action on the map with id `map_id`,
+    /// where actions at a position are repeated at `pos`. [`None`] if the action
+    /// can't be repeated, or needs a position and `pos` is [`None`].
+    pub fn repeated(&self, map_id: MapId, pos: Option<I32Pos2>) -> Option<Action> {
+        match self {
+            Action::Draw { .. } => pos.map(|pos| Action::Draw {
+                map_id,
+                pos,
+                complete: true,
+            }),
+            Action::Erase { .. } => pos.map(|pos| Action::Erase {
+                map_id,
+                pos,
+                complete: true,
+            }),
+            Action::EraseSelectedVisibleTiles { .. } => {
+                Some(Action::EraseSelectedVisibleTiles { map_id })
+            }
+            Action::StrokeSelection { tile, .. } => Some(Action::StrokeSelection {
+                map_id,
+                tile: *tile,
+            }),
+            Action::AutotileSelection { .. } => Some(Action::AutotileSelection { map_id }),
+            Action::ClearTransformsInSelection { .. } => {
+                Some(Action::ClearTransformsInSelection { map_id })
+            }
+            Action::RecolorSelection { index, .. } => Some(Action::RecolorSelection {
+                map_id,
+                index: *index,
+            }),
+            Action::AddLayer { .. } => Some(Action::AddLayer { map_id }),
+            Action::DeleteSelectedLayers { .. } => Some(Action::DeleteSelectedLayers { map_id }),
+            Action::MergeSelectedLayers { .. } => Some(Action::MergeSelectedLayers { map_id }),
+            Action::MergeDown { .. } => Some(Action::MergeDown { map_id }),
+            Action::MoveSelectedLayersHigher { .. } => {
+                Some(Action::MoveSelectedLayersHigher { map_id })
+            }
+            Action::MoveSelectedLayersLower { .. } => {
+                Some(Action::MoveSelectedLayersLower { map_id })
+            }
+            _ => None,
+        }
+    }
+}
+
 /// The result of performing an `Action`, in terms of whether it changed data, and
 /// whether it should cause a new revision to be produced if any changes are pending.
 pub struct ActionResult {
@@ -159,3 +338,37 @@ impl ActionResult {
         revision: true,
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::geom::i32pos2::i32pos2;
+
+    use super::*;
+
+    #[test]
+    fn repeat_actions_on_map_at_pos() {
+        let map_id = MapId::ONE;
+        let draw = Action::Draw {
+            map_id,
+            pos: i32pos2(1, 2),
+            complete: false,
+        };
+        assert!(draw.is_repeatable());
+        assert!(matches!(
+            draw.repeated(map_id, Some(i32pos2(3, 4))),
+            Some(Action::Draw { pos, complete: true, .. }) if pos == i32pos2(3, 4)
+        ));
+        assert!(draw.repeated(map_id, None).is_none());
+
+        // Actions that don't need a position are repeated without one
+        let add_layer = Action::AddLayer { map_id };
+        assert!(matches!(
+            add_layer.repeated(map_id, None),
+            Some(Action::AddLayer { .. })
+        ));
+
+        let delete_map = Action::DeleteMap { id: map_id };
+        assert!(!delete_map.is_repeatable());
+        assert!(delete_map.repeated(map_id, Some(i32pos2(0, 0))).is_none());
+    }
+}
@@ -11,6 +11,21 @@ pub enum Endianness {
     Little,
 }
 
+/// The format of each tile in exported map data, see [`Export::tile_format`]
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TileFormat {
+    /// One `u32` per tile, in the Tiled gid format with the palette index in bits
+    /// 16 to 23, so this supports palette indices up to 255 and flat tile indices
+    /// up to 65535.
+    #[default]
+    Compact,
+    /// One `u64` per tile, with the Tiled gid format (flip bits in bits 29 to 31) in
+    /// the low 32 bits, and the palette index in the high 32 bits. This supports any
+    /// palette index, and flat tile indices up to `2^29 - 1`. Tiles use a `WideTile`
+    /// type defined in the exported module, rather than a type from `tili`.
+    Wide,
+}
+
 #[derive(Debug, Serialize, Deserialize, Default, Clone)]
 pub struct Project {
     pub export: Option<Export>,
@@ -31,12 +46,28 @@ pub struct Export {
     #[serde(rename = "module-path")]
     pub module_path: Option<Utf8PathBuf>,
 
+    /// If specified, sets the format of each tile in the exported module.
+    /// If not specified, defaults to [`TileFormat::Compact`]. Use [`TileFormat::Wide`]
+    /// for projects with palette indices above 255, or more than 65535 tiles in total.
+    #[serde(rename = "tile-format")]
+    pub tile_format: Option<TileFormat>,
+
+    /// If true, tiles in the exported module use compact indices, numbering just the
+    /// tiles placed in exported maps from 1, in order of their usual flat index.
+    /// This keeps indices small when only a few tiles of large tilesets are used.
+    /// The module then also contains a `COMPACT_TILE_GIDS` table giving the usual
+    /// flat index (i.e. the position in the exported tileset image) of each compact
+    /// index. Other exports, e.g. the metadata JSON, still use the usual indices.
+    #[serde(rename = "compact-tile-indices")]
+    pub compact_tile_indices: Option<bool>,
+
     /// Export tilesets as a single png image to the specified file path.
     /// Note the path may be relative - when a workspace is loaded from a file, paths should be
     /// taken to be relative to that file.
     /// The image contains the tiles from all tilesets, ordered
     /// by tileset then tile indec within the tileset.
-    /// The image consists of a single column of tiles.
+    /// By default the image consists of a single column of tiles, see
+    /// [`Export::tileset_columns`] and [`Export::tileset_pad_to_pow2`].
     /// This is compatible with use in Bevy as an `ImageArrayLayout`, for
     /// example as the `tileset` for a `TilemapChunk`.
     #[serde(rename = "tileset-png-path")]
@@ -53,7 +84,8 @@ pub struct Export {
     /// This should be compatible with Imagemagick `convert` using a depth of 1.
     /// The image contains the tiles from all tilesets, ordered
     /// by tileset then tile indec within the tileset.
-    /// The image consists of a single column of tiles.
+    /// By default the image consists of a single column of tiles, see
+    /// [`Export::tileset_columns`] and [`Export::tileset_pad_to_pow2`].
     /// This is compatible with use in Bevy as an `ImageArrayLayout`, for
     /// example as the `tileset` for a `TilemapChunk`.
     #[serde(rename = "tileset-1bit-path")]
@@ -64,6 +96,29 @@ pub struct Export {
     #[serde(rename = "tileset-1bit-endianness")]
     pub tileset_1bit_endianness: Option<Endianness>,
 
+    /// If specified, the exported tileset images are laid out in rows of this many
+    /// tiles, instead of a single column. Tiles are still ordered by tileset then
+    /// tile index within the tileset, filling each row from left to right.
+    #[serde(rename = "tileset-columns")]
+    pub tileset_columns: Option<u32>,
+
+    /// If true, the exported tileset images are padded with transparent pixels on the
+    /// right and bottom, so that the width and height are powers of two. The position
+    /// of each tile in the image is unchanged.
+    #[serde(rename = "tileset-pad-to-pow2")]
+    pub tileset_pad_to_pow2: Option<bool>,
+
+    /// Export a JSON description of the exported tileset image as an array texture, to
+    /// the specified file path. This gives the tile size, the number of layers (one per
+    /// tile) and the Bevy `ImageArrayLayout`, for use as the `tileset` for a Bevy
+    /// `TilemapChunk`. The array layer for a tile is its `gid` minus one.
+    /// Requires the tileset image to be a single column with no padding,
+    /// see [`Export::tileset_columns`] and [`Export::tileset_pad_to_pow2`].
+    /// Note the path may be relative - when a workspace is loaded from a file, paths should be
+    /// taken to be relative to that file.
+    #[serde(rename = "tileset-layout-json-path")]
+    pub tileset_layout_json_path: Option<Utf8PathBuf>,
+
     /// Export palette as a png image to the specified file path.
     /// Note the path may be relative - when a workspace is loaded from a file, paths should be
     /// taken to be relative to that file.
@@ -76,6 +131,36 @@ pub struct Export {
     #[serde(rename = "palette-json-path")]
     pub palette_json_path: Option<Utf8PathBuf>,
 
+    /// Export palette as a Paint.NET palette (one `AARRGGBB` hex color per line, which
+    /// Aseprite can also import) to the specified file path.
+    /// Note the path may be relative - when a workspace is loaded from a file, paths should be
+    /// taken to be relative to that file.
+    #[serde(rename = "palette-hex-path")]
+    pub palette_hex_path: Option<Utf8PathBuf>,
+
+    /// Export palette as a 256x1 png lookup texture to the specified file path, where
+    /// pixel `i` is palette color `i`, and pixels past the end of the palette are
+    /// transparent. This allows a shader to resolve the palette index packed into
+    /// exported tiles (bits 16-23) to a color.
+    /// Note the path may be relative - when a workspace is loaded from a file, paths should be
+    /// taken to be relative to that file.
+    #[serde(rename = "palette-lookup-image-path")]
+    pub palette_lookup_image_path: Option<Utf8PathBuf>,
+
+    /// Export a JSON description of the exported maps to the specified file path,
+    /// giving the name, size, tile size and layers of each map, and the tilesets it
+    /// uses, with the `firstgid` used for each tileset in the exported tile data.
+    /// Note the path may be relative - when a workspace is loaded from a file, paths should be
+    /// taken to be relative to that file.
+    #[serde(rename = "metadata-json-path")]
+    pub metadata_json_path: Option<Utf8PathBuf>,
+
+    /// If true, allow export when maps and tilesets don't all have the same tile size.
+    /// By default this is an error when exporting a module or tileset, since these
+    /// assume all tiles are in one tileset image with a single tile size.
+    #[serde(rename = "allow-mixed-tile-sizes")]
+    pub allow_mixed_tile_sizes: Option<bool>,
+
     /// If this is specified, then the export will skip any map
     /// whose name starts with the given prefix, e.g. "_skip-this-map"
     #[serde(rename = "skip-maps-with-prefix")]
@@ -85,7 +170,11 @@ pub struct Export {
 impl Export {
     /// True if the export settings have any effect (i.e. they specify some data to be exported)
     pub fn has_effect(&self) -> bool {
-        self.module_path.is_some() || self.exports_tileset() || self.exports_palette()
+        self.module_path.is_some()
+            || self.metadata_json_path.is_some()
+            || self.tileset_layout_json_path.is_some()
+            || self.exports_tileset()
+            || self.exports_palette()
     }
 
     /// True if tileset is exported in any format
@@ -95,7 +184,10 @@ impl Export {
 
     /// True if palette is exported in any format
     pub fn exports_palette(&self) -> bool {
-        self.palette_image_path.is_some() || self.palette_json_path.is_some()
+        self.palette_image_path.is_some()
+            || self.palette_json_path.is_some()
+            || self.palette_hex_path.is_some()
+            || self.palette_lookup_image_path.is_some()
     }
 }
 
@@ -182,11 +274,20 @@ mod tests {
             default: Some(Project {
                 export: Some(Export {
                     module_path: Some("../src/maps.rs".into()),
+                    tile_format: Some(TileFormat::Wide),
+                    compact_tile_indices: Some(true),
                     tileset_png_path: Some("../assets/tilesets/tileset.png".into()),
                     tileset_1bit_path: None,
                     tileset_1bit_endianness: Some(Endianness::Little),
+                    tileset_columns: Some(16),
+                    tileset_pad_to_pow2: Some(true),
+                    tileset_layout_json_path: Some("../assets/tilesets/layout.json".into()),
                     palette_image_path: Some("../assets/palette/palette.png".into()),
                     palette_json_path: None,
+                    palette_hex_path: Some("../assets/palette/palette.txt".into()),
+                    palette_lookup_image_path: None,
+                    metadata_json_path: Some("../assets/maps.json".into()),
+                    allow_mixed_tile_sizes: Some(true),
                     skip_maps_with_prefix: Some("skip-".to_string()),
                 }),
             }),
@@ -203,9 +304,17 @@ mod tests {
         let toml = toml::to_string(&workspace)?;
         let expected = r#"[default.export]
 module-path = "../src/maps.rs"
+tile-format = "Wide"
+compact-tile-indices = true
 tileset-png-path = "../assets/tilesets/tileset.png"
 tileset-1bit-endianness = "Little"
+tileset-columns = 16
+tileset-pad-to-pow2 = true
+tileset-layout-json-path = "../assets/tilesets/layout.json"
 palette-image-path = "../assets/palette/palette.png"
+palette-hex-path = "../assets/palette/palette.txt"
+metadata-json-path = "../assets/maps.json"
+allow-mixed-tile-sizes = true
 skip-maps-with-prefix = "skip-"
 
 [project.example.export]
@@ -221,11 +330,20 @@ module-path = "example/example.rs"
             default: Some(Project {
                 export: Some(Export {
                     module_path: Some("../src/maps.rs".into()),
+                    tile_format: None,
+                    compact_tile_indices: None,
                     tileset_png_path: Some("../assets/tilesets/tileset.png".into()),
                     tileset_1bit_path: None,
                     tileset_1bit_endianness: None,
+                    tileset_columns: None,
+                    tileset_pad_to_pow2: None,
+                    tileset_layout_json_path: None,
                     palette_image_path: Some("../assets/palette/palette.png".into()),
                     palette_json_path: None,
+                    palette_hex_path: None,
+                    palette_lookup_image_path: None,
+                    metadata_json_path: None,
+                    allow_mixed_tile_sizes: None,
                     skip_maps_with_prefix: None,
                 }),
             }),
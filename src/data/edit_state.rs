@@ -11,6 +11,9 @@ use crate::{
     data::{map_edit_state::MapEditState, maps::MapId, modal::ModalState, mode::Mode},
 };
 
+/// The number of layers in each bank selected by the number key shortcuts
+pub const LAYER_SHORTCUT_BANK_SIZE: usize = 9;
+
 #[derive(Default, Clone, PartialEq)]
 pub struct EditState {
     /// The [`TilesetId`] of the selected [`crate::tilesets::Tileset`],
@@ -82,6 +85,26 @@ pub struct EditState {
 
     /// The current editing [`Mode`]
     pub mode: Mode,
+
+    /// If true, drawing auto-tiles the drawn cells and their neighbours,
+    /// using the [`crate::data::wang_set::WangSet`] of each tile's tileset
+    pub autotile: bool,
+
+    /// If true, maps are drawn with a heatmap overlay showing the number
+    /// of layers containing a tile in each cell. This only affects drawing.
+    pub heatmap: bool,
+
+    /// If true, maps are drawn with each cell's exported tile index, see
+    /// [`crate::ui::tiles::Overlay::TileIndices`]. This only affects drawing.
+    pub tile_indices: bool,
+
+    /// If true, hovering a map cell shows a tooltip describing its topmost tile
+    pub tile_tooltips: bool,
+
+    /// The bank of layers selected by the number key shortcuts. Keys 1 to 9
+    /// select the layers in the bank, so for bank 0 these are layers 1 to 9,
+    /// for bank 1 layers 10 to 18, and so on.
+    pub layer_shortcut_bank: usize,
 }
 
 impl EditState {
@@ -105,6 +128,23 @@ impl EditState {
         self.stamp = self.stamp.with_color(TileColor::from_palette_index(i));
     }
 
+    /// The layer index selected by the number key shortcut with the given index
+    /// (0 for key 1, up to 8 for key 9), using the current layer shortcut bank
+    pub fn layer_shortcut_index(&self, key_index: usize) -> usize {
+        self.layer_shortcut_bank * LAYER_SHORTCUT_BANK_SIZE + key_index
+    }
+
+    /// Move to the next layer shortcut bank, wrapping back to the first bank
+    /// after the bank containing the last of `layer_count` layers
+    pub fn next_layer_shortcut_bank(&mut self, layer_count: usize) {
+        let next = self.layer_shortcut_bank + 1;
+        self.layer_shortcut_bank = if next * LAYER_SHORTCUT_BANK_SIZE < layer_count {
+            next
+        } else {
+            0
+        };
+    }
+
     pub fn show_modal(&mut self, modal_state: ModalState) {
         self.modal = modal_state;
     }
@@ -124,6 +164,16 @@ impl EditState {
         }
     }
 
+    /// Reset any map scene rects that can't be displayed, see
+    /// [`MapEditState::repair_scene_rect`]. Returns the number of scene rects reset.
+    pub fn repair_scene_rects(&mut self) -> usize {
+        self.map_edit_state_by_id
+            .values_mut()
+            .map(|edit| edit.repair_scene_rect())
+            .filter(|repaired| *repaired)
+            .count()
+    }
+
     /// Merge the relevant parts of the [`EditState`] from an undo/redo state with this
     /// [`EditState`]. The relevant parts are those needed to show the correct "context"
     /// for the change - i.e. make sure that the location of the change is visible in the UI.
@@ -148,3 +198,61 @@ impl EditState {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layer_shortcut_banks_wrap() {
+        let mut edit = EditState::default();
+        assert_eq!(edit.layer_shortcut_index(0), 0);
+
+        edit.next_layer_shortcut_bank(20);
+        assert_eq!(edit.layer_shortcut_index(0), 9);
+        edit.next_layer_shortcut_bank(20);
+        assert_eq!(edit.layer_shortcut_index(1), 19);
+        edit.next_layer_shortcut_bank(20);
+        assert_eq!(edit.layer_shortcut_bank, 0);
+
+        // With nine or fewer layers there's only one bank
+        edit.next_layer_shortcut_bank(9);
+        assert_eq!(edit.layer_shortcut_bank, 0);
+    }
+
+    #[test]
+    fn non_finite_scene_rects_are_repaired() {
+        use crate::data::state::StateRepairs;
+        use egui::{Rect, pos2};
+
+        let mut edit = EditState::default();
+        let valid = Rect::from_min_max(pos2(-10.0, 0.0), pos2(20.0, 15.0));
+        let ids = [MapId::ONE, MapId::ONE.next(), MapId::ONE.next().next()];
+        for (id, scene_rect) in ids.into_iter().zip([
+            valid,
+            Rect::from_min_max(pos2(0.0, 0.0), pos2(f32::NAN, 10.0)),
+            Rect::from_min_max(pos2(f32::NEG_INFINITY, 0.0), pos2(1.0, 1.0)),
+        ]) {
+            let map_edit = edit.map_edit_state_by_id.entry(id).or_default();
+            map_edit.scene_rect = scene_rect;
+        }
+
+        let scene_rects = edit.repair_scene_rects();
+        assert_eq!(scene_rects, 2);
+        let rects: Vec<_> = ids
+            .iter()
+            .map(|id| edit.map_edit_state_by_id.get(id).map(|e| e.scene_rect))
+            .collect();
+        assert_eq!(rects, vec![Some(valid), Some(Rect::ZERO), Some(Rect::ZERO)]);
+        assert_eq!(edit.repair_scene_rects(), 0);
+
+        let repairs = StateRepairs {
+            scene_rects,
+            ..Default::default()
+        };
+        assert_eq!(
+            repairs.descriptions(),
+            vec!["2 map view(s) with an invalid position were reset".to_string()]
+        );
+    }
+}
@@ -3,7 +3,11 @@ use egui::Rect;
 use crate::{
     data::{
         maps::Map,
-        tiles::{layer_tiles::LayerId, tile_selection::TileSelection},
+        stamp::StampAnchor,
+        tiles::{
+            Tile, Tiles, layer_tiles::LayerId, solo_tiles::SoloTiles,
+            tile_mesh_cache::TileMeshCache, tile_selection::TileSelection,
+        },
     },
     geom::i32pos2::I32Pos2,
     geom::u32pos2::U32Pos2,
@@ -16,9 +20,26 @@ pub struct MapEditState {
     pub layer_selection: Selection<LayerId>,
     pub map_hovered: Option<U32Pos2>,
     pub map_scene_hovered: Option<I32Pos2>,
+    /// The position of the keyboard cursor, if it is in use. While this
+    /// is present it is used in place of the pointer hover position, until
+    /// the pointer is moved.
+    pub cursor: Option<U32Pos2>,
     selection: TileSelection,
     /// Tracks the position where the map is displayed in a [`egui::Scene`]
     pub scene_rect: Rect,
+    /// A layer to show on its own, hiding all other layers, without changing
+    /// the `visible` flag of any layer
+    pub solo_layer: Option<LayerId>,
+    /// If true, drawing only places tiles in cells that are currently empty,
+    /// leaving existing tiles unchanged
+    pub draw_empty_cells_only: bool,
+    /// The point of the stamp placed at the cursor when drawing
+    pub stamp_anchor: StampAnchor,
+    /// The start and current end of a shape being dragged out in a mode
+    /// where [`crate::data::mode::Mode::draws_shape`], if any
+    pub shape_drag: Option<(I32Pos2, I32Pos2)>,
+    /// Meshes used to draw the map, reused while the map is unchanged
+    mesh_cache: TileMeshCache,
 }
 
 impl Default for MapEditState {
@@ -27,8 +48,14 @@ impl Default for MapEditState {
             layer_selection: Selection::new(),
             map_hovered: None,
             map_scene_hovered: None,
+            cursor: None,
             selection: Default::default(),
             scene_rect: Rect::ZERO,
+            solo_layer: None,
+            draw_empty_cells_only: false,
+            stamp_anchor: StampAnchor::Center,
+            shape_drag: None,
+            mesh_cache: Default::default(),
         }
     }
 }
@@ -42,14 +69,79 @@ impl MapEditState {
         &mut self.selection
     }
 
+    /// Mutable access to both the selection and the mesh cache, for drawing the map
+    pub fn selection_and_mesh_cache_mut(&mut self) -> (&mut TileSelection, &mut TileMeshCache) {
+        (&mut self.selection, &mut self.mesh_cache)
+    }
+
     pub fn no_layers_selected(&self, map: &Map) -> bool {
         !map.tiles()
             .layers()
             .any(|layer| self.layer_selection.is_selected(layer.id()))
     }
 
+    /// Show the layer on its own, or if it is already solo show all layers again.
+    /// Only the solo layer is selected, so that edits aren't made to hidden layers.
+    /// Hidden layers can't be made solo, since they can't be edited.
+    pub fn toggle_solo_layer(&mut self, map: &Map, id: LayerId) {
+        if self.solo_layer == Some(id) {
+            self.solo_layer = None;
+        } else if Self::layer_visible(map, id) {
+            self.solo_layer = Some(id);
+            self.layer_selection.select_only(id);
+        }
+    }
+
+    fn layer_visible(map: &Map, id: LayerId) -> bool {
+        map.tiles()
+            .layer_index_for_id(id)
+            .and_then(|index| map.tiles().layer_visible(index))
+            .unwrap_or(false)
+    }
+
+    /// The index of the solo layer in the map, if there is one
+    pub fn solo_layer_index(&self, map: &Map) -> Option<usize> {
+        self.solo_layer
+            .and_then(|id| map.tiles().layer_index_for_id(id))
+    }
+
+    /// The topmost displayed tile of the map at the hovered position, or at the keyboard
+    /// cursor if the map isn't hovered. Only visible layers are displayed, or just the
+    /// solo layer if there is one.
+    pub fn displayed_tile_at_pointer(&self, map: &Map) -> Option<Tile> {
+        let pos = self.map_hovered.or(self.cursor)?;
+        let tiles = SoloTiles {
+            solo_layer_index: self.solo_layer_index(map),
+            inner_tiles: map.tiles(),
+        };
+        // Tiles of layers that aren't displayed are empty
+        (0..tiles.layer_count()).find_map(|layer| tiles.tile(layer, pos))
+    }
+
+    /// A scene rect that isn't finite can't be displayed, so reset it to
+    /// zero, which will fit the scene to the map when next shown.
+    /// Returns true if the scene rect was reset.
+    pub fn repair_scene_rect(&mut self) -> bool {
+        if self.scene_rect.is_finite() {
+            false
+        } else {
+            self.scene_rect = Rect::ZERO;
+            true
+        }
+    }
+
     /// Apply invariants for this edit state, against specified [`Map`]
     pub(crate) fn apply_invariants(&mut self, map: &Map) {
+        self.repair_scene_rect();
+
+        // Leave solo mode if the solo layer has been deleted or hidden
+        if !self
+            .solo_layer
+            .is_some_and(|id| Self::layer_visible(map, id))
+        {
+            self.solo_layer = None;
+        }
+
         // If layer selection is empty, select the first (topmost) layer
         if self.no_layers_selected(map) {
             if let Some(layer) = map.tiles().first_layer() {
@@ -1,7 +1,9 @@
+use std::collections::BTreeMap;
 use std::fmt::Display;
 use std::slice::{Iter, IterMut};
 
 use egui::ahash::HashSet;
+use eyre::bail;
 
 use crate::data::tiles::layer_tiles::LayerTiles;
 use crate::data::tiles::tile_color::UserColor;
@@ -38,6 +40,11 @@ pub struct Map {
     id: MapId,
     pub name: String,
     pub tiles: LayerTiles,
+    /// User metadata for the map, e.g. per-level flags for games, as keys and
+    /// values. This is kept sorted by key, so that files and exports are stable.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
 }
 
 impl Display for Map {
@@ -74,6 +81,7 @@ impl Map {
             id: Default::default(),
             name,
             tiles,
+            metadata: BTreeMap::new(),
         }
     }
 
@@ -89,10 +97,31 @@ impl Map {
             id: Default::default(),
             name,
             tiles,
+            metadata: BTreeMap::new(),
         }
     }
 }
 
+/// Convert metadata entries as edited in the UI to [`Map::metadata`]. Keys and
+/// values are trimmed, and entries with an empty key are ignored, so that partly
+/// edited entries can be left blank. Errors if the same key is used more than once.
+pub fn metadata_from_entries(
+    entries: &[(String, String)],
+) -> eyre::Result<BTreeMap<String, String>> {
+    let mut metadata = BTreeMap::new();
+    for (key, value) in entries {
+        let key = key.trim();
+        if !key.is_empty()
+            && metadata
+                .insert(key.to_string(), value.trim().to_string())
+                .is_some()
+        {
+            bail!("Map metadata has more than one entry with key '{}'", key);
+        }
+    }
+    Ok(metadata)
+}
+
 #[derive(Clone, Default, serde::Deserialize, serde::Serialize, PartialEq)]
 pub struct Maps {
     maps: Vec<Map>,
@@ -145,3 +174,40 @@ impl SelectableDefault<MapId> for Maps {
         self.maps.first().map(|t| t.id())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(key: &str, value: &str) -> (String, String) {
+        (key.to_string(), value.to_string())
+    }
+
+    #[test]
+    fn metadata_entries_are_trimmed_and_checked() -> eyre::Result<()> {
+        let metadata = metadata_from_entries(&[
+            entry(" music ", " cave.ogg "),
+            entry("", "ignored"),
+            entry("biome", "desert"),
+        ])?;
+        assert_eq!(
+            metadata.into_iter().collect::<Vec<_>>(),
+            vec![entry("biome", "desert"), entry("music", "cave.ogg")]
+        );
+
+        assert!(
+            metadata_from_entries(&[entry("biome", "desert"), entry("biome ", "ice")]).is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn maps_without_metadata_can_be_loaded() -> eyre::Result<()> {
+        let map = Map::default();
+        let json = serde_json::to_string(&map)?;
+        assert!(!json.contains("metadata"));
+        let loaded: Map = serde_json::from_str(&json)?;
+        assert!(loaded.metadata.is_empty());
+        Ok(())
+    }
+}
@@ -6,9 +6,16 @@ use crate::{
         resources::TileResourceUse,
         settings::Settings,
         tiled::TiledExportSettings,
-        tiles::{Tiles, layer_tiles::LayerId, tile_color::UserColor},
+        tiles::{
+            Tile, Tiles,
+            layer_tiles::{LayerId, LayerKind, ResizeAnchor},
+            tile_color::UserColor,
+            tile_diff::TileDiff,
+            tile_selection::TileSelection,
+        },
         tilesets::{Tileset, TilesetId, Tilesets},
     },
+    geom::{u32rect::U32Rect, u32size2::U32Size2},
     tiled::tiled_json::Tiled,
 };
 use camino::Utf8PathBuf;
@@ -50,10 +57,39 @@ pub enum DataLossOperation {
     ReplacePalette {
         palette: Palette,
         resource_use: TileResourceUse,
+        /// The palette indices in use that the new palette doesn't have
+        lost_indices: Vec<u32>,
+        /// Change tiles using the lost colors to the nearest color in the new
+        /// palette, rather than deleting them
+        remap_to_nearest: bool,
+    },
+    ChangeTilesetColumns {
+        tileset_id: TilesetId,
+        /// The updated tileset, with a different number of columns
+        tileset: Tileset,
+        tileset_use: TileResourceUse,
+        old_columns: u32,
+        /// Change tiles using the tileset to keep showing the same tiles,
+        /// rather than the tiles now at their index
+        remap_columns: bool,
     },
     Quit,
 }
 
+/// A tileset whose image file can't be found, see
+/// [`crate::ui::tileset_textures::TilesetTextures::missing_tileset_image`]
+#[derive(Clone, PartialEq)]
+pub struct MissingTilesetImage {
+    pub tileset_id: TilesetId,
+    pub tileset_name: String,
+    /// The path the image was expected at, including any base dir
+    pub resolved_path: Utf8PathBuf,
+    /// Short description of the problem, e.g. the image was not found
+    pub problem: String,
+    /// Notes on how the path was resolved, see [`crate::ui::tileset_textures::TilesetError::path_notes`]
+    pub path_notes: String,
+}
+
 #[derive(Clone, PartialEq, Default)]
 pub enum ModalState {
     #[default]
@@ -66,6 +102,16 @@ pub enum ModalState {
         tile_height: u32,
         background_color: UserColor,
         background_color_as_text: String,
+        /// If true, the map uses the default map background from app settings,
+        /// rather than `background_color`
+        inherit_background: bool,
+        default_tile: Option<Tile>,
+        /// The tile that would be drawn with the current brush, if any - this
+        /// can be used as the default tile
+        brush_tile: Option<Tile>,
+        /// Entries of [`Map::metadata`] as keys and values, in the order shown to
+        /// the user, see [`crate::data::maps::metadata_from_entries`]
+        metadata: Vec<(String, String)>,
         operation: MapOperation,
         result: ModalResult,
     },
@@ -82,6 +128,14 @@ pub enum ModalState {
         default_background_as_text: String,
         default_transparent: UserColor,
         default_transparent_as_text: String,
+        /// If true, the tile width and height are kept equal when either is changed
+        lock_square: bool,
+        result: ModalResult,
+    },
+    MergeTilesets {
+        src: TilesetId,
+        dst: Option<TilesetId>,
+        index_map_as_text: String,
         result: ModalResult,
     },
     Layer {
@@ -89,12 +143,21 @@ pub enum ModalState {
         layer_id: LayerId,
         name: String,
         opacity: Option<f32>,
+        tint: Option<UserColor>,
+        default_tint: UserColor,
+        default_tint_as_text: String,
+        kind: LayerKind,
+        /// Store the layer's tiles sparsely, see [`crate::data::tiles::layer_storage::LayerStorage`]
+        sparse: bool,
         result: ModalResult,
     },
     ImportTiled {
         tiled: Tiled,
         squash_layers: bool,
         prefer_relative_path: bool,
+        use_layer_properties: bool,
+        /// If true, add the Tiled layers to the selected map, rather than a new map
+        into_selected_map: bool,
         result: ModalResult,
     },
     Error {
@@ -114,6 +177,8 @@ pub enum ModalState {
     },
     Settings {
         settings: Settings,
+        default_map_background_as_text: String,
+        checkerboard_colors_as_text: [String; 2],
         result: ModalResult,
     },
     ExportPng {
@@ -127,6 +192,40 @@ pub enum ModalState {
     Help {
         result: ModalResult,
     },
+    CompareMaps {
+        a: Option<MapId>,
+        b: Option<MapId>,
+        /// The differences between the maps with the given ids, kept until different
+        /// maps are chosen, since the maps can't be edited while the modal is shown
+        diffs: Option<(MapId, MapId, Vec<TileDiff>)>,
+        result: ModalResult,
+    },
+    MissingTilesetImages {
+        missing: Vec<MissingTilesetImage>,
+        result: ModalResult,
+    },
+    SplitTileset {
+        tileset_id: TilesetId,
+        /// The tiles currently selected in the image, to add as a new region
+        selection: TileSelection,
+        /// The name and region in the image, in tiles, of each new tileset
+        regions: Vec<(String, U32Rect)>,
+        result: ModalResult,
+    },
+    ResizeAllMaps {
+        width: u32,
+        height: u32,
+        anchor: ResizeAnchor,
+        /// The names of the maps that would lose tiles when resized to the given size
+        /// and anchor, kept until the size or anchor change
+        clipped: Option<(U32Size2, ResizeAnchor, Vec<String>)>,
+        result: ModalResult,
+    },
+    /// Lists the problems repaired when opening a project
+    ProjectRepaired {
+        repairs: Vec<String>,
+        result: ModalResult,
+    },
 }
 
 impl ModalState {
@@ -135,6 +234,7 @@ impl ModalState {
             ModalState::None => None,
             ModalState::Map { result, .. } => Some(result.clone()),
             ModalState::Tileset { result, .. } => Some(result.clone()),
+            ModalState::MergeTilesets { result, .. } => Some(result.clone()),
             ModalState::Layer { result, .. } => Some(result.clone()),
             ModalState::ImportTiled { result, .. } => Some(result.clone()),
             ModalState::Error { result, .. } => Some(result.clone()),
@@ -144,6 +244,11 @@ impl ModalState {
             ModalState::ExportPng { result, .. } => Some(result.clone()),
             ModalState::ExportTiled { result, .. } => Some(result.clone()),
             ModalState::Help { result, .. } => Some(result.clone()),
+            ModalState::CompareMaps { result, .. } => Some(result.clone()),
+            ModalState::MissingTilesetImages { result, .. } => Some(result.clone()),
+            ModalState::SplitTileset { result, .. } => Some(result.clone()),
+            ModalState::ResizeAllMaps { result, .. } => Some(result.clone()),
+            ModalState::ProjectRepaired { result, .. } => Some(result.clone()),
         }
     }
 
@@ -152,6 +257,7 @@ impl ModalState {
             ModalState::None => {}
             ModalState::Map { result, .. } => *result = ModalResult::Active,
             ModalState::Tileset { result, .. } => *result = ModalResult::Active,
+            ModalState::MergeTilesets { result, .. } => *result = ModalResult::Active,
             ModalState::Layer { result, .. } => *result = ModalResult::Active,
             ModalState::ImportTiled { result, .. } => *result = ModalResult::Active,
             ModalState::Error { result, .. } => *result = ModalResult::Active,
@@ -161,11 +267,18 @@ impl ModalState {
             ModalState::ExportPng { result, .. } => *result = ModalResult::Active,
             ModalState::ExportTiled { result, .. } => *result = ModalResult::Active,
             ModalState::Help { result, .. } => *result = ModalResult::Active,
+            ModalState::CompareMaps { result, .. } => *result = ModalResult::Active,
+            ModalState::MissingTilesetImages { result, .. } => *result = ModalResult::Active,
+            ModalState::SplitTileset { result, .. } => *result = ModalResult::Active,
+            ModalState::ResizeAllMaps { result, .. } => *result = ModalResult::Active,
+            ModalState::ProjectRepaired { result, .. } => *result = ModalResult::Active,
         }
     }
 
     pub fn tileset(tileset: Tileset, operation: TilesetOperation) -> ModalState {
         let transparent = tileset.mode.default_transparent_background();
+        // Lock to square tiles unless editing a tileset that already has non-square tiles
+        let lock_square = tileset.tile_size.w == tileset.tile_size.h;
         let mut tilesets = Tilesets::new();
         tilesets.push_tileset(tileset);
 
@@ -178,6 +291,25 @@ impl ModalState {
             default_background_as_text: String::new(),
             default_transparent: transparent,
             default_transparent_as_text: transparent.as_css_string(),
+            lock_square,
+            result: ModalResult::Init,
+        }
+    }
+
+    pub fn merge_tilesets(src: TilesetId, dst: Option<TilesetId>) -> ModalState {
+        ModalState::MergeTilesets {
+            src,
+            dst,
+            index_map_as_text: String::new(),
+            result: ModalResult::Init,
+        }
+    }
+
+    pub fn split_tileset(tileset_id: TilesetId) -> ModalState {
+        ModalState::SplitTileset {
+            tileset_id,
+            selection: TileSelection::new(),
+            regions: Vec::new(),
             result: ModalResult::Init,
         }
     }
@@ -199,7 +331,7 @@ impl ModalState {
         }
     }
 
-    pub fn new_map() -> ModalState {
+    pub fn new_map(brush_tile: Option<Tile>) -> ModalState {
         ModalState::Map {
             name: "New Map".to_string(),
             width: 32,
@@ -208,12 +340,16 @@ impl ModalState {
             tile_height: 8,
             background_color: UserColor::BLACK,
             background_color_as_text: String::new(),
+            inherit_background: true,
+            default_tile: None,
+            brush_tile,
+            metadata: vec![],
             operation: MapOperation::NewMap,
             result: Default::default(),
         }
     }
 
-    pub(crate) fn edit_map(map: &Map) -> ModalState {
+    pub(crate) fn edit_map(map: &Map, brush_tile: Option<Tile>) -> ModalState {
         let size = map.tiles().map_size();
         let tile_size = map.tiles().tile_size();
         ModalState::Map {
@@ -222,8 +358,16 @@ impl ModalState {
             height: size.h,
             tile_width: tile_size.w,
             tile_height: tile_size.h,
-            background_color: map.tiles().background,
+            background_color: map.tiles().background(),
             background_color_as_text: String::new(),
+            inherit_background: map.tiles().background.is_none(),
+            default_tile: map.tiles().default_tile,
+            brush_tile,
+            metadata: map
+                .metadata
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
             operation: MapOperation::UpdateExistingMap(map.id()),
             result: Default::default(),
         }
@@ -238,6 +382,22 @@ impl ModalState {
     pub(crate) fn settings(settings: Settings) -> ModalState {
         ModalState::Settings {
             settings,
+            default_map_background_as_text: String::new(),
+            checkerboard_colors_as_text: Default::default(),
+            result: Default::default(),
+        }
+    }
+
+    pub(crate) fn missing_tileset_images(missing: Vec<MissingTilesetImage>) -> ModalState {
+        ModalState::MissingTilesetImages {
+            missing,
+            result: Default::default(),
+        }
+    }
+
+    pub(crate) fn project_repaired(repairs: Vec<String>) -> ModalState {
+        ModalState::ProjectRepaired {
+            repairs,
             result: Default::default(),
         }
     }
@@ -254,6 +414,8 @@ impl ModalState {
             tiled,
             squash_layers: false,
             prefer_relative_path: true,
+            use_layer_properties: true,
+            into_selected_map: false,
             result: Default::default(),
         }
     }
@@ -271,4 +433,23 @@ impl ModalState {
             result: Default::default(),
         }
     }
+
+    pub(crate) fn compare_maps(a: Option<MapId>, b: Option<MapId>) -> ModalState {
+        ModalState::CompareMaps {
+            a,
+            b,
+            diffs: None,
+            result: Default::default(),
+        }
+    }
+
+    pub(crate) fn resize_all_maps(size: U32Size2) -> ModalState {
+        ModalState::ResizeAllMaps {
+            width: size.w,
+            height: size.h,
+            anchor: ResizeAnchor::default(),
+            clipped: None,
+            result: ModalResult::Init,
+        }
+    }
 }
@@ -3,5 +3,33 @@ pub enum Mode {
     Select,
     #[default]
     Draw,
+    /// Draw the stamp along a line, from where a drag starts to where it stops
+    Line,
+    /// Draw the stamp in an ellipse, fitting the rect from where a drag starts
+    /// to where it stops
+    Ellipse,
     Erase,
 }
+
+impl Mode {
+    /// The next mode in order, wrapping back to the first mode after the last
+    pub fn next(&self) -> Mode {
+        match self {
+            Mode::Select => Mode::Draw,
+            Mode::Draw => Mode::Line,
+            Mode::Line => Mode::Ellipse,
+            Mode::Ellipse => Mode::Erase,
+            Mode::Erase => Mode::Select,
+        }
+    }
+
+    /// True if the mode draws with the stamp
+    pub fn draws_stamp(&self) -> bool {
+        matches!(self, Mode::Draw | Mode::Line | Mode::Ellipse)
+    }
+
+    /// True if the mode draws a shape dragged out from one position to another
+    pub fn draws_shape(&self) -> bool {
+        matches!(self, Mode::Line | Mode::Ellipse)
+    }
+}
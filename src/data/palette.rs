@@ -5,7 +5,7 @@ use eyre::{bail, eyre};
 use image::{ImageReader, RgbaImage};
 use std::{
     fs::File,
-    io::{BufReader, BufWriter},
+    io::{BufReader, BufWriter, Write},
     slice::{Iter, IterMut},
 };
 
@@ -152,6 +152,31 @@ impl Palette {
         Ok(())
     }
 
+    /// Write the palette as a Paint.NET palette, which can also be imported by Aseprite.
+    /// This is a text file with one `AARRGGBB` hex color per line, in palette index order,
+    /// so imported swatches have the same indices as the palette.
+    pub fn write_to_paintnet_by_path(&self, path: Utf8PathBuf) -> eyre::Result<()> {
+        let file = File::create(path.clone())?;
+        let mut buf_writer = BufWriter::new(file);
+        buf_writer.write_all(self.as_paintnet_string().as_bytes())?;
+        buf_writer.flush()?;
+        Ok(())
+    }
+
+    fn as_paintnet_string(&self) -> String {
+        let mut s = "; paint.net Palette File\n; Exported by mountain-tiles\n".to_string();
+        for color in self.colors() {
+            s.push_str(&format!(
+                "{:02X}{:02X}{:02X}{:02X}\n",
+                color.a(),
+                color.r(),
+                color.g(),
+                color.b()
+            ));
+        }
+        s
+    }
+
     pub fn new(colors: Vec<UserColor>) -> Self {
         Self { colors }
     }
@@ -217,6 +242,38 @@ impl Palette {
         }
     }
 
+    /// The index of the first entry in the palette with the given color, if any
+    pub fn index_of_color(&self, color: UserColor) -> Option<PaletteIndex> {
+        self.colors
+            .iter()
+            .position(|c| *c == color)
+            .map(|i| palette_index(i as u32))
+    }
+
+    /// The index of the color in this palette nearest to `color`, by Euclidean distance
+    /// between RGB components (ignoring alpha), using the first entry if several are
+    /// equally near. [`None`] if the palette is empty.
+    pub fn nearest_color_index(&self, color: UserColor) -> Option<PaletteIndex> {
+        let distance_squared = |c: &UserColor| {
+            [(c.r(), color.r()), (c.g(), color.g()), (c.b(), color.b())]
+                .iter()
+                .map(|(a, b)| (*a as i32 - *b as i32).pow(2))
+                .sum::<i32>()
+        };
+        self.colors
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, c)| distance_squared(c))
+            .map(|(i, _)| palette_index(i as u32))
+    }
+
+    /// A copy of this palette with `color` added at the end
+    pub fn with_color_appended(&self, color: UserColor) -> Palette {
+        let mut colors = self.colors.clone();
+        colors.push(color);
+        Palette::new(colors)
+    }
+
     pub fn is_tilecolor_available(&self, color: &TileColor) -> bool {
         match color {
             TileColor::Default => true,
@@ -225,3 +282,52 @@ impl Palette {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paintnet_colors_in_index_order() {
+        let palette = Palette::new(vec![
+            UserColor::new(0x12, 0x34, 0x56, 0xFF),
+            UserColor::new(0xAB, 0xCD, 0xEF, 0x80),
+        ]);
+        assert_eq!(
+            palette.as_paintnet_string(),
+            "; paint.net Palette File\n; Exported by mountain-tiles\nFF123456\n80ABCDEF\n"
+        );
+    }
+
+    #[test]
+    fn find_and_append_colors() {
+        let red = UserColor::new(0xFF, 0, 0, 0xFF);
+        let palette = Palette::new(vec![UserColor::BLACK, UserColor::WHITE]);
+        assert_eq!(
+            palette.index_of_color(UserColor::WHITE),
+            Some(palette_index(1))
+        );
+        assert_eq!(palette.index_of_color(red), None);
+
+        let appended = palette.with_color_appended(red);
+        assert_eq!(appended.len(), 3);
+        assert_eq!(appended.index_of_color(red), Some(palette_index(2)));
+    }
+
+    #[test]
+    fn nearest_color() {
+        let palette = Palette::new(vec![
+            UserColor::BLACK,
+            UserColor::new(200, 30, 40, 255),
+            UserColor::new(20, 40, 220, 255),
+            UserColor::WHITE,
+        ]);
+        let orange = UserColor::new(240, 100, 0, 255);
+        assert_eq!(palette.nearest_color_index(orange), Some(palette_index(1)));
+        assert_eq!(
+            palette.nearest_color_index(UserColor::new(10, 10, 10, 0)),
+            Some(palette_index(0))
+        );
+        assert_eq!(Palette::new(vec![]).nearest_color_index(orange), None);
+    }
+}
@@ -1,7 +1,14 @@
+use crate::geom::u32size2::U32Size2;
+
 #[derive(serde::Deserialize, serde::Serialize, Clone, PartialEq)]
 pub struct PngExportSettings {
     pub scale: u32,
     pub transparent: bool,
+    /// If specified, `scale` is ignored, and the image is exported at the largest
+    /// integer scale that fits within this many pixels in each dimension, see
+    /// [`PngExportSettings::scale_for_size`]
+    #[serde(default)]
+    pub fit_within: Option<u32>,
 }
 
 impl Default for PngExportSettings {
@@ -9,6 +16,61 @@ impl Default for PngExportSettings {
         Self {
             scale: 4,
             transparent: false,
+            fit_within: None,
+        }
+    }
+}
+
+impl PngExportSettings {
+    /// The scale to use when exporting an image of `unscaled_size` pixels at scale 1.
+    /// When fitting within a size this is the largest integer scale where both width and
+    /// height fit, so pixels stay crisp, but never less than 1, so images already larger
+    /// than the size are exported unscaled.
+    pub fn scale_for_size(&self, unscaled_size: U32Size2) -> u32 {
+        match self.fit_within {
+            Some(max) => {
+                let fit = |unscaled: u32| max.checked_div(unscaled).unwrap_or(max);
+                fit(unscaled_size.w).min(fit(unscaled_size.h)).max(1)
+            }
+            None => self.scale,
+        }
+    }
+
+    /// These settings with the scale resolved for an image of `unscaled_size`
+    /// pixels at scale 1, see [`PngExportSettings::scale_for_size`]
+    pub fn resolved_for_size(&self, unscaled_size: U32Size2) -> PngExportSettings {
+        PngExportSettings {
+            scale: self.scale_for_size(unscaled_size),
+            transparent: self.transparent,
+            fit_within: None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::geom::u32size2::u32size2;
+
+    use super::*;
+
+    #[test]
+    fn scale_to_fit_within_size() {
+        let fit = |max| PngExportSettings {
+            fit_within: Some(max),
+            ..Default::default()
+        };
+
+        // Largest integer scale where both dimensions fit
+        assert_eq!(fit(1024).scale_for_size(u32size2(160, 100)), 6);
+        assert_eq!(fit(1024).scale_for_size(u32size2(100, 256)), 4);
+        assert_eq!(fit(1024).scale_for_size(u32size2(1024, 8)), 1);
+
+        // Images that are already too large are not shrunk
+        assert_eq!(fit(1024).scale_for_size(u32size2(2000, 8)), 1);
+
+        // Otherwise the fixed scale is used
+        let fixed = PngExportSettings::default();
+        assert_eq!(fixed.scale_for_size(u32size2(2000, 8)), 4);
+        assert_eq!(fit(64).resolved_for_size(u32size2(8, 8)).scale, 8,);
+    }
+}
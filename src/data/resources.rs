@@ -27,6 +27,18 @@ impl Resources {
 pub struct TileResourceLocation {
     pub map_name: String,
     pub layer_name: String,
+    /// The number of tiles using the resource in this layer
+    pub tile_count: usize,
+}
+
+impl std::fmt::Display for TileResourceLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} ({} tiles)",
+            self.map_name, self.layer_name, self.tile_count
+        )
+    }
 }
 
 #[derive(Clone, PartialEq)]
@@ -39,7 +51,7 @@ impl TileResourceUse {
     pub fn locations_to_string(&self) -> String {
         self.locations
             .iter()
-            .map(|l| format!("{}: {}", l.map_name, l.layer_name))
+            .map(|l| l.to_string())
             .collect::<Vec<_>>()
             .join(", ")
     }
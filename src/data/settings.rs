@@ -1,7 +1,154 @@
+use egui::ThemePreference;
+use eyre::eyre;
+
+use crate::{data::tiles::tile_color::UserColor, geom::u32size2::U32Size2};
+
+/// Which theme to use, see [`Settings::theme`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Deserialize, serde::Serialize)]
+pub enum ThemeChoice {
+    #[default]
+    Dark,
+    Light,
+    /// Follow the system theme, where this is available, otherwise dark
+    System,
+}
+
+impl ThemeChoice {
+    pub const ALL: [ThemeChoice; 3] = [ThemeChoice::Dark, ThemeChoice::Light, ThemeChoice::System];
+
+    pub fn preference(&self) -> ThemePreference {
+        match self {
+            ThemeChoice::Dark => ThemePreference::Dark,
+            ThemeChoice::Light => ThemePreference::Light,
+            ThemeChoice::System => ThemePreference::System,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ThemeChoice::Dark => "Dark",
+            ThemeChoice::Light => "Light",
+            ThemeChoice::System => "Follow system",
+        }
+    }
+}
+
+/// How selected cells, and cells about to be erased, are highlighted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Deserialize, serde::Serialize)]
+pub enum HighlightStyle {
+    /// A fill of the theme's selection or erase color
+    #[default]
+    Fill,
+    /// Stripes in the theme's selection or erase color, sloping in opposite
+    /// directions, so they can be told apart by pattern as well as color
+    Pattern,
+}
+
 /// Global, persistent settings for the app
-#[derive(serde::Deserialize, serde::Serialize, Default, Clone, PartialEq)]
+#[derive(serde::Deserialize, serde::Serialize, Clone, PartialEq)]
 #[serde(default)] // if we add new fields, give them default values when deserializing old state
 pub struct Settings {
     /// If true, the tileset grid will have spacing to separate tiles visually
     pub tileset_grid_spacing_enabled: bool,
+
+    /// The maximum number of tiles (width times height) a map can be created
+    /// or resized to have. This is a soft limit to avoid accidentally trying to
+    /// allocate enormous maps, which can run out of memory.
+    pub max_map_tiles: u64,
+
+    /// The background for maps that inherit their background, rather than
+    /// having their own background color
+    pub default_map_background: UserColor,
+
+    /// The two colors of the checkerboard shown over tileset previews to show
+    /// the extent of each tile, or [`None`] to use the theme color alternating
+    /// with transparent squares
+    pub checkerboard_colors: Option<[UserColor; 2]>,
+
+    /// The width and height of each checkerboard square, in tiles
+    pub checkerboard_size: u32,
+
+    /// If true, the stamp's transform is cleared when changing mode, tileset,
+    /// selected tiles or layer, rather than being kept until cleared by the user
+    pub reset_transform_on_change: bool,
+
+    /// The theme to use for the app
+    pub theme: ThemeChoice,
+
+    /// How selected cells, and cells about to be erased, are highlighted
+    pub highlight_style: HighlightStyle,
+
+    /// If true, the map view is moved by less than a pixel after panning and zooming,
+    /// so the map is drawn aligned to screen pixels, keeping pixel art crisp
+    pub snap_scene_to_pixels: bool,
+
+    /// If true, tileset image files are watched, and reloaded when they change on disk,
+    /// e.g. when saved from an image editor. Not supported on web.
+    pub watch_tileset_images: bool,
+
+    /// Changes made within this many seconds of each other, before an edit is complete,
+    /// are undone in one step, see [`crate::undo::Settings::coalesce_seconds`]
+    pub undo_coalesce_seconds: f64,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            tileset_grid_spacing_enabled: false,
+            max_map_tiles: 16_000_000,
+            default_map_background: UserColor::BLACK,
+            checkerboard_colors: None,
+            checkerboard_size: 1,
+            reset_transform_on_change: false,
+            theme: ThemeChoice::Dark,
+            highlight_style: HighlightStyle::Fill,
+            snap_scene_to_pixels: true,
+            watch_tileset_images: false,
+            undo_coalesce_seconds: 0.5,
+        }
+    }
+}
+
+impl Settings {
+    /// Check that a map size is within [`Settings::max_map_tiles`], giving an error
+    /// explaining the limit if not
+    pub fn check_map_size(&self, map_size: U32Size2) -> eyre::Result<()> {
+        // Use u64 since the area of very large maps can overflow u32
+        let tiles = map_size.w as u64 * map_size.h as u64;
+        if tiles > self.max_map_tiles {
+            Err(eyre!(
+                "A map of {}x{} tiles has {} tiles, more than the maximum of {}.\nThe maximum can be changed in application settings.",
+                map_size.w,
+                map_size.h,
+                tiles,
+                self.max_map_tiles
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::geom::u32size2::u32size2;
+
+    use super::*;
+
+    #[test]
+    fn check_map_size() {
+        let settings = Settings {
+            max_map_tiles: 100,
+            ..Default::default()
+        };
+        assert!(settings.check_map_size(u32size2(10, 10)).is_ok());
+        assert!(settings.check_map_size(u32size2(10, 11)).is_err());
+
+        // Area overflows u32
+        assert!(
+            Settings::default()
+                .check_map_size(u32size2(100_000, 100_000))
+                .is_err()
+        );
+    }
 }
@@ -36,6 +36,16 @@ impl TileLocation {
     }
 }
 
+/// The point of a [`Stamp`] that is placed at the cursor when drawing
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum StampAnchor {
+    /// The center of the stamp, as it was selected
+    #[default]
+    Center,
+    /// The top left corner of the stamp's bounding box
+    TopLeft,
+}
+
 #[derive(Debug, Default, Clone, PartialEq)]
 pub struct Stamp {
     pub tiles: HashMap<TileLocation, Tile>,
@@ -102,6 +112,28 @@ impl Stamp {
         self.tiles.is_empty()
     }
 
+    /// The tile to use when a single tile is needed from the stamp, e.g.
+    /// for stroking a selection. This is the tile at the center of the
+    /// first stamp layer if there is one, otherwise the tile with the
+    /// lowest stamp layer index and position (in row-major order).
+    pub fn primary_tile(&self) -> Option<Tile> {
+        self.tiles
+            .get(&TileLocation::new(0, I32Pos2::new(0, 0)))
+            .or_else(|| {
+                self.tiles
+                    .iter()
+                    .min_by_key(|(location, _tile)| {
+                        (
+                            location.stamp_layer_index,
+                            location.position.y,
+                            location.position.x,
+                        )
+                    })
+                    .map(|(_location, tile)| tile)
+            })
+            .copied()
+    }
+
     pub fn with_transform(&self, transform: Transform) -> Stamp {
         let mut stamp = Stamp::new();
 
@@ -120,6 +152,7 @@ impl Stamp {
             let new_tile = tile.with_color(color);
             stamp.insert(location.clone(), new_tile);
         }
+        stamp.transform = self.transform;
         stamp
     }
 
@@ -127,17 +160,48 @@ impl Stamp {
         self.layer_count
     }
 
+    /// A stamp with a copy of this stamp's tiles centered at each of `positions`,
+    /// where later copies replace any overlapping tiles from earlier ones
+    pub fn repeated_at(&self, positions: &[I32Pos2]) -> Stamp {
+        let mut stamp = Stamp::new();
+        for pos in positions {
+            for (location, tile) in self.tiles.iter() {
+                stamp.insert(
+                    TileLocation::new(location.stamp_layer_index, location.position + *pos),
+                    *tile,
+                );
+            }
+        }
+        stamp.layer_count = self.layer_count;
+        stamp.transform = self.transform;
+        stamp
+    }
+
+    /// The offset to add to tile positions so that the specified anchor of
+    /// the stamp is at the origin. Tile positions are already relative to
+    /// the center, so this is zero for [`StampAnchor::Center`].
+    pub fn anchor_offset(&self, anchor: StampAnchor) -> I32Pos2 {
+        let origin = I32Pos2::new(0, 0);
+        match anchor {
+            StampAnchor::Center => origin,
+            StampAnchor::TopLeft => self
+                .tiles
+                .keys()
+                .map(|location| location.position)
+                .reduce(|a, b| a.min_corner(b))
+                .map(|top_left| origin - top_left)
+                .unwrap_or(origin),
+        }
+    }
+
     /// For each tile in the stamp, move the tileset tile we are using by the specified shift.
     pub fn shift(&mut self, tilesets: &Tilesets, shift: I32Pos2) {
         for tile in self.tiles.values_mut() {
             if let Some(tileset) = tilesets.get_by_id(tile.source.tileset_id) {
-                let tileset_pos = tileset
-                    .size_in_tiles
-                    .pos_from_linear_index(tile.source.tile_index.index());
-                let new_pos = tileset
-                    .size_in_tiles
-                    .u32pos_shifted_and_wrapped(tileset_pos, shift);
-                if let Some(new_index) = new_pos.linear_index(tileset.size_in_tiles) {
+                let grid_size = tileset.grid_size();
+                let tileset_pos = grid_size.pos_from_linear_index(tile.source.tile_index.index());
+                let new_pos = grid_size.u32pos_shifted_and_wrapped(tileset_pos, shift);
+                if let Some(new_index) = new_pos.linear_index(grid_size) {
                     tile.source.tile_index = TileIndex::new(new_index);
                 }
             }
@@ -148,3 +212,153 @@ impl Stamp {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        data::{tiles::TileSource, tilesets::TilesetId},
+        geom::i32pos2::i32pos2,
+    };
+
+    use super::*;
+
+    fn tile(index: u32) -> Tile {
+        Tile {
+            source: TileSource {
+                tileset_id: TilesetId::ONE,
+                tile_index: TileIndex::new(index),
+            },
+            color: TileColor::Default,
+            transform: Transform::None,
+        }
+    }
+
+    #[test]
+    fn rotate_l_shaped_stamp() {
+        // An L shape, with a different tile at each position
+        let positions = [i32pos2(0, -1), i32pos2(0, 0), i32pos2(0, 1), i32pos2(1, 1)];
+        let mut stamp = Stamp::new();
+        for (index, position) in positions.iter().enumerate() {
+            stamp.insert(TileLocation::new(0, *position), tile(index as u32));
+        }
+
+        let rotated = stamp.with_transform(Transform::Rotate90);
+        assert_eq!(rotated.transform, Transform::Rotate90);
+        assert_eq!(rotated.tiles.len(), positions.len());
+        for (index, position) in positions.iter().enumerate() {
+            // Each tile moves with the stamp as a whole, and is also rotated itself
+            let location = TileLocation::new(0, Transform::Rotate90.apply_to_pos(position));
+            assert_eq!(
+                rotated.tiles.get(&location),
+                Some(&tile(index as u32).with_transform(Transform::Rotate90))
+            );
+        }
+
+        // The L is no longer in its original arrangement
+        assert_ne!(
+            rotated.tiles.keys().collect::<HashSet<_>>(),
+            stamp.tiles.keys().collect::<HashSet<_>>()
+        );
+
+        // Four rotations bring us back to the original stamp
+        let mut back = rotated;
+        for _ in 0..3 {
+            back = back.with_transform(Transform::Rotate90);
+        }
+        assert_eq!(back.transform, Transform::None);
+        assert_eq!(back.tiles, stamp.tiles);
+    }
+
+    #[test]
+    fn recolored_stamp_keeps_transform() {
+        let mut stamp = Stamp::new();
+        stamp.insert(TileLocation::new(0, i32pos2(1, 0)), tile(1));
+        let color = TileColor::from_palette_index(crate::data::palette::palette_index(2));
+
+        let recolored = stamp
+            .with_transform(Transform::MirrorXRotate90)
+            .with_color(color);
+        assert_eq!(recolored.transform, Transform::MirrorXRotate90);
+
+        // So the transform can still be cleared
+        let cleared = recolored.with_transform(recolored.transform.inverse());
+        assert_eq!(cleared.transform, Transform::None);
+        assert_eq!(cleared.tiles, stamp.with_color(color).tiles);
+    }
+
+    #[test]
+    fn repeat_stamp_along_positions() {
+        let mut stamp = Stamp::new();
+        stamp.insert(TileLocation::new(0, i32pos2(0, 0)), tile(1));
+        stamp.insert(TileLocation::new(1, i32pos2(1, 0)), tile(2));
+
+        // Each copy keeps its tiles on their original stamp layers
+        let repeated = stamp.repeated_at(&[i32pos2(0, 0), i32pos2(1, 0)]);
+        assert_eq!(repeated.layer_count(), 2);
+        assert_eq!(repeated.tiles.len(), 4);
+        assert_eq!(
+            repeated.tiles.get(&TileLocation::new(0, i32pos2(1, 0))),
+            Some(&tile(1))
+        );
+        assert_eq!(
+            repeated.tiles.get(&TileLocation::new(1, i32pos2(2, 0))),
+            Some(&tile(2))
+        );
+    }
+
+    #[test]
+    fn top_left_anchor_offset() {
+        let mut stamp = Stamp::new();
+        assert_eq!(stamp.anchor_offset(StampAnchor::TopLeft), i32pos2(0, 0));
+
+        // The top left corner is taken across all layers, even where no tile is there
+        stamp.insert(TileLocation::new(0, i32pos2(-1, 0)), tile(0));
+        stamp.insert(TileLocation::new(0, i32pos2(1, 1)), tile(1));
+        stamp.insert(TileLocation::new(1, i32pos2(0, -2)), tile(2));
+        assert_eq!(stamp.anchor_offset(StampAnchor::Center), i32pos2(0, 0));
+        assert_eq!(stamp.anchor_offset(StampAnchor::TopLeft), i32pos2(1, 2));
+    }
+
+    #[test]
+    fn dragged_tileset_rect_builds_stamp() {
+        use crate::{
+            data::tiles::{TileEvent, tile_color::UserColor, tileset_tiles::TilesetTiles},
+            geom::{u32pos2::u32pos2, u32size2::u32size2},
+        };
+
+        let tileset_tiles = TilesetTiles {
+            foreground: UserColor::WHITE,
+            background: UserColor::BLACK,
+            tileset_id: TilesetId::ONE,
+            tile_size: u32size2(8, 8),
+            map_size: u32size2(4, 3),
+            tile_count: 12,
+            scale: 1.0,
+            gap: u32size2(0, 0),
+        };
+
+        // Drag out a 2x2 block from (1, 0) to (2, 1)
+        let mut selection = TileSelection::new();
+        selection.apply_tile_event(&TileEvent::DragStarted { pos: u32pos2(1, 0) }, false, false);
+        selection.apply_tile_event(&TileEvent::Dragged { pos: u32pos2(2, 0) }, false, false);
+        assert!(selection.apply_tile_event(
+            &TileEvent::DragStopped { pos: u32pos2(2, 1) },
+            false,
+            false
+        ));
+
+        // Positions are relative to the selection center, which rounds down to (1, 0)
+        let stamp = Stamp::from_selected_tiles(&tileset_tiles, &selection, None);
+        let index_at = |x, y| {
+            stamp
+                .tiles
+                .get(&TileLocation::new(0, i32pos2(x, y)))
+                .map(|tile| tile.source.tile_index)
+        };
+        assert_eq!(stamp.tiles.len(), 4);
+        assert_eq!(index_at(0, 0), Some(TileIndex::new(1)));
+        assert_eq!(index_at(1, 0), Some(TileIndex::new(2)));
+        assert_eq!(index_at(0, 1), Some(TileIndex::new(5)));
+        assert_eq!(index_at(1, 1), Some(TileIndex::new(6)));
+    }
+}
@@ -1,6 +1,9 @@
 use crate::{
-    data::palette::Palette,
-    data::tiles::Tile,
+    data::palette::{Palette, PaletteIndex},
+    data::tiles::{
+        Tile, TileIndex, TileSource,
+        tile_color::{TileColor, UserColor},
+    },
     data::tilesets::TilesetId,
     data::{
         action::ActionResult,
@@ -11,6 +14,8 @@ use crate::{
     undo::Undoable,
 };
 use camino::Utf8PathBuf;
+use egui::ahash::{HashMap, HashSet};
+use eyre::bail;
 use std::{
     fs::File,
     io::{BufReader, BufWriter},
@@ -43,6 +48,10 @@ impl Undoable for State {
     fn has_changed_from(&self, previous: &Self) -> bool {
         self.revision != previous.revision
     }
+
+    fn is_complete(&self) -> bool {
+        !self.pending_change
+    }
 }
 
 impl State {
@@ -84,6 +93,7 @@ impl State {
                     locations.push(TileResourceLocation {
                         map_name: map.name(),
                         layer_name: layer.name(),
+                        tile_count: layer_tile_count,
                     });
                     tile_count += layer_tile_count;
                 }
@@ -107,6 +117,19 @@ impl State {
         self.find_use_of_resource(|t| t.is_some_and(|t| !palette.is_tilecolor_available(&t.color)))
     }
 
+    /// The palette indices used by layer tiles that are not in `palette`, so would be
+    /// lost if it replaced the current palette, in increasing order. Default tiles
+    /// aren't cleared when replacing the palette, so their indices aren't included.
+    pub fn used_palette_indices_outside(&self, palette: &Palette) -> Vec<u32> {
+        let mut indices: Vec<u32> = self
+            .palette_indices(false)
+            .into_iter()
+            .filter(|index| *index >= palette.len())
+            .collect();
+        indices.sort_unstable();
+        indices
+    }
+
     pub fn clear_tiles_with_tileset(&mut self, tileset_id: TilesetId) -> bool {
         let mut change = false;
         for tiles in self.maps.iter_mut().map(|map| &mut map.tiles) {
@@ -115,13 +138,166 @@ impl State {
         change
     }
 
+    /// Keep tiles using `tileset_id` showing the same tiles when its number of columns
+    /// changes, see [`crate::data::tiles::layer_tiles::LayerTiles::remap_tileset_columns`].
+    /// Returns the number of tiles changed or removed.
+    pub fn remap_tileset_columns(
+        &mut self,
+        tileset_id: TilesetId,
+        old_columns: u32,
+        new_columns: u32,
+    ) -> usize {
+        self.maps
+            .iter_mut()
+            .map(|map| {
+                map.tiles
+                    .remap_tileset_columns(tileset_id, old_columns, new_columns)
+            })
+            .sum()
+    }
+
+    /// Merge tileset `src` into tileset `dst`: every tile using `src` is changed to use
+    /// `dst`, with tile indices mapped using `index_map` (indices not present in
+    /// `index_map` are unchanged, giving an identity mapping by default), then `src`
+    /// is deleted.
+    /// Returns the number of tiles remapped, or an error if the tilesets are
+    /// the same, either does not exist, or any remapped tile index would be
+    /// outside `dst`, in which case no changes are made.
+    pub fn merge_tilesets(
+        &mut self,
+        src: TilesetId,
+        dst: TilesetId,
+        index_map: &HashMap<u32, u32>,
+    ) -> eyre::Result<usize> {
+        if src == dst {
+            bail!("Cannot merge a tileset into itself");
+        }
+        let tilesets = self.resources.tilesets();
+        if tilesets.get_by_id(src).is_none() || tilesets.get_by_id(dst).is_none() {
+            bail!("Cannot merge tilesets, tileset not found");
+        }
+
+        // Check every tile will be valid in dst before changing anything
+        for map in self.maps.iter() {
+            let tiles = map
+                .tiles
+                .layers()
+                .flat_map(|layer| layer.tiles_iter())
+                .chain(std::iter::once(map.tiles.default_tile));
+            for tile in tiles.flatten().filter(|t| t.source.tileset_id == src) {
+                let index = tile.source.tile_index.index();
+                let remapped = TileSource {
+                    tileset_id: dst,
+                    tile_index: TileIndex::new(index_map.get(&index).copied().unwrap_or(index)),
+                };
+                if !tilesets.is_tile_source_available(remapped) {
+                    bail!(
+                        "Cannot merge tilesets, tile {} in map '{}' would become tile {}, which is outside the destination tileset.\nPlease map it to a tile in the destination tileset.",
+                        index,
+                        map.name,
+                        remapped.tile_index.index()
+                    );
+                }
+            }
+        }
+
+        let count = self
+            .maps
+            .iter_mut()
+            .map(|map| map.tiles.remap_tileset(src, dst, index_map))
+            .sum();
+        self.resources.tilesets_mut().delete_by_id(src);
+        Ok(count)
+    }
+
     pub fn clear_tiles_outside_palette(&mut self, palette: &Palette) {
         for map in self.maps.iter_mut() {
             map.tiles.clear_tiles_outside_palette(palette);
         }
     }
 
+    /// Change tiles using colors that aren't in `palette` to use the nearest color in
+    /// `palette` to their current color (see [`Palette::nearest_color_index`]), so they
+    /// look as similar as possible once `palette` replaces the current palette.
+    /// Tiles with colors that aren't in the current palette either are removed.
+    pub fn remap_tiles_outside_palette(&mut self, palette: &Palette) {
+        let current = &self.resources.palette;
+        let index_map: HashMap<u32, u32> = self
+            .used_palette_indices()
+            .into_iter()
+            .filter(|index| *index >= palette.len())
+            .filter_map(|index| {
+                let color = current.color_option(PaletteIndex::new(index))?;
+                let nearest = palette.nearest_color_index(color)?;
+                Some((index, nearest.index()))
+            })
+            .collect();
+        for map in self.maps.iter_mut() {
+            map.tiles.remap_palette(&index_map);
+        }
+        self.clear_tiles_outside_palette(palette);
+    }
+
+    /// The palette indices used by any tile in any map, including default tiles
+    pub fn used_palette_indices(&self) -> HashSet<u32> {
+        self.palette_indices(true)
+    }
+
+    /// The palette indices used by any layer tile in any map, and by default tiles
+    /// if `include_default_tiles` is true
+    fn palette_indices(&self, include_default_tiles: bool) -> HashSet<u32> {
+        let mut used = HashSet::default();
+        for map in self.maps.iter() {
+            let default_tile = map.tiles.default_tile.filter(|_| include_default_tiles);
+            let tiles = map
+                .tiles
+                .layers()
+                .flat_map(|layer| layer.tiles_iter())
+                .chain(std::iter::once(default_tile));
+            for tile in tiles.flatten() {
+                if let TileColor::Palette { index } = tile.color {
+                    used.insert(index.index());
+                }
+            }
+        }
+        used
+    }
+
+    /// Remove palette colors that aren't used by any tile, see [`State::used_palette_indices`],
+    /// and change tiles to use the new indices of the remaining colors, so they look the same.
+    /// The first color is kept if no colors are used, so the palette is never empty.
+    /// Returns the new index of each old index that was kept.
+    pub fn compact_palette(&mut self) -> HashMap<u32, u32> {
+        let used = self.used_palette_indices();
+        let palette = &self.resources.palette;
+        let keep = |i: u32| used.contains(&i) || (used.is_empty() && i == 0);
+
+        let mut index_map = HashMap::default();
+        let mut colors = vec![];
+        for (i, color) in (0..).zip(palette.colors()) {
+            if keep(i) {
+                index_map.insert(i, colors.len() as u32);
+                colors.push(*color);
+            }
+        }
+
+        if colors.len() as u32 != palette.len() {
+            for map in self.maps.iter_mut() {
+                map.tiles.remap_palette(&index_map);
+            }
+            self.resources.palette = Palette::new(colors);
+        }
+        index_map
+    }
+
+    /// Load a state from a file, repairing any problems with the data,
+    /// see [`State::validate_and_repair`]
     pub fn from_path(path: Utf8PathBuf) -> eyre::Result<State> {
+        Self::from_path_with_repairs(path).map(|(state, _)| state)
+    }
+
+    /// As [`State::from_path`], also returning the repairs made to the loaded data
+    pub fn from_path_with_repairs(path: Utf8PathBuf) -> eyre::Result<(State, StateRepairs)> {
         // First confirm file has correct format - this will give better errors
         // on unsupported files than trying to load as [`FileContents`] directly
         confirm_format(path.clone())?;
@@ -130,7 +306,46 @@ impl State {
         let buf_reader = BufReader::new(file);
         let file_contents: FileContents = serde_json::from_reader(buf_reader)?;
 
-        Ok(file_contents.state)
+        let mut state = file_contents.state;
+        let repairs = state.validate_and_repair();
+        Ok((state, repairs))
+    }
+
+    /// Check for data that can't be produced by editing normally, but may be present
+    /// in corrupted or externally edited files, and repair it:
+    /// - Tilesets or layers with duplicate ids are given new unique ids.
+    /// - Tiles using a tileset that doesn't exist, or a tile index outside their
+    ///   tileset, are removed.
+    /// - Tiles using a color outside the palette are removed.
+    ///
+    /// Returns a summary of the repairs made.
+    pub fn validate_and_repair(&mut self) -> StateRepairs {
+        // Repair tileset ids first, so tiles are checked against the repaired tilesets
+        let tileset_ids = self.resources.tilesets_mut().repair_tileset_ids();
+        let mut repairs = StateRepairs {
+            tileset_ids,
+            layer_ids: self.repair_layer_ids(),
+            ..Default::default()
+        };
+        for map in self.maps.iter_mut() {
+            repairs.tiles_with_missing_tilesets += map
+                .tiles
+                .clear_tiles_with_missing_tilesets(self.resources.tilesets());
+            repairs.tiles_outside_palette += map
+                .tiles
+                .clear_tiles_outside_palette(self.resources.palette());
+        }
+        repairs
+    }
+
+    /// Make sure layer ids are unique within each map, see
+    /// [`crate::data::tiles::layer_tiles::LayerTiles::repair_layer_ids`].
+    /// Returns the number of layers given new ids.
+    pub fn repair_layer_ids(&mut self) -> usize {
+        self.maps
+            .iter_mut()
+            .map(|map| map.tiles.repair_layer_ids())
+            .sum()
     }
 
     pub fn save_to_path(&mut self, path: Utf8PathBuf) -> eyre::Result<()> {
@@ -153,13 +368,203 @@ impl State {
     pub fn on_save(&mut self, path: Utf8PathBuf) {
         self.resources.tilesets.on_save(path);
     }
+
+    /// Set the background used by maps that don't have their own background, normally
+    /// [`crate::data::settings::Settings::default_map_background`]. This isn't saved, so
+    /// must be set after loading state, whether or not the application window is open.
+    pub fn set_inherited_backgrounds(&mut self, background: UserColor) {
+        for map in self.maps.iter_mut() {
+            map.tiles.set_inherited_background(background);
+        }
+    }
+
+    /// Prepare to save to `path`, where relative tileset paths are currently relative
+    /// to `base_dir`. If `path` is in a different directory, relative tileset paths
+    /// are made absolute, so that [`State::on_save`] can make them relative to the
+    /// new directory where preferred, rather than leaving them relative to the old one.
+    pub fn rebase_tileset_paths(&mut self, base_dir: Option<&Utf8PathBuf>, path: &Utf8PathBuf) {
+        if let Some(base_dir) = base_dir
+            && path.parent() != Some(base_dir.as_path())
+        {
+            self.resources.tilesets.resolve_relative_paths(base_dir);
+        }
+    }
+}
+
+/// The repairs made by [`State::validate_and_repair`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StateRepairs {
+    /// The number of tilesets given new ids
+    pub tileset_ids: usize,
+    /// The number of layers given new ids
+    pub layer_ids: usize,
+    /// The number of tiles removed since their tileset or tile index didn't exist
+    pub tiles_with_missing_tilesets: usize,
+    /// The number of tiles removed since their color was not in the palette
+    pub tiles_outside_palette: usize,
+    /// The number of map views reset since their scene rect was not finite, see
+    /// [`crate::data::map_edit_state::MapEditState::repair_scene_rect`]. Scene rects are
+    /// edit state, so this is only counted by [`crate::app::App::validate_and_repair`].
+    pub scene_rects: usize,
+}
+
+impl StateRepairs {
+    pub fn is_empty(&self) -> bool {
+        self == &StateRepairs::default()
+    }
+
+    /// A description of each kind of repair made, to show to the user
+    pub fn descriptions(&self) -> Vec<String> {
+        [
+            (
+                self.tileset_ids,
+                "tileset(s) with duplicate ids were given new ids",
+            ),
+            (
+                self.layer_ids,
+                "layer(s) with duplicate ids were given new ids",
+            ),
+            (
+                self.tiles_with_missing_tilesets,
+                "tile(s) using a missing tileset or tile were removed",
+            ),
+            (
+                self.tiles_outside_palette,
+                "tile(s) using a color outside the palette were removed",
+            ),
+            (
+                self.scene_rects,
+                "map view(s) with an invalid position were reset",
+            ),
+        ]
+        .into_iter()
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, description)| format!("{} {}", count, description))
+        .collect()
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use egui::ahash::HashMapExt;
+
+    use crate::{
+        data::{
+            maps::{Map, MapId},
+            palette::palette_index,
+            tiles::{
+                TileIndex, TileSource, Tiles,
+                tile_color::{TileColor, UserColor},
+            },
+            tilesets::{Tileset, TilesetMode, Tilesets},
+        },
+        geom::{transform::Transform, u32pos2::u32pos2, u32size2::u32size2},
+    };
 
     use super::*;
 
+    fn tileset(name: &str) -> Tileset {
+        Tileset::new_with_default_id(
+            name.to_string(),
+            format!("{}.png", name).into(),
+            u32size2(8, 8),
+            u32size2(4, 4),
+            TilesetMode::Direct,
+            None,
+            None,
+            false,
+        )
+    }
+
+    fn tile(tileset_id: TilesetId, index: u32) -> Tile {
+        Tile {
+            source: TileSource {
+                tileset_id,
+                tile_index: TileIndex::new(index),
+            },
+            color: TileColor::Default,
+            transform: Transform::None,
+        }
+    }
+
+    #[test]
+    fn merge_tilesets_remaps_tiles() -> eyre::Result<()> {
+        let mut state = State::default();
+        let src = state.resources.tilesets_mut().push_tileset(tileset("src"));
+        let dst = state.resources.tilesets_mut().push_tileset(tileset("dst"));
+
+        let mut map = Map::new_with_layer(
+            "Map".to_string(),
+            u32size2(4, 4),
+            u32size2(8, 8),
+            UserColor::BLACK,
+        );
+        map.tiles.set_tile(0, u32pos2(0, 0), Some(tile(src, 1)));
+        map.tiles.set_tile(0, u32pos2(1, 0), Some(tile(src, 2)));
+        map.tiles.set_tile(0, u32pos2(2, 0), Some(tile(dst, 1)));
+        let map_id = state.maps.push_map(map);
+
+        let mut index_map = HashMap::new();
+        index_map.insert(1, 3);
+        let count = state.merge_tilesets(src, dst, &index_map)?;
+        assert_eq!(count, 2);
+
+        assert!(state.resources.tilesets().get_by_id(src).is_none());
+        let tiles = &state
+            .maps
+            .get_by_id(map_id)
+            .ok_or(eyre::eyre!("no map"))?
+            .tiles;
+        assert_eq!(tiles.tile(0, u32pos2(0, 0)), Some(tile(dst, 3)));
+        assert_eq!(tiles.tile(0, u32pos2(1, 0)), Some(tile(dst, 2)));
+        assert_eq!(tiles.tile(0, u32pos2(2, 0)), Some(tile(dst, 1)));
+
+        // Merging a tileset that no longer exists, or into itself, fails
+        assert!(state.merge_tilesets(src, dst, &index_map).is_err());
+        assert!(state.merge_tilesets(dst, dst, &index_map).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn merge_into_smaller_tileset_is_refused() -> eyre::Result<()> {
+        let mut state = State::default();
+        // 16 tiles, merged into 4 tiles
+        let src = state.resources.tilesets_mut().push_tileset(tileset("src"));
+        let mut small = tileset("dst");
+        small.size_in_tiles = u32size2(2, 2);
+        let dst = state.resources.tilesets_mut().push_tileset(small);
+
+        let mut map = Map::new_with_layer(
+            "Map".to_string(),
+            u32size2(4, 4),
+            u32size2(8, 8),
+            UserColor::BLACK,
+        );
+        map.tiles.set_tile(0, u32pos2(0, 0), Some(tile(src, 1)));
+        map.tiles.set_tile(0, u32pos2(1, 0), Some(tile(src, 10)));
+        let map_id = state.maps.push_map(map);
+        let before = state.clone();
+
+        // Both an unmapped index, and one mapped outside dst, are refused
+        assert!(state.merge_tilesets(src, dst, &HashMap::default()).is_err());
+        let mut outside = HashMap::default();
+        outside.insert(10, 4);
+        assert!(state.merge_tilesets(src, dst, &outside).is_err());
+        assert!(state == before);
+
+        // Mapping the tile into dst allows the merge
+        let mut inside = HashMap::default();
+        inside.insert(10, 3);
+        assert_eq!(state.merge_tilesets(src, dst, &inside)?, 2);
+        let tiles = &state
+            .maps
+            .get_by_id(map_id)
+            .ok_or(eyre::eyre!("no map"))?
+            .tiles;
+        assert_eq!(tiles.tile(0, u32pos2(1, 0)), Some(tile(dst, 3)));
+        Ok(())
+    }
+
     #[test]
     fn defaults_make_sense() -> eyre::Result<()> {
         let state = State::default();
@@ -174,4 +579,309 @@ mod tests {
 
         Ok(())
     }
+
+    fn state_with_map(tiles: &[Tile]) -> (State, MapId) {
+        let mut state = State::default();
+        let mut map = Map::new_with_layer(
+            "Map".to_string(),
+            u32size2(4, 4),
+            u32size2(8, 8),
+            UserColor::BLACK,
+        );
+        for (x, tile) in (0..).zip(tiles) {
+            map.tiles.set_tile(0, u32pos2(x, 0), Some(*tile));
+        }
+        let map_id = state.maps.push_map(map);
+        (state, map_id)
+    }
+
+    fn map_tiles(state: &State, map_id: MapId) -> eyre::Result<Vec<Option<Tile>>> {
+        let map = state.maps.get_by_id(map_id).ok_or(eyre::eyre!("no map"))?;
+        Ok((0..4).map(|x| map.tiles.tile(0, u32pos2(x, 0))).collect())
+    }
+
+    #[test]
+    fn remap_tileset_columns_keeps_tile_positions() -> eyre::Result<()> {
+        let mut tilesets = Tilesets::new();
+        let id = tilesets.push_tileset(tileset("sheet"));
+        let other = tilesets.push_tileset(tileset("other"));
+        // In a 4 column grid, tiles in columns 0 and 3 of the second row, and from another tileset
+        let (mut state, map_id) = state_with_map(&[tile(id, 4), tile(id, 7), tile(other, 7)]);
+
+        // Dropping to 3 columns removes the tile from the last column
+        assert_eq!(state.remap_tileset_columns(id, 4, 3), 2);
+        assert_eq!(
+            map_tiles(&state, map_id)?,
+            vec![Some(tile(id, 3)), None, Some(tile(other, 7)), None]
+        );
+
+        // Going back to 4 columns restores the original index
+        assert_eq!(state.remap_tileset_columns(id, 3, 4), 1);
+        assert_eq!(map_tiles(&state, map_id)?.first(), Some(&Some(tile(id, 4))));
+        Ok(())
+    }
+
+    #[test]
+    fn repair_removes_tiles_with_missing_tilesets() -> eyre::Result<()> {
+        let mut tilesets = Tilesets::new();
+        let id = tilesets.push_tileset(tileset("present"));
+        let missing = tilesets.push_tileset(tileset("missing"));
+        tilesets.delete_by_id(missing);
+
+        // Valid tile, missing tileset, and index past the end of the 4x4 tileset
+        let (mut state, map_id) = state_with_map(&[tile(id, 15), tile(missing, 0), tile(id, 16)]);
+        state.resources.tilesets = tilesets;
+
+        let repairs = state.validate_and_repair();
+        assert_eq!(
+            repairs,
+            StateRepairs {
+                tiles_with_missing_tilesets: 2,
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            map_tiles(&state, map_id)?,
+            vec![Some(tile(id, 15)), None, None, None]
+        );
+
+        // Repairing again changes nothing
+        assert!(state.validate_and_repair().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn find_colors_lost_by_replacing_palette() {
+        let id = TilesetId::ONE;
+        let with_color = |index: u32| Tile {
+            color: TileColor::Palette {
+                index: palette_index(index),
+            },
+            ..tile(id, 0)
+        };
+        let (mut state, map_id) =
+            state_with_map(&[with_color(0), with_color(3), with_color(1), with_color(3)]);
+        if let Some(map) = state.maps.get_by_id_mut(map_id) {
+            let layer_index = map.tiles.layer_count();
+            map.tiles.insert_layer(layer_index, "Lower", true, None);
+            map.tiles
+                .set_tile(layer_index, u32pos2(0, 1), Some(with_color(2)));
+        }
+
+        let palette = Palette::new(vec![UserColor::BLACK, UserColor::WHITE]);
+        assert_eq!(state.used_palette_indices_outside(&palette), vec![2, 3]);
+
+        // Default tiles are kept when replacing the palette, so their colors aren't lost
+        if let Some(map) = state.maps.get_by_id_mut(map_id) {
+            map.tiles.default_tile = Some(with_color(5));
+        }
+        assert_eq!(state.used_palette_indices_outside(&palette), vec![2, 3]);
+        assert!(state.used_palette_indices().contains(&5));
+
+        let counts: Vec<_> = state
+            .find_use_of_colors_outside_palette(&palette)
+            .map(|resource_use| {
+                resource_use
+                    .locations
+                    .iter()
+                    .map(|l| (l.layer_name.clone(), l.tile_count))
+                    .collect()
+            })
+            .unwrap_or_default();
+        assert_eq!(
+            counts,
+            vec![("New Layer".to_string(), 2), ("Lower".to_string(), 1)]
+        );
+
+        let palette = Palette::new(vec![UserColor::BLACK; 4]);
+        assert!(state.used_palette_indices_outside(&palette).is_empty());
+        assert!(state.find_use_of_colors_outside_palette(&palette).is_none());
+    }
+
+    #[test]
+    fn remap_tiles_outside_palette_to_nearest_color() -> eyre::Result<()> {
+        let id = TilesetId::ONE;
+        let with_color = |index: u32| Tile {
+            color: TileColor::Palette {
+                index: palette_index(index),
+            },
+            ..tile(id, 0)
+        };
+        let (mut state, map_id) =
+            state_with_map(&[with_color(0), with_color(1), with_color(2), with_color(5)]);
+        let dark_red = UserColor::new(120, 10, 10, 255);
+        state.resources.palette = Palette::new(vec![UserColor::BLACK, UserColor::WHITE, dark_red]);
+
+        let palette = Palette::new(vec![UserColor::WHITE, UserColor::new(255, 0, 0, 255)]);
+        state.remap_tiles_outside_palette(&palette);
+
+        // Index 2 (dark red) is remapped to red, index 5 isn't in either palette so is removed
+        assert_eq!(
+            map_tiles(&state, map_id)?,
+            vec![
+                Some(with_color(0)),
+                Some(with_color(1)),
+                Some(with_color(1)),
+                None
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn repair_removes_tiles_outside_palette() -> eyre::Result<()> {
+        let mut tilesets = Tilesets::new();
+        let id = tilesets.push_tileset(tileset("tileset"));
+        let with_color = |index: u32| Tile {
+            color: TileColor::Palette {
+                index: palette_index(index),
+            },
+            ..tile(id, 0)
+        };
+
+        let (mut state, map_id) = state_with_map(&[with_color(1), with_color(2), tile(id, 1)]);
+        state.resources.tilesets = tilesets;
+        state.resources.palette = Palette::new(vec![UserColor::BLACK, UserColor::WHITE]);
+
+        let repairs = state.validate_and_repair();
+        assert_eq!(
+            repairs,
+            StateRepairs {
+                tiles_outside_palette: 1,
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            map_tiles(&state, map_id)?,
+            vec![Some(with_color(1)), None, Some(tile(id, 1)), None]
+        );
+        assert_eq!(repairs.descriptions().len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn repair_gives_duplicate_tilesets_new_ids() -> eyre::Result<()> {
+        let mut tilesets = Tilesets::new();
+        let first = tilesets.push_tileset(tileset("first"));
+        tilesets.push_tileset(tileset("second"));
+
+        // An edited file where both tilesets have the first id, and a stale next id
+        let json = serde_json::to_string(&tilesets)?;
+        let corrupted = json
+            .replace("\"id\":2", "\"id\":1")
+            .replace("\"next_tileset_id\":3", "\"next_tileset_id\":1");
+        assert_eq!(corrupted.matches("\"id\":1").count(), 2);
+
+        let (mut state, map_id) = state_with_map(&[tile(first, 0)]);
+        state.resources.tilesets = serde_json::from_str(&corrupted)?;
+
+        let repairs = state.validate_and_repair();
+        assert_eq!(
+            repairs,
+            StateRepairs {
+                tileset_ids: 1,
+                ..Default::default()
+            }
+        );
+        let tilesets = state.resources.tilesets();
+        let ids: Vec<u32> = tilesets.iter().map(|t| t.id().value()).collect();
+        assert_eq!(ids, vec![1, 2]);
+        assert_eq!(
+            tilesets.get_by_id(first).map(|t| t.name.as_str()),
+            Some("first")
+        );
+
+        // Tiles keep using the tileset that kept the id, and new tilesets get unique ids
+        assert_eq!(
+            map_tiles(&state, map_id)?.first(),
+            Some(&Some(tile(first, 0)))
+        );
+        let new_id = state
+            .resources
+            .tilesets_mut()
+            .push_tileset(tileset("third"));
+        assert_eq!(new_id.value(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn save_as_to_sibling_directory_rebases_tileset_paths() {
+        let mut state = State::default();
+        let mut relative = tileset("relative");
+        relative.path = "images/tiles.png".into();
+        relative.prefer_relative_path = true;
+        state.resources.tilesets_mut().push_tileset(relative);
+        let base_dir: Utf8PathBuf = "/maps/project".into();
+        let path = |state: &State| state.resources.tilesets().first().map(|t| t.path.clone());
+
+        // Saving in the same directory leaves relative paths unchanged
+        let mut same_dir = state.clone();
+        let same_dir_path: Utf8PathBuf = "/maps/project/renamed.mnp".into();
+        same_dir.rebase_tileset_paths(Some(&base_dir), &same_dir_path);
+        same_dir.on_save(same_dir_path);
+        assert_eq!(path(&same_dir), Some("images/tiles.png".into()));
+
+        // Saving in a sibling directory makes paths relative to that directory
+        let sibling_path: Utf8PathBuf = "/maps/sibling/project.mnp".into();
+        state.rebase_tileset_paths(Some(&base_dir), &sibling_path);
+        assert_eq!(path(&state), Some("/maps/project/images/tiles.png".into()));
+        state.on_save(sibling_path);
+        assert_eq!(path(&state), Some("../project/images/tiles.png".into()));
+    }
+
+    #[test]
+    fn compact_palette_removes_unused_colors() -> eyre::Result<()> {
+        let mut tilesets = Tilesets::new();
+        let id = tilesets.push_tileset(tileset("tileset"));
+        let with_color = |index: u32| Tile {
+            color: TileColor::Palette {
+                index: palette_index(index),
+            },
+            ..tile(id, 0)
+        };
+
+        // Colors 0 and 2 are unused, color 3 is only used by the default tile
+        let (mut state, map_id) = state_with_map(&[with_color(1), with_color(4), tile(id, 1)]);
+        state.resources.tilesets = tilesets;
+        let colors: Vec<UserColor> = (0..5).map(|i| UserColor::new(i, 0, 0, 255)).collect();
+        state.resources.palette = Palette::new(colors);
+        if let Some(map) = state.maps.get_by_id_mut(map_id) {
+            map.tiles.default_tile = Some(with_color(3));
+        }
+        let as_user_colors = |state: &State| -> eyre::Result<Vec<Option<UserColor>>> {
+            Ok(map_tiles(state, map_id)?
+                .iter()
+                .map(|t| t.map(|t| t.color.as_user_color(state.resources.palette())))
+                .collect())
+        };
+        let before = as_user_colors(&state)?;
+
+        let index_map = state.compact_palette();
+        assert_eq!(index_map.len(), 3);
+        assert_eq!(index_map.get(&4), Some(&2));
+        assert_eq!(
+            state
+                .resources
+                .palette()
+                .colors()
+                .copied()
+                .collect::<Vec<_>>(),
+            [1, 3, 4].map(|i| UserColor::new(i, 0, 0, 255)).to_vec()
+        );
+
+        // Tiles keep their colors, using the new indices
+        assert_eq!(as_user_colors(&state)?, before);
+        assert_eq!(
+            map_tiles(&state, map_id)?.get(1),
+            Some(&Some(with_color(2)))
+        );
+        let map = state.maps.get_by_id(map_id).ok_or(eyre::eyre!("no map"))?;
+        assert_eq!(map.tiles.default_tile, Some(with_color(1)));
+
+        // Compacting again changes nothing
+        let unchanged = state.clone();
+        assert_eq!(state.compact_palette().len(), 3);
+        assert!(state == unchanged);
+        Ok(())
+    }
 }
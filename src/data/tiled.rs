@@ -2,6 +2,10 @@
 pub struct TiledExportSettings {
     pub include_layer_data_as_properties: bool,
     pub export_tsx_files: bool,
+    /// Export every map in the project to a directory, sharing tilesets,
+    /// rather than just the selected map
+    #[serde(default)]
+    pub export_all_maps: bool,
 }
 
 impl Default for TiledExportSettings {
@@ -9,6 +13,7 @@ impl Default for TiledExportSettings {
         Self {
             include_layer_data_as_properties: true,
             export_tsx_files: true,
+            export_all_maps: false,
         }
     }
 }
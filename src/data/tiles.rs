@@ -1,6 +1,10 @@
 use crate::{
     data::{
-        tiles::tile_color::{TileColor, UserColor},
+        palette::PaletteIndex,
+        tiles::{
+            layer_tiles::{LayerKind, LayerVersion},
+            tile_color::{TileColor, UserColor},
+        },
         tilesets::TilesetId,
     },
     geom::{
@@ -10,11 +14,15 @@ use crate::{
         u32size2::U32Size2,
     },
 };
-use egui::{Pos2, Rect, Vec2};
+use egui::{Pos2, Rect, Vec2, ahash::HashMap};
 
+pub mod layer_storage;
 pub mod layer_tiles;
+pub mod solo_tiles;
 pub mod stamp_tiles;
 pub mod tile_color;
+pub mod tile_diff;
+pub mod tile_mesh_cache;
 pub mod tile_selection;
 pub mod tileset_stacked_tiles;
 pub mod tileset_tiles;
@@ -48,6 +56,10 @@ pub struct Tile {
 }
 
 impl Tile {
+    /// This tile with `transform` applied after its existing transform (see
+    /// [`Transform::and_then`]), as when rotating or mirroring tiles interactively.
+    /// To set an absolute transform, e.g. when importing tiles or clearing
+    /// transforms, use [`Tile::with_exact_transform`].
     pub fn with_transform(&self, transform: Transform) -> Tile {
         Tile {
             source: self.source,
@@ -56,6 +68,16 @@ impl Tile {
         }
     }
 
+    /// This tile with its transform replaced by `transform`, ignoring the existing
+    /// transform, unlike [`Tile::with_transform`], which combines the transforms
+    pub fn with_exact_transform(&self, transform: Transform) -> Tile {
+        Tile {
+            source: self.source,
+            color: self.color,
+            transform,
+        }
+    }
+
     pub fn with_color(&self, color: TileColor) -> Tile {
         Tile {
             source: self.source,
@@ -69,6 +91,20 @@ impl Tile {
             None => *self,
         }
     }
+
+    /// If the tile uses a palette color, change it to the palette index mapped
+    /// by `index_map`. Tiles with other colors or unmapped indices are unchanged.
+    pub fn with_palette_remapped(&self, index_map: &HashMap<u32, u32>) -> Tile {
+        match self.color {
+            TileColor::Palette { index } => match index_map.get(&index.index()) {
+                Some(new_index) => self.with_color(TileColor::Palette {
+                    index: PaletteIndex::new(*new_index),
+                }),
+                None => *self,
+            },
+            _ => *self,
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -185,6 +221,36 @@ pub trait Tiles {
     fn gap(&self) -> U32Size2;
     fn tile(&self, layer: usize, pos: U32Pos2) -> Option<Tile>;
 
+    /// The kind of the layer, which affects how it is displayed, see [`LayerKind`]
+    fn layer_kind(&self, _layer: usize) -> LayerKind {
+        LayerKind::Tile
+    }
+
+    /// The tint multiplied with the colors of all tiles in the layer, see
+    /// [`UserColor::tinted`]. [`None`] for no tint.
+    fn layer_tint(&self, _layer: usize) -> Option<UserColor> {
+        None
+    }
+
+    /// Opacity to display a single tile with, in addition to any layer opacity, e.g.
+    /// so that a preview can be seen through. [`None`] for fully opaque.
+    fn tile_opacity(&self, _layer: usize, _pos: U32Pos2) -> Option<f32> {
+        None
+    }
+
+    /// A tile to display beneath all layers, in cells where every layer is empty.
+    /// This is only used when drawing, it is not part of the tile data.
+    fn default_tile(&self) -> Option<Tile> {
+        None
+    }
+
+    /// Identifies the tiles displayed for a layer, so that drawing of the layer can
+    /// be cached and reused while this is unchanged. [`None`] if the layer's drawing
+    /// can't be cached, e.g. because the tiles are produced on the fly.
+    fn layer_version(&self, _layer: usize) -> Option<LayerVersion> {
+        None
+    }
+
     /// Attempt to set the tile at the specified layer and position to the specified
     /// value.
     /// Return true if this actually changes the layer contents at all (e.g. if the
@@ -348,4 +414,39 @@ mod tests {
         position_iterator_case(u32size2(1, 1024));
         position_iterator_case(u32size2(1024, 1));
     }
+
+    #[test]
+    fn compose_and_replace_transforms_differ() {
+        let rotated = Tile {
+            source: TileSource {
+                tileset_id: TilesetId::ONE,
+                tile_index: TileIndex::new(0),
+            },
+            color: TileColor::Default,
+            transform: Transform::Rotate90,
+        };
+
+        assert_eq!(
+            rotated.with_transform(Transform::Rotate90).transform,
+            Transform::Rotate180
+        );
+        assert_eq!(
+            rotated.with_exact_transform(Transform::Rotate90).transform,
+            Transform::Rotate90
+        );
+
+        assert_eq!(
+            rotated.with_transform(Transform::None).transform,
+            Transform::Rotate90
+        );
+        assert_eq!(
+            rotated.with_exact_transform(Transform::None).transform,
+            Transform::None
+        );
+
+        // Only the transform changes
+        let replaced = rotated.with_exact_transform(Transform::MirrorX);
+        assert_eq!(replaced.source, rotated.source);
+        assert_eq!(replaced.color, rotated.color);
+    }
 }
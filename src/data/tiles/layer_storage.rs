@@ -0,0 +1,315 @@
+use egui::ahash::HashMap;
+
+use crate::{
+    data::tiles::{MapPositionIterator, Tile},
+    geom::{
+        u32pos2::{U32Pos2, u32pos2},
+        u32size2::U32Size2,
+    },
+};
+
+/// Layers with at least this many cells are stored sparsely by default
+pub const SPARSE_LAYER_MIN_AREA: u32 = 256 * 256;
+
+/// The width and height in cells of each chunk of [`LayerStorage::Chunked`] storage,
+/// the same as the default chunk size of Tiled infinite maps
+pub const CHUNK_SIZE: u32 = 16;
+
+/// How the tiles of a layer are stored, see [`LayerStorage`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LayerStorageKind {
+    #[default]
+    Dense,
+    Sparse,
+    Chunked,
+}
+
+impl LayerStorageKind {
+    /// The kind of storage for a new layer of `size`, sparse for layers of at
+    /// least [`SPARSE_LAYER_MIN_AREA`] cells, otherwise dense
+    pub fn for_size(size: U32Size2) -> Self {
+        if size.w as u64 * size.h as u64 >= SPARSE_LAYER_MIN_AREA as u64 {
+            LayerStorageKind::Sparse
+        } else {
+            LayerStorageKind::Dense
+        }
+    }
+}
+
+/// The position of the chunk containing `pos`, and the index of `pos` within the chunk,
+/// for [`LayerStorage::Chunked`] storage
+fn chunk_pos_and_index(pos: U32Pos2) -> (U32Pos2, usize) {
+    let chunk_pos = u32pos2(pos.x / CHUNK_SIZE, pos.y / CHUNK_SIZE);
+    let index = (pos.y % CHUNK_SIZE) * CHUNK_SIZE + pos.x % CHUNK_SIZE;
+    (chunk_pos, index as usize)
+}
+
+/// The tiles of a [`super::layer_tiles::Layer`], stored either densely, with an entry for every
+/// cell, or sparsely, with entries only for cells containing a tile. Dense storage is
+/// faster and smaller for small or mostly-full layers, sparse storage uses memory in
+/// proportion to the number of tiles, so is better for large and mostly-empty layers.
+/// Chunked storage is in between, allocating fixed-size chunks only where there are tiles,
+/// as used for maps imported from Tiled infinite maps.
+///
+/// The storage doesn't know its own size, this is provided by the layer for each operation.
+#[derive(Debug, Clone)]
+pub enum LayerStorage {
+    /// One entry per cell, in row-major order
+    Dense(Vec<Option<Tile>>),
+    /// Entries only for cells that contain a tile
+    Sparse(HashMap<U32Pos2, Tile>),
+    /// [`CHUNK_SIZE`] square chunks, only for areas containing a tile, keyed by the
+    /// position of the chunk in chunks. Each chunk has one entry per cell, in row-major order.
+    Chunked(HashMap<U32Pos2, Box<[Option<Tile>]>>),
+}
+
+impl LayerStorage {
+    /// Empty storage of `kind` for a layer of `size`
+    pub fn new(size: U32Size2, kind: LayerStorageKind) -> Self {
+        match kind {
+            LayerStorageKind::Dense => LayerStorage::Dense(vec![None; size.area() as usize]),
+            LayerStorageKind::Sparse => LayerStorage::Sparse(HashMap::default()),
+            LayerStorageKind::Chunked => LayerStorage::Chunked(HashMap::default()),
+        }
+    }
+
+    /// Storage of `kind` for a layer of `size` from dense data. Dense storage
+    /// uses `tiles` directly.
+    pub fn from_dense(tiles: Vec<Option<Tile>>, size: U32Size2, kind: LayerStorageKind) -> Self {
+        match kind {
+            LayerStorageKind::Dense => LayerStorage::Dense(tiles),
+            _ => {
+                let mut storage = LayerStorage::new(size, kind);
+                for (pos, tile) in MapPositionIterator::new(size).zip(tiles) {
+                    if tile.is_some() {
+                        storage.set(pos, size, tile);
+                    }
+                }
+                storage
+            }
+        }
+    }
+
+    pub fn kind(&self) -> LayerStorageKind {
+        match self {
+            LayerStorage::Dense(_) => LayerStorageKind::Dense,
+            LayerStorage::Sparse(_) => LayerStorageKind::Sparse,
+            LayerStorage::Chunked(_) => LayerStorageKind::Chunked,
+        }
+    }
+
+    /// True if empty cells don't use memory, i.e. the storage is sparse or chunked
+    pub fn is_sparse(&self) -> bool {
+        self.kind() != LayerStorageKind::Dense
+    }
+
+    /// The tiles of every cell, in row-major order, if the storage is dense
+    pub fn dense_tiles(&self) -> Option<&[Option<Tile>]> {
+        match self {
+            LayerStorage::Dense(tiles) => Some(tiles),
+            LayerStorage::Sparse(_) | LayerStorage::Chunked(_) => None,
+        }
+    }
+
+    /// The number of cells in a layer of `size`, i.e. the number of tiles produced by
+    /// [`LayerStorage::iter`]. For dense storage this is the length of the storage, which
+    /// should always be the area of the layer.
+    pub fn len(&self, size: U32Size2) -> usize {
+        match self {
+            LayerStorage::Dense(tiles) => tiles.len(),
+            LayerStorage::Sparse(_) | LayerStorage::Chunked(_) => size.area() as usize,
+        }
+    }
+
+    /// The tile at `pos` in a layer of `size`, [`None`] if there's no tile or the position
+    /// is outside the layer.
+    pub fn get(&self, pos: U32Pos2, size: U32Size2) -> Option<Tile> {
+        let i = pos.linear_index(size)?;
+        match self {
+            LayerStorage::Dense(tiles) => tiles.get(i as usize).copied().flatten(),
+            LayerStorage::Sparse(tiles) => tiles.get(&pos).copied(),
+            LayerStorage::Chunked(chunks) => {
+                let (chunk_pos, index) = chunk_pos_and_index(pos);
+                chunks
+                    .get(&chunk_pos)
+                    .and_then(|chunk| chunk.get(index).copied().flatten())
+            }
+        }
+    }
+
+    /// Set the tile at `pos` in a layer of `size`. Returns false if the position is
+    /// outside the layer, so no tile was set.
+    pub fn set(&mut self, pos: U32Pos2, size: U32Size2, tile: Option<Tile>) -> bool {
+        let Some(i) = pos.linear_index(size) else {
+            return false;
+        };
+        match self {
+            LayerStorage::Dense(tiles) => match tiles.get_mut(i as usize) {
+                Some(target_tile) => {
+                    *target_tile = tile;
+                    true
+                }
+                None => false,
+            },
+            LayerStorage::Sparse(tiles) => {
+                match tile {
+                    Some(tile) => tiles.insert(pos, tile),
+                    None => tiles.remove(&pos),
+                };
+                true
+            }
+            LayerStorage::Chunked(chunks) => {
+                let (chunk_pos, index) = chunk_pos_and_index(pos);
+                match tile {
+                    Some(_) => {
+                        let chunk = chunks.entry(chunk_pos).or_insert_with(|| {
+                            vec![None; (CHUNK_SIZE * CHUNK_SIZE) as usize].into_boxed_slice()
+                        });
+                        if let Some(target_tile) = chunk.get_mut(index) {
+                            *target_tile = tile;
+                        }
+                    }
+                    // Release chunks once they have no tiles
+                    None => {
+                        if let Some(chunk) = chunks.get_mut(&chunk_pos) {
+                            if let Some(target_tile) = chunk.get_mut(index) {
+                                *target_tile = None;
+                            }
+                            if chunk.iter().all(Option::is_none) {
+                                chunks.remove(&chunk_pos);
+                            }
+                        }
+                    }
+                }
+                true
+            }
+        }
+    }
+
+    /// Iterate the tiles of a layer of `size`, one per cell in row-major order
+    pub fn iter(&self, size: U32Size2) -> LayerStorageIter<'_> {
+        match self {
+            LayerStorage::Dense(tiles) => LayerStorageIter::Dense(tiles.iter()),
+            LayerStorage::Sparse(tiles) => LayerStorageIter::Sparse {
+                tiles,
+                positions: MapPositionIterator::new(size),
+            },
+            LayerStorage::Chunked(_) => LayerStorageIter::Chunked {
+                storage: self,
+                size,
+                positions: MapPositionIterator::new(size),
+            },
+        }
+    }
+
+    /// Iterate the positions and tiles of just the cells containing a tile, in
+    /// a layer of `size`. Note that for sparse and chunked storage the order is arbitrary.
+    pub fn placed_tiles(&self, size: U32Size2) -> Box<dyn Iterator<Item = (U32Pos2, Tile)> + '_> {
+        match self {
+            LayerStorage::Dense(tiles) => Box::new(
+                MapPositionIterator::new(size)
+                    .zip(tiles.iter())
+                    .filter_map(|(pos, tile)| tile.map(|tile| (pos, tile))),
+            ),
+            LayerStorage::Sparse(tiles) => Box::new(tiles.iter().map(|(pos, tile)| (*pos, *tile))),
+            LayerStorage::Chunked(chunks) => {
+                Box::new(chunks.iter().flat_map(|(chunk_pos, chunk)| {
+                    (0..).zip(chunk.iter()).filter_map(move |(i, tile)| {
+                        tile.map(|tile| {
+                            let pos = u32pos2(
+                                chunk_pos.x * CHUNK_SIZE + i % CHUNK_SIZE,
+                                chunk_pos.y * CHUNK_SIZE + i / CHUNK_SIZE,
+                            );
+                            (pos, tile)
+                        })
+                    })
+                }))
+            }
+        }
+    }
+
+    /// Replace each tile with the result of `f`, which may return [`None`] to remove the tile
+    pub fn update_tiles<F>(&mut self, mut f: F)
+    where
+        F: FnMut(Tile) -> Option<Tile>,
+    {
+        match self {
+            LayerStorage::Dense(tiles) => {
+                for tile_option in tiles.iter_mut() {
+                    if let Some(tile) = tile_option {
+                        *tile_option = f(*tile);
+                    }
+                }
+            }
+            LayerStorage::Sparse(tiles) => {
+                tiles.retain(|_pos, tile| match f(*tile) {
+                    Some(new_tile) => {
+                        *tile = new_tile;
+                        true
+                    }
+                    None => false,
+                });
+            }
+            LayerStorage::Chunked(chunks) => {
+                chunks.retain(|_chunk_pos, chunk| {
+                    for tile_option in chunk.iter_mut() {
+                        if let Some(tile) = tile_option {
+                            *tile_option = f(*tile);
+                        }
+                    }
+                    chunk.iter().any(Option::is_some)
+                });
+            }
+        }
+    }
+
+    /// Convert to dense storage, for a layer of `size`
+    pub fn into_dense(self, size: U32Size2) -> Vec<Option<Tile>> {
+        match self {
+            LayerStorage::Dense(tiles) => tiles,
+            LayerStorage::Sparse(_) | LayerStorage::Chunked(_) => self.iter(size).collect(),
+        }
+    }
+
+    /// True if this contains the same tiles as `other`, regardless of how
+    /// they are stored
+    pub fn same_tiles(&self, other: &LayerStorage, size: U32Size2) -> bool {
+        match (self, other) {
+            (LayerStorage::Dense(a), LayerStorage::Dense(b)) => a == b,
+            (LayerStorage::Sparse(a), LayerStorage::Sparse(b)) => a == b,
+            _ => self.len(size) == other.len(size) && self.iter(size).eq(other.iter(size)),
+        }
+    }
+}
+
+/// Iterator over the tiles in [`LayerStorage`], see [`LayerStorage::iter`]
+pub enum LayerStorageIter<'a> {
+    Dense(std::slice::Iter<'a, Option<Tile>>),
+    Sparse {
+        tiles: &'a HashMap<U32Pos2, Tile>,
+        positions: MapPositionIterator,
+    },
+    Chunked {
+        storage: &'a LayerStorage,
+        size: U32Size2,
+        positions: MapPositionIterator,
+    },
+}
+
+impl Iterator for LayerStorageIter<'_> {
+    type Item = Option<Tile>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            LayerStorageIter::Dense(iter) => iter.next().copied(),
+            LayerStorageIter::Sparse { tiles, positions } => {
+                positions.next().map(|pos| tiles.get(&pos).copied())
+            }
+            LayerStorageIter::Chunked {
+                storage,
+                size,
+                positions,
+            } => positions.next().map(|pos| storage.get(pos, *size)),
+        }
+    }
+}
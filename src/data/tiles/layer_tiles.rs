@@ -1,15 +1,27 @@
+use crate::utils::read_text_file;
 use crate::{
     data::palette::Palette,
-    data::tiles::{Tile, Tiles, tile_color::UserColor},
-    data::tilesets::TilesetId,
+    data::tiles::{
+        Tile, TileIndex, TileSource, Tiles,
+        layer_storage::{LayerStorage, LayerStorageIter, LayerStorageKind},
+        tile_color::TileColor,
+        tile_color::UserColor,
+        tile_diff::TileDiff,
+    },
+    data::tilesets::{TilesetId, Tilesets},
+    geom::i32pos2::I32Pos2,
+    geom::transform::Transform,
     geom::u32pos2::{U32Pos2, u32pos2},
     geom::u32size2::{U32Size2, u32size2},
     selection::{
         Selectable, SelectableList, SelectableListIter, SelectableListIterDeletable, Selection,
     },
 };
-use egui::ahash::{HashSet, HashSetExt};
-use std::slice::Iter;
+use camino::Utf8Path;
+use egui::ahash::{HashMap, HashSet, HashSetExt};
+use eyre::{bail, eyre};
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicU64, Ordering};
 
 /// This is unique within a [`LayerTiles`], and persists for a
 /// given [`Layer`] even if edited (e.g. resized). This can be used
@@ -30,29 +42,318 @@ impl LayerId {
     }
 }
 
-#[derive(serde::Deserialize, serde::Serialize, Clone, PartialEq)]
+static NEXT_LAYER_GENERATION: AtomicU64 = AtomicU64::new(1);
+
+/// Identifies the tile contents of a [`Layer`]. Each time a layer's tiles may have changed
+/// it is given a new generation, unique across all layers, so if a layer has the same
+/// generation as it did previously, it still has the same tiles. This allows drawing of
+/// layers to be cached, see [`LayerVersion`].
+/// Generations are not persisted, and are ignored when comparing layers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LayerGeneration(u64);
+
+impl LayerGeneration {
+    fn next() -> LayerGeneration {
+        LayerGeneration(NEXT_LAYER_GENERATION.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+impl Default for LayerGeneration {
+    fn default() -> Self {
+        LayerGeneration::next()
+    }
+}
+
+/// Identifies the tiles displayed for a layer by a [`Tiles`], see [`Tiles::layer_version`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LayerVersion {
+    pub id: LayerId,
+    pub generation: LayerGeneration,
+    /// Whether the layer's tiles are displayed
+    pub visible: bool,
+}
+
+/// How a guide layer's opacity is scaled when it is displayed, so that guides
+/// can be told apart from the map's tiles
+pub const GUIDE_LAYER_OPACITY: f32 = 0.5;
+
+/// The index of the tile in a grid `new_columns` wide at the same position as tile
+/// `index` in a grid `old_columns` wide, or [`None`] if its column isn't in the new grid
+fn column_remapped_index(index: u32, old_columns: u32, new_columns: u32) -> Option<u32> {
+    let column = index.checked_rem(old_columns)?;
+    let row = index.checked_div(old_columns)?;
+    (column < new_columns).then_some(row * new_columns + column)
+}
+
+/// The role of a [`Layer`] in a map
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum LayerKind {
+    /// A layer of the map's tiles
+    #[default]
+    Tile,
+    /// A layer used for reference or guides while editing. Guide layers are
+    /// displayed faded, see [`GUIDE_LAYER_OPACITY`], and are not exported.
+    Guide,
+}
+
+impl LayerKind {
+    pub fn is_tile(&self) -> bool {
+        *self == LayerKind::Tile
+    }
+
+    /// True if layers of this kind are included when exporting
+    pub fn is_exported(&self) -> bool {
+        self.is_tile()
+    }
+
+    /// The opacity to display a layer of this kind with, given the layer's own opacity
+    pub fn display_opacity(&self, opacity: Option<f32>) -> Option<f32> {
+        match self {
+            LayerKind::Tile => opacity,
+            LayerKind::Guide => Some(opacity.unwrap_or(1.0) * GUIDE_LAYER_OPACITY),
+        }
+    }
+}
+
+/// Where the existing contents of a map are placed when it is resized, e.g. with
+/// [`ResizeAnchor::Center`] space is added or removed equally on each side
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ResizeAnchor {
+    #[default]
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl ResizeAnchor {
+    pub const ALL: [ResizeAnchor; 9] = [
+        ResizeAnchor::TopLeft,
+        ResizeAnchor::Top,
+        ResizeAnchor::TopRight,
+        ResizeAnchor::Left,
+        ResizeAnchor::Center,
+        ResizeAnchor::Right,
+        ResizeAnchor::BottomLeft,
+        ResizeAnchor::Bottom,
+        ResizeAnchor::BottomRight,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ResizeAnchor::TopLeft => "Top left",
+            ResizeAnchor::Top => "Top",
+            ResizeAnchor::TopRight => "Top right",
+            ResizeAnchor::Left => "Left",
+            ResizeAnchor::Center => "Center",
+            ResizeAnchor::Right => "Right",
+            ResizeAnchor::BottomLeft => "Bottom left",
+            ResizeAnchor::Bottom => "Bottom",
+            ResizeAnchor::BottomRight => "Bottom right",
+        }
+    }
+
+    /// The number of halves of the change in size to move contents by, in x and y
+    fn halves(&self) -> (i64, i64) {
+        match self {
+            ResizeAnchor::TopLeft => (0, 0),
+            ResizeAnchor::Top => (1, 0),
+            ResizeAnchor::TopRight => (2, 0),
+            ResizeAnchor::Left => (0, 1),
+            ResizeAnchor::Center => (1, 1),
+            ResizeAnchor::Right => (2, 1),
+            ResizeAnchor::BottomLeft => (0, 2),
+            ResizeAnchor::Bottom => (1, 2),
+            ResizeAnchor::BottomRight => (2, 2),
+        }
+    }
+
+    /// The offset to move existing contents by, when resizing from `old_size` to `new_size`
+    pub fn offset(&self, old_size: U32Size2, new_size: U32Size2) -> I32Pos2 {
+        let (hx, hy) = self.halves();
+        let dx = (new_size.w as i64 - old_size.w as i64) * hx / 2;
+        let dy = (new_size.h as i64 - old_size.h as i64) * hy / 2;
+        I32Pos2::new(dx as i32, dy as i32)
+    }
+}
+
+/// The stored form of a [`Layer`], borrowing from the layer when serializing. Dense layers
+/// store every cell in `tiles`, sparse and chunked layers store just the cells containing a
+/// tile in `placed`, so large mostly-empty layers stay small on disk too.
+#[derive(serde::Deserialize, serde::Serialize)]
+struct LayerData<'a> {
+    id: LayerId,
+    name: Cow<'a, str>,
+    visible: bool,
+    size: U32Size2,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    tiles: Cow<'a, [Option<Tile>]>,
+    /// The positions and tiles of cells containing a tile, in row-major order
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    placed: Vec<(U32Pos2, Tile)>,
+    opacity: Option<f32>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tint: Option<UserColor>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    sparse: bool,
+    /// Set with `sparse` for chunked storage, so older versions load these layers as sparse
+    #[serde(default)]
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    chunked: bool,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "LayerKind::is_tile")]
+    kind: LayerKind,
+    /// Layers that were not exported were saved with this before [`LayerKind`]
+    /// was added, these are loaded as guides
+    #[serde(default)]
+    #[serde(skip_serializing)]
+    not_exported: bool,
+}
+
+impl From<LayerData<'_>> for Layer {
+    fn from(data: LayerData<'_>) -> Self {
+        let kind = match (data.sparse, data.chunked) {
+            (_, true) => LayerStorageKind::Chunked,
+            (true, false) => LayerStorageKind::Sparse,
+            (false, false) => LayerStorageKind::Dense,
+        };
+        // Sparse layers were stored densely before `placed` was added, so use the dense
+        // tiles whenever they are present
+        let mut tiles = if data.tiles.is_empty() {
+            LayerStorage::new(data.size, kind)
+        } else {
+            LayerStorage::from_dense(data.tiles.into_owned(), data.size, kind)
+        };
+        for (pos, tile) in data.placed {
+            tiles.set(pos, data.size, Some(tile));
+        }
+        Self {
+            id: data.id,
+            name: data.name.into_owned(),
+            visible: data.visible,
+            size: data.size,
+            tiles,
+            opacity: data.opacity,
+            tint: data.tint,
+            kind: if data.not_exported {
+                LayerKind::Guide
+            } else {
+                data.kind
+            },
+            generation: LayerGeneration::next(),
+        }
+    }
+}
+
+impl<'a> From<&'a Layer> for LayerData<'a> {
+    fn from(layer: &'a Layer) -> Self {
+        let (tiles, placed) = match layer.tiles.dense_tiles() {
+            Some(tiles) => (Cow::Borrowed(tiles), vec![]),
+            None => {
+                let mut placed: Vec<_> = layer.tiles.placed_tiles(layer.size).collect();
+                // Sort so files don't change when the tiles don't
+                placed.sort_by_key(|(pos, _)| (pos.y, pos.x));
+                (Cow::Borrowed(&[][..]), placed)
+            }
+        };
+        Self {
+            id: layer.id,
+            name: Cow::Borrowed(&layer.name),
+            visible: layer.visible,
+            size: layer.size,
+            tiles,
+            placed,
+            sparse: layer.tiles.is_sparse(),
+            chunked: layer.tiles.kind() == LayerStorageKind::Chunked,
+            opacity: layer.opacity,
+            tint: layer.tint,
+            kind: layer.kind,
+            not_exported: false,
+        }
+    }
+}
+
+impl serde::Serialize for Layer {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        LayerData::from(self).serialize(serializer)
+    }
+}
+
+#[derive(serde::Deserialize, Clone)]
+#[serde(from = "LayerData<'static>")]
 pub struct Layer {
     id: LayerId,
     name: String,
     visible: bool,
     size: U32Size2,
-    tiles: Vec<Option<Tile>>,
+    tiles: LayerStorage,
     opacity: Option<f32>,
+    /// A color multiplied with the colors of all tiles in the layer, as for a
+    /// Tiled layer `tintcolor`, or [`None`] for no tint
+    tint: Option<UserColor>,
+    kind: LayerKind,
+    generation: LayerGeneration,
+}
+
+impl PartialEq for Layer {
+    fn eq(&self, other: &Self) -> bool {
+        // Ignore generation, it's only used for caching, and doesn't affect contents.
+        // Tiles are compared by contents, whether they're stored sparsely or not.
+        self.id == other.id
+            && self.name == other.name
+            && self.visible == other.visible
+            && self.size == other.size
+            && self.tiles.same_tiles(&other.tiles, self.size)
+            && self.opacity == other.opacity
+            && self.tint == other.tint
+            && self.kind == other.kind
+    }
 }
 
 impl Layer {
+    /// Create a new, empty layer. Large layers use sparse storage, see
+    /// [`LayerStorageKind::for_size`].
     fn new(id: LayerId, name: &str, visible: bool, size: U32Size2, opacity: Option<f32>) -> Self {
-        let tiles = vec![None; size.area() as usize];
+        let kind = LayerStorageKind::for_size(size);
+        Self::new_with_storage(id, name, visible, size, opacity, kind)
+    }
+
+    fn new_with_storage(
+        id: LayerId,
+        name: &str,
+        visible: bool,
+        size: U32Size2,
+        opacity: Option<f32>,
+        storage: LayerStorageKind,
+    ) -> Self {
         Self {
             id,
             name: name.to_string(),
             visible,
             size,
-            tiles,
+            tiles: LayerStorage::new(size, storage),
             opacity,
+            tint: None,
+            kind: LayerKind::Tile,
+            generation: LayerGeneration::next(),
         }
     }
 
+    /// Mutable access to our tiles, giving us a new generation since they may be changed
+    fn tiles_mut(&mut self) -> &mut LayerStorage {
+        self.generation = LayerGeneration::next();
+        &mut self.tiles
+    }
+
     pub fn id(&self) -> LayerId {
         self.id
     }
@@ -65,10 +366,40 @@ impl Layer {
         self.opacity
     }
 
+    pub fn tint(&self) -> Option<UserColor> {
+        self.tint
+    }
+
+    pub fn kind(&self) -> LayerKind {
+        self.kind
+    }
+
+    /// Whether the layer is included when exporting the map, see [`LayerKind::is_exported`]
+    pub fn exported(&self) -> bool {
+        self.kind.is_exported()
+    }
+
+    /// True if the layer's tiles are stored sparsely or in chunks, see [`LayerStorage`]
+    pub fn is_sparse(&self) -> bool {
+        self.tiles.is_sparse()
+    }
+
+    pub fn storage_kind(&self) -> LayerStorageKind {
+        self.tiles.kind()
+    }
+
+    /// Change how the layer's tiles are stored, keeping the same tiles
+    fn set_storage_kind(&mut self, kind: LayerStorageKind) {
+        if self.storage_kind() != kind {
+            let dense = std::mem::replace(&mut self.tiles, LayerStorage::Dense(vec![]))
+                .into_dense(self.size);
+            self.tiles = LayerStorage::from_dense(dense, self.size, kind);
+        }
+    }
+
     fn tile(&self, pos: U32Pos2) -> Option<Tile> {
         if self.visible {
-            pos.linear_index(self.size)
-                .and_then(|i| self.tiles.get(i as usize).copied())?
+            self.tiles.get(pos, self.size)
         } else {
             None
         }
@@ -76,27 +407,65 @@ impl Layer {
 
     fn set_tile(&mut self, pos: U32Pos2, tile: Option<Tile>) {
         if self.visible {
-            if let Some(i) = pos.linear_index(self.size) {
-                if let Some(target_tile) = self.tiles.get_mut(i as usize) {
-                    *target_tile = tile;
-                }
-            }
+            let size = self.size;
+            self.tiles_mut().set(pos, size, tile);
         }
     }
 
-    fn with_new_size(&self, new_size: U32Size2) -> Layer {
-        let mut new_layer = Layer::new(self.id, &self.name, self.visible, new_size, self.opacity);
-        for y in 0..new_size.h {
-            for x in 0..new_size.w {
-                let pos = u32pos2(x, y);
-                new_layer.set_tile(pos, self.tile(pos));
+    /// Check a position is inside the layer, giving an error if not
+    fn check_pos(&self, pos: U32Pos2) -> eyre::Result<()> {
+        pos.linear_index(self.size)
+            .filter(|i| (*i as usize) < self.tiles.len(self.size))
+            .map(|_| ())
+            .ok_or_else(|| {
+                eyre!(
+                    "Position {} is outside layer '{}' of size {}",
+                    pos,
+                    self.name,
+                    self.size
+                )
+            })
+    }
+
+    /// A copy of this layer with a new size, using the same kind of storage,
+    /// with tiles moved by `offset`, keeping any tiles that are then inside
+    /// the new size (even if the layer is not visible)
+    fn with_new_size(&self, new_size: U32Size2, offset: I32Pos2) -> Layer {
+        let mut new_layer = Layer::new_with_storage(
+            self.id,
+            &self.name,
+            self.visible,
+            new_size,
+            self.opacity,
+            self.storage_kind(),
+        );
+        new_layer.kind = self.kind;
+        new_layer.tint = self.tint;
+        for (pos, tile) in self.tiles.placed_tiles(self.size) {
+            if let Some(new_pos) = new_size.u32pos_if_contained(I32Pos2::from(pos) + offset) {
+                new_layer.tiles.set(new_pos, new_size, Some(tile));
             }
         }
         new_layer
     }
 
-    pub fn tiles_iter(&self) -> Iter<'_, Option<Tile>> {
-        self.tiles.iter()
+    /// True if any tiles would be outside the layer after resizing to `new_size`,
+    /// with tiles moved by `offset`, see [`Layer::with_new_size`]
+    fn clipped_by_resize(&self, new_size: U32Size2, offset: I32Pos2) -> bool {
+        self.tiles
+            .placed_tiles(self.size)
+            .any(|(pos, _)| !new_size.contains(I32Pos2::from(pos) + offset))
+    }
+
+    /// The number of cells in the layer, i.e. the number of tiles produced by
+    /// [`Layer::tiles_iter`]
+    pub fn cell_count(&self) -> usize {
+        self.tiles.len(self.size)
+    }
+
+    /// Iterate the tiles of the layer, one per cell in row-major order
+    pub fn tiles_iter(&self) -> LayerStorageIter<'_> {
+        self.tiles.iter(self.size)
     }
 
     pub fn name(&self) -> String {
@@ -105,42 +474,135 @@ impl Layer {
 
     fn clear_tiles_with_tileset(&mut self, tileset_id: TilesetId) -> bool {
         let mut change = false;
-        for tile_option in self.tiles.iter_mut() {
-            if let Some(tile) = tile_option {
-                if tile.source.tileset_id == tileset_id {
-                    *tile_option = None;
-                    change = true;
-                }
+        self.tiles_mut().update_tiles(|tile| {
+            if tile.source.tileset_id == tileset_id {
+                change = true;
+                None
+            } else {
+                Some(tile)
             }
-        }
+        });
         change
     }
 
-    pub fn clear_tiles_outside_palette(&mut self, palette: &Palette) {
-        for tile_option in self.tiles.iter_mut() {
-            if let Some(tile) = tile_option {
-                if !palette.is_tilecolor_available(&tile.color) {
-                    *tile_option = None;
+    fn remap_tileset(
+        &mut self,
+        src: TilesetId,
+        dst: TilesetId,
+        index_map: &HashMap<u32, u32>,
+    ) -> usize {
+        let mut count = 0;
+        self.tiles_mut().update_tiles(|mut tile| {
+            if tile.source.tileset_id == src {
+                let index = tile.source.tile_index.index();
+                tile.source = TileSource {
+                    tileset_id: dst,
+                    tile_index: TileIndex::new(index_map.get(&index).copied().unwrap_or(index)),
+                };
+                count += 1;
+            }
+            Some(tile)
+        });
+        count
+    }
+
+    fn remap_tileset_columns(
+        &mut self,
+        tileset_id: TilesetId,
+        old_columns: u32,
+        new_columns: u32,
+    ) -> usize {
+        let mut count = 0;
+        self.tiles_mut().update_tiles(|mut tile| {
+            if tile.source.tileset_id == tileset_id {
+                let index = tile.source.tile_index.index();
+                let remapped = column_remapped_index(index, old_columns, new_columns);
+                if remapped != Some(index) {
+                    count += 1;
                 }
+                tile.source.tile_index = TileIndex::new(remapped?);
             }
-        }
+            Some(tile)
+        });
+        count
+    }
+
+    fn remap_palette(&mut self, index_map: &HashMap<u32, u32>) {
+        self.tiles_mut()
+            .update_tiles(|tile| Some(tile.with_palette_remapped(index_map)));
+    }
+
+    /// Remove tiles for which `is_valid` is false, returning the number removed
+    fn clear_invalid_tiles<F>(&mut self, is_valid: F) -> usize
+    where
+        F: Fn(&Tile) -> bool,
+    {
+        let mut count = 0;
+        self.tiles_mut().update_tiles(|tile| {
+            if is_valid(&tile) {
+                Some(tile)
+            } else {
+                count += 1;
+                None
+            }
+        });
+        count
+    }
+
+    pub fn clear_tiles_outside_palette(&mut self, palette: &Palette) -> usize {
+        self.clear_invalid_tiles(|tile| palette.is_tilecolor_available(&tile.color))
+    }
+
+    pub fn clear_tiles_with_missing_tilesets(&mut self, tilesets: &Tilesets) -> usize {
+        self.clear_invalid_tiles(|tile| tilesets.is_tile_source_available(tile.source))
     }
 }
 
-#[derive(serde::Deserialize, serde::Serialize, Clone, PartialEq)]
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
 #[serde(default)] // if we add new fields, give them default values when deserializing old state
 pub struct LayerTiles {
-    pub background: UserColor,
+    /// The background color, or [`None`] to inherit the default map background
+    /// from app settings, see [`LayerTiles::set_inherited_background`].
+    /// Maps saved before backgrounds could be inherited always have a color here.
+    pub background: Option<UserColor>,
+    /// The background used when `background` is [`None`]. This comes from app
+    /// settings rather than the map data, so it is not saved or compared.
+    #[serde(skip)]
+    inherited_background: UserColor,
     tile_set_count: usize,
     layers: Vec<Layer>,
     tile_size: U32Size2,
     map_size: U32Size2,
     next_layer_id: LayerId,
+    /// A tile drawn beneath all layers in cells where every layer is empty.
+    /// This is for display only, it is not part of the tile data, so it is
+    /// not exported, and empty cells are still empty.
+    pub default_tile: Option<Tile>,
+    /// For maps imported from Tiled infinite maps, the position in Tiled's (possibly
+    /// negative) coordinates of the top left cell. These maps use chunked storage for
+    /// their layers, and are exported as infinite maps, see [`LayerTiles::make_infinite`].
+    /// [`None`] for finite maps.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    origin: Option<I32Pos2>,
+}
+
+// Compare all fields except `inherited_background`, since this isn't map data
+impl PartialEq for LayerTiles {
+    fn eq(&self, other: &Self) -> bool {
+        self.background == other.background
+            && self.tile_set_count == other.tile_set_count
+            && self.layers == other.layers
+            && self.tile_size == other.tile_size
+            && self.map_size == other.map_size
+            && self.next_layer_id == other.next_layer_id
+            && self.default_tile == other.default_tile
+            && self.origin == other.origin
+    }
 }
 
 impl Tiles for LayerTiles {
     fn background(&self) -> UserColor {
-        self.background
+        self.background.unwrap_or(self.inherited_background)
     }
 
     fn layer_count(&self) -> usize {
@@ -151,6 +613,10 @@ impl Tiles for LayerTiles {
         self.layers.get(layer).and_then(|l| l.opacity)
     }
 
+    fn layer_tint(&self, layer: usize) -> Option<UserColor> {
+        self.layers.get(layer).and_then(|l| l.tint)
+    }
+
     fn tile_size(&self) -> U32Size2 {
         self.tile_size
     }
@@ -171,6 +637,25 @@ impl Tiles for LayerTiles {
         self.layers.get(layer).and_then(|layer| layer.tile(pos))
     }
 
+    fn layer_kind(&self, layer: usize) -> LayerKind {
+        self.layers
+            .get(layer)
+            .map(|layer| layer.kind)
+            .unwrap_or_default()
+    }
+
+    fn default_tile(&self) -> Option<Tile> {
+        self.default_tile
+    }
+
+    fn layer_version(&self, layer: usize) -> Option<LayerVersion> {
+        self.layers.get(layer).map(|layer| LayerVersion {
+            id: layer.id,
+            generation: layer.generation,
+            visible: layer.visible,
+        })
+    }
+
     fn set_tile(&mut self, layer: usize, pos: U32Pos2, tile: Option<Tile>) -> bool {
         if let Some(layer) = self.layers.get_mut(layer) {
             if layer.tile(pos) != tile {
@@ -194,13 +679,92 @@ impl Default for LayerTiles {
 impl LayerTiles {
     pub fn new(background: UserColor, tile_size: U32Size2, map_size: U32Size2) -> Self {
         Self {
-            background,
+            background: Some(background),
+            inherited_background: UserColor::BLACK,
             tile_set_count: 1,
             layers: vec![],
             tile_size,
             map_size,
             next_layer_id: LayerId::ONE,
+            default_tile: None,
+            origin: None,
+        }
+    }
+
+    /// Make this an infinite map, with the top left cell at `origin` in Tiled's
+    /// coordinates, storing every layer in chunks (see [`LayerStorage::Chunked`])
+    pub fn make_infinite(&mut self, origin: I32Pos2) {
+        self.origin = Some(origin);
+        for layer in self.layers.iter_mut() {
+            layer.set_storage_kind(LayerStorageKind::Chunked);
+        }
+    }
+
+    /// The position of the top left cell in Tiled's coordinates, for infinite maps,
+    /// see [`LayerTiles::make_infinite`]
+    pub fn origin(&self) -> Option<I32Pos2> {
+        self.origin
+    }
+
+    fn layer_checked(&self, layer_index: usize) -> eyre::Result<&Layer> {
+        self.layers.get(layer_index).ok_or_else(|| {
+            eyre!(
+                "Layer index {} is out of range, there are {} layers",
+                layer_index,
+                self.layers.len()
+            )
+        })
+    }
+
+    /// Get the tile at a position in a layer, or an error describing the problem if the layer
+    /// index or position is out of range. Unlike [`Tiles::tile`], this reads the stored tile even
+    /// if the layer is not visible, so it is suitable for processing tile data (e.g. on import
+    /// or export), rather than for display and editing.
+    pub fn tile_checked(&self, layer_index: usize, pos: U32Pos2) -> eyre::Result<Option<Tile>> {
+        let layer = self.layer_checked(layer_index)?;
+        layer.check_pos(pos)?;
+        Ok(layer.tiles.get(pos, layer.size))
+    }
+
+    /// Set the tile at a position in a layer, or return an error describing the problem if the
+    /// layer index or position is out of range. As for [`LayerTiles::tile_checked`], this
+    /// sets the stored tile even if the layer is not visible.
+    pub fn set_tile_checked(
+        &mut self,
+        layer_index: usize,
+        pos: U32Pos2,
+        tile: Option<Tile>,
+    ) -> eyre::Result<()> {
+        self.layer_checked(layer_index)?.check_pos(pos)?;
+        if let Some(layer) = self.layers.get_mut(layer_index) {
+            let size = layer.size;
+            layer.tiles_mut().set(pos, size, tile);
         }
+        Ok(())
+    }
+
+    /// Find the differences between the tiles in this map and another. Layers are
+    /// compared by index, and any layer or position only present in one map is
+    /// treated as empty in the other. Hidden layers are compared too. Diffs are
+    /// ordered by layer, then by row and column.
+    pub fn diff(&self, other: &LayerTiles) -> Vec<TileDiff> {
+        let layer_count = self.layers.len().max(other.layers.len());
+        let w = self.map_size.w.max(other.map_size.w);
+        let h = self.map_size.h.max(other.map_size.h);
+        let mut diffs = vec![];
+        for layer_index in 0..layer_count {
+            for y in 0..h {
+                for x in 0..w {
+                    let pos = u32pos2(x, y);
+                    let from = self.tile_checked(layer_index, pos).ok().flatten();
+                    let to = other.tile_checked(layer_index, pos).ok().flatten();
+                    if let Some(diff) = TileDiff::between(layer_index, pos, from, to) {
+                        diffs.push(diff);
+                    }
+                }
+            }
+        }
+        diffs
     }
 
     /// Build a list of visible layer indices, this contains an entry
@@ -236,12 +800,30 @@ impl LayerTiles {
     /// that lie within the new size, and pad any additional size with empty tiles,
     /// i.e. [`None`].
     pub fn resize(&mut self, new_size: U32Size2) {
+        self.resize_anchored(new_size, ResizeAnchor::TopLeft);
+    }
+
+    /// As for [`LayerTiles::resize`], but with existing contents placed according
+    /// to `anchor` within the new size
+    pub fn resize_anchored(&mut self, new_size: U32Size2, anchor: ResizeAnchor) {
+        let offset = anchor.offset(self.map_size, new_size);
         let mut new_layers = Vec::with_capacity(self.layers.len());
         for layer in self.layers.iter() {
-            new_layers.push(layer.with_new_size(new_size));
+            new_layers.push(layer.with_new_size(new_size, offset));
         }
         self.layers = new_layers;
         self.map_size = new_size;
+        // Keep existing contents at the same position in an infinite map
+        self.origin = self.origin.map(|origin| origin - offset);
+    }
+
+    /// True if [`LayerTiles::resize_anchored`] with the same arguments would
+    /// remove any tiles, on any layer
+    pub fn resize_clips(&self, new_size: U32Size2, anchor: ResizeAnchor) -> bool {
+        let offset = anchor.offset(self.map_size, new_size);
+        self.layers
+            .iter()
+            .any(|layer| layer.clipped_by_resize(new_size, offset))
     }
 
     pub fn set_tile_size(&mut self, new_size: U32Size2) {
@@ -257,11 +839,114 @@ impl LayerTiles {
     ) -> LayerId {
         let id = self.next_layer_id;
         self.next_layer_id = id.next();
-        self.layers
-            .insert(i, Layer::new(id, name, visible, self.map_size, opacity));
+        let mut layer = Layer::new(id, name, visible, self.map_size, opacity);
+        if self.origin.is_some() {
+            layer.set_storage_kind(LayerStorageKind::Chunked);
+        }
+        self.layers.insert(i, layer);
         id
     }
 
+    /// Import a new layer from a CSV file at `path`, with one line of comma-separated
+    /// tile indices per row of the map. This uses the Tiled convention, where 0 is an
+    /// empty cell, and other values are flat indices into a single tileset with a
+    /// `firstgid` of 1, used here as the tileset with id `tileset_id`. Tiled flip bits
+    /// are converted to the tile's [`Transform`].
+    /// The layer is added at the top, named after the file.
+    /// Errors if the file can't be read or parsed, its rows and columns don't match the
+    /// map size, or it uses tiles not available in `tilesets`, in which case no layer is added.
+    pub fn import_layer_csv(
+        &mut self,
+        path: &Utf8Path,
+        tileset_id: TilesetId,
+        tilesets: &Tilesets,
+    ) -> eyre::Result<LayerId> {
+        let contents = read_text_file(path)?;
+        let name = path.file_stem().unwrap_or("Imported Layer");
+        self.import_layer_csv_str(&contents, name, tileset_id, tilesets)
+            .map_err(|e| eyre!("Can't import layer from '{}': {}", path, e))
+    }
+
+    fn import_layer_csv_str(
+        &mut self,
+        contents: &str,
+        name: &str,
+        tileset_id: TilesetId,
+        tilesets: &Tilesets,
+    ) -> eyre::Result<LayerId> {
+        let size = self.map_size;
+        let rows: Vec<&str> = contents
+            .lines()
+            .map(str::trim)
+            .filter(|row| !row.is_empty())
+            .collect();
+
+        if rows.len() != size.h as usize {
+            bail!(
+                "CSV has {} rows, but the map is {} tiles high",
+                rows.len(),
+                size.h
+            );
+        }
+
+        let mut tiles = Vec::with_capacity(size.area() as usize);
+        for (y, row) in rows.iter().enumerate() {
+            // Tiled ends every row but the last with a comma
+            let values: Vec<&str> = row.trim_end_matches(',').split(',').collect();
+            if values.len() != size.w as usize {
+                bail!(
+                    "CSV row {} has {} columns, but the map is {} tiles wide",
+                    y + 1,
+                    values.len(),
+                    size.w
+                );
+            }
+            for (x, value) in values.iter().enumerate() {
+                let gid: u32 = value.trim().parse().map_err(|_| {
+                    eyre!(
+                        "CSV row {}, column {} has invalid tile index '{}'",
+                        y + 1,
+                        x + 1,
+                        value.trim()
+                    )
+                })?;
+
+                // Ignore the top 4 bits (flip bits) to get the flat index, as for Tiled import
+                let flat_index = gid & 0xFFFFFFF;
+                let tile = match flat_index.checked_sub(1) {
+                    Some(index) => {
+                        let source = TileSource {
+                            tileset_id,
+                            tile_index: TileIndex::new(index),
+                        };
+                        if !tilesets.is_tile_source_available(source) {
+                            bail!(
+                                "CSV row {}, column {} has tile index {}, which is not in the tileset",
+                                y + 1,
+                                x + 1,
+                                flat_index
+                            );
+                        }
+                        Some(Tile {
+                            source,
+                            color: TileColor::Default,
+                            transform: Transform::from_tiled_flip_bits(gid),
+                        })
+                    }
+                    None => None,
+                };
+                tiles.push(tile);
+            }
+        }
+
+        let id = self.insert_layer(0, name, true, None);
+        if let Some(layer) = self.layers.first_mut() {
+            let kind = layer.storage_kind();
+            *layer.tiles_mut() = LayerStorage::from_dense(tiles, size, kind);
+        }
+        Ok(id)
+    }
+
     pub fn remove_layer(&mut self, i: usize) {
         self.layers.remove(i);
     }
@@ -280,6 +965,19 @@ impl LayerTiles {
         }
     }
 
+    /// Swap the positions of the layers at indices `a` and `b`. Layers keep
+    /// their [`LayerId`]s and tiles, so only their order changes.
+    /// Returns true if the layers were swapped, false if either index is out
+    /// of range, or the indices are equal.
+    pub fn swap_layers(&mut self, a: usize, b: usize) -> bool {
+        if a != b && a < self.layers.len() && b < self.layers.len() {
+            self.layers.swap(a, b);
+            true
+        } else {
+            false
+        }
+    }
+
     pub fn layer_name_mut(&mut self, i: usize) -> Option<&mut String> {
         self.layers.get_mut(i).map(|layer| &mut layer.name)
     }
@@ -314,6 +1012,31 @@ impl LayerTiles {
         self.next_layer_id
     }
 
+    /// Make sure every layer has a unique [`LayerId`], and that `next_layer_id` is
+    /// past all of them, so new layers also get unique ids. This is only needed
+    /// after loading data that may have been edited by hand or corrupted, since
+    /// ids are always unique when layers are created normally.
+    /// The first layer with a given id keeps it, any later layers with the same
+    /// id are given new ids. Returns the number of layers given new ids.
+    pub fn repair_layer_ids(&mut self) -> usize {
+        if let Some(max_id) = self.layers.iter().map(|layer| layer.id.0).max()
+            && self.next_layer_id.0 <= max_id
+        {
+            self.next_layer_id = LayerId(max_id).next();
+        }
+
+        let mut seen = HashSet::new();
+        let mut repaired = 0;
+        for layer in self.layers.iter_mut() {
+            if !seen.insert(layer.id) {
+                layer.id = self.next_layer_id;
+                self.next_layer_id = layer.id.next();
+                repaired += 1;
+            }
+        }
+        repaired
+    }
+
     /// Return the [`LayerId`]s for all layers between layer "a" and "b",
     /// where a and b are located using the specified [`LayerId`]s.
     /// Layer a and b can be in either order, note that the layer ids between
@@ -354,6 +1077,23 @@ impl LayerTiles {
         self.layers.first()
     }
 
+    /// Insert the layers of `other` at index `i`, in the same order, giving each
+    /// a new id that is unique in this [`LayerTiles`]. The layers should be the
+    /// same size as this [`LayerTiles`]. Returns the ids of the inserted layers.
+    pub fn insert_layers_from(&mut self, i: usize, other: LayerTiles) -> Vec<LayerId> {
+        let mut ids = Vec::with_capacity(other.layers.len());
+        for (offset, mut layer) in other.layers.into_iter().enumerate() {
+            let id = self.next_layer_id;
+            self.next_layer_id = id.next();
+            layer.id = id;
+            layer.generation = LayerGeneration::next();
+            self.layers
+                .insert((i + offset).min(self.layers.len()), layer);
+            ids.push(id);
+        }
+        ids
+    }
+
     pub fn remove_layer_by_index(&mut self, index: usize) {
         self.layers.remove(index);
     }
@@ -369,15 +1109,117 @@ impl LayerTiles {
         for layer in self.layers.iter_mut() {
             change |= layer.clear_tiles_with_tileset(tileset_id);
         }
+        if self
+            .default_tile
+            .is_some_and(|tile| tile.source.tileset_id == tileset_id)
+        {
+            self.default_tile = None;
+            change = true;
+        }
         change
     }
 
-    pub fn clear_tiles_outside_palette(&mut self, palette: &Palette) {
+    /// Change the index of every tile using tileset `tileset_id` so it keeps its position
+    /// in the tileset when the number of columns changes from `old_columns` to
+    /// `new_columns` (see [`crate::data::tilesets::Tileset::grid_size`]), including the
+    /// default tile. Tiles in columns that are no longer used are removed.
+    /// Returns the number of tiles changed or removed.
+    pub fn remap_tileset_columns(
+        &mut self,
+        tileset_id: TilesetId,
+        old_columns: u32,
+        new_columns: u32,
+    ) -> usize {
+        if let Some(tile) = self.default_tile
+            && tile.source.tileset_id == tileset_id
+        {
+            self.default_tile =
+                column_remapped_index(tile.source.tile_index.index(), old_columns, new_columns)
+                    .map(|index| Tile {
+                        source: TileSource {
+                            tileset_id,
+                            tile_index: TileIndex::new(index),
+                        },
+                        ..tile
+                    });
+        }
+        self.layers
+            .iter_mut()
+            .map(|layer| layer.remap_tileset_columns(tileset_id, old_columns, new_columns))
+            .sum()
+    }
+
+    /// Point every tile using tileset `src` at tileset `dst` instead, with the
+    /// tile index mapped by `index_map` (indices not in the map are unchanged).
+    /// Returns the number of tiles remapped.
+    pub fn remap_tileset(
+        &mut self,
+        src: TilesetId,
+        dst: TilesetId,
+        index_map: &HashMap<u32, u32>,
+    ) -> usize {
+        if let Some(tile) = self.default_tile.as_mut() {
+            if tile.source.tileset_id == src {
+                let index = tile.source.tile_index.index();
+                tile.source = TileSource {
+                    tileset_id: dst,
+                    tile_index: TileIndex::new(index_map.get(&index).copied().unwrap_or(index)),
+                };
+            }
+        }
+        self.layers
+            .iter_mut()
+            .map(|layer| layer.remap_tileset(src, dst, index_map))
+            .sum()
+    }
+
+    /// Change every tile using a palette color to use the palette index mapped by
+    /// `index_map` (indices not in the map are unchanged), including the default tile
+    pub fn remap_palette(&mut self, index_map: &HashMap<u32, u32>) {
+        self.default_tile = self
+            .default_tile
+            .map(|tile| tile.with_palette_remapped(index_map));
         for layer in self.layers.iter_mut() {
-            layer.clear_tiles_outside_palette(palette);
+            layer.remap_palette(index_map);
         }
     }
 
+    /// Remove tiles whose color is not in `palette`, returning the number removed
+    pub fn clear_tiles_outside_palette(&mut self, palette: &Palette) -> usize {
+        self.layers
+            .iter_mut()
+            .map(|layer| layer.clear_tiles_outside_palette(palette))
+            .sum()
+    }
+
+    /// Remove tiles from tilesets that don't exist, or with an index past the end of
+    /// their tileset, returning the number removed
+    pub fn clear_tiles_with_missing_tilesets(&mut self, tilesets: &Tilesets) -> usize {
+        self.layers
+            .iter_mut()
+            .map(|layer| layer.clear_tiles_with_missing_tilesets(tilesets))
+            .sum()
+    }
+
+    /// Choose whether a layer stores its tiles sparsely, see [`LayerStorage`]. Chunked
+    /// layers count as sparse, so are only changed when `sparse` is false.
+    /// This doesn't change the layer's tiles, just how much memory they use.
+    /// Returns true if the storage was changed.
+    pub fn set_layer_sparse(&mut self, id: LayerId, sparse: bool) -> bool {
+        let mut change = false;
+        for layer in self.layers.iter_mut() {
+            if layer.id() == id && layer.is_sparse() != sparse {
+                layer.set_storage_kind(if sparse {
+                    LayerStorageKind::Sparse
+                } else {
+                    LayerStorageKind::Dense
+                });
+                change = true;
+            }
+        }
+        change
+    }
+
     pub fn set_layer_visible(&mut self, id: LayerId, visible: bool) -> bool {
         let mut change = false;
         for layer in self.layers.iter_mut() {
@@ -411,6 +1253,53 @@ impl LayerTiles {
         change
     }
 
+    pub fn set_layer_tint(&mut self, id: LayerId, tint: Option<UserColor>) -> bool {
+        let mut change = false;
+        for layer in self.layers.iter_mut() {
+            if layer.id() == id && layer.tint != tint {
+                layer.tint = tint;
+                change = true;
+            }
+        }
+        change
+    }
+
+    pub fn set_layer_kind(&mut self, id: LayerId, kind: LayerKind) -> bool {
+        let mut change = false;
+        for layer in self.layers.iter_mut() {
+            if layer.id() == id && layer.kind != kind {
+                layer.kind = kind;
+                change = true;
+            }
+        }
+        change
+    }
+
+    /// Set the background to use when [`LayerTiles::background`] is [`None`]
+    pub fn set_inherited_background(&mut self, color: UserColor) {
+        self.inherited_background = color;
+    }
+
+    /// A copy of these tiles containing only the layers that are exported
+    pub fn exported_tiles(&self) -> LayerTiles {
+        LayerTiles {
+            layers: self
+                .layers
+                .iter()
+                .filter(|layer| layer.exported())
+                .cloned()
+                .collect(),
+            background: self.background,
+            inherited_background: self.inherited_background,
+            tile_set_count: self.tile_set_count,
+            tile_size: self.tile_size,
+            map_size: self.map_size,
+            next_layer_id: self.next_layer_id,
+            default_tile: self.default_tile,
+            origin: self.origin,
+        }
+    }
+
     pub fn layer_name_by_id(&self, id: LayerId) -> Option<String> {
         for layer in self.layers.iter() {
             if layer.id() == id {
@@ -465,3 +1354,459 @@ impl SelectableListIterDeletable<LayerId> for LayerTiles {
         self.remove_layers_by_selection(selection)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{data::tiles::tile_color::TileColor, geom::transform::Transform};
+
+    use super::*;
+
+    fn plain_tile(index: u32) -> Tile {
+        Tile {
+            source: TileSource {
+                tileset_id: TilesetId::ONE,
+                tile_index: TileIndex::new(index),
+            },
+            color: TileColor::Default,
+            transform: Transform::None,
+        }
+    }
+
+    fn tile(index: u32) -> Option<Tile> {
+        Some(plain_tile(index))
+    }
+
+    #[test]
+    fn resize_anchored_moves_and_clips_tiles() -> eyre::Result<()> {
+        let mut tiles = LayerTiles::new(UserColor::BLACK, u32size2(8, 8), u32size2(4, 4));
+        tiles.insert_layer(0, "Layer", true, None);
+        tiles.set_tile(0, u32pos2(0, 0), tile(1));
+        tiles.set_tile(0, u32pos2(3, 3), tile(2));
+
+        // Growing never clips, centered contents move by half the change in size
+        assert!(!tiles.resize_clips(u32size2(6, 8), ResizeAnchor::Center));
+        let mut grown = tiles.clone();
+        grown.resize_anchored(u32size2(6, 8), ResizeAnchor::Center);
+        assert_eq!(grown.tile_checked(0, u32pos2(1, 2))?, tile(1));
+        assert_eq!(grown.tile_checked(0, u32pos2(4, 5))?, tile(2));
+
+        // Shrinking from the bottom right keeps the bottom right tile
+        assert!(tiles.resize_clips(u32size2(2, 2), ResizeAnchor::BottomRight));
+        assert!(!tiles.resize_clips(u32size2(4, 4), ResizeAnchor::BottomRight));
+        tiles.resize_anchored(u32size2(2, 2), ResizeAnchor::BottomRight);
+        assert_eq!(tiles.tile_checked(0, u32pos2(1, 1))?, tile(2));
+        assert_eq!(tiles.tile_checked(0, u32pos2(0, 0))?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn swap_layers_keeps_ids_and_tiles() {
+        let mut tiles = LayerTiles::new(UserColor::BLACK, u32size2(8, 8), u32size2(4, 4));
+        let ids: Vec<LayerId> = (0..4)
+            .map(|i| tiles.insert_layer(i, &format!("Layer {i}"), true, None))
+            .collect();
+        for i in 0..4 {
+            tiles.set_tile(i, u32pos2(0, 0), tile(i as u32));
+        }
+
+        assert!(tiles.swap_layers(0, 3));
+
+        let swapped: Vec<LayerId> = tiles.layers().map(|l| l.id()).collect();
+        let mut expected = ids.clone();
+        expected.swap(0, 3);
+        assert_eq!(swapped, expected);
+        assert_eq!(tiles.tile(0, u32pos2(0, 0)), tile(3));
+        assert_eq!(tiles.tile(3, u32pos2(0, 0)), tile(0));
+        assert_eq!(tiles.layer_name(0), Some(&"Layer 3".to_string()));
+
+        assert!(!tiles.swap_layers(1, 1));
+        assert!(!tiles.swap_layers(1, 4));
+    }
+
+    #[test]
+    fn import_layer_from_csv() -> eyre::Result<()> {
+        use crate::data::tilesets::{Tileset, TilesetMode};
+
+        let mut tiles = LayerTiles::new(UserColor::BLACK, u32size2(8, 8), u32size2(3, 2));
+        tiles.insert_layer(0, "Layer 0", true, None);
+
+        // A tileset of 4x2 tiles, so flat indices up to 8 are available
+        let mut tilesets = Tilesets::default();
+        let tileset_id = tilesets.push_tileset(Tileset::new_with_default_id(
+            "tileset".to_string(),
+            "tileset.png".into(),
+            u32size2(8, 8),
+            u32size2(4, 2),
+            TilesetMode::Direct,
+            None,
+            None,
+            false,
+        ));
+        let import = |tiles: &mut LayerTiles, csv: &str, name: &str| {
+            tiles.import_layer_csv_str(csv, name, tileset_id, &tilesets)
+        };
+
+        let id = import(&mut tiles, "1,0,3,\n0, 5 ,0\n", "Ground")?;
+        assert_eq!(tiles.layer_id(0), Some(id));
+        assert_eq!(tiles.layer_name(0), Some(&"Ground".to_string()));
+        assert_eq!(tiles.tile(0, u32pos2(0, 0)), tile(0));
+        assert_eq!(tiles.tile(0, u32pos2(1, 0)), None);
+        assert_eq!(tiles.tile(0, u32pos2(2, 0)), tile(2));
+        assert_eq!(tiles.tile(0, u32pos2(1, 1)), tile(4));
+
+        // Tiled flip bits become the tile's transform
+        let flipped = 2 | Transform::MirrorX.as_tiled_flip_bits();
+        import(&mut tiles, &format!("{},0,0\n0,0,0", flipped), "Flipped")?;
+        assert_eq!(
+            tiles.tile(0, u32pos2(0, 0)),
+            Some(Tile {
+                transform: Transform::MirrorX,
+                ..plain_tile(1)
+            })
+        );
+
+        // Mismatched sizes, invalid indices and tiles outside the tileset are errors,
+        // and add no layer
+        assert!(import(&mut tiles, "1,0,3", "Short").is_err());
+        assert!(import(&mut tiles, "1,0\n0,5", "Narrow").is_err());
+        assert!(import(&mut tiles, "1,0,x\n0,5,0", "Bad").is_err());
+        let outside = import(&mut tiles, "1,0,3\n0,9,0", "Outside")
+            .err()
+            .map(|e| e.to_string());
+        assert_eq!(
+            outside,
+            Some("CSV row 2, column 2 has tile index 9, which is not in the tileset".to_string())
+        );
+        assert_eq!(tiles.layer_count(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn backgrounds_can_be_inherited() -> eyre::Result<()> {
+        let red = UserColor::new(255, 0, 0, 255);
+        let mut tiles = LayerTiles::new(red, u32size2(8, 8), u32size2(4, 2));
+        tiles.set_inherited_background(UserColor::WHITE);
+        assert_eq!(tiles.background(), red);
+
+        tiles.background = None;
+        assert_eq!(tiles.background(), UserColor::WHITE);
+
+        // The inherited background isn't map data
+        let mut other = tiles.clone();
+        other.set_inherited_background(UserColor::BLACK);
+        assert!(tiles == other);
+
+        // Maps saved with an explicit background keep it
+        let saved = serde_json::to_string(&LayerTiles::new(red, u32size2(8, 8), u32size2(4, 2)))?;
+        let loaded: LayerTiles = serde_json::from_str(&saved)?;
+        assert_eq!(loaded.background, Some(red));
+        Ok(())
+    }
+
+    #[test]
+    fn default_tile_is_not_tile_data() {
+        let mut tiles = LayerTiles {
+            default_tile: tile(7),
+            ..Default::default()
+        };
+
+        assert_eq!(tiles.default_tile(), tile(7));
+        assert!(
+            tiles
+                .map_positions()
+                .all(|pos| tiles.tile(0, pos).is_none())
+        );
+        assert!(
+            tiles
+                .first_layer()
+                .is_some_and(|layer| layer.tiles_iter().all(|t| t.is_none()))
+        );
+
+        // Removing the tileset clears the default tile
+        assert!(tiles.clear_tiles_with_tileset(TilesetId::ONE));
+        assert_eq!(tiles.default_tile(), None);
+    }
+
+    #[test]
+    fn checked_tile_access() {
+        let mut tiles = LayerTiles::new(UserColor::BLACK, u32size2(8, 8), u32size2(4, 2));
+        tiles.insert_layer(0, "Hidden", false, None);
+
+        // Checked access works on hidden layers, unlike `Tiles::tile`
+        assert!(tiles.set_tile_checked(0, u32pos2(3, 1), tile(5)).is_ok());
+        assert_eq!(tiles.tile_checked(0, u32pos2(3, 1)).ok(), Some(tile(5)));
+        assert_eq!(tiles.tile(0, u32pos2(3, 1)), None);
+
+        let error = |result: eyre::Result<Option<Tile>>| result.err().map(|e| e.to_string());
+        assert_eq!(
+            error(tiles.tile_checked(1, u32pos2(0, 0))),
+            Some("Layer index 1 is out of range, there are 1 layers".to_string())
+        );
+        assert_eq!(
+            error(tiles.tile_checked(0, u32pos2(4, 0))),
+            Some("Position [4 0] is outside layer 'Hidden' of size [4 2]".to_string())
+        );
+        assert!(tiles.set_tile_checked(0, u32pos2(0, 2), tile(1)).is_err());
+    }
+
+    #[test]
+    fn guide_layers_are_skipped() -> eyre::Result<()> {
+        let mut tiles = LayerTiles::new(UserColor::BLACK, u32size2(8, 8), u32size2(4, 3));
+        tiles.insert_layer(0, "Art", true, None);
+        let guide_id = tiles.insert_layer(1, "Guide", true, None);
+        assert!(tiles.set_layer_kind(guide_id, LayerKind::Guide));
+        assert!(!tiles.set_layer_kind(guide_id, LayerKind::Guide));
+
+        let exported = tiles.exported_tiles();
+        assert_eq!(exported.layer_count(), 1);
+        assert_eq!(exported.layer_name(0), Some(&"Art".to_string()));
+
+        // Kind is kept when saved, and layers saved without it are tiles
+        let json = serde_json::to_string(&tiles)?;
+        let loaded: LayerTiles = serde_json::from_str(&json)?;
+        assert!(loaded == tiles);
+        assert_eq!(loaded.layer_kind(0), LayerKind::Tile);
+        assert_eq!(loaded.layer_kind(1), LayerKind::Guide);
+        assert_eq!(json.matches("\"kind\"").count(), 1);
+
+        // Layers saved as not exported, before layer kinds, are guides
+        let old_json = json.replace("\"kind\":\"Guide\"", "\"not_exported\":true");
+        assert!(old_json.contains("not_exported"));
+        let loaded: LayerTiles = serde_json::from_str(&old_json)?;
+        assert!(loaded == tiles);
+
+        // Guides are displayed faded
+        assert_eq!(
+            LayerKind::Guide.display_opacity(None),
+            Some(GUIDE_LAYER_OPACITY)
+        );
+        assert_eq!(LayerKind::Guide.display_opacity(Some(0.5)), Some(0.25));
+        assert_eq!(LayerKind::Tile.display_opacity(Some(0.5)), Some(0.5));
+
+        Ok(())
+    }
+
+    #[test]
+    fn duplicate_layer_ids_are_repaired() -> eyre::Result<()> {
+        let mut tiles = LayerTiles::new(UserColor::BLACK, u32size2(8, 8), u32size2(4, 3));
+        tiles.insert_layer(0, "A", true, None);
+        tiles.insert_layer(1, "B", true, None);
+        tiles.insert_layer(2, "C", true, None);
+
+        // A save edited so that "B" and "C" share the id of "A", with a stale next id
+        let json = serde_json::to_string(&tiles)?;
+        let corrupted = json
+            .replace("\"id\":2", "\"id\":1")
+            .replace("\"id\":3", "\"id\":1")
+            .replace("\"next_layer_id\":4", "\"next_layer_id\":1");
+        assert_eq!(corrupted.matches("\"id\":1").count(), 3);
+        let mut loaded: LayerTiles = serde_json::from_str(&corrupted)?;
+
+        assert_eq!(loaded.repair_layer_ids(), 2);
+        let ids: Vec<u32> = loaded.layers().map(|l| l.id().value()).collect();
+        assert_eq!(ids, vec![1, 2, 3]);
+        assert_eq!(loaded.next_layer_id().value(), 4);
+        let new_id = loaded.insert_layer(3, "D", true, None);
+        assert_eq!(new_id.value(), 4);
+
+        // Layers with unique ids are unchanged
+        assert_eq!(loaded.repair_layer_ids(), 0);
+        assert_eq!(tiles.repair_layer_ids(), 0);
+        assert_eq!(tiles.next_layer_id().value(), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn sparse_and_dense_layers() -> eyre::Result<()> {
+        let mut tiles = LayerTiles::new(UserColor::BLACK, u32size2(8, 8), u32size2(4, 3));
+        let dense_id = tiles.insert_layer(0, "Dense", true, None);
+        let sparse_id = tiles.insert_layer(1, "Sparse", false, None);
+        assert!(tiles.set_layer_sparse(sparse_id, true));
+        assert!(!tiles.set_layer_sparse(dense_id, false));
+
+        for layer_index in 0..2 {
+            tiles.set_tile_checked(layer_index, u32pos2(1, 1), tile(1))?;
+            tiles.set_tile_checked(layer_index, u32pos2(3, 2), tile(2))?;
+            tiles.set_tile_checked(layer_index, u32pos2(3, 2), None)?;
+            tiles.set_tile_checked(layer_index, u32pos2(0, 2), tile(3))?;
+        }
+        let sparse = |tiles: &LayerTiles| tiles.layers().map(|l| l.is_sparse()).collect::<Vec<_>>();
+        assert_eq!(sparse(&tiles), vec![false, true]);
+
+        // Both layers contain the same tiles
+        let layer_tiles = |tiles: &LayerTiles, layer_index: usize| {
+            tiles
+                .layers()
+                .nth(layer_index)
+                .map(|l| l.tiles_iter().collect::<Vec<_>>())
+                .unwrap_or_default()
+        };
+        assert_eq!(layer_tiles(&tiles, 0), layer_tiles(&tiles, 1));
+        assert_eq!(layer_tiles(&tiles, 1).len(), 12);
+        assert_eq!(layer_tiles(&tiles, 1).iter().flatten().count(), 2);
+
+        // Serialization keeps the storage, and tiles
+        let json = serde_json::to_string(&tiles)?;
+        let loaded: LayerTiles = serde_json::from_str(&json)?;
+        assert!(loaded == tiles);
+        assert_eq!(sparse(&loaded), vec![false, true]);
+
+        // Sparse layers only store the placed tiles, in row-major order
+        let values: Vec<_> = tiles
+            .layers()
+            .map(serde_json::to_value)
+            .collect::<Result<_, _>>()?;
+        let field_len = |value: &serde_json::Value, field: &str| {
+            value.get(field).and_then(|v| v.as_array()).map(|a| a.len())
+        };
+        let dense_value = values.first().ok_or(eyre!("Missing layer"))?;
+        let sparse_value = values.get(1).ok_or(eyre!("Missing layer"))?;
+        assert_eq!(field_len(dense_value, "tiles"), Some(12));
+        assert_eq!(field_len(dense_value, "placed"), None);
+        assert_eq!(field_len(sparse_value, "tiles"), None);
+        assert_eq!(field_len(sparse_value, "placed"), Some(2));
+        let placed_positions: Vec<_> = tiles
+            .layers()
+            .nth(1)
+            .map(|l| LayerData::from(l).placed)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(pos, _)| pos)
+            .collect();
+        assert_eq!(placed_positions, vec![u32pos2(1, 1), u32pos2(0, 2)]);
+
+        // Sparse layers saved with dense tiles, before placed tiles were stored, still load
+        let mut old_sparse = dense_value.clone();
+        if let Some(object) = old_sparse.as_object_mut() {
+            object.insert("sparse".to_string(), serde_json::Value::Bool(true));
+        }
+        let old_sparse: Layer = serde_json::from_value(old_sparse)?;
+        assert!(old_sparse.is_sparse());
+        assert_eq!(
+            old_sparse.tiles_iter().collect::<Vec<_>>(),
+            layer_tiles(&tiles, 0)
+        );
+
+        // Changing storage doesn't change the tiles, so layers are still equal
+        let mut dense = tiles.clone();
+        assert!(dense.set_layer_sparse(sparse_id, false));
+        assert!(dense == tiles);
+
+        // Resizing keeps storage and tiles inside the new size, even on hidden layers
+        tiles.resize(u32size2(2, 3));
+        assert_eq!(sparse(&tiles), vec![false, true]);
+        for layer_index in 0..2 {
+            assert_eq!(tiles.tile_checked(layer_index, u32pos2(1, 1))?, tile(1));
+            assert_eq!(tiles.tile_checked(layer_index, u32pos2(0, 2))?, tile(3));
+            assert_eq!(layer_tiles(&tiles, layer_index).len(), 6);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn infinite_maps_use_chunked_layers() -> eyre::Result<()> {
+        let mut tiles = LayerTiles::new(UserColor::BLACK, u32size2(8, 8), u32size2(40, 20));
+        tiles.insert_layer(0, "Before", true, None);
+        tiles.make_infinite(I32Pos2::new(-5, 3));
+        tiles.insert_layer(1, "After", true, None);
+        let kinds =
+            |tiles: &LayerTiles| tiles.layers().map(|l| l.storage_kind()).collect::<Vec<_>>();
+        assert_eq!(kinds(&tiles), vec![LayerStorageKind::Chunked; 2]);
+
+        // Tiles are stored across chunks, including partial chunks at the edges
+        for pos in [u32pos2(1, 1), u32pos2(20, 17), u32pos2(39, 19)] {
+            tiles.set_tile_checked(0, pos, tile(pos.x))?;
+        }
+        tiles.set_tile_checked(0, u32pos2(20, 17), None)?;
+        assert_eq!(tiles.tile_checked(0, u32pos2(1, 1))?, tile(1));
+        assert_eq!(tiles.tile_checked(0, u32pos2(20, 17))?, None);
+        assert_eq!(tiles.tile_checked(0, u32pos2(39, 19))?, tile(39));
+        assert!(tiles.set_tile_checked(0, u32pos2(40, 19), tile(1)).is_err());
+        let layer = tiles.layers().next().ok_or(eyre!("Missing layer"))?;
+        assert_eq!(layer.tiles_iter().count(), 800);
+        assert_eq!(layer.tiles_iter().flatten().count(), 2);
+
+        // Serialization keeps the origin and storage
+        let json = serde_json::to_string(&tiles)?;
+        let loaded: LayerTiles = serde_json::from_str(&json)?;
+        assert!(loaded == tiles);
+        assert_eq!(loaded.origin(), Some(I32Pos2::new(-5, 3)));
+        assert_eq!(kinds(&loaded), vec![LayerStorageKind::Chunked; 2]);
+
+        // Growing to the top left moves the origin so tiles stay at the same Tiled position
+        tiles.resize_anchored(u32size2(42, 24), ResizeAnchor::BottomRight);
+        assert_eq!(tiles.origin(), Some(I32Pos2::new(-7, -1)));
+        assert_eq!(tiles.tile_checked(0, u32pos2(3, 5))?, tile(1));
+        assert_eq!(kinds(&tiles), vec![LayerStorageKind::Chunked; 2]);
+        Ok(())
+    }
+
+    #[test]
+    fn large_layers_are_sparse() {
+        let mut tiles = LayerTiles::new(UserColor::BLACK, u32size2(8, 8), u32size2(512, 512));
+        tiles.insert_layer(0, "Layer", true, None);
+        assert!(tiles.layers().all(|layer| layer.is_sparse()));
+
+        let mut tiles = LayerTiles::new(UserColor::BLACK, u32size2(8, 8), u32size2(64, 64));
+        tiles.insert_layer(0, "Layer", true, None);
+        assert!(tiles.layers().all(|layer| !layer.is_sparse()));
+    }
+
+    #[test]
+    fn diff_reports_added_removed_and_changed() {
+        use crate::data::tiles::tile_diff::TileDiffSummary;
+
+        let mut a = LayerTiles::new(UserColor::BLACK, u32size2(8, 8), u32size2(3, 3));
+        a.insert_layer(0, "Layer", true, None);
+        a.set_tile(0, u32pos2(0, 0), tile(1));
+        a.set_tile(0, u32pos2(1, 0), tile(2));
+        a.set_tile(0, u32pos2(2, 0), tile(3));
+
+        let mut b = a.clone();
+        assert!(a.diff(&b).is_empty());
+        assert_eq!(
+            TileDiffSummary::from_diffs(&a.diff(&b)).to_string(),
+            "No differences"
+        );
+
+        b.set_tile(0, u32pos2(0, 0), None);
+        b.set_tile(0, u32pos2(1, 0), tile(4));
+        b.insert_layer(1, "Extra", true, None);
+        b.set_tile(1, u32pos2(2, 2), tile(5));
+
+        let diffs = a.diff(&b);
+        assert_eq!(
+            diffs,
+            vec![
+                TileDiff::Removed {
+                    layer_index: 0,
+                    pos: u32pos2(0, 0),
+                    tile: plain_tile(1),
+                },
+                TileDiff::Changed {
+                    layer_index: 0,
+                    pos: u32pos2(1, 0),
+                    from: plain_tile(2),
+                    to: plain_tile(4),
+                },
+                TileDiff::Added {
+                    layer_index: 1,
+                    pos: u32pos2(2, 2),
+                    tile: plain_tile(5),
+                },
+            ]
+        );
+        assert_eq!(
+            diffs.iter().map(|d| d.to_string()).collect::<Vec<_>>(),
+            vec![
+                "Layer 0 [0 0]: tile removed",
+                "Layer 0 [1 0]: tile changed",
+                "Layer 1 [2 2]: tile added"
+            ]
+        );
+        assert_eq!(
+            TileDiffSummary::from_diffs(&diffs).to_string(),
+            "1 added, 1 removed, 1 changed"
+        );
+    }
+}
@@ -0,0 +1,128 @@
+use crate::{
+    data::tiles::{
+        Tile, Tiles,
+        layer_tiles::{LayerKind, LayerTiles, LayerVersion},
+        tile_color::UserColor,
+    },
+    geom::u32pos2::U32Pos2,
+    geom::u32size2::U32Size2,
+};
+
+/// Displays [`LayerTiles`] with an optional "solo" layer. When there is a solo layer,
+/// only that layer is shown, even if it is not visible, and all other layers are hidden.
+/// This is only for display, the `visible` flags of the layers are not changed.
+pub struct SoloTiles<'a> {
+    pub solo_layer_index: Option<usize>,
+    pub inner_tiles: &'a LayerTiles,
+}
+
+impl Tiles for SoloTiles<'_> {
+    fn background(&self) -> UserColor {
+        self.inner_tiles.background()
+    }
+
+    fn layer_count(&self) -> usize {
+        self.inner_tiles.layer_count()
+    }
+
+    fn layer_opacity(&self, layer: usize) -> Option<f32> {
+        self.inner_tiles.layer_opacity(layer)
+    }
+
+    fn layer_tint(&self, layer: usize) -> Option<UserColor> {
+        self.inner_tiles.layer_tint(layer)
+    }
+
+    fn layer_kind(&self, layer: usize) -> LayerKind {
+        self.inner_tiles.layer_kind(layer)
+    }
+
+    fn tile_size(&self) -> U32Size2 {
+        self.inner_tiles.tile_size()
+    }
+
+    fn map_size(&self) -> U32Size2 {
+        self.inner_tiles.map_size()
+    }
+
+    fn scale(&self) -> f32 {
+        self.inner_tiles.scale()
+    }
+
+    fn gap(&self) -> U32Size2 {
+        self.inner_tiles.gap()
+    }
+
+    fn tile(&self, layer: usize, pos: U32Pos2) -> Option<Tile> {
+        match self.solo_layer_index {
+            Some(solo_layer_index) if solo_layer_index == layer => {
+                self.inner_tiles.tile_checked(layer, pos).ok().flatten()
+            }
+            Some(_) => None,
+            None => self.inner_tiles.tile(layer, pos),
+        }
+    }
+
+    fn default_tile(&self) -> Option<Tile> {
+        self.inner_tiles.default_tile()
+    }
+
+    fn layer_version(&self, layer: usize) -> Option<LayerVersion> {
+        let version = self.inner_tiles.layer_version(layer)?;
+        Some(match self.solo_layer_index {
+            Some(solo_layer_index) => LayerVersion {
+                visible: solo_layer_index == layer,
+                ..version
+            },
+            None => version,
+        })
+    }
+
+    fn set_tile(&mut self, _layer: usize, _pos: U32Pos2, _tile: Option<Tile>) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        data::{
+            tiles::{TileIndex, TileSource, tile_color::TileColor},
+            tilesets::TilesetId,
+        },
+        geom::{transform::Transform, u32pos2::u32pos2, u32size2::u32size2},
+    };
+
+    use super::*;
+
+    #[test]
+    fn solo_shows_only_solo_layer() {
+        let tile = Tile {
+            source: TileSource {
+                tileset_id: TilesetId::ONE,
+                tile_index: TileIndex::new(1),
+            },
+            color: TileColor::Default,
+            transform: Transform::None,
+        };
+        let mut tiles = LayerTiles::new(UserColor::BLACK, u32size2(8, 8), u32size2(2, 2));
+        tiles.insert_layer(0, "Visible", true, None);
+        tiles.insert_layer(1, "Hidden", false, None);
+        tiles.set_tile(0, u32pos2(0, 0), Some(tile));
+        let _ = tiles.set_tile_checked(1, u32pos2(1, 1), Some(tile));
+
+        let pos_a = u32pos2(0, 0);
+        let pos_b = u32pos2(1, 1);
+        let visible = |solo_layer_index| {
+            let solo = SoloTiles {
+                solo_layer_index,
+                inner_tiles: &tiles,
+            };
+            [solo.tile(0, pos_a).is_some(), solo.tile(1, pos_b).is_some()]
+        };
+
+        assert_eq!(visible(None), [true, false]);
+        assert_eq!(visible(Some(0)), [true, false]);
+        assert_eq!(visible(Some(1)), [false, true]);
+    }
+}
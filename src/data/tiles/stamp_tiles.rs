@@ -1,6 +1,10 @@
 use crate::{
     data::stamp::{Stamp, TileLocation},
-    data::tiles::{Tile, Tiles, tile_color::UserColor},
+    data::tiles::{
+        Tile, Tiles,
+        layer_tiles::{LayerKind, LayerVersion},
+        tile_color::UserColor,
+    },
     geom::i32pos2::I32Pos2,
     geom::u32pos2::U32Pos2,
     geom::u32size2::U32Size2,
@@ -11,6 +15,30 @@ pub struct StampTiles<'a, T: Tiles> {
     pub offset: I32Pos2,
     pub layer_to_stamp_layer: &'a [Option<usize>],
     pub inner_tiles: &'a T,
+    /// The opacity to display the stamp's tiles with, or [`None`] for fully opaque
+    pub opacity: Option<f32>,
+}
+
+impl<T: Tiles> StampTiles<'_, T> {
+    /// The stamp tile shown at a layer and position, if any
+    fn stamp_tile(&self, layer_index: usize, pos: U32Pos2) -> Option<Tile> {
+        let ipos: I32Pos2 = pos.into();
+        let position = ipos - self.offset;
+
+        // Outer `Some` is for whether we are in bounds of Vec, and inner `Some` is whether
+        // this is a visible layer
+        if let Some(Some(visible_layer_index)) = self.layer_to_stamp_layer.get(layer_index) {
+            self.stamp
+                .tiles
+                .get(&TileLocation {
+                    stamp_layer_index: *visible_layer_index,
+                    position,
+                })
+                .copied()
+        } else {
+            None
+        }
+    }
 }
 
 impl<T: Tiles> Tiles for StampTiles<'_, T> {
@@ -26,6 +54,14 @@ impl<T: Tiles> Tiles for StampTiles<'_, T> {
         self.inner_tiles.layer_opacity(layer)
     }
 
+    fn layer_tint(&self, layer: usize) -> Option<UserColor> {
+        self.inner_tiles.layer_tint(layer)
+    }
+
+    fn layer_kind(&self, layer: usize) -> LayerKind {
+        self.inner_tiles.layer_kind(layer)
+    }
+
     fn tile_size(&self) -> U32Size2 {
         self.inner_tiles.tile_size()
     }
@@ -43,23 +79,70 @@ impl<T: Tiles> Tiles for StampTiles<'_, T> {
     }
 
     fn tile(&self, layer_index: usize, pos: U32Pos2) -> Option<Tile> {
-        let ipos: I32Pos2 = pos.into();
-        let position = ipos - self.offset;
+        self.stamp_tile(layer_index, pos)
+            .or_else(|| self.inner_tiles.tile(layer_index, pos))
+    }
 
-        // Outer `Some` is for whether we are in bounds of Vec, and inner `Some` is whether
-        // this is a visible layer
-        if let Some(Some(visible_layer_index)) = self.layer_to_stamp_layer.get(layer_index) {
-            if let Some(tile) = self.stamp.tiles.get(&TileLocation {
-                stamp_layer_index: *visible_layer_index,
-                position,
-            }) {
-                return Some(*tile);
-            }
+    fn tile_opacity(&self, layer_index: usize, pos: U32Pos2) -> Option<f32> {
+        match self.stamp_tile(layer_index, pos) {
+            Some(_) => self.opacity,
+            None => self.inner_tiles.tile_opacity(layer_index, pos),
+        }
+    }
+
+    fn layer_version(&self, layer: usize) -> Option<LayerVersion> {
+        // Layers the stamp is drawn into change as the stamp moves, so can't be cached
+        match self.layer_to_stamp_layer.get(layer) {
+            Some(Some(_)) if !self.stamp.is_empty() => None,
+            _ => self.inner_tiles.layer_version(layer),
         }
-        self.inner_tiles.tile(layer_index, pos)
     }
 
     fn set_tile(&mut self, _layer: usize, _pos: U32Pos2, _tile: Option<Tile>) -> bool {
         false
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        data::{
+            tiles::{TileIndex, TileSource, layer_tiles::LayerTiles, tile_color::TileColor},
+            tilesets::TilesetId,
+        },
+        geom::{i32pos2::i32pos2, transform::Transform, u32pos2::u32pos2, u32size2::u32size2},
+    };
+
+    use super::*;
+
+    #[test]
+    fn only_stamp_tiles_use_stamp_opacity() {
+        let tile = |index| Tile {
+            source: TileSource {
+                tileset_id: TilesetId::ONE,
+                tile_index: TileIndex::new(index),
+            },
+            color: TileColor::Default,
+            transform: Transform::None,
+        };
+        let mut tiles = LayerTiles::new(UserColor::BLACK, u32size2(8, 8), u32size2(3, 3));
+        tiles.insert_layer(0, "Layer", true, None);
+        tiles.set_tile(0, u32pos2(0, 0), Some(tile(1)));
+
+        let mut stamp = Stamp::new();
+        stamp.insert(TileLocation::new(0, i32pos2(0, 0)), tile(2));
+        let layer_to_stamp_layer = [Some(0)];
+        let stamp_tiles = StampTiles {
+            stamp: &stamp,
+            offset: i32pos2(1, 1),
+            layer_to_stamp_layer: &layer_to_stamp_layer,
+            inner_tiles: &tiles,
+            opacity: Some(0.5),
+        };
+
+        assert_eq!(stamp_tiles.tile(0, u32pos2(1, 1)), Some(tile(2)));
+        assert_eq!(stamp_tiles.tile_opacity(0, u32pos2(1, 1)), Some(0.5));
+        assert_eq!(stamp_tiles.tile(0, u32pos2(0, 0)), Some(tile(1)));
+        assert_eq!(stamp_tiles.tile_opacity(0, u32pos2(0, 0)), None);
+    }
+}
@@ -31,7 +31,7 @@ impl UserColor {
     pub const PLACEHOLDER: UserColor = UserColor([255, 0, 255, 255]);
 
     /// A new color with specified components (see main docs, sRGB plus unmultiplied alpha 0-255)
-    pub fn new(r: u8, g: u8, b: u8, a: u8) -> Self {
+    pub const fn new(r: u8, g: u8, b: u8, a: u8) -> Self {
         UserColor([r, g, b, a])
     }
 
@@ -83,6 +83,28 @@ impl UserColor {
         }
     }
 
+    /// Produce a new color tinted by `tint` - each component (including alpha) is
+    /// multiplied by the matching component of `tint`, on a scale of 0.0 to 1.0,
+    /// as for a Tiled layer `tintcolor`.
+    pub fn tinted(&self, tint: UserColor) -> UserColor {
+        let multiply = |a: u8, b: u8| ((a as u16 * b as u16 + 127) / 255) as u8;
+        UserColor::new(
+            multiply(self.r(), tint.r()),
+            multiply(self.g(), tint.g()),
+            multiply(self.b(), tint.b()),
+            multiply(self.a(), tint.a()),
+        )
+    }
+
+    /// Produce a new color with an optional tint applied, see [`UserColor::tinted`].
+    /// If tint is None, the color is unchanged.
+    pub fn with_optional_tint(&self, tint: Option<UserColor>) -> UserColor {
+        match tint {
+            Some(tint) => self.tinted(tint),
+            None => *self,
+        }
+    }
+
     pub fn slice_mut(&mut self) -> &mut [u8; 4] {
         &mut self.0
     }
@@ -0,0 +1,132 @@
+use std::fmt;
+
+use crate::{data::tiles::Tile, geom::u32pos2::U32Pos2};
+
+/// A difference between the tiles at one position in one layer of two maps,
+/// as found by [`crate::data::tiles::layer_tiles::LayerTiles::diff`]. Layers are
+/// matched by index, and a missing layer or position is treated as empty.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TileDiff {
+    /// The cell is empty in the first map, and has a tile in the second
+    Added {
+        layer_index: usize,
+        pos: U32Pos2,
+        tile: Tile,
+    },
+    /// The cell has a tile in the first map, and is empty in the second
+    Removed {
+        layer_index: usize,
+        pos: U32Pos2,
+        tile: Tile,
+    },
+    /// The cell has different tiles in the two maps
+    Changed {
+        layer_index: usize,
+        pos: U32Pos2,
+        from: Tile,
+        to: Tile,
+    },
+}
+
+impl TileDiff {
+    pub fn layer_index(&self) -> usize {
+        match self {
+            TileDiff::Added { layer_index, .. }
+            | TileDiff::Removed { layer_index, .. }
+            | TileDiff::Changed { layer_index, .. } => *layer_index,
+        }
+    }
+
+    pub fn pos(&self) -> U32Pos2 {
+        match self {
+            TileDiff::Added { pos, .. }
+            | TileDiff::Removed { pos, .. }
+            | TileDiff::Changed { pos, .. } => *pos,
+        }
+    }
+
+    /// Compare two optional tiles, producing a diff if they are different
+    pub fn between(
+        layer_index: usize,
+        pos: U32Pos2,
+        from: Option<Tile>,
+        to: Option<Tile>,
+    ) -> Option<TileDiff> {
+        match (from, to) {
+            (None, Some(tile)) => Some(TileDiff::Added {
+                layer_index,
+                pos,
+                tile,
+            }),
+            (Some(tile), None) => Some(TileDiff::Removed {
+                layer_index,
+                pos,
+                tile,
+            }),
+            (Some(from), Some(to)) if from != to => Some(TileDiff::Changed {
+                layer_index,
+                pos,
+                from,
+                to,
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for TileDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let change = match self {
+            TileDiff::Added { .. } => "added",
+            TileDiff::Removed { .. } => "removed",
+            TileDiff::Changed { .. } => "changed",
+        };
+        write!(
+            f,
+            "Layer {} {}: tile {}",
+            self.layer_index(),
+            self.pos(),
+            change
+        )
+    }
+}
+
+/// Counts of each kind of [`TileDiff`], displayed as a one line summary
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TileDiffSummary {
+    pub added: usize,
+    pub removed: usize,
+    pub changed: usize,
+}
+
+impl TileDiffSummary {
+    pub fn from_diffs(diffs: &[TileDiff]) -> Self {
+        let mut summary = Self::default();
+        for diff in diffs {
+            match diff {
+                TileDiff::Added { .. } => summary.added += 1,
+                TileDiff::Removed { .. } => summary.removed += 1,
+                TileDiff::Changed { .. } => summary.changed += 1,
+            }
+        }
+        summary
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added == 0 && self.removed == 0 && self.changed == 0
+    }
+}
+
+impl fmt::Display for TileDiffSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            write!(f, "No differences")
+        } else {
+            write!(
+                f,
+                "{} added, {} removed, {} changed",
+                self.added, self.removed, self.changed
+            )
+        }
+    }
+}
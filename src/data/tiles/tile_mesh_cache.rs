@@ -0,0 +1,240 @@
+use std::sync::Arc;
+
+use egui::{
+    Mesh, Pos2, TextureId,
+    ahash::{HashMap, HashSet},
+};
+
+use crate::{
+    data::{
+        palette::Palette,
+        tiles::{
+            layer_tiles::{LayerId, LayerVersion},
+            tile_color::UserColor,
+        },
+        tilesets::TilesetId,
+    },
+    geom::{u32rect::U32Rect, u32size2::U32Size2},
+};
+
+/// Identifies a cached mesh - there is one mesh per layer and tileset
+pub type TileMeshKey = (LayerId, TilesetId);
+
+/// The inputs a cached mesh was built from, other than the palette.
+/// If any of these change, the mesh needs to be rebuilt.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TileMeshInputs {
+    pub version: LayerVersion,
+    pub texture_id: TextureId,
+    pub texture_loaded: bool,
+    pub tileset_size: U32Size2,
+    pub tileset_columns: u32,
+    pub tileset_region: Option<U32Rect>,
+    pub opacity: Option<f32>,
+    pub tint: Option<UserColor>,
+    pub tile_size: U32Size2,
+    pub gap: U32Size2,
+    pub scale: f32,
+    pub origin: Pos2,
+    /// The range of grid positions included in the mesh
+    pub grid_rect: U32Rect,
+}
+
+struct CachedTileMesh {
+    inputs: TileMeshInputs,
+    palette: Palette,
+    mesh: Arc<Mesh>,
+}
+
+/// Caches the meshes used to draw each layer and tileset of a map, so they only need to be
+/// rebuilt when a layer's tiles (see [`LayerVersion`]) or the way they are drawn changes,
+/// rather than every frame.
+///
+/// The cache is not really part of the state it is stored in - cloning a cache gives an
+/// empty cache (so that copies of edit state, e.g. for undo, don't keep meshes alive), and
+/// all caches are equal.
+#[derive(Default)]
+pub struct TileMeshCache {
+    meshes: HashMap<TileMeshKey, CachedTileMesh>,
+}
+
+impl TileMeshCache {
+    /// Get the cached mesh for `key` if it was built from the same inputs and palette,
+    /// otherwise build the mesh using `build`, and cache it.
+    pub fn mesh<F>(
+        &mut self,
+        key: TileMeshKey,
+        inputs: TileMeshInputs,
+        palette: &Palette,
+        build: F,
+    ) -> Arc<Mesh>
+    where
+        F: FnOnce() -> Mesh,
+    {
+        if let Some(cached) = self.meshes.get(&key) {
+            if cached.inputs == inputs && cached.palette == *palette {
+                return cached.mesh.clone();
+            }
+        }
+        let mesh = Arc::new(build());
+        self.meshes.insert(
+            key,
+            CachedTileMesh {
+                inputs,
+                palette: palette.clone(),
+                mesh: mesh.clone(),
+            },
+        );
+        mesh
+    }
+
+    /// Remove any cached meshes whose keys are not in `keys`, e.g. for deleted layers
+    pub fn retain(&mut self, keys: &HashSet<TileMeshKey>) {
+        self.meshes.retain(|key, _| keys.contains(key));
+    }
+
+    pub fn len(&self) -> usize {
+        self.meshes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.meshes.is_empty()
+    }
+}
+
+impl Clone for TileMeshCache {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl PartialEq for TileMeshCache {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl std::fmt::Debug for TileMeshCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "TileMeshCache({} meshes)", self.meshes.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::ahash::HashSetExt;
+
+    use crate::{
+        data::tiles::{Tile, TileIndex, TileSource, Tiles, layer_tiles::LayerTiles},
+        data::tiles::{solo_tiles::SoloTiles, tile_color::TileColor, tile_color::UserColor},
+        geom::{transform::Transform, u32pos2::u32pos2, u32size2::u32size2},
+    };
+
+    use super::*;
+
+    fn tile(index: u32) -> Tile {
+        Tile {
+            source: TileSource {
+                tileset_id: TilesetId::ONE,
+                tile_index: TileIndex::new(index),
+            },
+            color: TileColor::Default,
+            transform: Transform::None,
+        }
+    }
+
+    fn inputs(version: LayerVersion) -> TileMeshInputs {
+        TileMeshInputs {
+            version,
+            texture_id: TextureId::default(),
+            texture_loaded: true,
+            tileset_size: u32size2(16, 16),
+            tileset_columns: 16,
+            tileset_region: None,
+            opacity: None,
+            tint: None,
+            tile_size: u32size2(8, 8),
+            gap: U32Size2::ZERO,
+            scale: 1.0,
+            origin: Pos2::ZERO,
+            grid_rect: U32Rect::from_two_pos(u32pos2(0, 0), u32pos2(3, 3)),
+        }
+    }
+
+    /// Get the mesh for each layer from the cache, returning which layers were rebuilt
+    fn rebuilt_layers<T: Tiles>(
+        cache: &mut TileMeshCache,
+        tiles: &T,
+        palette: &Palette,
+    ) -> Vec<usize> {
+        let mut rebuilt = vec![];
+        for layer_index in 0..tiles.layer_count() {
+            if let Some(version) = tiles.layer_version(layer_index) {
+                cache.mesh(
+                    (version.id, TilesetId::ONE),
+                    inputs(version),
+                    palette,
+                    || {
+                        rebuilt.push(layer_index);
+                        Mesh::default()
+                    },
+                );
+            }
+        }
+        rebuilt
+    }
+
+    #[test]
+    fn edits_invalidate_only_edited_layer() {
+        let mut tiles = LayerTiles::new(UserColor::BLACK, u32size2(8, 8), u32size2(4, 4));
+        tiles.insert_layer(0, "Top", true, None);
+        tiles.insert_layer(1, "Bottom", true, None);
+        let palette = Palette::default();
+        let mut cache = TileMeshCache::default();
+
+        // Everything is built the first time, then reused
+        assert_eq!(rebuilt_layers(&mut cache, &tiles, &palette), vec![0, 1]);
+        assert!(rebuilt_layers(&mut cache, &tiles, &palette).is_empty());
+
+        // Editing a layer only rebuilds that layer
+        assert!(tiles.set_tile(1, u32pos2(2, 2), Some(tile(3))));
+        assert_eq!(rebuilt_layers(&mut cache, &tiles, &palette), vec![1]);
+
+        // Setting a tile to its current value isn't an edit
+        assert!(!tiles.set_tile(1, u32pos2(2, 2), Some(tile(3))));
+        assert!(rebuilt_layers(&mut cache, &tiles, &palette).is_empty());
+
+        // Swapping layers just draws the same meshes in a different order
+        assert!(tiles.swap_layers(0, 1));
+        assert!(rebuilt_layers(&mut cache, &tiles, &palette).is_empty());
+
+        // Soloing a layer hides the others
+        let solo = SoloTiles {
+            solo_layer_index: Some(0),
+            inner_tiles: &tiles,
+        };
+        assert_eq!(rebuilt_layers(&mut cache, &solo, &palette), vec![1]);
+
+        // Restoring an earlier copy of the tiles (e.g. on undo) rebuilds edited layers
+        let before = tiles.clone();
+        assert!(rebuilt_layers(&mut cache, &tiles, &palette).contains(&1));
+        assert!(tiles.set_tile(0, u32pos2(0, 0), Some(tile(4))));
+        assert_eq!(rebuilt_layers(&mut cache, &tiles, &palette), vec![0]);
+        assert_eq!(rebuilt_layers(&mut cache, &before, &palette), vec![0]);
+
+        // A different palette rebuilds everything
+        let palette = Palette::new(vec![UserColor::BLACK]);
+        assert_eq!(rebuilt_layers(&mut cache, &before, &palette), vec![0, 1]);
+
+        // Meshes for layers that are no longer drawn can be removed
+        let mut keys = HashSet::new();
+        if let Some(version) = before.layer_version(0) {
+            keys.insert((version.id, TilesetId::ONE));
+        }
+        cache.retain(&keys);
+        assert_eq!(cache.len(), 1);
+
+        // Cloning doesn't copy the cache
+        assert!(cache.clone().is_empty());
+    }
+}
@@ -61,6 +61,37 @@ impl TileSelection {
         self.positions.iter()
     }
 
+    pub fn contains(&self, pos: &U32Pos2) -> bool {
+        self.positions.contains(pos)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Find the selected positions on the boundary of the selection, i.e. those
+    /// that have at least one 4-neighbour (up, down, left or right) that is not
+    /// selected. Neighbours outside the map (e.g. with negative coordinates) are
+    /// never selected, so cells on the edge of the map can be on the boundary.
+    pub fn boundary(&self) -> Vec<U32Pos2> {
+        self.positions
+            .iter()
+            .filter(|pos| {
+                let left = pos.x.checked_sub(1).map(|x| u32pos2(x, pos.y));
+                let up = pos.y.checked_sub(1).map(|y| u32pos2(pos.x, y));
+                let right = pos.x.checked_add(1).map(|x| u32pos2(x, pos.y));
+                let down = pos.y.checked_add(1).map(|y| u32pos2(pos.x, y));
+                [left, up, right, down]
+                    .iter()
+                    .any(|neighbour| match neighbour {
+                        Some(neighbour) => !self.positions.contains(neighbour),
+                        None => true,
+                    })
+            })
+            .copied()
+            .collect()
+    }
+
     pub fn add_rect_selection_from_last(&mut self, pos: U32Pos2) {
         match self.last_position {
             Some(last) => {
@@ -19,20 +19,34 @@ struct TilesetIndexing {
     tile_count: u32,
 }
 
+/// The tiles of all tilesets, ordered by tileset and then tile index within the
+/// tileset, laid out in rows of a given number of columns (by default a single column).
+/// The tile with Tiled-style flat index `gid` (starting from 1) is at column
+/// `(gid - 1) % columns`, row `(gid - 1) / columns`.
 pub struct TilesetStackedTiles {
     tile_size: U32Size2,
     indexing: Vec<TilesetIndexing>,
     map_size: U32Size2,
+    columns: u32,
 }
 
 impl TilesetStackedTiles {
+    /// Tiles in a single column
     pub fn new(tilesets: &Tilesets) -> Self {
+        Self::with_columns(tilesets, 1)
+    }
+
+    /// Tiles in rows of `columns` tiles, the last row may be incomplete.
+    /// At least one column is always used.
+    pub fn with_columns(tilesets: &Tilesets, columns: u32) -> Self {
+        let columns = columns.max(1);
+
         // Build tileset first gids
         let mut firstgid = 1;
         let mut indexing = Vec::new();
 
         for tileset in tilesets.iter() {
-            let tile_count = tileset.size_in_tiles.area();
+            let tile_count = tileset.tile_count();
             indexing.push(TilesetIndexing {
                 tileset_id: tileset.id(),
                 firstgid,
@@ -46,12 +60,13 @@ impl TilesetStackedTiles {
             .map(|ts| ts.tile_size)
             .unwrap_or(u32size2(8, 8));
 
-        let total_tile_count = tilesets.iter().map(|ts| ts.size_in_tiles.area()).sum();
+        let total_tile_count: u32 = tilesets.iter().map(|ts| ts.tile_count()).sum();
 
         Self {
             tile_size,
             indexing,
-            map_size: u32size2(1, total_tile_count),
+            map_size: u32size2(columns, total_tile_count.div_ceil(columns)),
+            columns,
         }
     }
 }
@@ -86,9 +101,9 @@ impl Tiles for TilesetStackedTiles {
     }
 
     fn tile(&self, layer: usize, pos: U32Pos2) -> Option<Tile> {
-        if layer == 0 && pos.x == 0 {
+        if layer == 0 && pos.x < self.columns {
             // We're using tiled-style index, so it should start from 1
-            let flat_tile_index = pos.y + 1;
+            let flat_tile_index = pos.y * self.columns + pos.x + 1;
 
             if let Some(index) = self
                 .indexing
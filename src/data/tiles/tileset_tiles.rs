@@ -17,6 +17,8 @@ pub struct TilesetTiles {
     pub tileset_id: TilesetId,
     pub tile_size: U32Size2,
     pub map_size: U32Size2,
+    /// The number of tiles in the tileset, cells after this in `map_size` are empty
+    pub tile_count: u32,
     pub scale: f32,
     pub gap: U32Size2,
 }
@@ -52,14 +54,16 @@ impl Tiles for TilesetTiles {
 
     fn tile(&self, layer: usize, pos: U32Pos2) -> Option<Tile> {
         if layer == 0 {
-            pos.linear_index(self.map_size).map(|tile_index| Tile {
-                source: TileSource {
-                    tileset_id: self.tileset_id,
-                    tile_index: TileIndex::new(tile_index),
-                },
-                color: TileColor::UserColor(self.foreground),
-                transform: Transform::None,
-            })
+            pos.linear_index(self.map_size)
+                .filter(|tile_index| *tile_index < self.tile_count)
+                .map(|tile_index| Tile {
+                    source: TileSource {
+                        tileset_id: self.tileset_id,
+                        tile_index: TileIndex::new(tile_index),
+                    },
+                    color: TileColor::UserColor(self.foreground),
+                    transform: Transform::None,
+                })
         } else {
             None
         }
@@ -1,10 +1,17 @@
 use crate::{
     data::tiles::{TileSource, tile_color::UserColor},
-    geom::u32size2::{U32Size2, u32size2},
+    data::wang_set::WangSet,
+    geom::{
+        u32pos2::{U32Pos2, u32pos2},
+        u32rect::U32Rect,
+        u32size2::{U32Size2, u32size2},
+    },
     selection::{Selectable, SelectableDefault},
+    utils,
 };
-use camino::Utf8PathBuf;
-use egui::ahash::HashSet;
+use camino::{Utf8Path, Utf8PathBuf};
+use egui::ahash::{HashMap, HashMapExt, HashSet, HashSetExt};
+use eyre::eyre;
 use log::info;
 use std::{fmt::Display, slice::Iter};
 
@@ -41,7 +48,19 @@ pub enum TilesetMode {
     Direct,
     TransparentBackground {
         background: UserColor,
+        /// Pixels within this Euclidean RGB distance of the background are also
+        /// treated as transparent, e.g. for anti-aliased edges. A tolerance of 0
+        /// requires an exact match.
+        #[serde(default)]
+        tolerance: u8,
     },
+    /// Treat the image as having only two colors - pixels matching the background
+    /// color, or already fully transparent, are made transparent, and all other pixels
+    /// become the foreground (opaque white, so they will be displayed in the color of
+    /// each tile).
+    /// This is useful for one-bit style tilesets where the foreground is not
+    /// already white, or contains slight variations in color.
+    ForegroundBackground { background: UserColor },
 }
 
 impl TilesetMode {
@@ -49,28 +68,76 @@ impl TilesetMode {
         match self {
             TilesetMode::Direct => "Use image directly",
             TilesetMode::TransparentBackground { .. } => "Use transparent color",
+            TilesetMode::ForegroundBackground { .. } => "Use transparent color and foreground",
         }
     }
 
     pub fn default_transparent_background(&self) -> UserColor {
         match self {
             TilesetMode::Direct => UserColor::BLACK,
-            TilesetMode::TransparentBackground { background } => *background,
+            TilesetMode::TransparentBackground { background, .. } => *background,
+            TilesetMode::ForegroundBackground { background } => *background,
+        }
+    }
+
+    /// The color treated as transparent by this mode, if any
+    pub fn transparent_color(&self) -> Option<UserColor> {
+        match self {
+            TilesetMode::Direct => None,
+            TilesetMode::TransparentBackground { background, .. } => Some(*background),
+            TilesetMode::ForegroundBackground { background } => Some(*background),
         }
     }
 
     pub fn transform_color_slice(&self, color: &mut [u8; 4]) {
+        let tolerance = match self {
+            TilesetMode::TransparentBackground { tolerance, .. } => *tolerance,
+            _ => 0,
+        };
+        self.transform_color_slice_with_tolerance(color, tolerance);
+    }
+
+    /// Transform a pixel color according to this mode, treating pixels as matching the
+    /// background color if they have the same alpha, and are within Euclidean RGB
+    /// distance `tolerance` of the background. A tolerance of 0 requires an exact match.
+    ///
+    /// For [`TilesetMode::TransparentBackground`] and [`TilesetMode::ForegroundBackground`],
+    /// pixels that are already fully transparent in the source image stay transparent (with their color cleared too,
+    /// to avoid fringes when filtering), whether or not they match the background.
+    pub fn transform_color_slice_with_tolerance(&self, color: &mut [u8; 4], tolerance: u8) {
         match self {
             TilesetMode::Direct => {}
-            TilesetMode::TransparentBackground { background } => {
-                if *color == background.as_slice() {
+            TilesetMode::TransparentBackground { background, .. } => {
+                let [_, _, _, alpha] = *color;
+                if alpha == 0 || color_matches(color, &background.as_slice(), tolerance) {
+                    *color = [0, 0, 0, 0];
+                }
+            }
+            TilesetMode::ForegroundBackground { background } => {
+                let [_, _, _, alpha] = *color;
+                if alpha == 0 || color_matches(color, &background.as_slice(), tolerance) {
                     *color = [0, 0, 0, 0];
+                } else {
+                    *color = [255, 255, 255, 255];
                 }
             }
         }
     }
 }
 
+fn color_matches(color: &[u8; 4], target: &[u8; 4], tolerance: u8) -> bool {
+    let [r, g, b, a] = *color;
+    let [tr, tg, tb, ta] = *target;
+    if a != ta {
+        return false;
+    }
+    let distance_squared: u32 = [(r, tr), (g, tg), (b, tb)]
+        .iter()
+        .map(|(c, t)| (c.abs_diff(*t) as u32).pow(2))
+        .sum();
+    distance_squared <= (tolerance as u32).pow(2)
+}
+
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize, PartialEq, Eq, Hash)]
 pub struct Tileset {
     id: TilesetId,
@@ -98,6 +165,25 @@ pub struct Tileset {
     /// tileset image as tile textures.
     pub size_in_tiles: U32Size2,
 
+    /// The number of columns of tiles to use, for sheets that use fewer
+    /// columns than fit in the image width (as in Tiled's `columns` attribute).
+    /// If this is [`None`] all the columns in the image are used, i.e.
+    /// `size_in_tiles.w`. See [`Tileset::grid_size`].
+    #[serde(default)]
+    pub columns: Option<u32>,
+
+    /// The part of the image used by this tileset, measured in tiles, for tilesets
+    /// that share an image with other tilesets, see [`Tileset::split`]. If this is
+    /// [`None`] the whole image is used.
+    #[serde(default)]
+    pub region: Option<U32Rect>,
+
+    /// The number of tiles in the tileset, for sheets where the last row is only
+    /// partly used (as in Tiled's `tilecount` attribute). If this is [`None`], every
+    /// cell in [`Tileset::grid_size`] is a tile. See [`Tileset::tile_count`].
+    #[serde(default)]
+    pub tilecount: Option<u32>,
+
     /// The mode for the tileset - this determines how the tileset is
     /// used and processed, for example by treating a particular color
     /// as transparent.
@@ -118,6 +204,10 @@ pub struct Tileset {
     /// relative to the file the tileset is saved to (either
     /// as an individual tileset, or as part of a map, etc.)
     pub prefer_relative_path: bool,
+
+    /// Optional rules for auto-tiling using tiles from this tileset
+    #[serde(default)]
+    pub wang_set: Option<Box<WangSet>>,
 }
 
 impl Display for Tileset {
@@ -138,6 +228,10 @@ impl Default for Tileset {
             foreground: None,
             background: None,
             prefer_relative_path: true,
+            columns: None,
+            region: None,
+            tilecount: None,
+            wang_set: None,
         }
     }
 }
@@ -186,6 +280,10 @@ impl Tileset {
             foreground,
             background,
             prefer_relative_path,
+            columns: None,
+            region: None,
+            tilecount: None,
+            wang_set: None,
         }
     }
 
@@ -210,6 +308,10 @@ impl Tileset {
             foreground,
             background,
             prefer_relative_path,
+            columns: None,
+            region: None,
+            tilecount: None,
+            wang_set: None,
         }
     }
 
@@ -217,6 +319,107 @@ impl Tileset {
         self.id
     }
 
+    /// Exchange the [`Tileset::foreground`] and [`Tileset::background`] colors,
+    /// for previewing tiles on an inverted background
+    pub fn swap_foreground_background(&mut self) {
+        std::mem::swap(&mut self.foreground, &mut self.background);
+    }
+
+    /// The position in the image of the top left tile of [`Tileset::region`],
+    /// or of the image itself if there's no region
+    pub fn region_origin(&self) -> U32Pos2 {
+        self.region
+            .map(|region| region.with_positive_size().min)
+            .unwrap_or(u32pos2(0, 0))
+    }
+
+    /// The size of [`Tileset::region`], or of the image if there's no region
+    pub fn region_size(&self) -> U32Size2 {
+        self.region
+            .map(|region| region.size())
+            .unwrap_or(self.size_in_tiles)
+    }
+
+    /// The number of columns of tiles used, from [`Tileset::columns`] if set,
+    /// limited to the columns in the region of the image used
+    pub fn column_count(&self) -> u32 {
+        let region_columns = self.region_size().w;
+        match self.columns {
+            Some(columns) if columns > 0 => columns.min(region_columns),
+            _ => region_columns,
+        }
+    }
+
+    /// The size of the grid of tiles used, i.e. [`Tileset::column_count`] columns,
+    /// with a row for each row of tiles in the region of the image used. Tile indices
+    /// are positions in this grid, in row-major order.
+    pub fn grid_size(&self) -> U32Size2 {
+        u32size2(self.column_count(), self.region_size().h)
+    }
+
+    /// The position in the image, in tiles, of the tile with `index`
+    pub fn image_pos(&self, index: u32) -> U32Pos2 {
+        let pos = self.grid_size().pos_from_linear_index(index);
+        let origin = self.region_origin();
+        u32pos2(pos.x + origin.x, pos.y + origin.y)
+    }
+
+    /// Split this tileset into a new tileset for each of `regions`, as `(name, region)`.
+    /// The new tilesets use the same image and settings, but only the tiles in their
+    /// region, which is relative to the whole image. Regions are limited to the image,
+    /// regions that are completely outside it are skipped.
+    pub fn split(&self, regions: &[(String, U32Rect)]) -> Vec<Tileset> {
+        let image_size = self.size_in_tiles;
+        regions
+            .iter()
+            .filter_map(|(name, region)| {
+                let region = region.with_positive_size();
+                if region.min.x >= image_size.w || region.min.y >= image_size.h {
+                    return None;
+                }
+                let max = u32pos2(
+                    region.max.x.min(image_size.w - 1),
+                    region.max.y.min(image_size.h - 1),
+                );
+                let mut tileset = Tileset::new_with_default_id(
+                    name.clone(),
+                    self.path.clone(),
+                    self.tile_size,
+                    self.size_in_tiles,
+                    self.mode,
+                    self.foreground,
+                    self.background,
+                    self.prefer_relative_path,
+                );
+                tileset.region = Some(U32Rect::from_two_pos(region.min, max));
+                Some(tileset)
+            })
+            .collect()
+    }
+
+    /// The number of tiles in the tileset, from [`Tileset::tilecount`] if set,
+    /// limited to the cells in [`Tileset::grid_size`]. Valid tile indices are
+    /// less than this.
+    pub fn tile_count(&self) -> u32 {
+        let grid_count = self.grid_size().area();
+        match self.tilecount {
+            Some(tilecount) => tilecount.min(grid_count),
+            None => grid_count,
+        }
+    }
+
+    /// Change the image path, e.g. when the image has been moved. If the tileset
+    /// prefers relative paths and `base_dir` is known, the path is stored relative
+    /// to `base_dir` where possible.
+    pub fn relocate(&mut self, path: Utf8PathBuf, base_dir: Option<&Utf8PathBuf>) {
+        self.path = match base_dir {
+            Some(base_dir) if self.prefer_relative_path && path.is_absolute() => {
+                pathdiff::diff_utf8_paths(&path, base_dir).unwrap_or(path)
+            }
+            _ => path,
+        };
+    }
+
     fn on_save(&mut self, path: Utf8PathBuf) {
         // Note that path is the file we've been saved to, we want the directory containing this to
         // use as the base_dir for any relative paths
@@ -252,10 +455,18 @@ impl Tileset {
 }
 
 impl Tilesets {
-    pub fn find_or_insert_matching_tileset(&mut self, tileset: Tileset) -> TilesetId {
-        // TODO: Better comparison - just uses the filename, should use the actual path
+    /// Find an existing tileset with an equivalent image path (see `utils::paths_equivalent`,
+    /// relative paths are resolved against `base_dir`), the same mode and the same tile and
+    /// tileset sizes, or insert the tileset if there is none.
+    /// Returns the id of the found or inserted tileset.
+    pub fn find_or_insert_matching_tileset(
+        &mut self,
+        tileset: Tileset,
+        base_dir: Option<&Utf8Path>,
+    ) -> TilesetId {
         if let Some(existing) = self.tilesets.iter().find(|existing| {
-            existing.path.file_name() == tileset.path.file_name()
+            utils::paths_equivalent(&existing.path, &tileset.path, base_dir)
+                && existing.mode == tileset.mode
                 && existing.tile_size == tileset.tile_size
                 && existing.size_in_tiles == tileset.size_in_tiles
         }) {
@@ -277,6 +488,10 @@ impl Tilesets {
                 foreground: Some(UserColor::WHITE),
                 background: Some(UserColor::BLACK),
                 prefer_relative_path: false,
+                columns: None,
+                region: None,
+                tilecount: None,
+                wang_set: None,
             }],
             next_tileset_id: TilesetId::ONE.next(),
         }
@@ -413,6 +628,17 @@ impl Tilesets {
         }
     }
 
+    /// Make relative image paths absolute, by resolving them from `base_dir`. This
+    /// allows saving to a different directory, after which [`Tilesets::on_save`] will
+    /// make paths relative to the new directory for tilesets that prefer relative paths.
+    pub fn resolve_relative_paths(&mut self, base_dir: &Utf8PathBuf) {
+        for tileset in self.tilesets.iter_mut() {
+            if tileset.path.is_relative() {
+                tileset.path = base_dir.join(&tileset.path);
+            }
+        }
+    }
+
     pub fn previous_by_id(&self, id: TilesetId) -> Option<&Tileset> {
         if let Some(index) = self.tilesets.iter().position(|t| t.id() == id) {
             if index > 0 {
@@ -443,19 +669,83 @@ impl Tilesets {
         self.tilesets.len() != start_len
     }
 
+    /// Make sure every tileset has a unique [`TilesetId`], and that `next_tileset_id`
+    /// is past all of them, see [`crate::data::tiles::layer_tiles::LayerTiles::repair_layer_ids`].
+    /// The first tileset with a given id keeps it, so tiles using that id will use
+    /// that tileset. Returns the number of tilesets given new ids.
+    pub fn repair_tileset_ids(&mut self) -> usize {
+        if let Some(max_id) = self.tilesets.iter().map(|tileset| tileset.id.0).max()
+            && self.next_tileset_id.0 <= max_id
+        {
+            self.next_tileset_id = TilesetId(max_id).next();
+        }
+
+        let mut seen = HashSet::new();
+        let mut repaired = 0;
+        for tileset in self.tilesets.iter_mut() {
+            if !seen.insert(tileset.id) {
+                tileset.id = self.next_tileset_id;
+                self.next_tileset_id = tileset.id.next();
+                repaired += 1;
+            }
+        }
+        repaired
+    }
+
     pub fn is_tile_source_available(&self, source: TileSource) -> bool {
         if let Some(tileset) = self.get_by_id(source.tileset_id) {
-            source.tile_index.index() < tileset.size_in_tiles.area()
+            source.tile_index.index() < tileset.tile_count()
         } else {
             false
         }
     }
 }
 
+/// Parse a mapping from tile indices in one tileset to tile indices in another,
+/// e.g. for [`crate::data::state::State::merge_tilesets`].
+/// The text contains `from=to` pairs, separated by commas and/or whitespace,
+/// e.g. `"0=4, 1=5"`. Empty text produces an empty mapping.
+pub fn parse_tile_index_map(text: &str) -> eyre::Result<HashMap<u32, u32>> {
+    let mut index_map = HashMap::new();
+    for pair in text
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|s| !s.is_empty())
+    {
+        let (from, to) = pair
+            .split_once('=')
+            .ok_or_else(|| eyre!("Expected 'from=to' but found '{}'", pair))?;
+        let from: u32 = from
+            .parse()
+            .map_err(|_| eyre!("Invalid tile index '{}' in '{}'", from, pair))?;
+        let to: u32 = to
+            .parse()
+            .map_err(|_| eyre!("Invalid tile index '{}' in '{}'", to, pair))?;
+        index_map.insert(from, to);
+    }
+    Ok(index_map)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn parse_tile_index_map_pairs() -> eyre::Result<()> {
+        assert!(parse_tile_index_map("")?.is_empty());
+        assert!(parse_tile_index_map(" , ")?.is_empty());
+
+        let index_map = parse_tile_index_map("0=4, 1=5 7=2")?;
+        assert_eq!(index_map.len(), 3);
+        assert_eq!(index_map.get(&0), Some(&4));
+        assert_eq!(index_map.get(&1), Some(&5));
+        assert_eq!(index_map.get(&7), Some(&2));
+
+        assert!(parse_tile_index_map("0").is_err());
+        assert!(parse_tile_index_map("0=a").is_err());
+        assert!(parse_tile_index_map("-1=2").is_err());
+        Ok(())
+    }
+
     #[test]
     fn json() -> eyre::Result<()> {
         let mode = TilesetMode::Direct;
@@ -463,12 +753,284 @@ mod tests {
         assert_eq!(json, "\"Direct\"");
         let mode = TilesetMode::TransparentBackground {
             background: UserColor::WHITE,
+            tolerance: 0,
         };
         let json = serde_json::to_string(&mode)?;
         assert_eq!(
             json,
-            "{\"TransparentBackground\":{\"background\":[255,255,255,255]}}"
+            "{\"TransparentBackground\":{\"background\":[255,255,255,255],\"tolerance\":0}}"
         );
+
+        // Modes saved before tolerance was added default to exact matching
+        let old_mode: TilesetMode =
+            serde_json::from_str("{\"TransparentBackground\":{\"background\":[255,255,255,255]}}")?;
+        assert_eq!(old_mode, mode);
         Ok(())
     }
+
+    #[test]
+    fn swap_foreground_background() {
+        let mut tileset = Tileset {
+            foreground: Some(UserColor::WHITE),
+            background: None,
+            ..Default::default()
+        };
+        tileset.swap_foreground_background();
+        assert_eq!(tileset.foreground, None);
+        assert_eq!(tileset.background, Some(UserColor::WHITE));
+
+        tileset.foreground = Some(UserColor::BLACK);
+        tileset.swap_foreground_background();
+        assert_eq!(tileset.foreground, Some(UserColor::WHITE));
+        assert_eq!(tileset.background, Some(UserColor::BLACK));
+    }
+
+    #[test]
+    fn foreground_background_transform() {
+        let background = UserColor::new(10, 20, 30, 255);
+        let mode = TilesetMode::ForegroundBackground { background };
+
+        let mut pixel = [10, 20, 30, 255];
+        mode.transform_color_slice(&mut pixel);
+        assert_eq!(pixel, [0, 0, 0, 0]);
+
+        let mut pixel = [200, 100, 50, 128];
+        mode.transform_color_slice(&mut pixel);
+        assert_eq!(pixel, [255, 255, 255, 255]);
+
+        // Fully transparent pixels stay transparent, rather than becoming foreground
+        let mut pixel = [200, 100, 50, 0];
+        mode.transform_color_slice(&mut pixel);
+        assert_eq!(pixel, [0, 0, 0, 0]);
+
+        assert_eq!(mode.transparent_color(), Some(background));
+        assert_eq!(TilesetMode::Direct.transparent_color(), None);
+    }
+
+    #[test]
+    fn transparent_background_tolerance() {
+        let mode = TilesetMode::TransparentBackground {
+            background: UserColor::new(100, 100, 100, 255),
+            tolerance: 0,
+        };
+
+        // Distance 5 (3, 4, 0), only matches with tolerance of at least 5
+        let near = [103, 104, 100, 255];
+
+        let mut pixel = near;
+        mode.transform_color_slice(&mut pixel);
+        assert_eq!(pixel, near);
+
+        let mut pixel = near;
+        mode.transform_color_slice_with_tolerance(&mut pixel, 4);
+        assert_eq!(pixel, near);
+
+        let mut pixel = near;
+        mode.transform_color_slice_with_tolerance(&mut pixel, 5);
+        assert_eq!(pixel, [0, 0, 0, 0]);
+
+        // Alpha must match exactly
+        let mut pixel = [100, 100, 100, 128];
+        mode.transform_color_slice_with_tolerance(&mut pixel, 255);
+        assert_eq!(pixel, [100, 100, 100, 128]);
+
+        // Tolerance from the mode itself is used by `transform_color_slice`
+        let mode = TilesetMode::TransparentBackground {
+            background: UserColor::new(100, 100, 100, 255),
+            tolerance: 5,
+        };
+        let mut pixel = near;
+        mode.transform_color_slice(&mut pixel);
+        assert_eq!(pixel, [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn transparent_background_source_alpha() {
+        let mode = TilesetMode::TransparentBackground {
+            background: UserColor::new(100, 100, 100, 255),
+            tolerance: 0,
+        };
+
+        // Already transparent, not matching the background
+        let mut pixel = [10, 20, 30, 0];
+        mode.transform_color_slice(&mut pixel);
+        assert_eq!(pixel, [0, 0, 0, 0]);
+
+        // Opaque, matching the background
+        let mut pixel = [100, 100, 100, 255];
+        mode.transform_color_slice(&mut pixel);
+        assert_eq!(pixel, [0, 0, 0, 0]);
+
+        // Opaque, not matching the background
+        let mut pixel = [10, 20, 30, 255];
+        mode.transform_color_slice(&mut pixel);
+        assert_eq!(pixel, [10, 20, 30, 255]);
+    }
+
+    #[test]
+    fn default_transparent_background() {
+        let background = UserColor::new(1, 2, 3, 255);
+        assert_eq!(
+            TilesetMode::Direct.default_transparent_background(),
+            UserColor::BLACK
+        );
+        assert_eq!(
+            TilesetMode::TransparentBackground {
+                background,
+                tolerance: 0
+            }
+            .default_transparent_background(),
+            background
+        );
+        assert_eq!(
+            TilesetMode::ForegroundBackground { background }.default_transparent_background(),
+            background
+        );
+    }
+
+    #[test]
+    fn split_uses_image_regions() {
+        let tileset = Tileset {
+            name: "sheet".into(),
+            path: "sheet.png".into(),
+            size_in_tiles: u32size2(16, 8),
+            ..Default::default()
+        };
+        let split = tileset.split(&[
+            (
+                "terrain".into(),
+                U32Rect::from_two_pos(u32pos2(0, 0), u32pos2(7, 7)),
+            ),
+            // Limited to the image
+            (
+                "objects".into(),
+                U32Rect::from_two_pos(u32pos2(12, 2), u32pos2(20, 20)),
+            ),
+            // Outside the image
+            ("missing".into(), U32Rect::from_pos(u32pos2(16, 0))),
+        ]);
+        assert_eq!(split.len(), 2);
+
+        let objects = split.last();
+        assert_eq!(objects.map(|t| t.name.as_str()), Some("objects"));
+        assert_eq!(objects.map(|t| t.path.as_str()), Some("sheet.png"));
+        assert_eq!(objects.map(|t| t.grid_size()), Some(u32size2(4, 6)));
+        assert_eq!(objects.map(|t| t.tile_count()), Some(24));
+        // Tile indices are relative to the region
+        assert_eq!(objects.map(|t| t.image_pos(0)), Some(u32pos2(12, 2)));
+        assert_eq!(objects.map(|t| t.image_pos(5)), Some(u32pos2(13, 3)));
+    }
+
+    #[test]
+    fn relocate_prefers_relative_paths() {
+        let mut tileset = Tileset::default();
+        let base_dir: Utf8PathBuf = "/maps".into();
+
+        tileset.relocate("/maps/images/tiles.png".into(), Some(&base_dir));
+        assert_eq!(tileset.path, "images/tiles.png");
+
+        tileset.relocate("/images/tiles.png".into(), Some(&base_dir));
+        assert_eq!(tileset.path, "../images/tiles.png");
+
+        // Without a base dir the path can't be made relative
+        tileset.relocate("/maps/tiles.png".into(), None);
+        assert_eq!(tileset.path, "/maps/tiles.png");
+
+        tileset.prefer_relative_path = false;
+        tileset.relocate("/maps/images/tiles.png".into(), Some(&base_dir));
+        assert_eq!(tileset.path, "/maps/images/tiles.png");
+    }
+
+    #[test]
+    fn relative_paths_resolved_for_new_directory() {
+        let tileset = |path: &str, prefer_relative_path: bool| {
+            Tileset::new_with_default_id(
+                "tiles".into(),
+                path.into(),
+                u32size2(8, 8),
+                u32size2(16, 16),
+                TilesetMode::Direct,
+                None,
+                None,
+                prefer_relative_path,
+            )
+        };
+        let mut tilesets = Tilesets::new();
+        tilesets.push_tileset(tileset("images/a.png", true));
+        tilesets.push_tileset(tileset("images/b.png", false));
+        tilesets.push_tileset(tileset("/images/c.png", true));
+
+        tilesets.resolve_relative_paths(&"/maps/project".into());
+        tilesets.on_save("/maps/copy/project.mnp".into());
+        let paths: Vec<&str> = tilesets.iter().map(|t| t.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "../project/images/a.png",
+                "/maps/project/images/b.png",
+                "../../images/c.png"
+            ]
+        );
+    }
+
+    #[test]
+    fn find_or_insert_compares_paths() {
+        let tileset = |path: &str, mode: TilesetMode| {
+            Tileset::new_with_default_id(
+                "tiles".into(),
+                path.into(),
+                u32size2(8, 8),
+                u32size2(16, 16),
+                mode,
+                None,
+                None,
+                false,
+            )
+        };
+        let direct = TilesetMode::Direct;
+
+        let base_dir = Some(Utf8Path::new("/maps"));
+
+        let mut tilesets = Tilesets::new();
+        let a = tilesets
+            .find_or_insert_matching_tileset(tileset("/maps/a/tiles.png", direct), base_dir);
+        let b = tilesets
+            .find_or_insert_matching_tileset(tileset("/maps/b/tiles.png", direct), base_dir);
+        assert_ne!(a, b);
+
+        // Equivalent paths match, with relative paths resolved against the base dir
+        assert_eq!(
+            tilesets.find_or_insert_matching_tileset(
+                tileset("/maps/b/../a/./tiles.png", direct),
+                base_dir
+            ),
+            a
+        );
+        assert_eq!(
+            tilesets.find_or_insert_matching_tileset(tileset("b/tiles.png", direct), base_dir),
+            b
+        );
+        assert_eq!(tilesets.len(), 2);
+
+        // A relative path doesn't match just because an absolute path ends with it
+        let other_dir = tilesets.find_or_insert_matching_tileset(
+            tileset("b/tiles.png", direct),
+            Some(Utf8Path::new("/other")),
+        );
+        assert_ne!(other_dir, b);
+        let unknown_dir =
+            tilesets.find_or_insert_matching_tileset(tileset("a/tiles.png", direct), None);
+        assert_ne!(unknown_dir, a);
+        assert_eq!(tilesets.len(), 4);
+
+        // Same path with a different mode doesn't match
+        let transparent = TilesetMode::TransparentBackground {
+            background: UserColor::BLACK,
+            tolerance: 0,
+        };
+        let a_transparent = tilesets
+            .find_or_insert_matching_tileset(tileset("/maps/a/tiles.png", transparent), base_dir);
+        assert_ne!(a_transparent, a);
+        assert_eq!(tilesets.len(), 5);
+    }
 }
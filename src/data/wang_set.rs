@@ -0,0 +1,109 @@
+use crate::{data::tiles::TileIndex, geom::i32pos2::I32Pos2};
+
+/// Bit set in a corner mask when the top left corner of a cell is terrain
+pub const TOP_LEFT: u8 = 1;
+/// Bit set in a corner mask when the top right corner of a cell is terrain
+pub const TOP_RIGHT: u8 = 2;
+/// Bit set in a corner mask when the bottom right corner of a cell is terrain
+pub const BOTTOM_RIGHT: u8 = 4;
+/// Bit set in a corner mask when the bottom left corner of a cell is terrain
+pub const BOTTOM_LEFT: u8 = 8;
+
+/// The number of tiles in a [`WangSet`], one for each possible corner mask
+pub const WANG_SET_TILE_COUNT: usize = 16;
+
+/// A set of rules for auto-tiling a terrain, similar to a Tiled corner
+/// "Wang set", using the 16 tile, 4 bit corner scheme.
+///
+/// Each cell containing a tile from the set is treated as terrain. When
+/// auto-tiling, each terrain cell has a corner mask built from the
+/// [`TOP_LEFT`], [`TOP_RIGHT`], [`BOTTOM_RIGHT`] and [`BOTTOM_LEFT`] bits,
+/// where a corner is set if all the cells sharing that corner are terrain.
+/// The cell's tile is then replaced by the tile for that mask, if the
+/// set has one. For example a mask of 15 (all corners set) is used
+/// for cells in the interior of the terrain, and 0 (no corners set) for
+/// isolated cells.
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize, PartialEq, Eq, Hash)]
+pub struct WangSet {
+    /// The tile to use for each corner mask, indexed by mask, or [`None`] to
+    /// leave cells with that mask unchanged.
+    pub tiles: [Option<TileIndex>; WANG_SET_TILE_COUNT],
+}
+
+impl WangSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// True if the tile index is used by any corner mask in this set
+    pub fn contains(&self, tile_index: TileIndex) -> bool {
+        self.tiles.contains(&Some(tile_index))
+    }
+
+    /// The tile index to use for a cell with the given corner mask, if any.
+    pub fn tile_for_corners(&self, corners: u8) -> Option<TileIndex> {
+        self.tiles.get(corners as usize).copied().flatten()
+    }
+
+    /// Find the corner mask for the cell at `pos`, assuming that cell is itself
+    /// terrain, where `is_terrain` determines whether another cell is terrain.
+    pub fn corners_at<F>(pos: I32Pos2, is_terrain: F) -> u8
+    where
+        F: Fn(I32Pos2) -> bool,
+    {
+        let at = |dx: i32, dy: i32| is_terrain(I32Pos2::new(pos.x + dx, pos.y + dy));
+        let up = at(0, -1);
+        let down = at(0, 1);
+        let left = at(-1, 0);
+        let right = at(1, 0);
+
+        let mut corners = 0;
+        if up && left && at(-1, -1) {
+            corners |= TOP_LEFT;
+        }
+        if up && right && at(1, -1) {
+            corners |= TOP_RIGHT;
+        }
+        if down && right && at(1, 1) {
+            corners |= BOTTOM_RIGHT;
+        }
+        if down && left && at(-1, 1) {
+            corners |= BOTTOM_LEFT;
+        }
+        corners
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::ahash::{HashSet, HashSetExt};
+
+    use crate::geom::i32pos2::i32pos2;
+
+    use super::*;
+
+    #[test]
+    fn corners_of_rect() {
+        // A 3x3 block of terrain
+        let mut terrain = HashSet::new();
+        for y in 0..3 {
+            for x in 0..3 {
+                terrain.insert(i32pos2(x, y));
+            }
+        }
+        let corners = |x, y| WangSet::corners_at(i32pos2(x, y), |p| terrain.contains(&p));
+
+        assert_eq!(corners(0, 0), BOTTOM_RIGHT);
+        assert_eq!(corners(1, 0), BOTTOM_LEFT | BOTTOM_RIGHT);
+        assert_eq!(corners(2, 0), BOTTOM_LEFT);
+        assert_eq!(corners(0, 1), TOP_RIGHT | BOTTOM_RIGHT);
+        assert_eq!(corners(1, 1), 15);
+        assert_eq!(corners(2, 1), TOP_LEFT | BOTTOM_LEFT);
+        assert_eq!(corners(0, 2), TOP_RIGHT);
+        assert_eq!(corners(1, 2), TOP_LEFT | TOP_RIGHT);
+        assert_eq!(corners(2, 2), TOP_LEFT);
+
+        // Isolated cell
+        assert_eq!(WangSet::corners_at(i32pos2(10, 10), |_| false), 0);
+    }
+}
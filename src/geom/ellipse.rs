@@ -0,0 +1,87 @@
+use crate::geom::{
+    u32pos2::{U32Pos2, u32pos2},
+    u32rect::U32Rect,
+};
+
+/// The cells of an ellipse fitting the cells of `rect`, in row-major order.
+///
+/// A cell is inside the ellipse if its center is. If `filled` is false, only the
+/// outline is produced - cells inside the ellipse with at least one 4-neighbour
+/// outside it, which gives a connected outline one cell wide.
+pub fn ellipse_positions(rect: U32Rect, filled: bool) -> Vec<U32Pos2> {
+    let rect = rect.with_positive_size();
+    let size = rect.size();
+
+    // Work relative to the center of the rect, in cell units
+    let radius_x = size.w as f64 / 2.0;
+    let radius_y = size.h as f64 / 2.0;
+    let inside = |x: i64, y: i64| {
+        let dx = (x as f64 + 0.5 - radius_x) / radius_x;
+        let dy = (y as f64 + 0.5 - radius_y) / radius_y;
+        dx * dx + dy * dy <= 1.0
+    };
+
+    let mut positions = vec![];
+    for y in 0..size.h as i64 {
+        for x in 0..size.w as i64 {
+            if inside(x, y)
+                && (filled
+                    || !inside(x - 1, y)
+                    || !inside(x + 1, y)
+                    || !inside(x, y - 1)
+                    || !inside(x, y + 1))
+            {
+                positions.push(u32pos2(rect.min.x + x as u32, rect.min.y + y as u32));
+            }
+        }
+    }
+    positions
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::ahash::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn filled_circle() {
+        let rect = U32Rect::from_two_pos(u32pos2(2, 1), u32pos2(6, 5));
+        let positions: HashSet<_> = ellipse_positions(rect, true).into_iter().collect();
+
+        // Center, and middle of each edge are inside, corners are outside
+        for pos in [(4, 3), (2, 3), (6, 3), (4, 1), (4, 5)] {
+            assert!(positions.contains(&u32pos2(pos.0, pos.1)), "{pos:?}");
+        }
+        for pos in [(2, 1), (6, 1), (2, 5), (6, 5)] {
+            assert!(!positions.contains(&u32pos2(pos.0, pos.1)), "{pos:?}");
+        }
+        assert!(
+            positions
+                .iter()
+                .all(|pos| (2..=6).contains(&pos.x) && (1..=5).contains(&pos.y))
+        );
+    }
+
+    #[test]
+    fn outline_is_filled_ellipse_edge() {
+        // Rect with corners swapped, as from dragging up and left
+        let rect = U32Rect {
+            min: u32pos2(9, 6),
+            max: u32pos2(0, 0),
+        };
+        let filled: HashSet<_> = ellipse_positions(rect, true).into_iter().collect();
+        let outline = ellipse_positions(rect, false);
+
+        assert!(outline.len() < filled.len());
+        assert!(outline.iter().all(|pos| filled.contains(pos)));
+        // Center is not on the outline
+        assert!(!outline.contains(&u32pos2(4, 3)));
+        assert!(outline.contains(&u32pos2(0, 3)));
+        assert!(outline.contains(&u32pos2(9, 3)));
+
+        // Small rects are entirely outline
+        let small = U32Rect::from_two_pos(u32pos2(0, 0), u32pos2(1, 0));
+        assert_eq!(ellipse_positions(small, false).len(), 2);
+    }
+}
@@ -0,0 +1,76 @@
+use crate::geom::i32pos2::I32Pos2;
+
+/// The cells on a line from `start` to `end` inclusive, in order, using
+/// Bresenham's algorithm. There is exactly one cell for each step along the
+/// longer axis, and each cell is adjacent (including diagonally) to the previous.
+pub fn line_positions(start: I32Pos2, end: I32Pos2) -> Vec<I32Pos2> {
+    let dx = (end.x - start.x).abs();
+    let dy = -(end.y - start.y).abs();
+    let step_x = if start.x < end.x { 1 } else { -1 };
+    let step_y = if start.y < end.y { 1 } else { -1 };
+
+    let mut positions = Vec::with_capacity((dx.max(-dy) + 1) as usize);
+    let mut pos = start;
+    let mut error = dx + dy;
+    loop {
+        positions.push(pos);
+        if pos == end {
+            return positions;
+        }
+        let double_error = 2 * error;
+        if double_error >= dy {
+            error += dy;
+            pos.x += step_x;
+        }
+        if double_error <= dx {
+            error += dx;
+            pos.y += step_y;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::geom::i32pos2::i32pos2;
+
+    use super::*;
+
+    #[test]
+    fn straight_and_diagonal_lines() {
+        assert_eq!(
+            line_positions(i32pos2(1, 1), i32pos2(1, 1)),
+            vec![i32pos2(1, 1)]
+        );
+        assert_eq!(
+            line_positions(i32pos2(2, 0), i32pos2(-1, 0)),
+            vec![i32pos2(2, 0), i32pos2(1, 0), i32pos2(0, 0), i32pos2(-1, 0)]
+        );
+        assert_eq!(
+            line_positions(i32pos2(0, 0), i32pos2(2, -2)),
+            vec![i32pos2(0, 0), i32pos2(1, -1), i32pos2(2, -2)]
+        );
+    }
+
+    #[test]
+    fn sloped_lines_are_connected() {
+        for end in [
+            i32pos2(5, 2),
+            i32pos2(-2, 7),
+            i32pos2(-6, -3),
+            i32pos2(3, -8),
+        ] {
+            let start = i32pos2(1, 1);
+            let positions = line_positions(start, end);
+            let steps = (end.x - start.x).abs().max((end.y - start.y).abs());
+
+            assert_eq!(positions.len(), steps as usize + 1);
+            assert_eq!(positions.first(), Some(&start));
+            assert_eq!(positions.last(), Some(&end));
+            for pair in positions.windows(2) {
+                if let [a, b] = pair {
+                    assert!((a.x - b.x).abs() <= 1 && (a.y - b.y).abs() <= 1);
+                }
+            }
+        }
+    }
+}
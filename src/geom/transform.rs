@@ -107,6 +107,21 @@ impl Transform {
         }
     }
 
+    /// A short description for users, in the same terms as the variant names,
+    /// i.e. mirroring (if any) followed by a clockwise rotation
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::None => "not transformed",
+            Self::Rotate90 => "rotated 90°",
+            Self::Rotate180 => "rotated 180°",
+            Self::Rotate270 => "rotated 270°",
+            Self::MirrorX => "mirrored",
+            Self::MirrorXRotate90 => "mirrored, rotated 90°",
+            Self::MirrorXRotate180 => "mirrored, rotated 180°",
+            Self::MirrorXRotate270 => "mirrored, rotated 270°",
+        }
+    }
+
     pub fn apply_to_pos(&self, pos: &I32Pos2) -> I32Pos2 {
         let mut x = pos.x;
         let mut y = pos.y;
@@ -16,7 +16,7 @@ use crate::{
 ///
 /// Normally the unit is in grid cells.
 #[repr(C)]
-#[derive(Clone, Copy, Eq, PartialEq, serde::Deserialize, serde::Serialize, Debug)]
+#[derive(Clone, Copy, Eq, PartialEq, Hash, serde::Deserialize, serde::Serialize, Debug)]
 pub struct U32Rect {
     /// One of the corners of the rectangle, usually the left top one.
     pub min: U32Pos2,
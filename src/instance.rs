@@ -1,3 +1,4 @@
+use crate::cli::file_argument;
 use camino::Utf8PathBuf;
 use interprocess::local_socket::ListenerOptions;
 use interprocess::local_socket::{GenericNamespaced, Stream, prelude::*};
@@ -180,16 +181,13 @@ pub fn instance_startup(unique_id: &str) -> bool {
 
         // TODO: Handle multiple filenames?
         let args: Vec<String> = env::args().collect();
-        let filename = args.get(1);
 
-        if let Some(filename) = filename {
+        if let Some(filepath) = file_argument(&args) {
             log::trace!(
                 "...received filename {}, sending to main instance",
-                filename
+                filepath
             );
 
-            let filepath = Utf8PathBuf::from(filename);
-
             match send_to_first_instance(
                 format!("{}.interprocess", unique_id).as_str(),
                 &IpcMessage::FileOpen { filepath },
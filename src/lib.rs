@@ -8,11 +8,15 @@
 )]
 
 pub mod app;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod cli;
 pub mod instance;
 mod render;
 mod selection;
 mod undo;
 mod utils;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod watcher;
 
 pub mod data {
     pub mod action;
@@ -31,13 +35,16 @@ pub mod data {
     pub mod tiled;
     pub mod tiles;
     pub mod tilesets;
+    pub mod wang_set;
     pub mod config {
         pub mod workspace;
     }
 }
 
 pub mod geom {
+    pub mod ellipse;
     pub mod i32pos2;
+    pub mod line;
     pub mod transform;
     pub mod u32pos2;
     pub mod u32rect;
@@ -54,6 +61,7 @@ pub mod ui {
     pub mod color_edit;
     pub mod egui_utils;
     pub mod file_dialog;
+    pub mod file_manager;
     pub mod layers;
     pub mod map;
     pub mod maps;
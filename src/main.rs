@@ -14,7 +14,10 @@ use std::process::exit;
 // When compiling natively:
 #[cfg(not(target_arch = "wasm32"))]
 fn main() -> eframe::Result {
-    use mountain_tiles::app::{App, UNIQUE_ID};
+    use mountain_tiles::{
+        app::{App, UNIQUE_ID},
+        cli::{Command, USAGE},
+    };
 
     env_logger::init(); // Log to stderr (if you run with `RUST_LOG=debug`).
 
@@ -23,6 +26,23 @@ fn main() -> eframe::Result {
         exit(1);
     }
 
+    // Run a command line command if requested, without opening a window
+    let args: Vec<String> = std::env::args().collect();
+    match Command::from_args(&args) {
+        Ok(Some(command)) => match command.run() {
+            Ok(()) => exit(0),
+            Err(e) => {
+                eprintln!("{}", e);
+                exit(1);
+            }
+        },
+        Ok(None) => {}
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            exit(2);
+        }
+    }
+
     // Handle instance startup - if this returns true we should
     // exit without error
     if instance_startup(UNIQUE_ID) {
@@ -30,6 +50,8 @@ fn main() -> eframe::Result {
         exit(0);
     }
 
+    // This is the size on first run - after that, the last window size and position
+    // are restored, see `persist_window` below
     let mut viewport = egui::ViewportBuilder::default()
         .with_inner_size([400.0, 300.0])
         .with_min_inner_size([300.0, 220.0])
@@ -58,6 +80,10 @@ fn main() -> eframe::Result {
 
     let native_options = eframe::NativeOptions {
         viewport,
+        // Save the window size and position on exit, in eframe storage alongside the
+        // app state, and restore them on the next run. This only overrides the size and
+        // position of the viewport, so the macOS titlebar settings above still apply.
+        persist_window: true,
         ..Default::default()
     };
     eframe::run_native(
@@ -7,12 +7,38 @@ use crate::{
 };
 use egui::ahash::{HashMap, HashMapExt};
 use eyre::{Result, bail, eyre};
-use image::Pixel;
-use image::{GenericImage, GenericImageView, ImageReader, RgbaImage, imageops::resize};
+use image::{GenericImage, GenericImageView, Rgba, RgbaImage, imageops::resize};
 
-type TilesetImages = HashMap<TilesetId, RgbaImage>;
+/// Decoded tileset images, by tileset id, with the tileset mode already applied
+pub type TilesetImages = HashMap<TilesetId, RgbaImage>;
 
-fn cache_tileset_images<T: Tiles>(
+/// Composite `src` over `dst` using the "over" operator, where both colors
+/// (and the result) use straight (not premultiplied) alpha.
+/// Results are rounded rather than truncated, so that for example compositing
+/// any color over an opaque color always gives an opaque color.
+fn blend_over(dst: [u8; 4], src: [u8; 4]) -> [u8; 4] {
+    let [sr, sg, sb, sa] = src.map(|c| c as f32 / 255.0);
+    let [dr, dg, db, da] = dst.map(|c| c as f32 / 255.0);
+
+    let dst_weight = da * (1.0 - sa);
+    let a = sa + dst_weight;
+    if a <= 0.0 {
+        return [0, 0, 0, 0];
+    }
+
+    // Note that cast from f32 to u8 is saturating, result will be clamped to 0-255 range
+    let channel = |s: f32, d: f32| ((s * sa + d * dst_weight) / a * 255.0).round() as u8;
+    [
+        channel(sr, dr),
+        channel(sg, dg),
+        channel(sb, db),
+        (a * 255.0).round() as u8,
+    ]
+}
+
+/// Decode the images for all tilesets, checking they all have tile size matching
+/// the tiles.
+pub fn decode_tileset_images<T: Tiles>(
     tiles: &T,
     tilesets: &Tilesets,
     textures: &TilesetTextures,
@@ -23,14 +49,10 @@ fn cache_tileset_images<T: Tiles>(
         if tileset.tile_size != tiles.tile_size() {
             bail!(
                 "Cannot export as an image unless all tilesets have the same tile size as the map."
-            )
+            );
         }
 
-        let path = textures
-            .path_for_tileset(tileset)
-            .ok_or(eyre!("No path for tileset"))?;
-        let dynamic_image = ImageReader::open(path)?.decode()?;
-        let image = dynamic_image.into_rgba8();
+        let image = textures.decode_tileset_image(tileset)?;
         tileset_images.insert(tileset.id(), image);
     }
     Ok(tileset_images)
@@ -42,14 +64,27 @@ pub fn render_tiles<T: Tiles>(
     tilesets: &Tilesets,
     textures: &TilesetTextures,
     settings: &PngExportSettings,
+) -> Result<RgbaImage> {
+    // Cache tileset images, and check they all have tile_size matching the map
+    let tileset_images = decode_tileset_images(tiles, tilesets, textures)?;
+    render_tiles_with_images(tiles, palette, tilesets, &tileset_images, settings)
+}
+
+/// Render tiles to an image, using already decoded tileset images, see [`decode_tileset_images`].
+/// The output image uses straight (not premultiplied) alpha, as expected by PNG.
+/// Tileset pixels are tinted by the (straight alpha) tile color with layer tint and opacity
+/// applied, then composited over the existing image using straight alpha "over" blending.
+pub fn render_tiles_with_images<T: Tiles>(
+    tiles: &T,
+    palette: &Palette,
+    tilesets: &Tilesets,
+    tileset_images: &TilesetImages,
+    settings: &PngExportSettings,
 ) -> Result<RgbaImage> {
     let size = tiles.pixel_size_unscaled();
     let scale = settings.scale;
     let transparent = settings.transparent;
 
-    // Cache tileset images, and check they all have tile_size matching the map
-    let tileset_images = cache_tileset_images(tiles, tilesets, textures)?;
-
     let mut image = RgbaImage::new(size.w, size.h);
 
     if !transparent {
@@ -61,10 +96,9 @@ pub fn render_tiles<T: Tiles>(
 
     for layer_index in (0..tiles.layer_count()).rev() {
         let opacity = tiles.layer_opacity(layer_index);
+        let tint = tiles.layer_tint(layer_index);
         for tileset in tilesets.iter() {
-            let tile_set_size = tileset.size_in_tiles;
             let tile_size = tileset.tile_size;
-            let mode = tileset.mode;
 
             for grid_pos in tiles.map_positions() {
                 if let Some(ref tile) = tiles.tile(layer_index, grid_pos) {
@@ -75,10 +109,10 @@ pub fn render_tiles<T: Tiles>(
                         let color = tile
                             .color
                             .as_user_color(palette)
+                            .with_optional_tint(tint)
                             .with_optional_opacity(opacity)
                             .as_slice();
-                        let tile_pos =
-                            tile_set_size.pos_from_linear_index(tile.source.tile_index.index());
+                        let tile_pos = tileset.image_pos(tile.source.tile_index.index());
                         let tile_pixel_pos = tile_pos * tile_size;
 
                         let tile_dest_pixel_pos = grid_pos * tile_size;
@@ -112,17 +146,14 @@ pub fn render_tiles<T: Tiles>(
 
                                 let mut src = tile_source_image.get_pixel(sx, sy);
 
-                                mode.transform_color_slice(&mut src.0);
-
                                 for (s, c) in src.0.iter_mut().zip(color.iter()) {
                                     let mut v: u32 = *s as u32;
                                     v = (v * *c as u32) / 255;
                                     *s = v as u8;
                                 }
 
-                                let mut dst = tile_dest_image.get_pixel(x, y);
-                                dst.blend(&src);
-                                tile_dest_image.put_pixel(x, y, dst);
+                                let dst = tile_dest_image.get_pixel(x, y);
+                                tile_dest_image.put_pixel(x, y, Rgba(blend_over(dst.0, src.0)));
                             }
                         }
                     }
@@ -140,3 +171,117 @@ pub fn render_tiles<T: Tiles>(
 
     Ok(image)
 }
+
+#[cfg(test)]
+mod tests {
+    use camino::Utf8PathBuf;
+
+    use crate::{
+        data::{
+            tiles::{
+                Tile, TileIndex, TileSource,
+                layer_tiles::LayerTiles,
+                tile_color::{TileColor, UserColor},
+            },
+            tilesets::{Tileset, TilesetMode},
+        },
+        geom::{transform::Transform, u32pos2::u32pos2, u32size2::u32size2},
+    };
+
+    use super::*;
+
+    /// Render a single 1x1 pixel tile from a white tileset, with the given tile
+    /// color and layer opacity
+    fn render_pixel(color: UserColor, opacity: Option<f32>, transparent: bool) -> Result<[u8; 4]> {
+        let one = u32size2(1, 1);
+        let mut tilesets = Tilesets::new();
+        let tileset_id = tilesets.push_tileset(Tileset::new_with_default_id(
+            "white".to_string(),
+            Utf8PathBuf::new(),
+            one,
+            one,
+            TilesetMode::Direct,
+            None,
+            None,
+            false,
+        ));
+        let mut tileset_images = HashMap::new();
+        tileset_images.insert(tileset_id, RgbaImage::from_pixel(1, 1, Rgba([255; 4])));
+
+        let mut tiles = LayerTiles::new(UserColor::BLACK, one, one);
+        tiles.insert_layer(0, "Layer", true, opacity);
+        tiles.set_tile(
+            0,
+            u32pos2(0, 0),
+            Some(Tile {
+                source: TileSource {
+                    tileset_id,
+                    tile_index: TileIndex::new(0),
+                },
+                color: TileColor::UserColor(color),
+                transform: Transform::None,
+            }),
+        );
+
+        let settings = PngExportSettings {
+            scale: 1,
+            transparent,
+            fit_within: None,
+        };
+        let image = render_tiles_with_images(
+            &tiles,
+            &Palette::new(vec![]),
+            &tilesets,
+            &tileset_images,
+            &settings,
+        )?;
+        Ok(image.get_pixel(0, 0).0)
+    }
+
+    #[test]
+    fn render_uses_straight_alpha() -> Result<()> {
+        let red = UserColor::new(255, 0, 0, 255);
+        let translucent_red = UserColor::new(255, 0, 0, 128);
+
+        // With a transparent background, the color channels are not darkened by the alpha
+        assert_eq!(render_pixel(red, Some(0.5), true)?, [255, 0, 0, 127]);
+        assert_eq!(render_pixel(translucent_red, None, true)?, [255, 0, 0, 128]);
+        assert_eq!(
+            render_pixel(translucent_red, Some(0.5), true)?,
+            [255, 0, 0, 64]
+        );
+
+        // Over an opaque black background, the result is opaque and blended
+        assert_eq!(render_pixel(red, Some(0.5), false)?, [127, 0, 0, 255]);
+        assert_eq!(
+            render_pixel(translucent_red, None, false)?,
+            [128, 0, 0, 255]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn render_builtin_tilesets_without_context() -> Result<()> {
+        use crate::data::tiles::tileset_stacked_tiles::TilesetStackedTiles;
+
+        let tilesets = Tilesets::builtin_tilesets();
+        let tiles = TilesetStackedTiles::new(&tilesets);
+        let settings = PngExportSettings {
+            scale: 1,
+            transparent: true,
+            fit_within: None,
+        };
+        let image = render_tiles(
+            &tiles,
+            &Palette::new(vec![]),
+            &tilesets,
+            &TilesetTextures::builtin_tileset_textures(),
+            &settings,
+        )?;
+        // Tiles are stacked in a single column, one 8x8 tile per tile index
+        assert_eq!((image.width(), image.height()), (8, 8 * 256));
+        // Some of the builtin tiles are not empty
+        assert!(image.pixels().any(|p| p.0[3] != 0));
+        Ok(())
+    }
+}
@@ -9,9 +9,16 @@ pub struct TiledColor {
     pub a: u8,
 }
 
+/// True if `hex` contains only hexadecimal digits. This is checked before
+/// slicing components, since `u8::from_str_radix` accepts a leading `+`,
+/// and slicing other non-ASCII characters could split them.
+fn is_hex_digits(hex: &str) -> bool {
+    hex.chars().all(|c| c.is_ascii_hexdigit())
+}
+
 impl TiledColor {
     pub fn from_hex_rgb(hex: &str) -> Option<TiledColor> {
-        if hex.len() == 6 {
+        if hex.len() == 6 && is_hex_digits(hex) {
             let r = u8::from_str_radix(&hex[0..2], 16).ok()?;
             let g = u8::from_str_radix(&hex[2..4], 16).ok()?;
             let b = u8::from_str_radix(&hex[4..6], 16).ok()?;
@@ -22,7 +29,7 @@ impl TiledColor {
     }
 
     pub fn from_hex_rgba(hex: &str) -> Option<TiledColor> {
-        if hex.len() == 8 {
+        if hex.len() == 8 && is_hex_digits(hex) {
             let a = u8::from_str_radix(&hex[0..2], 16).ok()?;
             let r = u8::from_str_radix(&hex[2..4], 16).ok()?;
             let g = u8::from_str_radix(&hex[4..6], 16).ok()?;
@@ -33,7 +40,19 @@ impl TiledColor {
         }
     }
 
-    fn as_hex_string(&self) -> String {
+    /// Parse a color in either of the formats supported by [`TiledColor::from_hex_rgb`] and
+    /// [`TiledColor::from_hex_rgba`], with an optional leading '#', as used in Tiled files
+    pub fn from_hex(hex: &str) -> Option<TiledColor> {
+        let hex = hex.trim();
+        let hex = hex.strip_prefix("#").unwrap_or(hex);
+        match hex.len() {
+            6 => TiledColor::from_hex_rgb(hex),
+            8 => TiledColor::from_hex_rgba(hex),
+            _ => None,
+        }
+    }
+
+    pub fn as_hex_string(&self) -> String {
         if self.a < 255 {
             format!("#{:02x}{:02x}{:02x}{:02x}", self.a, self.r, self.g, self.b)
         } else {
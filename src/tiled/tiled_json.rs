@@ -2,25 +2,26 @@ use crate::{
     data::palette::{Palette, palette_index},
     data::tiles::{
         Tile, TileIndex, TileSource, Tiles,
-        layer_tiles::LayerTiles,
+        layer_tiles::{LayerId, LayerTiles},
         tile_color::{TileColor, UserColor},
     },
     data::{
         maps::{Map, MapId},
+        settings::Settings,
         state::State,
     },
-    geom::{transform::Transform, u32pos2::u32pos2, u32size2::u32size2},
+    geom::{i32pos2::I32Pos2, transform::Transform, u32pos2::u32pos2, u32size2::u32size2},
     tiled::{
         tiled_color::TiledColor,
         tiled_xml::{TiledXml, TilesetXml},
     },
     utils,
 };
-use camino::Utf8PathBuf;
+use camino::{Utf8Path, Utf8PathBuf};
 use egui::ahash::{HashMap, HashMapExt};
 use eyre::eyre;
 use serde::{Deserialize, Serialize};
-use std::{fs::File, io::BufReader};
+use std::borrow::Cow;
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct TiledTileset {
@@ -54,7 +55,13 @@ pub struct Tiled {
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct TiledLayer {
+    /// The tile data, for finite maps
+    #[serde(default)]
     pub data: Vec<u32>,
+    /// The tile data as chunks, for infinite maps
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub chunks: Vec<TiledChunk>,
     pub height: u32,
     pub width: u32,
     pub id: u32,
@@ -66,6 +73,178 @@ pub struct TiledLayer {
     pub visible: bool,
     // pub x: u32,
     // pub y: u32,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub properties: Vec<TiledProperty>,
+}
+
+/// A rectangular chunk of the tile data for a layer in an infinite map.
+/// Chunk positions are in tiles, and may be negative.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TiledChunk {
+    pub data: Vec<u32>,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// The area covered by the chunks of all layers in an infinite map, in tiles
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ChunkBounds {
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+}
+
+impl ChunkBounds {
+    fn from_layers(layers: &[TiledLayer]) -> eyre::Result<ChunkBounds> {
+        let mut min = (i64::MAX, i64::MAX);
+        let mut max = (i64::MIN, i64::MIN);
+        for chunk in layers.iter().flat_map(|l| l.chunks.iter()) {
+            min = (min.0.min(chunk.x as i64), min.1.min(chunk.y as i64));
+            max = (
+                max.0.max(chunk.x as i64 + chunk.width as i64),
+                max.1.max(chunk.y as i64 + chunk.height as i64),
+            );
+        }
+
+        // No chunks at all gives an empty map
+        if min.0 > max.0 {
+            return Ok(ChunkBounds {
+                x: 0,
+                y: 0,
+                width: 0,
+                height: 0,
+            });
+        }
+
+        Ok(ChunkBounds {
+            x: min.0 as i32,
+            y: min.1 as i32,
+            width: u32::try_from(max.0 - min.0)?,
+            height: u32::try_from(max.1 - min.1)?,
+        })
+    }
+}
+
+/// Convert the chunks of a layer to dense data covering `bounds`, with
+/// the top left of the bounds at (0, 0)
+fn chunks_to_data(chunks: &[TiledChunk], bounds: ChunkBounds) -> eyre::Result<Vec<u32>> {
+    let mut data = vec![0; bounds.width as usize * bounds.height as usize];
+    for chunk in chunks.iter() {
+        if chunk.data.len() != chunk.width as usize * chunk.height as usize {
+            return Err(eyre!(
+                "Layer chunk at ({}, {}) has {} tiles, expected {}x{}.",
+                chunk.x,
+                chunk.y,
+                chunk.data.len(),
+                chunk.width,
+                chunk.height
+            ));
+        }
+        let x0 = (chunk.x as i64 - bounds.x as i64) as usize;
+        let y0 = (chunk.y as i64 - bounds.y as i64) as usize;
+        for (i, gid) in chunk.data.iter().enumerate() {
+            let x = x0 + i % chunk.width as usize;
+            let y = y0 + i / chunk.width as usize;
+            if let Some(target) = data.get_mut(x + y * bounds.width as usize) {
+                *target = *gid;
+            }
+        }
+    }
+    Ok(data)
+}
+
+/// A custom property, as attached to a layer. Tiled allows values of several
+/// types, we keep the raw JSON value and convert to a string when needed.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TiledProperty {
+    pub name: String,
+    pub value: serde_json::Value,
+}
+
+/// Property used on export to record the name of the layer a Tiled layer was split from
+pub const LAYER_NAME_PROPERTY: &str = "com.mountaintiles.layer_name";
+
+/// Property used on export to record the index of the layer a Tiled layer was split from,
+/// among the exported layers. This is unique for each layer, unlike the layer name, so
+/// is used to merge the split Tiled layers back into one layer on import.
+pub const LAYER_INDEX_PROPERTY: &str = "com.mountaintiles.layer_index";
+
+/// Property used on export to record the palette index used by all tiles in a Tiled layer
+pub const PALETTE_INDEX_PROPERTY: &str = "com.mountaintiles.palette_index";
+
+/// Property used on export to record the tint of the layer a Tiled layer was split from,
+/// since the Tiled layer's `tintcolor` combines this with the palette color
+pub const LAYER_TINT_PROPERTY: &str = "com.mountaintiles.layer_tint";
+
+/// The MountainTiles layer a Tiled layer was split from on export, so that split layers
+/// can be merged back together on import. Files exported before [`LAYER_INDEX_PROPERTY`]
+/// was added only record the layer name.
+#[derive(Debug, Clone, PartialEq)]
+enum SourceLayer {
+    Index(String),
+    Name(String),
+}
+
+impl TiledLayer {
+    /// The value of the named property as a string, if present
+    pub fn property(&self, name: &str) -> Option<String> {
+        self.properties
+            .iter()
+            .find(|p| p.name == name)
+            .map(|p| match &p.value {
+                serde_json::Value::String(s) => s.clone(),
+                value => value.to_string(),
+            })
+    }
+
+    /// The palette index recorded on export, if `use_layer_properties` is true and the
+    /// layer was exported from MountainTiles with layer data as properties
+    fn recorded_palette_index(&self, use_layer_properties: bool) -> Option<u32> {
+        if use_layer_properties {
+            self.property(PALETTE_INDEX_PROPERTY)
+                .and_then(|index| index.parse().ok())
+        } else {
+            None
+        }
+    }
+
+    /// The tint recorded on export, if `use_layer_properties` is true and the layer
+    /// was exported from MountainTiles from a layer with a tint
+    fn import_tint(&self, use_layer_properties: bool) -> Option<UserColor> {
+        if use_layer_properties {
+            self.property(LAYER_TINT_PROPERTY)
+                .and_then(|tint| TiledColor::from_hex(&tint))
+                .map(UserColor::from)
+        } else {
+            None
+        }
+    }
+
+    /// The layer this was split from on export, if `use_layer_properties` is true and
+    /// the layer was exported from MountainTiles with layer data as properties
+    fn source_layer(&self, use_layer_properties: bool) -> Option<SourceLayer> {
+        if !use_layer_properties {
+            return None;
+        }
+        self.property(LAYER_INDEX_PROPERTY)
+            .map(SourceLayer::Index)
+            .or_else(|| self.property(LAYER_NAME_PROPERTY).map(SourceLayer::Name))
+    }
+
+    /// The name to use for this layer on import. When `use_layer_properties` is true
+    /// and the layer was exported from MountainTiles with layer data as properties,
+    /// this is the original layer name, otherwise it is the Tiled layer name.
+    fn import_name(&self, use_layer_properties: bool) -> String {
+        if use_layer_properties && let Some(name) = self.property(LAYER_NAME_PROPERTY) {
+            name
+        } else {
+            self.name.clone()
+        }
+    }
 }
 
 struct ColorCache {
@@ -80,11 +259,6 @@ impl ColorCache {
             colors: vec![],
         }
     }
-    fn insert_tintcolor(&mut self, tintcolor: &TiledColor) -> TileColor {
-        let user_color: UserColor = tintcolor.into();
-        self.insert_user_color(user_color)
-    }
-
     fn insert_user_color(&mut self, user_color: UserColor) -> TileColor {
         if let Some(index) = self.color_to_index.get(&user_color) {
             TileColor::from_palette_index(palette_index(*index))
@@ -96,15 +270,90 @@ impl ColorCache {
         }
     }
 
+    /// Find or insert the tint color, using the recorded palette index if that
+    /// entry has the tint color (once tinted by the recorded layer `tint`, if any),
+    /// so that duplicate colors in the palette keep their own index
+    fn insert_tintcolor(
+        &mut self,
+        tintcolor: &TiledColor,
+        recorded_index: Option<u32>,
+        tint: Option<UserColor>,
+    ) -> TileColor {
+        match recorded_index {
+            Some(index) if self.recorded_color_matches(tintcolor, index, tint) => {
+                TileColor::from_palette_index(palette_index(index))
+            }
+            _ => self.insert_user_color(tintcolor.into()),
+        }
+    }
+
+    /// True if a Tiled layer split from a layer with `tint` on export has a tint color
+    /// that is still the recorded palette color with `tint` applied, or has no tint color
+    fn split_layer_matches(
+        &self,
+        layer: &TiledLayer,
+        use_layer_properties: bool,
+        tint: UserColor,
+    ) -> bool {
+        match (
+            &layer.tintcolor,
+            layer.recorded_palette_index(use_layer_properties),
+        ) {
+            (Some(tintcolor), Some(index)) => {
+                self.recorded_color_matches(tintcolor, index, Some(tint))
+            }
+            (Some(_), None) => false,
+            (None, _) => true,
+        }
+    }
+
+    /// True if the palette color at `recorded_index`, with `tint` applied, is `tintcolor`,
+    /// so the palette index recorded on export can be used as-is
+    fn recorded_color_matches(
+        &self,
+        tintcolor: &TiledColor,
+        recorded_index: u32,
+        tint: Option<UserColor>,
+    ) -> bool {
+        self.colors
+            .get(recorded_index as usize)
+            .map(|color| color.with_optional_tint(tint))
+            == Some(tintcolor.into())
+    }
+
+    /// Start from an existing palette, keeping all entries (including duplicates)
+    /// so existing palette indices are unchanged
     fn from_palette(palette: &Palette) -> Self {
         let mut cache = Self::new();
-        for user_color in palette.colors() {
-            cache.insert_user_color(*user_color);
+        for (index, user_color) in palette.colors().enumerate() {
+            cache.colors.push(*user_color);
+            cache
+                .color_to_index
+                .entry(*user_color)
+                .or_insert(index as u32);
         }
         cache
     }
 }
 
+/// Convert a Tiled layer opacity, clamping it to `0.0..=1.0`, where fully opaque
+/// layers have no opacity. Errors if the opacity is NaN or infinite, since it
+/// can't be meaningfully clamped.
+fn convert_layer_opacity(layer: &TiledLayer) -> eyre::Result<Option<f32>> {
+    if !layer.opacity.is_finite() {
+        eyre::bail!(
+            "Tiled layer '{}' has invalid opacity {}",
+            layer.name,
+            layer.opacity
+        );
+    }
+    Ok(if layer.opacity >= 1.0 {
+        None
+    } else {
+        Some(layer.opacity.max(0.0))
+    })
+}
+
 fn convert_background_color(color: &Option<TiledColor>) -> UserColor {
     if let Some(color) = color {
         color.into()
@@ -134,19 +383,137 @@ impl Tiled {
             eyre::bail!(
                 "Can't find tileset for Tiled tile index {}",
                 tiled_tile_index
-            )
+            );
         }
     }
 
     /// Append Tiled data to a [`State`], reusing matching resources (tilesets and palette colors)
-    /// where possible, adding them where they are not already present.
+    /// where possible, adding them where they are not already present. Relative tileset image
+    /// paths in `state` are resolved against `base_dir`, the project directory, if known.
     /// See https://doc.mapeditor.org/en/stable/reference/global-tile-ids/
     pub fn append_to_state(
         &self,
         state: &mut State,
+        settings: &Settings,
+        base_dir: Option<&Utf8Path>,
         squash_layers: bool,
         prefer_relative_path: bool,
+        use_layer_properties: bool,
     ) -> eyre::Result<MapId> {
+        let mut tiles = self.to_finite(settings)?.import_tiles(
+            state,
+            base_dir,
+            squash_layers,
+            prefer_relative_path,
+            use_layer_properties,
+        )?;
+
+        // Keep infinite maps infinite, with the same origin, so they export to the same chunks
+        if self.infinite {
+            let bounds = ChunkBounds::from_layers(&self.layers)?;
+            tiles.make_infinite(I32Pos2::new(bounds.x, bounds.y));
+        }
+
+        let mut map = Map::default();
+        // TODO: Better name?
+        map.name = "Tiled import".to_string();
+        map.tiles = tiles;
+
+        let map_id = state.maps.push_map(map);
+
+        Ok(map_id)
+    }
+
+    /// As for [`Tiled::append_to_state`], but the Tiled map's layers are added above the
+    /// existing layers of the map with `map_id`, rather than as a new map. The Tiled map must
+    /// have the same map size and tile size as the existing map.
+    /// Returns the ids of the added layers.
+    #[allow(clippy::too_many_arguments)]
+    pub fn append_layers_to_map(
+        &self,
+        state: &mut State,
+        settings: &Settings,
+        base_dir: Option<&Utf8Path>,
+        map_id: MapId,
+        squash_layers: bool,
+        prefer_relative_path: bool,
+        use_layer_properties: bool,
+    ) -> eyre::Result<Vec<LayerId>> {
+        let tiled = self.to_finite(settings)?;
+        let tile_size = u32size2(tiled.tilewidth, tiled.tileheight);
+        let map_size = u32size2(tiled.width, tiled.height);
+        let map = state
+            .maps
+            .get_by_id(map_id)
+            .ok_or(eyre!("Cannot find map to import Tiled layers into"))?;
+        if map.tiles.map_size() != map_size || map.tiles.tile_size() != tile_size {
+            return Err(eyre!(
+                "Cannot import Tiled layers into map '{}'.\nThe Tiled map is {}x{} tiles of {}x{} pixels, but the map is {}x{} tiles of {}x{} pixels.",
+                map.name(),
+                map_size.w,
+                map_size.h,
+                tile_size.w,
+                tile_size.h,
+                map.tiles.map_size().w,
+                map.tiles.map_size().h,
+                map.tiles.tile_size().w,
+                map.tiles.tile_size().h,
+            ));
+        }
+
+        let tiles = tiled.import_tiles(
+            state,
+            base_dir,
+            squash_layers,
+            prefer_relative_path,
+            use_layer_properties,
+        )?;
+
+        let map = state
+            .maps
+            .get_by_id_mut(map_id)
+            .ok_or(eyre!("Cannot find map to import Tiled layers into"))?;
+        Ok(map.tiles.insert_layers_from(0, tiles))
+    }
+
+    /// This map as a finite map. Infinite maps are flattened to finite maps just large
+    /// enough to contain the chunks of all layers, with the top left chunk at the origin.
+    /// [`Tiled::append_to_state`] restores the origin with [`LayerTiles::make_infinite`].
+    /// Errors if the map (after flattening) would be
+    /// over the maximum map size in `settings`, which is checked before any tile data is
+    /// allocated.
+    pub fn to_finite(&self, settings: &Settings) -> eyre::Result<Cow<'_, Tiled>> {
+        if !self.infinite {
+            settings.check_map_size(u32size2(self.width, self.height))?;
+            return Ok(Cow::Borrowed(self));
+        }
+
+        let bounds = ChunkBounds::from_layers(&self.layers)?;
+        settings.check_map_size(u32size2(bounds.width, bounds.height))?;
+
+        let mut finite = self.clone();
+        finite.infinite = false;
+        finite.width = bounds.width;
+        finite.height = bounds.height;
+        for layer in finite.layers.iter_mut() {
+            layer.data = chunks_to_data(&layer.chunks, bounds)?;
+            layer.chunks.clear();
+            layer.width = bounds.width;
+            layer.height = bounds.height;
+        }
+        Ok(Cow::Owned(finite))
+    }
+
+    /// Convert the Tiled layers of a finite map to [`LayerTiles`], adding any tilesets and
+    /// palette colors needed to `state`, see [`Tiled::append_to_state`]
+    fn import_tiles(
+        &self,
+        state: &mut State,
+        base_dir: Option<&Utf8Path>,
+        squash_layers: bool,
+        prefer_relative_path: bool,
+        use_layer_properties: bool,
+    ) -> eyre::Result<LayerTiles> {
         let background = convert_background_color(&self.backgroundcolor);
         let tile_size = u32size2(self.tilewidth, self.tileheight);
         let map_size = u32size2(self.width, self.height);
@@ -162,26 +529,64 @@ impl Tiled {
         let mut tiled_tileset_index_to_tileset_id = HashMap::new();
         for (index, xml_tileset) in self.xml_tilesets.iter().enumerate() {
             let tileset = xml_tileset.as_tileset(prefer_relative_path, self.file_path.clone())?;
-            let tileset_id = tilesets.find_or_insert_matching_tileset(tileset);
+            let tileset_id = tilesets.find_or_insert_matching_tileset(tileset, base_dir);
             tiled_tileset_index_to_tileset_id.insert(index, tileset_id);
         }
 
-        for layer in self.layers.iter().rev() {
-            let layer_index = tiles.layer_count();
+        // When exporting, each layer is split into one Tiled layer per palette color,
+        // as consecutive Tiled layers with the same layer index property. We track
+        // the index of the previous layer so we can merge these back into one layer.
+        // Note that we only use the layer name for older files without the index, since
+        // separate layers may share a name.
+        let mut previous_source_layer = None;
+
+        let layers: Vec<_> = self.layers.iter().rev().collect();
+        for (i, layer) in layers.iter().enumerate() {
             if layer.width != map_size.w || layer.height != map_size.h {
                 return Err(eyre!(
                     "Tiled maps must have all layers the same size as map"
                 ));
             }
-            let opacity = if layer.opacity >= 1.0 {
-                None
-            } else {
-                Some(layer.opacity.clamp(0.0, 1.0))
+
+            let source_layer = layer.source_layer(use_layer_properties);
+            let layer_index = match tiles.layer_count().checked_sub(1) {
+                Some(last_index)
+                    if source_layer.is_some() && source_layer == previous_source_layer =>
+                {
+                    last_index
+                }
+                _ => {
+                    let layer_index = tiles.layer_count();
+                    let opacity = convert_layer_opacity(layer)?;
+                    let name = layer.import_name(use_layer_properties);
+                    let layer_id = tiles.insert_layer(layer_index, &name, layer.visible, opacity);
+                    // The Tiled layers split from this layer have tint colors combining the
+                    // tint with their palette color. Only restore the tint if every recorded
+                    // palette color still matches, otherwise the combined colors are used with
+                    // no tint, so tiles aren't tinted twice.
+                    // A tint is only recorded along with the source layer, so the split
+                    // layers are this one and the following layers with the same source.
+                    let tint = layer.import_tint(use_layer_properties).filter(|tint| {
+                        layers
+                            .iter()
+                            .skip(i)
+                            .take_while(|split| {
+                                split.source_layer(use_layer_properties) == source_layer
+                            })
+                            .all(|split| {
+                                color_cache.split_layer_matches(split, use_layer_properties, *tint)
+                            })
+                    });
+                    tiles.set_layer_tint(layer_id, tint);
+                    layer_index
+                }
             };
-            tiles.insert_layer(layer_index, &layer.name, layer.visible, opacity);
+            previous_source_layer = source_layer;
+            let tint = tiles.layer_tint(layer_index);
 
             let color = if let Some(ref tintcolor) = layer.tintcolor {
-                color_cache.insert_tintcolor(tintcolor)
+                let recorded_index = layer.recorded_palette_index(use_layer_properties);
+                color_cache.insert_tintcolor(tintcolor, recorded_index, tint)
             } else {
                 // There's no tint color, so use default tile color (no recoloring of tiles)
                 TileColor::Default
@@ -224,7 +629,7 @@ impl Tiled {
                             color,
                             transform,
                         };
-                        tiles.set_tile(layer_index, pos, Some(tile));
+                        tiles.set_tile_checked(layer_index, pos, Some(tile))?;
                     }
                 }
             }
@@ -250,21 +655,28 @@ impl Tiled {
         let palette = Palette::new(color_cache.colors);
 
         state.resources.palette = palette;
-        let mut map = Map::default();
-        // TODO: Better name?
-        map.name = "Tiled import".to_string();
-        map.tiles = tiles;
 
-        let map_id = state.maps.push_map(map);
-
-        Ok(map_id)
+        Ok(tiles)
     }
 
     /// Convert Tiled data to [`State`], with map tiles and palette
     /// See https://doc.mapeditor.org/en/stable/reference/global-tile-ids/
-    pub fn to_state(&self, squash_layers: bool, prefer_relative_path: bool) -> eyre::Result<State> {
+    pub fn to_state(
+        &self,
+        settings: &Settings,
+        squash_layers: bool,
+        prefer_relative_path: bool,
+        use_layer_properties: bool,
+    ) -> eyre::Result<State> {
         let mut state = State::default();
-        self.append_to_state(&mut state, squash_layers, prefer_relative_path)?;
+        self.append_to_state(
+            &mut state,
+            settings,
+            None,
+            squash_layers,
+            prefer_relative_path,
+            use_layer_properties,
+        )?;
         Ok(state)
     }
 
@@ -292,9 +704,8 @@ impl Tiled {
     }
 
     pub fn from_path_json(path: Utf8PathBuf) -> eyre::Result<Tiled> {
-        let file = File::open(path.clone())?;
-        let buf_reader = BufReader::new(file);
-        let mut tiled: Tiled = serde_json::from_reader(buf_reader)?;
+        let text = utils::read_text_file(&path)?;
+        let mut tiled: Tiled = serde_json::from_str(&text)?;
 
         tiled.import_xml_tilesets(path.clone())?;
         tiled.file_path = Some(path);
@@ -312,13 +723,15 @@ impl Tiled {
             Some(extension) => match extension.to_ascii_lowercase().as_str() {
                 "tmx" | "xml" => Self::from_path_xml(path),
                 "tmj" | "json" => Self::from_path_json(path),
-                extension => eyre::bail!(
-                    "Tiled extension '.{}' unknown (.tmx, .xml, .tmj and .json supported)",
-                    extension
-                ),
+                extension => {
+                    eyre::bail!(
+                        "Tiled extension '.{}' unknown (.tmx, .xml, .tmj and .json supported)",
+                        extension
+                    );
+                }
             },
             None => {
-                eyre::bail!("Tiled file has no extension (.tmx, .xml, .tmj and .json supported)")
+                eyre::bail!("Tiled file has no extension (.tmx, .xml, .tmj and .json supported)");
             }
         }
     }
@@ -381,4 +794,149 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn import_out_of_range_values() -> eyre::Result<()> {
+        let map_json = |opacity: &str, tintcolor: &str| {
+            format!(
+                r##"{{
+                    "backgroundcolor": "#102030", "height": 1, "width": 1, "infinite": false,
+                    "tileheight": 8, "tilewidth": 8, "tilesets": [],
+                    "layers": [{{
+                        "data": [0], "height": 1, "width": 1, "id": 1, "name": "Layer",
+                        "opacity": {opacity}, "tintcolor": "{tintcolor}", "visible": true
+                    }}]
+                }}"##
+            )
+        };
+        let import_opacity = |opacity: &str| -> eyre::Result<Option<f32>> {
+            let tiled: Tiled = serde_json::from_str(&map_json(opacity, "#ffffff"))?;
+            let mut state = State::default();
+            let map_id =
+                tiled.append_to_state(&mut state, &Settings::default(), None, false, true, true)?;
+            let map = state
+                .maps
+                .get_by_id(map_id)
+                .ok_or(eyre!("Missing imported map"))?;
+            Ok(map.tiles.layer_opacity(0))
+        };
+
+        // Out of range opacities are clamped
+        assert_eq!(import_opacity("0.25")?, Some(0.25));
+        assert_eq!(import_opacity("1.5")?, None);
+        assert_eq!(import_opacity("-0.5")?, Some(0.0));
+
+        // Non-finite opacities can't come from JSON, but are rejected on import
+        let mut layer: TiledLayer = serde_json::from_str(
+            r#"{"data": [0], "height": 1, "width": 1, "id": 1, "name": "Layer", "opacity": 1.0, "visible": true}"#,
+        )?;
+        for opacity in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+            layer.opacity = opacity;
+            let e = convert_layer_opacity(&layer)
+                .err()
+                .ok_or(eyre!("Expected an error for opacity {}", opacity))?;
+            assert!(e.to_string().contains("invalid opacity"));
+        }
+
+        // Malformed color components are rejected, including a `+` sign, which
+        // would otherwise parse, and non-ASCII characters, which would otherwise
+        // be split when slicing components
+        for tintcolor in ["#ff+f00", "#gg0000", "#f\u{20ac}00", "#ff00ff0\u{e9}"] {
+            assert!(serde_json::from_str::<Tiled>(&map_json("1.0", tintcolor)).is_err());
+        }
+        let tiled: Tiled = serde_json::from_str(&map_json("1.0", "#80ff0000"))?;
+        assert_eq!(
+            tiled.layers.first().and_then(|l| l.tintcolor),
+            Some(TiledColor {
+                r: 255,
+                g: 0,
+                b: 0,
+                a: 128
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn layer_name_from_properties() -> eyre::Result<()> {
+        let json = r#"{
+            "data": [0], "height": 1, "width": 1, "id": 1,
+            "name": "Ground-c2", "opacity": 1.0, "visible": true,
+            "properties": [
+                {"name": "com.mountaintiles.layer_name", "type": "string", "value": "Ground"},
+                {"name": "com.mountaintiles.palette_index", "type": "int", "value": 2}
+            ]
+        }"#;
+        let layer: TiledLayer = serde_json::from_str(json)?;
+        assert_eq!(layer.import_name(true), "Ground");
+        assert_eq!(layer.import_name(false), "Ground-c2");
+        assert_eq!(
+            layer.property(PALETTE_INDEX_PROPERTY),
+            Some("2".to_string())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn append_layers_to_existing_map() -> eyre::Result<()> {
+        let map_json = |tile_size: u32, layer_name: &str| {
+            format!(
+                r##"{{
+                    "height": 1, "width": 1, "infinite": false,
+                    "tileheight": {tile_size}, "tilewidth": {tile_size}, "tilesets": [],
+                    "layers": [{{
+                        "data": [0], "height": 1, "width": 1, "id": 1,
+                        "name": "{layer_name}", "opacity": 1.0, "visible": true
+                    }}]
+                }}"##
+            )
+        };
+        let mut state = State::default();
+        let base: Tiled = serde_json::from_str(&map_json(8, "Base"))?;
+        let map_id =
+            base.append_to_state(&mut state, &Settings::default(), None, false, true, true)?;
+        let map_count = state.maps.iter().count();
+
+        // Matching map is added as new layers at the top of the existing map
+        let top: Tiled = serde_json::from_str(&map_json(8, "Top"))?;
+        let layer_ids = top.append_layers_to_map(
+            &mut state,
+            &Settings::default(),
+            None,
+            map_id,
+            false,
+            true,
+            true,
+        )?;
+        assert_eq!(layer_ids.len(), 1);
+        assert_eq!(state.maps.iter().count(), map_count);
+        let map = state
+            .maps
+            .get_by_id(map_id)
+            .ok_or(eyre!("Missing imported map"))?;
+        let names: Vec<String> = map.tiles.layers().map(|l| l.name()).collect();
+        assert_eq!(names, vec!["Top", "Base"]);
+
+        // Mismatched tile size is rejected, leaving the map unchanged
+        let mismatched: Tiled = serde_json::from_str(&map_json(16, "Mismatched"))?;
+        assert!(
+            mismatched
+                .append_layers_to_map(
+                    &mut state,
+                    &Settings::default(),
+                    None,
+                    map_id,
+                    false,
+                    true,
+                    true
+                )
+                .is_err()
+        );
+        let map = state
+            .maps
+            .get_by_id(map_id)
+            .ok_or(eyre!("Missing imported map"))?;
+        assert_eq!(map.tiles.layers().count(), 2);
+        Ok(())
+    }
 }
@@ -1,18 +1,27 @@
 use std::fmt::Display;
 use std::io::BufWriter;
-use std::{fs::File, io::BufReader, num::ParseIntError};
+use std::{fs::File, num::ParseIntError};
 
 use crate::data::tilesets::TilesetMode;
 use crate::{
     app::maps::MapEditing,
+    data::maps::Maps,
     data::palette::{Palette, PaletteIndex},
     data::tiled::TiledExportSettings,
-    data::tiles::{Tiles, layer_tiles::LayerTiles, tile_color::TileColor},
-    data::tilesets::Tileset,
+    data::tiles::{
+        Tiles, layer_storage::CHUNK_SIZE, layer_tiles::LayerTiles, tile_color::TileColor,
+    },
     data::tilesets::Tilesets,
-    geom::u32size2::u32size2,
+    data::tilesets::{Tileset, TilesetId},
+    geom::i32pos2::I32Pos2,
+    geom::u32pos2::{U32Pos2, u32pos2},
+    geom::u32rect::U32Rect,
+    geom::u32size2::{U32Size2, u32size2},
     tiled::tiled_color::TiledColor,
-    tiled::tiled_json::{Tiled, TiledLayer, TiledTileset},
+    tiled::tiled_json::{
+        LAYER_INDEX_PROPERTY, LAYER_NAME_PROPERTY, LAYER_TINT_PROPERTY, PALETTE_INDEX_PROPERTY,
+        Tiled, TiledChunk, TiledLayer, TiledProperty, TiledTileset,
+    },
     ui::file_dialog,
     utils,
 };
@@ -26,7 +35,17 @@ use serde::{Deserialize, Serialize};
 pub const TSX_VERSION: &str = "1.10";
 pub const TMX_VERSION: &str = "1.10";
 pub const TILED_VERSION: &str = "1.11.0";
+
+/// Tileset property used on export to record a [`TilesetMode`] that can't be
+/// represented in Tiled, so it can be restored on import
+pub const TILESET_MODE_PROPERTY: &str = "com.mountaintiles.tileset_mode";
+
+/// The value of [`TILESET_MODE_PROPERTY`] for [`TilesetMode::ForegroundBackground`],
+/// which is otherwise exported as [`TilesetMode::TransparentBackground`]
+const FOREGROUND_BACKGROUND_MODE: &str = "foreground_background";
+
 pub const INFINITE_FALSE: u32 = 0;
+pub const INFINITE_TRUE: u32 = 1;
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct TilesetReferenceXml {
@@ -151,7 +170,7 @@ pub struct TiledXml {
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct LayerXml {
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub properties: Option<LayerPropertiesXml>,
+    pub properties: Option<PropertiesXml>,
 
     pub data: LayerDataXml,
 
@@ -181,13 +200,13 @@ pub struct LayerXml {
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
-pub struct LayerPropertiesXml {
+pub struct PropertiesXml {
     #[serde(default)]
-    pub property: Vec<LayerPropertyXml>,
+    pub property: Vec<PropertyXml>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
-pub struct LayerPropertyXml {
+pub struct PropertyXml {
     #[serde(rename = "@name")]
     pub name: String,
 
@@ -205,8 +224,91 @@ pub struct LayerDataXml {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub compression: Option<String>,
 
-    #[serde(rename = "$value")]
+    /// The csv tile data, for finite maps
+    #[serde(rename = "$text")]
+    #[serde(default)]
     pub contents: String,
+
+    /// The tile data as chunks, for infinite maps
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub chunk: Vec<LayerChunkXml>,
+}
+
+/// A rectangular chunk of the tile data for a layer in an infinite map.
+/// Chunk positions are in tiles, and may be negative.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LayerChunkXml {
+    #[serde(rename = "@x")]
+    pub x: i32,
+    #[serde(rename = "@y")]
+    pub y: i32,
+    #[serde(rename = "@width")]
+    pub width: u32,
+    #[serde(rename = "@height")]
+    pub height: u32,
+
+    #[serde(rename = "$text")]
+    #[serde(default)]
+    pub contents: String,
+}
+
+/// Split the global tile ids of a layer in an infinite map, with the top left cell at
+/// `origin` in Tiled's coordinates, into chunks of [`CHUNK_SIZE`] tiles, clipped to the
+/// map. Empty chunks are skipped, apart from the top left and bottom right chunks when
+/// `include_corners` is true. Tiled finds the map bounds from the chunks, so including the
+/// corners in one layer preserves the map's origin and size on reimport.
+fn gids_to_chunks(
+    gids: &[u32],
+    map_size: U32Size2,
+    origin: I32Pos2,
+    include_corners: bool,
+) -> Vec<LayerChunkXml> {
+    let chunks_w = map_size.w.div_ceil(CHUNK_SIZE);
+    let chunks_h = map_size.h.div_ceil(CHUNK_SIZE);
+    let mut chunks = vec![];
+    for cy in 0..chunks_h {
+        for cx in 0..chunks_w {
+            let x0 = cx * CHUNK_SIZE;
+            let y0 = cy * CHUNK_SIZE;
+            let width = CHUNK_SIZE.min(map_size.w - x0);
+            let height = CHUNK_SIZE.min(map_size.h - y0);
+            let chunk_gids: Vec<u32> = (y0..y0 + height)
+                .flat_map(|y| {
+                    (x0..x0 + width).map(move |x| {
+                        gids.get(y as usize * map_size.w as usize + x as usize)
+                            .copied()
+                            .unwrap_or(0)
+                    })
+                })
+                .collect();
+            let corner = (cx == 0 && cy == 0) || (cx == chunks_w - 1 && cy == chunks_h - 1);
+            if chunk_gids.iter().any(|gid| *gid != 0) || (include_corners && corner) {
+                chunks.push(LayerChunkXml {
+                    x: origin.x + x0 as i32,
+                    y: origin.y + y0 as i32,
+                    width,
+                    height,
+                    contents: gids_to_csv(&chunk_gids),
+                });
+            }
+        }
+    }
+    chunks
+}
+
+fn gids_to_csv(gids: &[u32]) -> String {
+    gids.iter()
+        .map(|gid| gid.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn parse_csv_data(contents: &str) -> Result<Vec<u32>, ParseIntError> {
+    contents
+        .split(",")
+        .map(|s| s.trim().parse::<u32>())
+        .collect()
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -228,7 +330,19 @@ pub struct TilesetXml {
     #[serde(rename = "@backgroundcolor")]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub backgroundcolor: Option<TiledColor>,
-    pub image: ImageXml,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub properties: Option<PropertiesXml>,
+    /// The image for the whole tileset, or [`None`] for a collection of images,
+    /// where each tile has its own image in [`TilesetXml::tile`]
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<ImageXml>,
+    /// Tiles with their own data, here only used for tiles with their own image,
+    /// as sub-rectangles of one image for a tileset using part of its image
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tile: Vec<TileXml>,
 
     /// The path to the tsx file this data was loaded
     /// from, if known.
@@ -245,6 +359,33 @@ pub struct TilesetXml {
     pub tmx_to_tsx_file_path: Option<Utf8PathBuf>,
 }
 
+/// A tile in a collection of images. Since Tiled 1.9 each tile can use a
+/// sub-rectangle of its image, given in pixels.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TileXml {
+    #[serde(rename = "@id")]
+    pub id: u32,
+    #[serde(rename = "@x")]
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub x: Option<u32>,
+    #[serde(rename = "@y")]
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub y: Option<u32>,
+    #[serde(rename = "@width")]
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(rename = "@height")]
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<ImageXml>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct ImageXml {
     #[serde(rename = "@source")]
@@ -268,15 +409,13 @@ impl TiledXml {
             ));
         }
 
-        if let Some(renderorder) = self.renderorder {
-            if renderorder != RenderOrder::RightDown {
-                return Err(eyre!(
-                    "Unsupported Tiled map render order '{}', only {} is supported.",
-                    renderorder,
-                    RenderOrder::RightDown
-                ));
-            }
-        }
+        // Any render order is accepted - this only changes the order Tiled draws tiles
+        // in, which matters for tiles larger than the map's cells, not the layout of the
+        // tile data, which is always in rows from the top left. MountainTiles draws
+        // cell-sized tiles in its own order, so maps look the same in every render order.
+
+        // For some reason, Tiled uses 1/0 in XML, and true/false in JSON
+        let infinite = self.infinite == 1;
 
         let mut layers = vec![];
         for layer_xml in self.layer.iter() {
@@ -300,15 +439,25 @@ impl TiledXml {
                 ));
             };
 
-            let data_r: Result<Vec<u32>, ParseIntError> = layer_xml
-                .data
-                .contents
-                .split(",")
-                .map(|s| s.trim().parse::<u32>())
-                .collect();
-            let data = data_r?;
+            // Infinite maps have data in chunks, flattened on import, see [`Tiled::to_finite`]
+            let (data, chunks) = if infinite {
+                let mut chunks = vec![];
+                for chunk in layer_xml.data.chunk.iter() {
+                    chunks.push(TiledChunk {
+                        data: parse_csv_data(&chunk.contents)?,
+                        x: chunk.x,
+                        y: chunk.y,
+                        width: chunk.width,
+                        height: chunk.height,
+                    });
+                }
+                (vec![], chunks)
+            } else {
+                (parse_csv_data(&layer_xml.data.contents)?, vec![])
+            };
             let layer = TiledLayer {
                 data,
+                chunks,
                 height: layer_xml.height,
                 width: layer_xml.width,
                 id: layer_xml.id,
@@ -316,6 +465,15 @@ impl TiledXml {
                 opacity: layer_xml.opacity.unwrap_or(1.0),
                 tintcolor: layer_xml.tintcolor,
                 visible: layer_xml.visible.unwrap_or(true),
+                properties: layer_xml
+                    .properties
+                    .iter()
+                    .flat_map(|p| p.property.iter())
+                    .map(|p| TiledProperty {
+                        name: p.name.clone(),
+                        value: serde_json::Value::String(p.value.clone()),
+                    })
+                    .collect(),
             };
             layers.push(layer);
         }
@@ -329,8 +487,6 @@ impl TiledXml {
             tilesets.push(tileset);
         }
 
-        // For some reason, Tiled uses 1/0 in XML, and true/false in JSON
-        let infinite = self.infinite == 1;
         let tiled = Tiled {
             backgroundcolor: self.backgroundcolor,
             height: self.height,
@@ -371,9 +527,8 @@ impl TiledXml {
     }
 
     pub fn from_path_xml(path: Utf8PathBuf) -> eyre::Result<TiledXml> {
-        let file = File::open(path.clone())?;
-        let buf_reader = BufReader::new(file);
-        let mut tiled: TiledXml = quick_xml::de::from_reader(buf_reader)?;
+        let text = utils::read_text_file(&path)?;
+        let mut tiled: TiledXml = quick_xml::de::from_str(&text)?;
         tiled.import_xml_tilesets(path.clone())?;
         tiled.file_path = Some(path);
 
@@ -386,75 +541,46 @@ impl TiledXml {
         tiles: &LayerTiles,
         palette: &Palette,
         tilesets: &Tilesets,
+    ) -> eyre::Result<TiledXml> {
+        let tileset_parts = TilesetPartsXml::from_tilesets(tilesets)?;
+        Self::from_map_parts_with_tilesets(path, settings, tiles, palette, tileset_parts)
+    }
+
+    /// As for [`TiledXml::from_map_parts`], but using already converted tilesets, so
+    /// that the same tilesets (and `firstgid`s) can be shared by multiple maps.
+    fn from_map_parts_with_tilesets(
+        path: Utf8PathBuf,
+        settings: &TiledExportSettings,
+        tiles: &LayerTiles,
+        palette: &Palette,
+        tileset_parts: TilesetPartsXml,
     ) -> eyre::Result<TiledXml> {
         let map_size = tiles.map_size();
         let tile_size = tiles.tile_size();
 
-        let mut firstgid = 1;
-        let mut tileset_refs = vec![];
-        let mut firstgids = HashMap::new();
-        let mut xml_tilesets = vec![];
-
-        let mut tsx_file_names = HashSet::new();
-
-        // Convert tileset
-        for tileset in tilesets.iter() {
-            // First the reference
-            let source_base = sanitize_filename::sanitize(tileset.name.clone());
-            let source = format!("{}.{}", source_base, file_dialog::TSX_EXTENSION);
-
-            if !tsx_file_names.insert(source.clone()) {
-                bail!(
-                    "Cannot export map as Tiled .tmx format.\nThere is more than one tileset named '{}'\n To export as Tiled, edit tileset names to be unique.\nThis is to allow each tileset to be saved as 'name.tsx'.",
-                    tileset.name
-                )
-            }
-
-            tileset_refs.push(TilesetReferenceXml {
-                firstgid,
-                source: source.to_string(),
-            });
-            firstgids.insert(tileset.id(), firstgid);
-            firstgid += tileset.size_in_tiles.area();
-
-            // Now everything for the .tsx file
-            let name = tileset.name.clone();
-
-            let pixel_size = tileset.size_in_tiles * tileset.tile_size;
-            let image = ImageXml {
-                source: tileset.path.to_string(),
-                height: pixel_size.h,
-                width: pixel_size.w,
-                trans: None,
-            };
-
-            xml_tilesets.push(TilesetXml {
-                version: TSX_VERSION.to_string(),
-                tiledversion: TILED_VERSION.to_string(),
-                name,
-                tileheight: tileset.tile_size.h,
-                tilewidth: tileset.tile_size.w,
-                tilecount: tileset.size_in_tiles.area(),
-                columns: tileset.size_in_tiles.w,
-                backgroundcolor: tileset.background.map(|c| c.into()),
-                image,
-                // We will put the tsx file alongside the tmx, so the path is just the name of the tsx file
-                tmx_to_tsx_file_path: Some(Utf8PathBuf::from(source)),
-            });
-        }
+        let TilesetPartsXml {
+            tileset_refs,
+            firstgids,
+            xml_tilesets,
+        } = tileset_parts;
 
         // Convert layers
         let mut layers = vec![];
         // Note we can't use our own layer ids, since we need to split layers by color
         let mut layer_id: u32 = 1;
-        for layer in tiles.layers().rev() {
+        for (source_layer_index, layer) in tiles
+            .layers()
+            .rev()
+            .filter(|layer| layer.exported())
+            .enumerate()
+        {
             // Note we go through colors in reverse, so we end up with them in the right order in Tiled.
             // This looks better in tiled, and means the palette doesn't get reversed if we reimport
             for palette_index in (0..palette.len()).rev() {
                 let color = TileColor::Palette {
                     index: PaletteIndex::new(palette_index),
                 };
-                let tile_data: Vec<_> = layer
+                let gids: Vec<u32> = layer
                     .tiles_iter()
                     .map(|tile| {
                         if let Some(tile) = tile
@@ -467,31 +593,47 @@ impl TiledXml {
                             0
                         }
                     })
-                    .map(|i| i.to_string())
                     .collect();
-                let contents = tile_data.join(", ");
 
-                let data = LayerDataXml {
-                    encoding: Some("csv".to_string()),
-                    compression: None,
-                    contents,
+                let data = match tiles.origin() {
+                    Some(origin) => LayerDataXml {
+                        encoding: Some("csv".to_string()),
+                        compression: None,
+                        contents: String::new(),
+                        chunk: gids_to_chunks(&gids, map_size, origin, layers.is_empty()),
+                    },
+                    None => LayerDataXml {
+                        encoding: Some("csv".to_string()),
+                        compression: None,
+                        contents: gids_to_csv(&gids),
+                        chunk: vec![],
+                    },
                 };
 
                 let name = format!("{}-c{}", layer.name(), palette_index);
 
                 let properties = if settings.include_layer_data_as_properties {
-                    Some(LayerPropertiesXml {
-                        property: vec![
-                            LayerPropertyXml {
-                                name: "com.mountaintiles.layer_name".to_string(),
-                                value: layer.name(),
-                            },
-                            LayerPropertyXml {
-                                name: "com.mountaintiles.palette_index".to_string(),
-                                value: format!("{}", palette_index),
-                            },
-                        ],
-                    })
+                    let mut property = vec![
+                        PropertyXml {
+                            name: LAYER_NAME_PROPERTY.to_string(),
+                            value: layer.name(),
+                        },
+                        PropertyXml {
+                            name: LAYER_INDEX_PROPERTY.to_string(),
+                            value: format!("{}", source_layer_index),
+                        },
+                        PropertyXml {
+                            name: PALETTE_INDEX_PROPERTY.to_string(),
+                            value: format!("{}", palette_index),
+                        },
+                    ];
+                    if let Some(tint) = layer.tint() {
+                        property.push(PropertyXml {
+                            name: LAYER_TINT_PROPERTY.to_string(),
+                            value: TiledColor::from(tint).as_hex_string(),
+                        });
+                    }
+                    Some(PropertiesXml { property })
                 } else {
                     None
                 };
@@ -504,9 +646,11 @@ impl TiledXml {
                     id: layer_id,
                     name,
                     opacity: layer.opacity(),
+                    // Tiled applies a single tint to the layer, so combine the
+                    // palette color with any layer tint
                     tintcolor: palette
                         .color_option(PaletteIndex::new(palette_index))
-                        .map(|c| c.into()),
+                        .map(|c| c.with_optional_tint(layer.tint()).into()),
                     visible: Some(layer.visible()),
                 });
                 layer_id += 1;
@@ -528,7 +672,11 @@ impl TiledXml {
             backgroundcolor: Some(tiles.background().into()),
             height: map_size.h,
             width: map_size.w,
-            infinite: INFINITE_FALSE,
+            infinite: if tiles.origin().is_some() {
+                INFINITE_TRUE
+            } else {
+                INFINITE_FALSE
+            },
             layer: layers,
             tileheight: tile_size.h,
             tilewidth: tile_size.w,
@@ -590,6 +738,195 @@ impl TiledXml {
     }
 }
 
+/// The parts of a Tiled export produced from a set of [`Tilesets`] - the references
+/// to include in a `.tmx` file, the `firstgid` of each tileset, and the data for the
+/// `.tsx` files. Each tileset is saved as a `.tsx` file named after the tileset.
+#[derive(Debug, Clone)]
+struct TilesetPartsXml {
+    tileset_refs: Vec<TilesetReferenceXml>,
+    firstgids: HashMap<TilesetId, u32>,
+    xml_tilesets: Vec<TilesetXml>,
+}
+
+impl TilesetPartsXml {
+    fn from_tilesets(tilesets: &Tilesets) -> eyre::Result<TilesetPartsXml> {
+        let mut firstgid = 1;
+        let mut tileset_refs = vec![];
+        let mut firstgids = HashMap::new();
+        let mut xml_tilesets = vec![];
+
+        let mut tsx_file_names = HashSet::new();
+
+        // Convert tileset
+        for tileset in tilesets.iter() {
+            // First the reference
+            let source_base = sanitize_filename::sanitize(tileset.name.clone());
+            let source = format!("{}.{}", source_base, file_dialog::TSX_EXTENSION);
+
+            if !tsx_file_names.insert(source.clone()) {
+                bail!(
+                    "Cannot export map as Tiled .tmx format.\nThere is more than one tileset named '{}'\n To export as Tiled, edit tileset names to be unique.\nThis is to allow each tileset to be saved as 'name.tsx'.",
+                    tileset.name
+                );
+            }
+
+            tileset_refs.push(TilesetReferenceXml {
+                firstgid,
+                source: source.to_string(),
+            });
+            firstgids.insert(tileset.id(), firstgid);
+            firstgid += tileset.tile_count();
+
+            // Now everything for the .tsx file
+            let name = tileset.name.clone();
+
+            let pixel_size = tileset.size_in_tiles * tileset.tile_size;
+            let image = ImageXml {
+                source: tileset.path.to_string(),
+                height: pixel_size.h,
+                width: pixel_size.w,
+                trans: tileset.mode.transparent_color().map(|c| c.into()),
+            };
+
+            // Tiled tilesets always use their whole image, so a tileset using part of
+            // its image is exported as a collection of images, with each tile using
+            // its own sub-rectangle of the shared image
+            let (image, tile) = if tileset.region.is_some() {
+                let tile = (0..tileset.tile_count())
+                    .map(|id| {
+                        let pos = tileset.image_pos(id);
+                        TileXml {
+                            id,
+                            x: Some(pos.x * tileset.tile_size.w),
+                            y: Some(pos.y * tileset.tile_size.h),
+                            width: Some(tileset.tile_size.w),
+                            height: Some(tileset.tile_size.h),
+                            image: Some(image.clone()),
+                        }
+                    })
+                    .collect();
+                (None, tile)
+            } else {
+                (Some(image), vec![])
+            };
+
+            xml_tilesets.push(TilesetXml {
+                version: TSX_VERSION.to_string(),
+                tiledversion: TILED_VERSION.to_string(),
+                name,
+                tileheight: tileset.tile_size.h,
+                tilewidth: tileset.tile_size.w,
+                tilecount: tileset.tile_count(),
+                columns: tileset.column_count(),
+                backgroundcolor: tileset.background.map(|c| c.into()),
+                properties: match tileset.mode {
+                    TilesetMode::ForegroundBackground { .. } => Some(PropertiesXml {
+                        property: vec![PropertyXml {
+                            name: TILESET_MODE_PROPERTY.to_string(),
+                            value: FOREGROUND_BACKGROUND_MODE.to_string(),
+                        }],
+                    }),
+                    _ => None,
+                },
+                image,
+                tile,
+                // We will put the tsx file alongside the tmx, so the path is just the name of the tsx file
+                tmx_to_tsx_file_path: Some(Utf8PathBuf::from(source)),
+            });
+        }
+
+        Ok(TilesetPartsXml {
+            tileset_refs,
+            firstgids,
+            xml_tilesets,
+        })
+    }
+}
+
+/// Tiled data for all the maps in a project, to be exported to a single
+/// directory. Each map is saved as a `.tmx` file named after the map, and
+/// the tilesets are shared by all maps, so each `.tsx` file is only saved once,
+/// and the `firstgid` of each tileset is the same in every map.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TiledProjectXml {
+    /// The maps, each with the `file_path` it will be saved to, and no
+    /// `xml_tilesets` of its own
+    pub maps: Vec<TiledXml>,
+    pub xml_tilesets: Vec<TilesetXml>,
+}
+
+impl TiledProjectXml {
+    pub fn from_project_parts(
+        dir: Utf8PathBuf,
+        settings: &TiledExportSettings,
+        maps: &Maps,
+        palette: &Palette,
+        tilesets: &Tilesets,
+    ) -> eyre::Result<TiledProjectXml> {
+        let tileset_parts = TilesetPartsXml::from_tilesets(tilesets)?;
+        let xml_tilesets = tileset_parts.xml_tilesets.clone();
+
+        let mut tmx_file_names = HashSet::new();
+        let mut tiled_maps = vec![];
+        for map in maps.iter() {
+            let file_name = format!(
+                "{}.{}",
+                sanitize_filename::sanitize(map.name()),
+                file_dialog::TMX_EXTENSION
+            );
+            if !tmx_file_names.insert(file_name.clone()) {
+                bail!(
+                    "Cannot export maps as Tiled .tmx format.\nThere is more than one map named '{}'\n To export as Tiled, edit map names to be unique.\nThis is to allow each map to be saved as 'name.tmx'.",
+                    map.name()
+                );
+            }
+
+            let mut path = dir.clone();
+            path.push(file_name);
+
+            let mut tiled = TiledXml::from_map_parts_with_tilesets(
+                path,
+                settings,
+                map.tiles(),
+                palette,
+                tileset_parts.clone(),
+            )?;
+            // Tilesets are saved once for the whole project
+            tiled.xml_tilesets = vec![];
+            tiled_maps.push(tiled);
+        }
+
+        Ok(TiledProjectXml {
+            maps: tiled_maps,
+            xml_tilesets,
+        })
+    }
+
+    /// Save a `.tmx` file for each map, and if requested in the settings, a `.tsx`
+    /// file for each tileset, all in the directory `dir`.
+    pub fn save(&self, dir: Utf8PathBuf, settings: &TiledExportSettings) -> eyre::Result<()> {
+        for tiled in self.maps.iter() {
+            let path = tiled.file_path.clone().ok_or(eyre!(
+                "Map has missing path - this should not happen, please report a bug."
+            ))?;
+            tiled.save_tmx(path)?;
+        }
+
+        if settings.export_tsx_files {
+            for xml_tileset in self.xml_tilesets.iter() {
+                let mut tileset_path = dir.clone();
+                let relative_path = xml_tileset.tmx_to_tsx_file_path.clone().ok_or(eyre!(
+                    "Tileset has missing path - this should not happen, please report a bug."
+                ))?;
+                tileset_path.push(&relative_path);
+                xml_tileset.save(tileset_path)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
 impl TilesetXml {
     /// Convert to a mountaintiles tileset.
     ///
@@ -638,21 +975,49 @@ impl TilesetXml {
         }
 
         let tile_size = u32size2(self.tilewidth, self.tileheight);
-        let size_in_tiles = u32size2(columns, rows);
+        let (image, region) = self.image_and_region(tile_size)?;
+
+        // Tiled stores the number of columns explicitly, which may be fewer than fit in
+        // the image, so use the image size where it's known, and keep the columns as an
+        // override if they differ
+        let grid_size = u32size2(columns, rows);
+        let size_in_tiles = if tile_size.w > 0 && tile_size.h > 0 {
+            let image_size_in_tiles = u32size2(image.width, image.height) / tile_size;
+            if image_size_in_tiles.w >= columns && image_size_in_tiles.h >= rows {
+                image_size_in_tiles
+            } else {
+                grid_size
+            }
+        } else {
+            grid_size
+        };
 
         // Push the image source - if this is absolute, it replaces the base path, otherwise
         // it is added to the base path, so we have the full path to the image
-        path.push(self.image.source.clone());
-
-        let mode = self
-            .image
+        path.push(image.source.clone());
+
+        let foreground_background = self
+            .properties
+            .iter()
+            .flat_map(|p| p.property.iter())
+            .any(|p| p.name == TILESET_MODE_PROPERTY && p.value == FOREGROUND_BACKGROUND_MODE);
+        let mode = image
             .trans
-            .map(|color| TilesetMode::TransparentBackground {
-                background: color.into(),
+            .map(|color| {
+                if foreground_background {
+                    TilesetMode::ForegroundBackground {
+                        background: color.into(),
+                    }
+                } else {
+                    TilesetMode::TransparentBackground {
+                        background: color.into(),
+                        tolerance: 0,
+                    }
+                }
             })
             .unwrap_or_default();
 
-        let tileset = Tileset::new_with_default_id(
+        let mut tileset = Tileset::new_with_default_id(
             self.name.clone(),
             path,
             tile_size,
@@ -662,10 +1027,65 @@ impl TilesetXml {
             background,
             prefer_relative_path,
         );
+        tileset.region = region;
+        // The columns of a region are those of the grid of tiles, so only need an override
+        // when there is no region
+        tileset.columns = (region.is_none() && size_in_tiles.w != columns).then_some(columns);
+        // Keep the exact tile count, so a partly used last row doesn't have extra tiles
+        tileset.tilecount = (self.tilecount < tileset.grid_size().area()).then_some(self.tilecount);
 
         Ok(tileset)
     }
 
+    /// The image used by the tileset, and the region of it used, in tiles. The region is
+    /// [`None`] for a tileset with one image, and is only supported for a collection of images
+    /// where every tile is a sub-rectangle of the same image, in a grid with
+    /// [`TilesetXml::columns`], as exported for a tileset using part of its image.
+    fn image_and_region(&self, tile_size: U32Size2) -> eyre::Result<(&ImageXml, Option<U32Rect>)> {
+        if let Some(image) = &self.image {
+            return Ok((image, None));
+        }
+
+        let unsupported = || {
+            eyre!(
+                "Unsupported Tiled tileset '{}', a collection of images is only supported when every tile uses part of the same image, in a grid.",
+                self.name
+            )
+        };
+        if tile_size.w == 0 || tile_size.h == 0 || self.columns == 0 {
+            return Err(unsupported());
+        }
+        let first = self.tile.first().ok_or_else(unsupported)?;
+        let image = first.image.as_ref().ok_or_else(unsupported)?;
+
+        // The position of a tile's sub-rectangle in the image, in tiles
+        let image_pos = |tile: &TileXml| -> Option<U32Pos2> {
+            let (x, y) = (tile.x.unwrap_or(0), tile.y.unwrap_or(0));
+            (tile.image.as_ref() == Some(image)
+                && tile.width == Some(tile_size.w)
+                && tile.height == Some(tile_size.h)
+                && x % tile_size.w == 0
+                && y % tile_size.h == 0)
+                .then(|| u32pos2(x / tile_size.w, y / tile_size.h))
+        };
+
+        let origin = image_pos(first).ok_or_else(unsupported)?;
+        let grid_size = u32size2(self.columns, self.tilecount.div_ceil(self.columns));
+        if self.tile.len() != self.tilecount as usize {
+            return Err(unsupported());
+        }
+        for (index, tile) in (0..).zip(self.tile.iter()) {
+            let grid_pos = grid_size.pos_from_linear_index(index);
+            let expected = u32pos2(origin.x + grid_pos.x, origin.y + grid_pos.y);
+            if tile.id != index || image_pos(tile) != Some(expected) {
+                return Err(unsupported());
+            }
+        }
+
+        let max = u32pos2(origin.x + grid_size.w - 1, origin.y + grid_size.h - 1);
+        Ok((image, Some(U32Rect::from_two_pos(origin, max))))
+    }
+
     pub fn add_to_tilesets(
         &self,
         tilesets: &mut Tilesets,
@@ -678,10 +1098,9 @@ impl TilesetXml {
     }
 
     pub fn from_path_xml(path: Utf8PathBuf) -> eyre::Result<TilesetXml> {
-        let file = File::open(path.clone())
+        let text = utils::read_text_file(&path)
             .wrap_err_with(|| format!("Failed to open Tiled .tsx file, expected at {}", path))?;
-        let buf_reader = BufReader::new(file);
-        let tiled: TilesetXml = quick_xml::de::from_reader(buf_reader)?;
+        let tiled: TilesetXml = quick_xml::de::from_str(&text)?;
         Ok(tiled)
     }
 
@@ -700,6 +1119,7 @@ impl TilesetXml {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::data::settings::Settings;
 
     #[test]
     fn import_tiled_xml_matches_json() -> eyre::Result<()> {
@@ -727,15 +1147,1011 @@ mod tests {
             tilecount: 256,
             columns: 16,
             backgroundcolor: None,
-            image: ImageXml {
+            properties: None,
+            image: Some(ImageXml {
                 source: "mountain-tiles.png".to_string(),
                 height: 128,
                 width: 128,
                 trans: None,
-            },
+            }),
+            tile: vec![],
             tmx_to_tsx_file_path: None,
         };
         assert_eq!(tileset_xml, expected);
+
+        // The image has as many columns as are used, so there's no override
+        let tileset = tileset_xml.as_tileset(true, None)?;
+        assert_eq!(tileset.size_in_tiles, u32size2(16, 16));
+        assert_eq!(tileset.columns, None);
+
+        // Fewer columns than fit in the image are kept as an override
+        let narrow_xml = TilesetXml {
+            tilecount: 120,
+            columns: 12,
+            ..tileset_xml
+        };
+        let tileset = narrow_xml.as_tileset(true, None)?;
+        assert_eq!(tileset.size_in_tiles, u32size2(16, 16));
+        assert_eq!(tileset.columns, Some(12));
+        assert_eq!(tileset.grid_size(), u32size2(12, 16));
+        assert_eq!(tileset.tile_count(), 120);
+        Ok(())
+    }
+
+    #[test]
+    fn foreground_background_mode_survives_export_and_import() -> eyre::Result<()> {
+        let background = crate::data::tiles::tile_color::UserColor::new(10, 20, 30, 255);
+        for mode in [
+            TilesetMode::Direct,
+            TilesetMode::TransparentBackground {
+                background,
+                tolerance: 0,
+            },
+            TilesetMode::ForegroundBackground { background },
+        ] {
+            let mut tilesets = Tilesets::default();
+            tilesets.push_tileset(Tileset::new_with_default_id(
+                "tileset".to_string(),
+                "tileset.png".into(),
+                u32size2(8, 8),
+                u32size2(4, 4),
+                mode,
+                None,
+                None,
+                false,
+            ));
+            let parts = TilesetPartsXml::from_tilesets(&tilesets)?;
+            let tileset_xml = parts
+                .xml_tilesets
+                .first()
+                .ok_or(eyre!("No tileset exported"))?;
+            let text = quick_xml::se::to_string_with_root("tileset", tileset_xml)?;
+            let imported: TilesetXml = quick_xml::de::from_str(&text)?;
+            assert_eq!(imported.as_tileset(false, None)?.mode, mode);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn tileset_region_survives_export_and_import() -> eyre::Result<()> {
+        let mut tilesets = Tilesets::default();
+        let tileset = Tileset::new_with_default_id(
+            "tileset".to_string(),
+            "tileset.png".into(),
+            u32size2(8, 8),
+            u32size2(8, 8),
+            TilesetMode::Direct,
+            None,
+            None,
+            false,
+        );
+        let regions = [(
+            "part".to_string(),
+            U32Rect::from_two_pos(u32pos2(2, 1), u32pos2(4, 5)),
+        )];
+        for part in tileset.split(&regions) {
+            tilesets.push_tileset(part);
+        }
+
+        let parts = TilesetPartsXml::from_tilesets(&tilesets)?;
+        let tileset_xml = parts
+            .xml_tilesets
+            .first()
+            .ok_or(eyre!("No tileset exported"))?;
+        assert_eq!(tileset_xml.image, None);
+        assert_eq!(tileset_xml.tile.len(), 15);
+        let last = tileset_xml.tile.last().ok_or(eyre!("No tiles exported"))?;
+        assert_eq!((last.x, last.y), (Some(32), Some(40)));
+
+        let text = quick_xml::se::to_string_with_root("tileset", tileset_xml)?;
+        let imported: TilesetXml = quick_xml::de::from_str(&text)?;
+        let imported = imported.as_tileset(false, None)?;
+        assert_eq!(
+            imported.region,
+            Some(U32Rect::from_two_pos(u32pos2(2, 1), u32pos2(4, 5)))
+        );
+        assert_eq!(imported.size_in_tiles, u32size2(8, 8));
+        assert_eq!(imported.columns, None);
+        assert_eq!(imported.tile_count(), 15);
+        Ok(())
+    }
+
+    #[test]
+    fn import_tileset_xml_partial_last_row() -> eyre::Result<()> {
+        use crate::data::tiles::{TileIndex, TileSource};
+
+        let tileset_xml = TilesetXml {
+            tilecount: 250,
+            ..TilesetXml::from_path_xml("test-data/mountain-tiles.tsx".into())?
+        };
+        let tileset = tileset_xml.as_tileset(true, None)?;
+        assert_eq!(tileset.columns, None);
+        assert_eq!(tileset.tilecount, Some(250));
+        assert_eq!(tileset.tile_count(), 250);
+
+        let mut tilesets = Tilesets::default();
+        let id = tilesets.push_tileset(tileset);
+        let source = |index| TileSource {
+            tileset_id: id,
+            tile_index: TileIndex::new(index),
+        };
+        assert!(tilesets.is_tile_source_available(source(249)));
+        assert!(!tilesets.is_tile_source_available(source(250)));
+        Ok(())
+    }
+
+    #[test]
+    fn export_project_shares_tilesets() -> eyre::Result<()> {
+        use crate::data::{maps::Map, tiles::tile_color::UserColor};
+
+        let tilesets = Tilesets::builtin_tilesets();
+        let palette = Palette::new(vec![UserColor::WHITE]);
+        let settings = TiledExportSettings::default();
+        let mut maps = Maps::default();
+        for name in ["First", "Second"] {
+            maps.push_map(Map::new_with_layer(
+                name.to_string(),
+                u32size2(4, 4),
+                u32size2(16, 16),
+                UserColor::BLACK,
+            ));
+        }
+
+        let temp = assert_fs::TempDir::new()?;
+        let dir = Utf8PathBuf::from_path_buf(temp.path().to_path_buf())
+            .map_err(|_| eyre!("Can't convert temp dir to utf8"))?;
+
+        let project = TiledProjectXml::from_project_parts(
+            dir.clone(),
+            &settings,
+            &maps,
+            &palette,
+            &tilesets,
+        )?;
+
+        assert_eq!(project.maps.len(), 2);
+        assert_eq!(project.xml_tilesets.len(), tilesets.iter().count());
+        for tiled in project.maps.iter() {
+            assert!(tiled.xml_tilesets.is_empty());
+            assert_eq!(
+                tiled.tileset,
+                project
+                    .maps
+                    .first()
+                    .map(|m| m.tileset.clone())
+                    .unwrap_or_default()
+            );
+        }
+
+        project.save(dir.clone(), &settings)?;
+        assert!(dir.join("First.tmx").is_file());
+        assert!(dir.join("Second.tmx").is_file());
+        for xml_tileset in project.xml_tilesets.iter() {
+            let tsx = xml_tileset
+                .tmx_to_tsx_file_path
+                .clone()
+                .ok_or(eyre!("Missing tsx path"))?;
+            assert!(dir.join(tsx).is_file());
+        }
+
+        // Map names must be unique to give unique file names
+        maps.push_map(Map::new_with_layer(
+            "First".to_string(),
+            u32size2(4, 4),
+            u32size2(16, 16),
+            UserColor::BLACK,
+        ));
+        assert!(
+            TiledProjectXml::from_project_parts(dir, &settings, &maps, &palette, &tilesets)
+                .is_err()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn export_and_import_restores_layers() -> eyre::Result<()> {
+        use crate::{
+            data::{
+                state::State,
+                tiles::{Tile, TileIndex, TileSource, tile_color::UserColor},
+            },
+            geom::{transform::Transform, u32pos2::u32pos2},
+        };
+
+        let tilesets = Tilesets::builtin_tilesets();
+        let tileset_id = tilesets
+            .iter()
+            .next()
+            .map(|t| t.id())
+            .ok_or(eyre!("No builtin tileset"))?;
+        // Include a duplicate color, to check palette indices are restored
+        let palette = Palette::new(vec![
+            UserColor::WHITE,
+            UserColor::new(255, 0, 0, 255),
+            UserColor::WHITE,
+        ]);
+        let tile = |index: u32, palette_index: u32, transform: Transform| {
+            Some(Tile {
+                source: TileSource {
+                    tileset_id,
+                    tile_index: TileIndex::new(index),
+                },
+                color: TileColor::from_palette_index(PaletteIndex::new(palette_index)),
+                transform,
+            })
+        };
+
+        let map_size = u32size2(3, 2);
+        let mut tiles = LayerTiles::new(UserColor::BLACK, u32size2(8, 8), map_size);
+        tiles.insert_layer(0, "Ground", true, None);
+        tiles.insert_layer(1, "Top", true, Some(0.5));
+        tiles.set_tile(0, u32pos2(0, 0), tile(1, 0, Transform::None));
+        tiles.set_tile(0, u32pos2(1, 0), tile(2, 1, Transform::Rotate90));
+        tiles.set_tile(0, u32pos2(2, 1), tile(3, 2, Transform::MirrorX));
+        tiles.set_tile(1, u32pos2(0, 1), tile(4, 1, Transform::None));
+
+        let temp = assert_fs::TempDir::new()?;
+        let dir = Utf8PathBuf::from_path_buf(temp.path().to_path_buf())
+            .map_err(|_| eyre!("Can't convert temp dir to utf8"))?;
+        let path = dir.join("map.tmx");
+        let settings = TiledExportSettings::default();
+        TiledXml::from_map_parts(path.clone(), &settings, &tiles, &palette, &tilesets)?
+            .save(path.clone(), &settings)?;
+
+        let tiled = Tiled::from_path(path)?;
+        assert_eq!(tiled.layers.len(), 6);
+
+        let mut state = State::default();
+        state.resources.palette = palette.clone();
+        let map_id =
+            tiled.append_to_state(&mut state, &Settings::default(), None, false, true, true)?;
+        let imported = &state
+            .maps
+            .get_by_id(map_id)
+            .ok_or(eyre!("Missing imported map"))?
+            .tiles;
+
+        assert_eq!(state.resources.palette, palette);
+        assert_eq!(imported.layer_count(), 2);
+        let layers: Vec<_> = imported
+            .layers()
+            .map(|l| (l.name(), l.visible(), l.opacity()))
+            .collect();
+        assert_eq!(
+            layers,
+            vec![
+                ("Ground".to_string(), true, None),
+                ("Top".to_string(), true, Some(0.5))
+            ]
+        );
+
+        // Tileset ids may differ after import, so compare everything else
+        let tileset_name = |tile: &Tile, tilesets: &Tilesets| {
+            tilesets
+                .get_by_id(tile.source.tileset_id)
+                .map(|t| t.name.clone())
+        };
+        for layer_index in 0..2 {
+            for y in 0..map_size.h {
+                for x in 0..map_size.w {
+                    let pos = u32pos2(x, y);
+                    let expected = tiles.tile(layer_index, pos);
+                    let actual = imported.tile(layer_index, pos);
+                    assert_eq!(
+                        expected.map(|t| (t.source.tile_index, t.color, t.transform)),
+                        actual.map(|t| (t.source.tile_index, t.color, t.transform))
+                    );
+                    if let (Some(expected), Some(actual)) = (expected, actual) {
+                        assert_eq!(
+                            tileset_name(&expected, &tilesets),
+                            tileset_name(&actual, state.resources.tilesets())
+                        );
+                    }
+                }
+            }
+        }
+
+        // Separate layers with the same name stay separate, keeping their own
+        // visibility and opacity
+        let mut same_names = LayerTiles::new(UserColor::BLACK, u32size2(8, 8), map_size);
+        same_names.insert_layer(0, "Walls", true, None);
+        same_names.insert_layer(1, "Walls", false, Some(0.25));
+        same_names.set_tile(0, u32pos2(0, 0), tile(1, 0, Transform::None));
+        same_names.set_tile(1, u32pos2(1, 0), tile(2, 1, Transform::None));
+        let same_names_path = dir.join("same_names.tmx");
+        TiledXml::from_map_parts(
+            same_names_path.clone(),
+            &settings,
+            &same_names,
+            &palette,
+            &tilesets,
+        )?
+        .save(same_names_path.clone(), &settings)?;
+        let mut state = State::default();
+        state.resources.palette = palette.clone();
+        let map_id = Tiled::from_path(same_names_path)?.append_to_state(
+            &mut state,
+            &Settings::default(),
+            None,
+            false,
+            true,
+            true,
+        )?;
+        let imported = &state
+            .maps
+            .get_by_id(map_id)
+            .ok_or(eyre!("Missing imported map"))?
+            .tiles;
+        let layers: Vec<_> = imported
+            .layers()
+            .map(|l| (l.name(), l.visible(), l.opacity()))
+            .collect();
+        assert_eq!(
+            layers,
+            vec![
+                ("Walls".to_string(), true, None),
+                ("Walls".to_string(), false, Some(0.25))
+            ]
+        );
+        for layer_index in 0..2 {
+            for pos in [u32pos2(0, 0), u32pos2(1, 0)] {
+                assert_eq!(
+                    same_names
+                        .tile(layer_index, pos)
+                        .map(|t| t.source.tile_index),
+                    imported.tile(layer_index, pos).map(|t| t.source.tile_index)
+                );
+            }
+        }
+
+        // Without using properties, we get one layer per color and layer
+        let mut state = State::default();
+        let map_id =
+            tiled.append_to_state(&mut state, &Settings::default(), None, false, true, false)?;
+        let imported = &state
+            .maps
+            .get_by_id(map_id)
+            .ok_or(eyre!("Missing imported map"))?
+            .tiles;
+        assert_eq!(imported.layer_count(), 6);
+
+        Ok(())
+    }
+
+    #[test]
+    fn layer_tint_round_trip() -> eyre::Result<()> {
+        use crate::{
+            data::{
+                state::State,
+                tiles::{Tile, TileIndex, TileSource, tile_color::UserColor},
+            },
+            geom::{transform::Transform, u32pos2::u32pos2},
+        };
+
+        let tilesets = Tilesets::builtin_tilesets();
+        let tileset_id = tilesets
+            .iter()
+            .next()
+            .map(|t| t.id())
+            .ok_or(eyre!("No builtin tileset"))?;
+        let red = UserColor::new(255, 0, 0, 255);
+        let palette = Palette::new(vec![UserColor::WHITE, red]);
+        let tint = UserColor::new(128, 64, 255, 255);
+
+        let map_size = u32size2(2, 1);
+        let mut tiles = LayerTiles::new(UserColor::BLACK, u32size2(8, 8), map_size);
+        let layer_id = tiles.insert_layer(0, "Tinted", true, None);
+        tiles.set_layer_tint(layer_id, Some(tint));
+        for (x, palette_index) in [0, 1].into_iter().enumerate() {
+            tiles.set_tile(
+                0,
+                u32pos2(x as u32, 0),
+                Some(Tile {
+                    source: TileSource {
+                        tileset_id,
+                        tile_index: TileIndex::new(0),
+                    },
+                    color: TileColor::from_palette_index(PaletteIndex::new(palette_index)),
+                    transform: Transform::None,
+                }),
+            );
+        }
+
+        let temp = assert_fs::TempDir::new()?;
+        let dir = Utf8PathBuf::from_path_buf(temp.path().to_path_buf())
+            .map_err(|_| eyre!("Can't convert temp dir to utf8"))?;
+        let path = dir.join("map.tmx");
+        let settings = TiledExportSettings::default();
+        TiledXml::from_map_parts(path.clone(), &settings, &tiles, &palette, &tilesets)?
+            .save(path.clone(), &settings)?;
+
+        // Tiled sees the palette colors combined with the layer tint
+        let tiled = Tiled::from_path(path)?;
+        let tintcolors: Vec<_> = tiled
+            .layers
+            .iter()
+            .map(|layer| layer.tintcolor.map(UserColor::from))
+            .collect();
+        assert_eq!(
+            tintcolors,
+            vec![Some(red.tinted(tint)), Some(UserColor::WHITE.tinted(tint))]
+        );
+
+        // Using properties restores the tint and the original palette colors
+        let mut state = State::default();
+        state.resources.palette = palette.clone();
+        let map_id =
+            tiled.append_to_state(&mut state, &Settings::default(), None, false, true, true)?;
+        let imported = &state
+            .maps
+            .get_by_id(map_id)
+            .ok_or(eyre!("Missing imported map"))?
+            .tiles;
+        assert_eq!(state.resources.palette, palette);
+        assert_eq!(imported.layer_count(), 1);
+        assert_eq!(imported.layer_tint(0), Some(tint));
+        for pos in tiles.map_positions() {
+            assert_eq!(
+                imported.tile(0, pos).map(|t| t.color),
+                tiles.tile(0, pos).map(|t| t.color)
+            );
+        }
+
+        // Without properties, the tinted colors are used for tiles instead
+        let mut state = State::default();
+        state.resources.palette = palette.clone();
+        let map_id =
+            tiled.append_to_state(&mut state, &Settings::default(), None, false, true, false)?;
+        let imported = &state
+            .maps
+            .get_by_id(map_id)
+            .ok_or(eyre!("Missing imported map"))?
+            .tiles;
+        assert!(imported.layers().all(|layer| layer.tint().is_none()));
+        let palette = state.resources.palette();
+        assert!(palette.colors().any(|c| *c == red.tinted(tint)));
+
+        // With properties, but a palette where the recorded colors don't match, the
+        // tinted colors are also used, without restoring the tint, so tiles aren't
+        // tinted twice
+        let mut state = State::default();
+        state.resources.palette = Palette::new(vec![UserColor::new(0, 0, 255, 255), red]);
+        let map_id =
+            tiled.append_to_state(&mut state, &Settings::default(), None, false, true, true)?;
+        let imported = &state
+            .maps
+            .get_by_id(map_id)
+            .ok_or(eyre!("Missing imported map"))?
+            .tiles;
+        assert_eq!(imported.layer_count(), 1);
+        assert_eq!(imported.layer_tint(0), None);
+        let palette = state.resources.palette();
+        let colors: Vec<_> = tiles
+            .map_positions()
+            .map(|pos| {
+                imported.tile(0, pos).and_then(|t| match t.color {
+                    TileColor::Palette { index } => palette.color_option(index),
+                    _ => None,
+                })
+            })
+            .collect();
+        assert_eq!(
+            colors,
+            vec![Some(UserColor::WHITE.tinted(tint)), Some(red.tinted(tint))]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn recolored_transformed_tiles_survive_color_split() -> eyre::Result<()> {
+        use crate::{
+            data::{
+                state::State,
+                tiles::{Tile, TileIndex, TileSource, tile_color::UserColor},
+            },
+            geom::{transform::Transform, u32pos2::u32pos2},
+        };
+
+        let tilesets = Tilesets::builtin_tilesets();
+        let tileset_id = tilesets
+            .iter()
+            .next()
+            .map(|t| t.id())
+            .ok_or(eyre!("No builtin tileset"))?;
+        let palette = Palette::new(vec![UserColor::WHITE, UserColor::new(0, 255, 0, 255)]);
+        let transforms = [
+            Transform::None,
+            Transform::Rotate90,
+            Transform::Rotate180,
+            Transform::Rotate270,
+            Transform::MirrorX,
+            Transform::MirrorXRotate90,
+            Transform::MirrorXRotate180,
+            Transform::MirrorXRotate270,
+        ];
+
+        // Transform tiles, then recolor them, alternating palette colors so each
+        // color layer in the export contains some of the transformed tiles
+        let map_size = u32size2(transforms.len() as u32, 1);
+        let mut tiles = LayerTiles::new(UserColor::BLACK, u32size2(8, 8), map_size);
+        tiles.insert_layer(0, "Layer", true, None);
+        for (x, transform) in transforms.iter().enumerate() {
+            let tile = Tile {
+                source: TileSource {
+                    tileset_id,
+                    tile_index: TileIndex::new(x as u32),
+                },
+                color: TileColor::Default,
+                transform: Transform::None,
+            }
+            .with_exact_transform(*transform)
+            .with_color(TileColor::from_palette_index(PaletteIndex::new(
+                x as u32 % 2,
+            )));
+            assert_eq!(tile.transform, *transform);
+            tiles.set_tile(0, u32pos2(x as u32, 0), Some(tile));
+        }
+
+        let temp = assert_fs::TempDir::new()?;
+        let dir = Utf8PathBuf::from_path_buf(temp.path().to_path_buf())
+            .map_err(|_| eyre!("Can't convert temp dir to utf8"))?;
+        let path = dir.join("map.tmx");
+        let settings = TiledExportSettings::default();
+        TiledXml::from_map_parts(path.clone(), &settings, &tiles, &palette, &tilesets)?
+            .save(path.clone(), &settings)?;
+        let tiled = Tiled::from_path(path)?;
+
+        // Check both recombining the color layers using properties, and importing
+        // one layer per color, where each tile is on the layer for its color
+        for use_layer_properties in [true, false] {
+            let mut state = State::default();
+            state.resources.palette = palette.clone();
+            let map_id = tiled.append_to_state(
+                &mut state,
+                &Settings::default(),
+                None,
+                false,
+                true,
+                use_layer_properties,
+            )?;
+            let imported = &state
+                .maps
+                .get_by_id(map_id)
+                .ok_or(eyre!("Missing imported map"))?
+                .tiles;
+            for (x, transform) in transforms.iter().enumerate() {
+                let pos = u32pos2(x as u32, 0);
+                let actual = (0..imported.layer_count())
+                    .filter_map(|layer_index| imported.tile(layer_index, pos))
+                    .map(|t| (t.source.tile_index, t.color, t.transform))
+                    .collect::<Vec<_>>();
+                assert_eq!(
+                    actual,
+                    vec![(
+                        TileIndex::new(x as u32),
+                        TileColor::from_palette_index(PaletteIndex::new(x as u32 % 2)),
+                        *transform
+                    )]
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn import_same_named_images_in_different_dirs() -> eyre::Result<()> {
+        use crate::data::state::State;
+
+        let temp = assert_fs::TempDir::new()?;
+        let dir = Utf8PathBuf::from_path_buf(temp.path().to_path_buf())
+            .map_err(|_| eyre!("Can't convert temp dir to utf8"))?;
+
+        // Two maps, each with a tileset using an image called "tiles.png" in its own dir
+        let tsx = r#"<?xml version="1.0" encoding="UTF-8"?>
+<tileset version="1.10" tiledversion="1.11.0" name="tiles" tilewidth="8" tileheight="8" tilecount="256" columns="16">
+ <image source="tiles.png" width="128" height="128"/>
+</tileset>
+"#;
+        let tmx = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" tiledversion="1.11.0" orientation="orthogonal" renderorder="right-down" width="2" height="1" tilewidth="8" tileheight="8" infinite="0" nextlayerid="2" nextobjectid="1">
+ <tileset firstgid="1" source="tiles.tsx"/>
+ <layer id="1" name="layer" width="2" height="1">
+  <data encoding="csv">
+1,2
+</data>
+ </layer>
+</map>
+"#;
+        let mut state = State::default();
+        let tileset_count = state.resources.tilesets().len();
+        let import = |state: &mut State, map_dir: &str| -> eyre::Result<()> {
+            let map_dir = dir.join(map_dir);
+            std::fs::create_dir_all(&map_dir)?;
+            std::fs::write(map_dir.join("tiles.tsx"), tsx)?;
+            std::fs::write(map_dir.join("map.tmx"), tmx)?;
+            Tiled::from_path(map_dir.join("map.tmx"))?.append_to_state(
+                state,
+                &Settings::default(),
+                None,
+                false,
+                false,
+                true,
+            )?;
+            Ok(())
+        };
+
+        import(&mut state, "a")?;
+        import(&mut state, "b")?;
+        assert_eq!(state.resources.tilesets().len(), tileset_count + 2);
+
+        // Importing a map using the same image again reuses its tileset
+        import(&mut state, "a")?;
+        assert_eq!(state.resources.tilesets().len(), tileset_count + 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn import_legacy_layer_properties() -> eyre::Result<()> {
+        use crate::data::state::State;
+
+        let temp = assert_fs::TempDir::new()?;
+        let dir = Utf8PathBuf::from_path_buf(temp.path().to_path_buf())
+            .map_err(|_| eyre!("Can't convert temp dir to utf8"))?;
+
+        // Exported before the layer index property, so split layers only record their
+        // source layer name and palette index
+        let tsx = r#"<?xml version="1.0" encoding="UTF-8"?>
+<tileset version="1.10" tiledversion="1.11.0" name="tiles" tilewidth="8" tileheight="8" tilecount="256" columns="16">
+ <image source="tiles.png" width="128" height="128"/>
+</tileset>
+"#;
+        let tmx = r##"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" tiledversion="1.11.0" orientation="orthogonal" renderorder="right-down" width="2" height="1" tilewidth="8" tileheight="8" infinite="0" nextlayerid="4" nextobjectid="1">
+ <tileset firstgid="1" source="tiles.tsx"/>
+ <layer id="1" name="Top-c1" width="2" height="1" tintcolor="#ff0000">
+  <properties>
+   <property name="com.mountaintiles.layer_name" value="Top"/>
+   <property name="com.mountaintiles.palette_index" value="1"/>
+  </properties>
+  <data encoding="csv">
+0,2
+</data>
+ </layer>
+ <layer id="2" name="Top-c0" width="2" height="1" tintcolor="#ffffff">
+  <properties>
+   <property name="com.mountaintiles.layer_name" value="Top"/>
+   <property name="com.mountaintiles.palette_index" value="0"/>
+  </properties>
+  <data encoding="csv">
+1,0
+</data>
+ </layer>
+ <layer id="3" name="Ground-c0" width="2" height="1" tintcolor="#ffffff">
+  <properties>
+   <property name="com.mountaintiles.layer_name" value="Ground"/>
+   <property name="com.mountaintiles.palette_index" value="0"/>
+  </properties>
+  <data encoding="csv">
+3,3
+</data>
+ </layer>
+</map>
+"##;
+        std::fs::write(dir.join("tiles.tsx"), tsx)?;
+        std::fs::write(dir.join("map.tmx"), tmx)?;
+
+        // Split layers are merged back together using the layer name
+        let mut state = State::default();
+        let map_id = Tiled::from_path(dir.join("map.tmx"))?.append_to_state(
+            &mut state,
+            &Settings::default(),
+            None,
+            false,
+            true,
+            true,
+        )?;
+        let imported = &state
+            .maps
+            .get_by_id(map_id)
+            .ok_or(eyre!("Missing imported map"))?
+            .tiles;
+        let names: Vec<_> = imported.layers().map(|l| l.name()).collect();
+        assert_eq!(names, vec!["Ground".to_string(), "Top".to_string()]);
+        let top_tiles: Vec<_> = (0..2)
+            .map(|x| {
+                imported
+                    .tile(1, crate::geom::u32pos2::u32pos2(x, 0))
+                    .map(|t| t.source.tile_index.index())
+            })
+            .collect();
+        assert_eq!(top_tiles, vec![Some(0), Some(1)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn import_all_render_orders() -> eyre::Result<()> {
+        use crate::data::{
+            state::State,
+            tiles::{MapPositionIterator, Tile},
+        };
+
+        let temp = assert_fs::TempDir::new()?;
+        let dir = Utf8PathBuf::from_path_buf(temp.path().to_path_buf())
+            .map_err(|_| eyre!("Can't convert temp dir to utf8"))?;
+        let tsx = r#"<?xml version="1.0" encoding="UTF-8"?>
+<tileset version="1.10" tiledversion="1.11.0" name="tiles" tilewidth="8" tileheight="8" tilecount="256" columns="16">
+ <image source="tiles.png" width="128" height="128"/>
+</tileset>
+"#;
+        std::fs::write(dir.join("tiles.tsx"), tsx)?;
+
+        let import = |renderorder: RenderOrder| -> eyre::Result<Vec<Option<Tile>>> {
+            let tmx = format!(
+                r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" tiledversion="1.11.0" orientation="orthogonal" renderorder="{renderorder}" width="2" height="2" tilewidth="8" tileheight="8" infinite="0" nextlayerid="2" nextobjectid="1">
+ <tileset firstgid="1" source="tiles.tsx"/>
+ <layer id="1" name="layer" width="2" height="2">
+  <data encoding="csv">
+1,2,
+3,0
+</data>
+ </layer>
+</map>
+"#
+            );
+            let path = dir.join(format!("{renderorder}.tmx"));
+            std::fs::write(&path, tmx)?;
+            let mut state = State::default();
+            let map_id = Tiled::from_path(path)?.append_to_state(
+                &mut state,
+                &Settings::default(),
+                None,
+                false,
+                false,
+                true,
+            )?;
+            let map = state
+                .maps
+                .get_by_id(map_id)
+                .ok_or(eyre!("Missing imported map"))?;
+            Ok(MapPositionIterator::new(map.tiles.map_size())
+                .map(|pos| map.tiles.tile(0, pos))
+                .collect())
+        };
+
+        let right_down = import(RenderOrder::RightDown)?;
+        let indices: Vec<Option<u32>> = right_down
+            .iter()
+            .map(|t| t.map(|t| t.source.tile_index.index()))
+            .collect();
+        assert_eq!(indices, vec![Some(0), Some(1), Some(2), None]);
+        for renderorder in [
+            RenderOrder::RightUp,
+            RenderOrder::LeftDown,
+            RenderOrder::LeftUp,
+        ] {
+            assert_eq!(import(renderorder)?, right_down, "{}", renderorder);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn import_files_with_byte_order_mark() -> eyre::Result<()> {
+        let temp = assert_fs::TempDir::new()?;
+        let dir = Utf8PathBuf::from_path_buf(temp.path().to_path_buf())
+            .map_err(|_| eyre!("Can't convert temp dir to utf8"))?;
+
+        // Byte order mark and whitespace before the xml declaration
+        let tsx = "\u{feff}\r\n<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<tileset version=\"1.10\" tiledversion=\"1.11.0\" name=\"tiles\" tilewidth=\"8\" tileheight=\"8\" tilecount=\"256\" columns=\"16\">
+ <image source=\"tiles.png\" width=\"128\" height=\"128\"/>
+</tileset>
+";
+        let tmx = "\u{feff}  <?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<map version=\"1.10\" tiledversion=\"1.11.0\" orientation=\"orthogonal\" renderorder=\"right-down\" width=\"2\" height=\"1\" tilewidth=\"8\" tileheight=\"8\" infinite=\"0\" nextlayerid=\"2\" nextobjectid=\"1\">
+ <tileset firstgid=\"1\" source=\"tiles.tsx\"/>
+ <layer id=\"1\" name=\"layer\" width=\"2\" height=\"1\">
+  <data encoding=\"csv\">
+1,2
+</data>
+ </layer>
+</map>
+";
+        std::fs::write(dir.join("tiles.tsx"), tsx)?;
+        std::fs::write(dir.join("map.tmx"), tmx)?;
+        let tiled = Tiled::from_path(dir.join("map.tmx"))?;
+        assert_eq!((tiled.width, tiled.height), (2, 1));
+        assert_eq!(tiled.xml_tilesets.len(), 1);
+
+        // The same map as JSON, also with a byte order mark
+        let tmj = format!("\u{feff}\n{}", serde_json::to_string(&tiled)?);
+        std::fs::write(dir.join("map.tmj"), tmj)?;
+        let tiled_json = Tiled::from_path(dir.join("map.tmj"))?;
+        assert_eq!((tiled_json.width, tiled_json.height), (2, 1));
+        Ok(())
+    }
+
+    #[test]
+    fn import_infinite_map_chunks() -> eyre::Result<()> {
+        let tmx = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" tiledversion="1.11.0" orientation="orthogonal" renderorder="right-down" width="4" height="4" tilewidth="8" tileheight="8" infinite="1" nextlayerid="3" nextobjectid="1">
+ <layer id="1" name="first" width="4" height="4">
+  <data encoding="csv">
+   <chunk x="-2" y="-2" width="2" height="2">
+1,2,
+3,4
+</chunk>
+   <chunk x="2" y="0" width="2" height="2">
+5,0,
+0,6
+</chunk>
+  </data>
+ </layer>
+ <layer id="2" name="second" width="4" height="4">
+  <data encoding="csv">
+   <chunk x="0" y="-2" width="2" height="2">
+7,0,
+0,0
+</chunk>
+  </data>
+ </layer>
+</map>
+"#;
+        let tiled_xml: TiledXml = quick_xml::de::from_str(tmx)?;
+        let tiled = tiled_xml.into_tiled()?;
+        let tiled = tiled.to_finite(&Settings::default())?;
+
+        // Chunks cover x from -2 to 4 and y from -2 to 2, moved to the origin
+        assert_eq!((tiled.width, tiled.height), (6, 4));
+        let data: Vec<Vec<u32>> = tiled.layers.iter().map(|l| l.data.clone()).collect();
+        assert_eq!(
+            data,
+            vec![
+                vec![
+                    1, 2, 0, 0, 0, 0, //
+                    3, 4, 0, 0, 0, 0, //
+                    0, 0, 0, 0, 5, 0, //
+                    0, 0, 0, 0, 0, 6, //
+                ],
+                vec![
+                    0, 0, 7, 0, 0, 0, //
+                    0, 0, 0, 0, 0, 0, //
+                    0, 0, 0, 0, 0, 0, //
+                    0, 0, 0, 0, 0, 0, //
+                ],
+            ]
+        );
+        for layer in tiled.layers.iter() {
+            assert_eq!((layer.width, layer.height), (6, 4));
+        }
+
+        // The same chunks in a TMJ file give the same finite map
+        let tmj = r#"{
+            "height": 4, "width": 4, "infinite": true, "tilewidth": 8, "tileheight": 8,
+            "tilesets": [],
+            "layers": [
+                {"id": 1, "name": "first", "width": 4, "height": 4, "opacity": 1, "visible": true,
+                 "chunks": [
+                    {"data": [1, 2, 3, 4], "x": -2, "y": -2, "width": 2, "height": 2},
+                    {"data": [5, 0, 0, 6], "x": 2, "y": 0, "width": 2, "height": 2}
+                 ]},
+                {"id": 2, "name": "second", "width": 4, "height": 4, "opacity": 1, "visible": true,
+                 "chunks": [{"data": [7, 0, 0, 0], "x": 0, "y": -2, "width": 2, "height": 2}]}
+            ]
+        }"#;
+        let tiled_json: Tiled = serde_json::from_str(tmj)?;
+        let tiled_json = tiled_json.to_finite(&Settings::default())?;
+        assert_eq!((tiled_json.width, tiled_json.height), (6, 4));
+        let json_data: Vec<Vec<u32>> = tiled_json.layers.iter().map(|l| l.data.clone()).collect();
+        assert_eq!(json_data, data);
+
+        // Chunks spread over an area larger than the maximum map size are refused,
+        // before allocating tile data
+        let settings = |max_map_tiles| Settings {
+            max_map_tiles,
+            ..Default::default()
+        };
+        let tiled_xml: TiledXml = quick_xml::de::from_str(tmx)?;
+        let infinite = tiled_xml.into_tiled()?;
+        assert!(infinite.to_finite(&settings(24)).is_ok());
+        assert!(infinite.to_finite(&settings(23)).is_err());
+
+        // As are finite maps
+        assert!(tiled_json.to_finite(&settings(24)).is_ok());
+        assert!(tiled_json.to_finite(&settings(23)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn infinite_map_round_trip() -> eyre::Result<()> {
+        use crate::{
+            data::{
+                state::State,
+                tiles::{
+                    Tile, TileIndex, TileSource, layer_storage::LayerStorageKind,
+                    tile_color::UserColor,
+                },
+            },
+            geom::{transform::Transform, u32pos2::u32pos2},
+        };
+
+        let tilesets = Tilesets::builtin_tilesets();
+        let tileset_id = tilesets
+            .iter()
+            .next()
+            .map(|t| t.id())
+            .ok_or(eyre!("No builtin tileset"))?;
+        let palette = Palette::new(vec![UserColor::WHITE]);
+
+        let map_size = u32size2(20, 18);
+        let mut tiles = LayerTiles::new(UserColor::BLACK, u32size2(8, 8), map_size);
+        tiles.insert_layer(0, "Layer", true, None);
+        tiles.make_infinite(I32Pos2::new(-2, -2));
+        for (x, y) in [(5, 3), (17, 3)] {
+            tiles.set_tile(
+                0,
+                u32pos2(x, y),
+                Some(Tile {
+                    source: TileSource {
+                        tileset_id,
+                        tile_index: TileIndex::new(x),
+                    },
+                    color: TileColor::from_palette_index(PaletteIndex::new(0)),
+                    transform: Transform::None,
+                }),
+            );
+        }
+
+        let temp = assert_fs::TempDir::new()?;
+        let dir = Utf8PathBuf::from_path_buf(temp.path().to_path_buf())
+            .map_err(|_| eyre!("Can't convert temp dir to utf8"))?;
+        let path = dir.join("map.tmx");
+        let settings = TiledExportSettings::default();
+        let xml = TiledXml::from_map_parts(path.clone(), &settings, &tiles, &palette, &tilesets)?;
+        assert_eq!(xml.infinite, INFINITE_TRUE);
+        xml.save(path.clone(), &settings)?;
+
+        // Chunks are written for non-empty areas, plus the corners to keep the map bounds
+        let tiled = Tiled::from_path(path)?;
+        assert!(tiled.infinite);
+        let chunks: Vec<_> = tiled
+            .layers
+            .iter()
+            .flat_map(|layer| layer.chunks.iter())
+            .map(|c| (c.x, c.y, c.width, c.height))
+            .collect();
+        assert_eq!(
+            chunks,
+            vec![(-2, -2, 16, 16), (14, -2, 4, 16), (14, 14, 4, 2)]
+        );
+
+        // Importing restores the origin, size, storage and tiles
+        let mut state = State::default();
+        state.resources.palette = palette;
+        let map_id =
+            tiled.append_to_state(&mut state, &Settings::default(), None, false, true, true)?;
+        let imported = &state
+            .maps
+            .get_by_id(map_id)
+            .ok_or(eyre!("Missing imported map"))?
+            .tiles;
+        assert_eq!(imported.origin(), Some(I32Pos2::new(-2, -2)));
+        assert_eq!(imported.map_size(), map_size);
+        let layer = imported.layers().next().ok_or(eyre!("Missing layer"))?;
+        assert_eq!(layer.storage_kind(), LayerStorageKind::Chunked);
+        assert_eq!(layer.tiles_iter().flatten().count(), 2);
+        for (x, y) in [(5, 3), (17, 3)] {
+            let index = imported
+                .tile(0, u32pos2(x, y))
+                .map(|tile| tile.source.tile_index.index());
+            assert_eq!(index, Some(x));
+        }
+
         Ok(())
     }
 }
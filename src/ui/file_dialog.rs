@@ -47,6 +47,22 @@ pub const JSON_NAME: &str = "JSON data";
 
 pub const JSON_EXTENSION: &str = "json";
 
+#[cfg(not(target_os = "windows"))]
+pub const PAINTNET_NAME: &str = "Paint.NET palette (.txt)";
+
+#[cfg(target_os = "windows")]
+pub const PAINTNET_NAME: &str = "Paint.NET palette";
+
+pub const PAINTNET_EXTENSION: &str = "txt";
+
+#[cfg(not(target_os = "windows"))]
+pub const CSV_NAME: &str = "CSV tile indices (.csv)";
+
+#[cfg(target_os = "windows")]
+pub const CSV_NAME: &str = "CSV tile indices";
+
+pub const CSV_EXTENSION: &str = "csv";
+
 pub fn optional_pathbuf_to_utf8(pathbuf: Option<PathBuf>) -> eyre::Result<Option<Utf8PathBuf>> {
     match pathbuf {
         Some(pathbuf) => {
@@ -161,3 +177,7 @@ pub fn save_rs_file(default_path: &Option<Utf8PathBuf>) -> eyre::Result<Option<U
 pub fn save_json_file(default_path: &Option<Utf8PathBuf>) -> eyre::Result<Option<Utf8PathBuf>> {
     save_file_with_extension_and_default(JSON_NAME, JSON_EXTENSION, default_path)
 }
+
+pub fn save_paintnet_file(default_path: &Option<Utf8PathBuf>) -> eyre::Result<Option<Utf8PathBuf>> {
+    save_file_with_extension_and_default(PAINTNET_NAME, PAINTNET_EXTENSION, default_path)
+}
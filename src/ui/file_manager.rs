@@ -0,0 +1,97 @@
+use camino::Utf8Path;
+#[cfg(not(target_arch = "wasm32"))]
+use eyre::bail;
+use eyre::eyre;
+#[cfg(not(target_arch = "wasm32"))]
+use std::process::Command;
+
+/// Open the platform file manager showing the contents of the folder at `dir`
+#[cfg(not(target_arch = "wasm32"))]
+pub fn open_folder(dir: &Utf8Path) -> eyre::Result<()> {
+    if !dir.is_dir() {
+        bail!("Can't find folder '{}'", dir);
+    }
+    spawn(folder_command(dir), dir)
+}
+
+/// Open the platform file manager showing the file at `path`, selecting it where
+/// the platform supports this, otherwise just showing the folder containing it.
+/// If the file doesn't exist, but its folder does, the folder is shown.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn reveal_file(path: &Utf8Path) -> eyre::Result<()> {
+    if path.is_file() {
+        spawn(reveal_command(path), path)
+    } else {
+        match path.parent() {
+            Some(dir) if dir.is_dir() => open_folder(dir),
+            _ => Err(eyre!(
+                "Can't find file '{}', or the folder containing it",
+                path
+            )),
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn open_folder(_dir: &Utf8Path) -> eyre::Result<()> {
+    Err(eyre!("Opening folders isn't supported on the web"))
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn reveal_file(_path: &Utf8Path) -> eyre::Result<()> {
+    Err(eyre!("Showing files isn't supported on the web"))
+}
+
+#[cfg(target_os = "macos")]
+fn folder_command(dir: &Utf8Path) -> Command {
+    let mut command = Command::new("open");
+    command.arg(dir);
+    command
+}
+
+#[cfg(target_os = "macos")]
+fn reveal_command(path: &Utf8Path) -> Command {
+    let mut command = Command::new("open");
+    command.arg("-R").arg(path);
+    command
+}
+
+#[cfg(target_os = "windows")]
+fn folder_command(dir: &Utf8Path) -> Command {
+    let mut command = Command::new("explorer");
+    command.arg(dir);
+    command
+}
+
+#[cfg(target_os = "windows")]
+fn reveal_command(path: &Utf8Path) -> Command {
+    let mut command = Command::new("explorer");
+    command.arg(format!("/select,{}", path));
+    command
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_arch = "wasm32")))]
+fn folder_command(dir: &Utf8Path) -> Command {
+    let mut command = Command::new("xdg-open");
+    command.arg(dir);
+    command
+}
+
+// There's no standard way to select a file in the file manager on other platforms,
+// so show the folder containing it
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_arch = "wasm32")))]
+fn reveal_command(path: &Utf8Path) -> Command {
+    folder_command(path.parent().unwrap_or(path))
+}
+
+/// Run the file manager `command` without waiting for it to finish, since some
+/// file managers keep running after opening the window
+#[cfg(not(target_arch = "wasm32"))]
+fn spawn(mut command: Command, path: &Utf8Path) -> eyre::Result<()> {
+    let mut child = command
+        .spawn()
+        .map_err(|e| eyre!("Failed to open file manager at '{}': {}", path, e))?;
+    // Wait in the background, so the finished process is cleaned up
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
@@ -1,6 +1,6 @@
 use crate::{
     app::App,
-    data::tiles::Tiles,
+    data::tiles::{Tiles, layer_tiles::LayerKind},
     data::{action::Action, maps::MapId},
     ui::egui_utils::{
         enabled_square_button, sized_button, sized_main_dir_cross_align_center, square_button,
@@ -35,6 +35,8 @@ pub fn layers_ui(ui: &mut Ui, app: &mut App) {
                 .column(Column::exact(20.0))
                 .column(Column::remainder().clip(true))
                 .column(Column::exact(40.0))
+                .column(Column::exact(48.0))
+                .column(Column::exact(24.0))
                 .column(Column::exact(24.0))
                 .column(Column::exact(40.0))
                 .min_scrolled_height(0.0)
@@ -52,6 +54,12 @@ pub fn layers_ui(ui: &mut Ui, app: &mut App) {
                     header.col(|ui| {
                         unselectable_label_strong(ui, "");
                     });
+                    header.col(|ui| {
+                        unselectable_label_strong(ui, "Opacity");
+                    });
+                    header.col(|ui| {
+                        unselectable_label_strong(ui, "");
+                    });
                     header.col(|ui| {
                         unselectable_label_strong(ui, "");
                     });
@@ -77,7 +85,13 @@ pub fn layers_ui(ui: &mut Ui, app: &mut App) {
                             });
                             row.col(|ui| {
                                 if let Some(layer_name) = me.map.tiles().layer_name(layer_index) {
-                                    unselectable_label(ui, layer_name);
+                                    if me.map.tiles().layer_kind(layer_index) == LayerKind::Guide
+                                    {
+                                        unselectable_label(ui, format!("{layer_name} (guide)"))
+                                            .on_hover_text("Guide layers are shown faded, and are not exported");
+                                    } else {
+                                        unselectable_label(ui, layer_name);
+                                    }
                                 }
                             });
                             row.col(|ui| {
@@ -87,6 +101,36 @@ pub fn layers_ui(ui: &mut Ui, app: &mut App) {
                                     unselectable_label(ui, text);
                                 }
                             });
+                            row.col(|ui| {
+                                if let Some(id) = me.map.tiles().layer_id(layer_index) {
+                                    let initial_opacity = me.map.tiles().layer_opacity(layer_index);
+                                    let initial_percent = initial_opacity.unwrap_or(1.0) * 100.0;
+                                    let mut percent = initial_percent;
+                                    let response = ui.add(
+                                        egui::DragValue::new(&mut percent)
+                                            .range(0.0..=100.0)
+                                            .max_decimals(0)
+                                            .suffix("%"),
+                                    );
+                                    // While dragging, changes are not complete, so they are
+                                    // accumulated into a single revision when the drag stops
+                                    let changed = percent != initial_percent;
+                                    if changed || response.drag_stopped() {
+                                        operations.push(LayerOperation::Action(
+                                            Action::SetLayerOpacity {
+                                                map_id,
+                                                layer_id: id,
+                                                opacity: if changed {
+                                                    Some(percent / 100.0)
+                                                } else {
+                                                    initial_opacity
+                                                },
+                                                complete: !response.dragged(),
+                                            },
+                                        ));
+                                    }
+                                }
+                            });
                             row.col(|ui| {
                                 ui.style_mut().visuals.widgets.inactive.weak_bg_fill =
                                     Color32::TRANSPARENT;
@@ -98,6 +142,32 @@ pub fn layers_ui(ui: &mut Ui, app: &mut App) {
                                     // app.show_layer_modal(map, layer_index);
                                 };
                             });
+                            row.col(|ui| {
+                                if let Some(id) = me.map.tiles().layer_id(layer_index) {
+                                    let solo = me.edit.solo_layer == Some(id);
+                                    if !solo {
+                                        ui.style_mut().visuals.widgets.inactive.weak_bg_fill =
+                                            Color32::TRANSPARENT;
+                                    }
+                                    let visible =
+                                        me.map.tiles().layer_visible(layer_index) == Some(true);
+                                    ui.add_enabled_ui(solo || visible, |ui| {
+                                        if ui
+                                            .add_sized(
+                                                vec2(24.0, 24.0),
+                                                egui::Button::new("󰈈").selected(solo),
+                                            )
+                                            .on_hover_text("Show only this layer (I)")
+                                            .on_disabled_hover_text(
+                                                "Hidden layers can't be shown on their own",
+                                            )
+                                            .clicked()
+                                        {
+                                            me.edit.toggle_solo_layer(me.map, id);
+                                        }
+                                    });
+                                }
+                            });
 
                             row.col(|ui| {
                                 if let (Some(visible_initial), Some(id)) = (
@@ -166,6 +236,13 @@ pub fn layers_ui(ui: &mut Ui, app: &mut App) {
                 }));
             }
 
+            if enabled_square_button(ui, me.can_merge_down(), "󰘭 Down")
+                .on_hover_text("Merge the selected layer into the layer below")
+                .clicked()
+            {
+                operations.push(LayerOperation::Action(Action::MergeDown { map_id }));
+            }
+
             if enabled_square_button(ui, me.can_move_selected_layers_higher(), "󰁝").clicked() {
                 operations.push(LayerOperation::Action(Action::MoveSelectedLayersHigher {
                     map_id: me.map.id(),
@@ -177,6 +254,20 @@ pub fn layers_ui(ui: &mut Ui, app: &mut App) {
                     map_id: me.map.id(),
                 }));
             }
+
+            let pair = me.selected_layer_pair();
+            if enabled_square_button(ui, pair.is_some(), "󰓢")
+                .on_hover_text("Swap the two selected layers")
+                .clicked()
+            {
+                if let Some((a, b)) = pair {
+                    operations.push(LayerOperation::Action(Action::SwapLayers {
+                        map_id: me.map.id(),
+                        a,
+                        b,
+                    }));
+                }
+            }
         });
     };
 
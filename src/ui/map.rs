@@ -1,12 +1,110 @@
-use egui::{DragPanButtons, Sense, Ui};
+use egui::{DragPanButtons, Rect, Sense, Ui};
 
 use crate::{
     app::App,
-    data::tiles::{Tiles, stamp_tiles::StampTiles, tile_selection::TileSelection},
-    data::{action::Action, mode::Mode},
+    data::tiles::{
+        SceneEvent, Tiles, solo_tiles::SoloTiles, stamp_tiles::StampTiles, tile_color::TileColor,
+        tile_selection::TileSelection,
+    },
+    data::{action::Action, maps::MapId, mode::Mode, tilesets::Tilesets},
+    geom::{
+        ellipse::ellipse_positions, i32pos2::I32Pos2, line::line_positions, u32pos2::U32Pos2,
+        u32rect::U32Rect, u32size2::U32Size2,
+    },
     ui::tiles::{Overlay, tiles_no_sense},
 };
 
+/// The cells drawn for a shape dragged from `start` to `end` in a mode that
+/// [`Mode::draws_shape`], where `filled` applies to shapes that can be filled
+fn shape_positions(
+    mode: Mode,
+    start: I32Pos2,
+    end: I32Pos2,
+    map_size: U32Size2,
+    filled: bool,
+) -> Vec<I32Pos2> {
+    match mode {
+        Mode::Ellipse => ellipse_positions(shape_rect(start, end, map_size), filled)
+            .into_iter()
+            .map(I32Pos2::from)
+            .collect(),
+        _ => line_positions(start, end),
+    }
+}
+
+/// The rect for a shape dragged from `start` to `end`, constrained to the map
+fn shape_rect(start: I32Pos2, end: I32Pos2, map_size: U32Size2) -> U32Rect {
+    U32Rect {
+        min: map_size.u32pos_constrained(start),
+        max: map_size.u32pos_constrained(end),
+    }
+}
+
+/// The action to draw a shape dragged from `start` to `end`, see [`shape_positions`]
+fn shape_action(
+    mode: Mode,
+    map_id: MapId,
+    start: I32Pos2,
+    end: I32Pos2,
+    map_size: U32Size2,
+    filled: bool,
+) -> Action {
+    match mode {
+        Mode::Ellipse => Action::DrawEllipse {
+            map_id,
+            rect: shape_rect(start, end, map_size),
+            filled,
+        },
+        _ => Action::DrawLine { map_id, start, end },
+    }
+}
+
+/// Move `scene_rect`, the part of the scene shown in `screen_rect`, by less than a
+/// pixel so that the scene origin (the top left of the map) is drawn on a whole
+/// physical pixel, with `pixels_per_point` physical pixels per screen point. This
+/// keeps tiles sampled with `NEAREST` filtering crisp, rather than shimmering at
+/// sub-pixel offsets.
+fn pixel_aligned_scene_rect(scene_rect: Rect, screen_rect: Rect, pixels_per_point: f32) -> Rect {
+    let zoom = screen_rect.width() / scene_rect.width();
+    let pixels_per_scene_unit = zoom * pixels_per_point;
+    if !pixels_per_scene_unit.is_finite() || pixels_per_scene_unit <= 0.0 {
+        return scene_rect;
+    }
+
+    // Physical pixel position of the scene origin on screen
+    let origin = screen_rect.min.to_vec2() * pixels_per_point
+        - scene_rect.min.to_vec2() * pixels_per_scene_unit;
+    let offset = origin - origin.round();
+    scene_rect.translate(offset / pixels_per_scene_unit)
+}
+
+/// The opacity of the stamp preview shown under the pointer when drawing, so
+/// the layers beneath it can still be seen
+const STAMP_PREVIEW_OPACITY: f32 = 0.6;
+
+/// Describe the topmost tile at `pos`, for display in a tooltip
+fn tile_tooltip_text<T: Tiles>(tiles: &T, tilesets: &Tilesets, pos: U32Pos2) -> String {
+    let position = format!("Position: ({}, {})", pos.x, pos.y);
+    let Some(tile) = (0..tiles.layer_count()).find_map(|layer| tiles.tile(layer, pos)) else {
+        return format!("{position}\nNo tile");
+    };
+
+    let tileset = tilesets
+        .get_by_id(tile.source.tileset_id)
+        .map(|t| t.to_string())
+        .unwrap_or("Missing tileset".to_string());
+    let color = match tile.color {
+        TileColor::Default => "Default".to_string(),
+        TileColor::Palette { index } => format!("Palette {}", index.index()),
+        TileColor::UserColor(color) => color.as_hex_string(),
+    };
+    format!(
+        "{position}\nTileset: {tileset}\nTile index: {}\nColor: {color}\nTransform: {}",
+        tile.source.tile_index.index(),
+        tile.transform
+    )
+}
+
 pub fn map_ui(ui: &mut Ui, app: &mut App) {
     // Only sense drag, to avoid delay in starting drag,
     // and only allow built-in drag panning with right mouse button
@@ -22,6 +120,16 @@ pub fn map_ui(ui: &mut Ui, app: &mut App) {
         .drag_pan_buttons(DragPanButtons::SECONDARY);
 
     let mode = app.edit.mode;
+    let tile_tooltips = app.edit.tile_tooltips;
+    let highlight = app.settings.highlight_style;
+    let snap_scene_to_pixels = app.settings.snap_scene_to_pixels;
+    let overlay = if app.edit.heatmap {
+        Overlay::Heatmap
+    } else if app.edit.tile_indices {
+        Overlay::TileIndices
+    } else {
+        Overlay::None
+    };
 
     // Note that we produce an action from rendering, so we can carry it out later
     // when app is no longer borrowed
@@ -29,30 +137,51 @@ pub fn map_ui(ui: &mut Ui, app: &mut App) {
         let mut scene_rect = me.edit.scene_rect;
         let map_hovered = me.edit.map_hovered;
         let map_scene_hovered = me.edit.map_scene_hovered;
+        let anchor_offset = me.stamp.anchor_offset(me.edit.stamp_anchor);
         let layer_to_stamp_layer = me.layer_index_to_stamp_layer_index();
-        let selection = me.edit.selection_mut();
+        let solo_layer_index = me.edit.solo_layer_index(me.map);
+        let map_size = me.map.tiles().map_size();
+        let filled = ui.input(|i| i.modifiers.shift);
+        let mut shape_drag = me.edit.shape_drag.filter(|_| mode.draws_shape());
+        // While dragging out a shape, preview the stamp drawn at each of its cells
+        let shape_stamp = shape_drag.map(|(start, end)| {
+            me.stamp
+                .repeated_at(&shape_positions(mode, start, end, map_size, filled))
+        });
+        let (selection, mesh_cache) = me.edit.selection_and_mesh_cache_mut();
 
         let double_response = scene.show(ui, &mut scene_rect, |ui| {
             let palette = me.resources.palette();
             let tilesets = me.resources.tilesets();
             let textures = me.textures;
+            let display_tiles = SoloTiles {
+                solo_layer_index,
+                inner_tiles: me.map.tiles(),
+            };
 
             match mode {
                 Mode::Select => ui.add(tiles_no_sense(
-                    me.map.tiles(),
+                    &display_tiles,
                     palette,
                     tilesets,
                     textures,
                     Some(selection),
-                    Overlay::None,
+                    highlight,
+                    overlay,
+                    Some(mesh_cache),
                 )),
-                Mode::Draw => {
-                    if let Some(offset) = map_scene_hovered {
+                Mode::Draw | Mode::Line | Mode::Ellipse => {
+                    let preview = match &shape_stamp {
+                        Some(shape_stamp) => Some((shape_stamp, I32Pos2::new(0, 0))),
+                        None => map_scene_hovered.map(|offset| (&*me.stamp, offset)),
+                    };
+                    if let Some((stamp, offset)) = preview {
                         let render_tiles = StampTiles {
-                            stamp: me.stamp,
-                            offset,
+                            stamp,
+                            offset: offset + anchor_offset,
                             layer_to_stamp_layer: &layer_to_stamp_layer,
-                            inner_tiles: me.map.tiles(),
+                            inner_tiles: &display_tiles,
+                            opacity: Some(STAMP_PREVIEW_OPACITY),
                         };
                         ui.add(tiles_no_sense(
                             &render_tiles,
@@ -60,32 +189,39 @@ pub fn map_ui(ui: &mut Ui, app: &mut App) {
                             tilesets,
                             textures,
                             None,
-                            Overlay::None,
+                            highlight,
+                            overlay,
+                            Some(mesh_cache),
                         ))
                     } else {
                         ui.add(tiles_no_sense(
-                            me.map.tiles(),
+                            &display_tiles,
                             palette,
                             tilesets,
                             textures,
                             None,
-                            Overlay::None,
+                            highlight,
+                            overlay,
+                            Some(mesh_cache),
                         ))
                     }
                 }
                 Mode::Erase => ui.add(tiles_no_sense(
-                    me.map.tiles(),
+                    &display_tiles,
                     palette,
                     tilesets,
                     textures,
                     Some(&TileSelection::erase(map_hovered)),
-                    Overlay::None,
+                    highlight,
+                    overlay,
+                    Some(mesh_cache),
                 )),
             }
         });
 
         let tiles_response = double_response.inner;
         let scene_response = double_response.response;
+        let screen_rect = scene_response.rect;
 
         let action = if let Some(event) = me.map.tiles().scene_event(&scene_response) {
             match mode {
@@ -107,6 +243,19 @@ pub fn map_ui(ui: &mut Ui, app: &mut App) {
                         complete,
                     })
                 }
+                Mode::Line | Mode::Ellipse => match event {
+                    SceneEvent::DragStarted { pos } => {
+                        shape_drag = Some((pos, pos));
+                        None
+                    }
+                    SceneEvent::Dragged { pos } => {
+                        shape_drag = shape_drag.map(|(start, _)| (start, pos));
+                        None
+                    }
+                    SceneEvent::DragStopped { pos } => shape_drag.take().map(|(start, _)| {
+                        shape_action(mode, me.map.id(), start, pos, map_size, filled)
+                    }),
+                },
                 Mode::Erase => {
                     let pos = event.pos();
                     let complete = event.complete();
@@ -120,11 +269,28 @@ pub fn map_ui(ui: &mut Ui, app: &mut App) {
         } else {
             None
         };
+        me.edit.shape_drag = shape_drag;
 
         // Remember where map and scene were hovered, to draw next frame
         me.update_map_hover(&tiles_response, &scene_response);
 
-        // Remember any pan/zoom applied by user
+        if tile_tooltips && let Some(pos) = me.edit.map_hovered {
+            let display_tiles = SoloTiles {
+                solo_layer_index,
+                inner_tiles: me.map.tiles(),
+            };
+            let text = tile_tooltip_text(&display_tiles, me.resources.tilesets(), pos);
+            scene_response.on_hover_ui_at_pointer(|ui| {
+                ui.label(text);
+            });
+        }
+
+        // Remember any pan/zoom applied by user, aligning to pixels once any drag has finished
+        let dragging = ui.input(|i| i.pointer.any_down());
+        if snap_scene_to_pixels && !dragging {
+            scene_rect =
+                pixel_aligned_scene_rect(scene_rect, screen_rect, ui.ctx().pixels_per_point());
+        }
         me.edit.scene_rect = scene_rect;
 
         action
@@ -137,3 +303,96 @@ pub fn map_ui(ui: &mut Ui, app: &mut App) {
         app.act(action);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use egui::{pos2, vec2};
+
+    use crate::{
+        data::{
+            palette::PaletteIndex,
+            tiles::{Tile, TileIndex, TileSource, layer_tiles::LayerTiles, tile_color::UserColor},
+            tilesets::TilesetId,
+        },
+        geom::{transform::Transform, u32pos2::u32pos2, u32size2::u32size2},
+    };
+
+    use super::*;
+
+    #[test]
+    fn tile_tooltip_describes_topmost_displayed_tile() {
+        let tilesets = Tilesets::builtin_tilesets();
+        let mut tiles = LayerTiles::new(UserColor::BLACK, u32size2(8, 8), u32size2(4, 4));
+        tiles.insert_layer(0, "Lower", true, None);
+        tiles.insert_layer(0, "Upper", true, None);
+        let tile = |index, color, transform| Tile {
+            source: TileSource {
+                tileset_id: TilesetId::ONE,
+                tile_index: TileIndex::new(index),
+            },
+            color,
+            transform,
+        };
+        let pos = u32pos2(1, 2);
+        tiles.set_tile(1, pos, Some(tile(3, TileColor::Default, Transform::None)));
+        tiles.set_tile(
+            0,
+            pos,
+            Some(tile(
+                5,
+                TileColor::from_palette_index(PaletteIndex::new(2)),
+                Transform::MirrorX,
+            )),
+        );
+        let name = tilesets
+            .get_by_id(TilesetId::ONE)
+            .map(|tileset| tileset.name.clone())
+            .unwrap_or_default();
+
+        assert_eq!(
+            tile_tooltip_text(&tiles, &tilesets, pos),
+            format!(
+                "Position: (1, 2)\nTileset: {name}\nTile index: 5\nColor: Palette 2\nTransform: MirrorX"
+            )
+        );
+
+        // Hidden layers are skipped
+        if let Some(visible) = tiles.layer_visible_mut(0) {
+            *visible = false;
+        }
+        assert_eq!(
+            tile_tooltip_text(&tiles, &tilesets, pos),
+            format!(
+                "Position: (1, 2)\nTileset: {name}\nTile index: 3\nColor: Default\nTransform: None"
+            )
+        );
+
+        assert_eq!(
+            tile_tooltip_text(&tiles, &tilesets, u32pos2(0, 0)),
+            "Position: (0, 0)\nNo tile"
+        );
+    }
+
+    #[test]
+    fn scene_origin_is_pixel_aligned() {
+        let screen_rect = Rect::from_min_size(pos2(10.0, 20.0), vec2(400.0, 300.0));
+        // Zoom of 2 screen points per scene unit
+        let scene_rect = Rect::from_min_size(pos2(-3.3, 1.7), vec2(200.0, 150.0));
+
+        for pixels_per_point in [1.0, 1.5, 2.0] {
+            let aligned = pixel_aligned_scene_rect(scene_rect, screen_rect, pixels_per_point);
+            assert_eq!(aligned.size(), scene_rect.size());
+
+            // The origin is on a whole pixel, moved by at most half a pixel
+            let origin =
+                (screen_rect.min.to_vec2() - aligned.min.to_vec2() * 2.0) * pixels_per_point;
+            assert!((origin - origin.round()).length() < 1e-3);
+            let moved = (aligned.min - scene_rect.min) * 2.0 * pixels_per_point;
+            assert!(moved.x.abs() <= 0.5 && moved.y.abs() <= 0.5);
+
+            // Aligning again has no effect
+            let again = pixel_aligned_scene_rect(aligned, screen_rect, pixels_per_point);
+            assert!((again.min - aligned.min).length() < 1e-4);
+        }
+    }
+}
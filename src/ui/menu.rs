@@ -1,5 +1,9 @@
+use crate::data::stamp::StampAnchor;
 #[cfg(not(target_arch = "wasm32"))]
-use crate::{app::App, data::mode::Mode, ui::egui_utils::unselectable_label};
+use crate::{
+    app::App, data::action::Action, data::mode::Mode, ui::egui_utils::unselectable_label,
+    ui::palette::palette_index_indicator_ui, ui::shortcuts::RELOAD_TILESETS_SHORTCUT,
+};
 use egui::{Color32, InnerResponse, Stroke, Style, Ui, vec2};
 
 fn set_menu_style(style: &mut Style) {
@@ -45,6 +49,36 @@ fn add_file_menu(ui: &mut Ui, app: &mut App) {
                 app.show_save_as_document_modal();
             }
 
+            if ui
+                .button("󰆏 Save a copy...")
+                .on_hover_text(
+                    "Save the project to another file, and keep working on the current file",
+                )
+                .clicked()
+            {
+                app.show_save_copy_document_modal();
+            }
+
+            if ui
+                .add_enabled(
+                    app.save_path.is_some(),
+                    egui::Button::new("󰉋 Open project folder"),
+                )
+                .clicked()
+            {
+                app.open_project_folder();
+            }
+
+            if ui
+                .add_enabled(
+                    app.selected_tileset().is_some(),
+                    egui::Button::new("󰉋 Show tileset image"),
+                )
+                .clicked()
+            {
+                app.reveal_selected_tileset_image();
+            }
+
             ui.menu_button("󱋡 Recent files...", |ui| {
                 let mut path_to_open = None;
                 for (index, path) in app.recent_paths.iter().enumerate() {
@@ -78,10 +112,21 @@ fn add_file_menu(ui: &mut Ui, app: &mut App) {
                 app.show_export_palette_lospec_modal();
             }
 
+            if ui
+                .button("󰈇 Export Palette (Paint.NET/Aseprite)...")
+                .clicked()
+            {
+                app.show_export_palette_paintnet_modal();
+            }
+
             if ui.button("󰋺 Import Tiled...").clicked() {
                 app.pick_tiled_file_to_import();
             }
 
+            if ui.button("󰋺 Import layer from CSV...").clicked() {
+                app.pick_layer_csv_file_to_import();
+            }
+
             if ui.button("󰈇 Export Tiled...").clicked() {
                 app.show_export_tiled_modal();
             }
@@ -120,12 +165,26 @@ pub fn menu_ui(ui: &mut Ui, app: &mut App) {
         add_file_menu(ui, app);
 
         ui.menu_button("Edit", |ui| {
+            let with_count = |text: &str, count: usize| {
+                if count > 0 {
+                    format!("{text} ({count})")
+                } else {
+                    text.to_string()
+                }
+            };
+
             let undo_clicked = ui
-                .add_enabled(app.can_undo(), egui::Button::new("󰕌 Undo"))
+                .add_enabled(
+                    app.can_undo(),
+                    egui::Button::new(with_count("󰕌 Undo", app.available_undos())),
+                )
                 .clicked();
 
             let redo_clicked = ui
-                .add_enabled(app.can_redo(), egui::Button::new("󰑎 Redo"))
+                .add_enabled(
+                    app.can_redo(),
+                    egui::Button::new(with_count("󰑎 Redo", app.available_redos())),
+                )
                 .clicked();
 
             if undo_clicked {
@@ -136,6 +195,133 @@ pub fn menu_ui(ui: &mut Ui, app: &mut App) {
                 app.redo();
             }
 
+            if ui
+                .add_enabled(
+                    app.can_stroke_selection(),
+                    egui::Button::new("󰹞 Stroke selection"),
+                )
+                .on_hover_text("Draw the stamp's tile around the edge of the selection")
+                .clicked()
+            {
+                app.stroke_selection();
+            }
+
+            if ui
+                .add_enabled(
+                    app.selected_map_edit_state()
+                        .is_some_and(|edit| !edit.selection().is_empty()),
+                    egui::Button::new("󰕰 Auto-tile selection"),
+                )
+                .on_hover_text("Replace terrain tiles with the tiles matching their corners, removing rotation and mirroring")
+                .clicked()
+            {
+                app.autotile_selection();
+            }
+
+            if ui
+                .add_enabled(
+                    app.selected_map_edit_state()
+                        .is_some_and(|edit| !edit.selection().is_empty()),
+                    egui::Button::new("󰦛 Clear transforms in selection"),
+                )
+                .on_hover_text("Remove rotation and mirroring from the selected tiles")
+                .clicked()
+            {
+                app.clear_transforms_in_selection();
+            }
+
+            ui.checkbox(&mut app.edit.autotile, "Auto-tile when drawing")
+                .on_hover_text("Replace drawn terrain tiles and their neighbours with the tiles matching their corners, removing rotation and mirroring");
+
+            match app.selected_map_edit_state_mut() {
+                Some(edit) => {
+                    ui.checkbox(&mut edit.draw_empty_cells_only, "Draw into empty cells only")
+                        .on_hover_text("Only place tiles in cells that are empty, leaving existing tiles unchanged");
+                }
+                None => {
+                    ui.add_enabled(
+                        false,
+                        egui::Checkbox::new(&mut false, "Draw into empty cells only"),
+                    );
+                }
+            }
+
+            match app.selected_map_edit_state_mut() {
+                Some(edit) => {
+                    let mut top_left = edit.stamp_anchor == StampAnchor::TopLeft;
+                    if ui
+                        .checkbox(&mut top_left, "Anchor stamp at top left")
+                        .on_hover_text("Place the top left of the stamp at the cursor when drawing, instead of the center")
+                        .changed()
+                    {
+                        edit.stamp_anchor = if top_left {
+                            StampAnchor::TopLeft
+                        } else {
+                            StampAnchor::Center
+                        };
+                    }
+                }
+                None => {
+                    ui.add_enabled(
+                        false,
+                        egui::Checkbox::new(&mut false, "Anchor stamp at top left"),
+                    );
+                }
+            }
+
+            if ui
+                .add_enabled(
+                    app.can_select_nonempty(),
+                    egui::Button::new("󰒆 Select tiles on layer"),
+                )
+                .on_hover_text("Select every cell containing a tile on the selected layer (F)")
+                .clicked()
+            {
+                app.select_nonempty_on_selected_layer();
+            }
+
+            if ui
+                .add_enabled(
+                    app.selected_map_edit_state()
+                        .is_some_and(|edit| !edit.selection().is_empty()),
+                    egui::Button::new("󰆏 Selection to new map"),
+                )
+                .on_hover_text("Copy the selected tiles on selected visible layers into a new map")
+                .clicked()
+            {
+                app.selection_to_new_map();
+            }
+
+            if ui
+                .add_enabled(
+                    app.state.maps.iter().count() > 1,
+                    egui::Button::new("󰦒 Compare maps..."),
+                )
+                .on_hover_text("List the tiles that differ between two maps")
+                .clicked()
+            {
+                app.show_compare_maps_modal();
+            }
+
+            if ui
+                .add_enabled(
+                    app.state.maps.iter().count() > 0,
+                    egui::Button::new("󰁌 Resize all maps..."),
+                )
+                .on_hover_text("Resize every map in the project to the same size")
+                .clicked()
+            {
+                app.show_resize_all_maps_modal();
+            }
+
+            if ui
+                .button("󰏘 Compact palette")
+                .on_hover_text("Remove palette colors that aren't used by any tile, keeping the colors of all tiles")
+                .clicked()
+            {
+                app.act(Action::CompactPalette);
+            }
+
             if ui.button("󰒓 Application settings...").clicked() {
                 app.show_application_settings_modal();
             }
@@ -145,6 +331,22 @@ pub fn menu_ui(ui: &mut Ui, app: &mut App) {
             if ui.button("󱉶 Reset zoom").clicked() {
                 app.reset_selected_map_zoom();
             }
+            if ui
+                .button("󰑐 Reload tileset images")
+                .on_hover_text(format!(
+                    "Load all tileset images again from their files, after editing them in another application ({})",
+                    ui.ctx().format_shortcut(&RELOAD_TILESETS_SHORTCUT)
+                ))
+                .clicked()
+            {
+                app.reload_all_tilesets(ui.ctx());
+            }
+            ui.checkbox(&mut app.edit.heatmap, "Layer heatmap")
+                .on_hover_text("Color each cell by the number of layers with a tile in that cell");
+            ui.checkbox(&mut app.edit.tile_indices, "Tile indices")
+                .on_hover_text("Show the exported tile index of the topmost tile in each cell, when zoomed in far enough to read");
+            ui.checkbox(&mut app.edit.tile_tooltips, "Tile tooltips")
+                .on_hover_text("Show the position and details of the tile under the pointer");
         });
 
         if ui.button("Help...").clicked() {
@@ -158,6 +360,15 @@ pub fn menu_ui(ui: &mut Ui, app: &mut App) {
         if ui.selectable_label(mode == Mode::Draw, "󰏫 Draw").clicked() {
             app.draw_mode();
         };
+        if ui.selectable_label(mode == Mode::Line, "󰕞 Line").clicked() {
+            app.line_mode();
+        };
+        if ui
+            .selectable_label(mode == Mode::Ellipse, "󰝦 Ellipse")
+            .clicked()
+        {
+            app.ellipse_mode();
+        };
         if ui
             .selectable_label(mode == Mode::Erase, "󰇾 Erase")
             .clicked()
@@ -170,5 +381,10 @@ pub fn menu_ui(ui: &mut Ui, app: &mut App) {
         {
             app.select_mode();
         };
+
+        ui.add_space(16.0);
+        unselectable_label(ui, "Color:");
+        ui.add_space(4.0);
+        palette_index_indicator_ui(ui, app);
     });
 }
@@ -1,3 +1,4 @@
+pub mod compare_maps_modal;
 pub mod data_loss_modal;
 pub mod error_modal;
 pub mod export_png_modal;
@@ -6,6 +7,11 @@ pub mod help_modal;
 pub mod import_tiled_modal;
 pub mod layer_modal;
 pub mod map_modal;
+pub mod merge_tilesets_modal;
+pub mod missing_tileset_images_modal;
 pub mod palette_modal;
+pub mod project_repaired_modal;
+pub mod resize_all_maps_modal;
 pub mod settings_modal;
+pub mod split_tileset_modal;
 pub mod tileset_modal;
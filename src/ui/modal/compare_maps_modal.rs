@@ -0,0 +1,108 @@
+use egui::{Id, Modal, Ui};
+
+use crate::{
+    app::App,
+    data::{
+        maps::{MapId, Maps},
+        modal::{ModalResult, ModalState},
+        tiles::tile_diff::TileDiffSummary,
+    },
+    ui::theme::{DEFAULT_THEME, Theme},
+};
+
+/// The maximum number of individual differences to list
+const MAX_LISTED_DIFFS: usize = 100;
+
+fn map_combo_box(ui: &mut Ui, id_salt: &str, maps: &Maps, selected: &mut Option<MapId>) {
+    let selected_text = selected
+        .and_then(|id| maps.get_by_id(id))
+        .map(|m| format!("{m}"))
+        .unwrap_or_default();
+    egui::ComboBox::from_id_salt(id_salt)
+        .selected_text(selected_text)
+        .truncate()
+        .width(ui.available_width())
+        .show_ui(ui, |ui| {
+            for map in maps.iter() {
+                ui.selectable_value(selected, Some(map.id()), format!("{map}"));
+            }
+        });
+}
+
+pub fn compare_maps_modal_ui(ui: &mut Ui, app: &mut App) {
+    let maps = &app.state.maps;
+    if let ModalState::CompareMaps {
+        a,
+        b,
+        diffs,
+        result,
+    } = &mut app.edit.modal
+    {
+        Modal::new(Id::new("Compare Maps Modal"))
+            .frame(Theme::current(ui.ctx()).modal_frame())
+            .show(ui.ctx(), |ui| {
+                ui.set_width(300.0);
+
+                ui.heading("Compare Maps");
+                ui.add_space(DEFAULT_THEME.modal_spacing);
+                ui.label(
+                    "Lists the tiles that differ between two maps, comparing layers in order.",
+                );
+                ui.add_space(DEFAULT_THEME.modal_spacing);
+
+                ui.label("First map");
+                map_combo_box(ui, "compare_maps_a", maps, a);
+                ui.label("Second map");
+                map_combo_box(ui, "compare_maps_b", maps, b);
+
+                ui.add_space(DEFAULT_THEME.modal_spacing);
+
+                if let (Some(map_a), Some(map_b)) = (
+                    a.and_then(|id| maps.get_by_id(id)),
+                    b.and_then(|id| maps.get_by_id(id)),
+                ) {
+                    // Only compare again when different maps are chosen
+                    let (id_a, id_b) = (map_a.id(), map_b.id());
+                    if !matches!(diffs, Some((a, b, _)) if (*a, *b) == (id_a, id_b)) {
+                        *diffs = Some((id_a, id_b, map_a.tiles.diff(&map_b.tiles)));
+                    }
+                    let diffs = diffs
+                        .as_ref()
+                        .map(|(_, _, diffs)| diffs.as_slice())
+                        .unwrap_or_default();
+                    ui.label(format!("{}", TileDiffSummary::from_diffs(diffs)));
+                    if !diffs.is_empty() {
+                        egui::ScrollArea::vertical()
+                            .max_height(200.0)
+                            .show(ui, |ui| {
+                                for diff in diffs.iter().take(MAX_LISTED_DIFFS) {
+                                    ui.label(format!("{diff}"));
+                                }
+                                if diffs.len() > MAX_LISTED_DIFFS {
+                                    ui.label(format!(
+                                        "...and {} more",
+                                        diffs.len() - MAX_LISTED_DIFFS
+                                    ));
+                                }
+                            });
+                    }
+                }
+
+                ui.add_space(DEFAULT_THEME.modal_spacing);
+                ui.separator();
+                ui.add_space(DEFAULT_THEME.modal_spacing);
+
+                egui::Sides::new().show(
+                    ui,
+                    |_ui| {},
+                    |ui| {
+                        if ui.button("Close").clicked() {
+                            *result = ModalResult::Apply;
+                        }
+                    },
+                );
+            });
+
+        app.progress_modal_state();
+    }
+}
@@ -6,17 +6,17 @@ use crate::{
         action::Action,
         modal::{DataLossOperation, ModalResult, ModalState},
     },
-    ui::theme::DEFAULT_THEME,
+    ui::theme::{DEFAULT_THEME, Theme},
 };
 
 pub fn data_loss_modal_ui(ui: &mut Ui, app: &mut App) {
     let modal_to_apply = if let &mut ModalState::DataLoss {
-        ref operation,
+        ref mut operation,
         ref mut result,
     } = &mut app.edit.modal
     {
         Modal::new(Id::new("Data Loss Modal"))
-            .frame(DEFAULT_THEME.modal_frame())
+            .frame(Theme::current(ui.ctx()).modal_frame())
             .show(ui.ctx(), |ui| {
                 ui.set_width(250.0);
 
@@ -29,7 +29,7 @@ pub fn data_loss_modal_ui(ui: &mut Ui, app: &mut App) {
                         ui.heading("󱂥 Tileset is in use");
                         ui.add_space(DEFAULT_THEME.modal_spacing);
 
-                        ui.label(tileset_name);
+                        ui.label(tileset_name.as_str());
                         ui.add_space(DEFAULT_THEME.modal_spacing);
                         ui.label(format!(
                             "Used by {} tiles, on maps/layers:",
@@ -41,6 +41,8 @@ pub fn data_loss_modal_ui(ui: &mut Ui, app: &mut App) {
                     DataLossOperation::ReplacePalette {
                         palette,
                         resource_use,
+                        lost_indices,
+                        remap_to_nearest,
                     } => {
                         ui.heading("󱂥 Deleted colors are in use");
                         ui.add_space(DEFAULT_THEME.modal_spacing);
@@ -51,12 +53,60 @@ pub fn data_loss_modal_ui(ui: &mut Ui, app: &mut App) {
                             palette.len()
                         ));
                         ui.add_space(DEFAULT_THEME.modal_spacing);
+                        let lost_indices = lost_indices
+                            .iter()
+                            .map(|index| index.to_string())
+                            .collect::<Vec<_>>()
+                            .join(", ");
+                        ui.label(format!("Deleted colors in use: {}", lost_indices));
+                        ui.add_space(DEFAULT_THEME.modal_spacing);
                         ui.label(format!(
-                            "Deleted colors are used by {} tiles, on maps/layers:",
+                            "These are used by {} tiles, on maps/layers:",
                             resource_use.tile_count
                         ));
                         ui.add_space(DEFAULT_THEME.modal_spacing);
-                        ui.label(resource_use.locations_to_string());
+                        egui::ScrollArea::vertical()
+                            .max_height(200.0)
+                            .show(ui, |ui| {
+                                for location in resource_use.locations.iter() {
+                                    ui.label(location.to_string());
+                                }
+                            });
+                        ui.add_space(DEFAULT_THEME.modal_spacing);
+                        ui.checkbox(remap_to_nearest, "Keep tiles, using nearest colors")
+                            .on_hover_text(
+                                "Change tiles using deleted colors to the nearest color in the new palette, instead of deleting them",
+                            );
+                    }
+                    DataLossOperation::ChangeTilesetColumns {
+                        tileset,
+                        tileset_use,
+                        old_columns,
+                        remap_columns,
+                        ..
+                    } => {
+                        ui.heading("󱂥 Tileset is in use");
+                        ui.add_space(DEFAULT_THEME.modal_spacing);
+
+                        ui.label(tileset.name.as_str());
+                        ui.add_space(DEFAULT_THEME.modal_spacing);
+                        ui.label(format!(
+                            "The number of columns changes from {} to {}, so tile indices refer to different tiles.",
+                            old_columns,
+                            tileset.grid_size().w
+                        ));
+                        ui.add_space(DEFAULT_THEME.modal_spacing);
+                        ui.label(format!(
+                            "Used by {} tiles, on maps/layers:",
+                            tileset_use.tile_count
+                        ));
+                        ui.add_space(DEFAULT_THEME.modal_spacing);
+                        ui.label(tileset_use.locations_to_string());
+                        ui.add_space(DEFAULT_THEME.modal_spacing);
+                        ui.checkbox(remap_columns, "Keep tiles, changing their indices")
+                            .on_hover_text(
+                                "Change tiles to keep showing the same part of the image, deleting tiles in columns that are no longer used",
+                            );
                     }
                     _ => {
                         ui.heading("󱂥 Warning - unsaved data");
@@ -78,22 +128,48 @@ pub fn data_loss_modal_ui(ui: &mut Ui, app: &mut App) {
                     |ui| {
                         ui.scope(|ui| {
                             ui.style_mut().visuals.widgets.inactive.bg_stroke =
-                                Stroke::new(1.0, Color32::RED);
+                                Stroke::new(1.0_f32, Color32::RED);
                             ui.style_mut().visuals.widgets.active.bg_stroke =
-                                Stroke::new(1.0, Color32::RED);
+                                Stroke::new(1.0_f32, Color32::RED);
                             ui.style_mut().visuals.widgets.hovered.bg_stroke =
-                                Stroke::new(1.0, Color32::RED);
+                                Stroke::new(1.0_f32, Color32::RED);
 
                             let text = match operation {
                                 DataLossOperation::DeleteTileset { tileset_use, .. } => {
                                     format!("Delete tileset and {} tiles", tileset_use.tile_count)
                                 }
+                                DataLossOperation::ReplacePalette {
+                                    resource_use,
+                                    remap_to_nearest: true,
+                                    ..
+                                } => {
+                                    format!(
+                                        "Delete palette colors and recolor {} tiles",
+                                        resource_use.tile_count
+                                    )
+                                }
                                 DataLossOperation::ReplacePalette { resource_use, .. } => {
                                     format!(
                                         "Delete palette colors and {} tiles",
                                         resource_use.tile_count
                                     )
                                 }
+                                DataLossOperation::ChangeTilesetColumns {
+                                    tileset_use,
+                                    remap_columns: true,
+                                    ..
+                                } => {
+                                    format!(
+                                        "Update tileset and reindex {} tiles",
+                                        tileset_use.tile_count
+                                    )
+                                }
+                                DataLossOperation::ChangeTilesetColumns { tileset_use, .. } => {
+                                    format!(
+                                        "Update tileset and change {} tiles",
+                                        tileset_use.tile_count
+                                    )
+                                }
                                 _ => "Continue (delete data)".to_string(),
                             };
                             if ui.button(text).clicked() {
@@ -129,8 +205,27 @@ pub fn data_loss_modal_ui(ui: &mut Ui, app: &mut App) {
             DataLossOperation::DeleteTileset { tileset_id, .. } => {
                 app.act(Action::DeleteTileset { id: tileset_id });
             }
-            DataLossOperation::ReplacePalette { palette, .. } => {
-                app.act(Action::ReplacePalette { palette });
+            DataLossOperation::ReplacePalette {
+                palette,
+                remap_to_nearest,
+                ..
+            } => {
+                app.act(Action::ReplacePalette {
+                    palette,
+                    remap_to_nearest,
+                });
+            }
+            DataLossOperation::ChangeTilesetColumns {
+                tileset_id,
+                tileset,
+                remap_columns,
+                ..
+            } => {
+                app.act(Action::UpdateTileset {
+                    id: tileset_id,
+                    tileset,
+                    remap_columns,
+                });
             }
             DataLossOperation::Quit => {
                 app.quit(ui.ctx());
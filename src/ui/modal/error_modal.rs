@@ -3,7 +3,7 @@ use egui::{Id, Modal, Ui};
 use crate::{
     app::App,
     data::modal::{ModalResult, ModalState},
-    ui::theme::DEFAULT_THEME,
+    ui::theme::{DEFAULT_THEME, Theme},
 };
 
 pub fn error_modal_ui(ui: &mut Ui, app: &mut App) {
@@ -13,7 +13,7 @@ pub fn error_modal_ui(ui: &mut Ui, app: &mut App) {
     } = &mut app.edit.modal
     {
         Modal::new(Id::new("Error Modal"))
-            .frame(DEFAULT_THEME.modal_frame())
+            .frame(Theme::current(ui.ctx()).modal_frame())
             .show(ui.ctx(), |ui| {
                 ui.set_width(250.0);
 
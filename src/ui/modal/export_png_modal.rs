@@ -1,11 +1,14 @@
-use egui::{Id, Modal, Slider, Ui};
+use egui::{DragValue, Id, Modal, Slider, Ui};
 
 use crate::{
     app::App,
     data::modal::{ModalResult, ModalState},
-    ui::theme::DEFAULT_THEME,
+    ui::theme::{DEFAULT_THEME, Theme},
 };
 
+/// The initial size to fit within, when enabled
+const DEFAULT_FIT_WITHIN: u32 = 1024;
+
 pub fn export_png_modal_ui(ui: &mut Ui, app: &mut App) {
     let modal_to_apply = if let ModalState::ExportPng {
         ref mut settings,
@@ -13,7 +16,7 @@ pub fn export_png_modal_ui(ui: &mut Ui, app: &mut App) {
     } = app.edit.modal
     {
         Modal::new(Id::new("Export PNG Modal"))
-            .frame(DEFAULT_THEME.modal_frame())
+            .frame(Theme::current(ui.ctx()).modal_frame())
             .show(ui.ctx(), |ui| {
                 ui.set_width(250.0);
 
@@ -25,10 +28,25 @@ pub fn export_png_modal_ui(ui: &mut Ui, app: &mut App) {
 
                 ui.add_space(DEFAULT_THEME.modal_spacing);
 
-                ui.label("Scale factor");
-                ui.add(
-                    Slider::new(&mut settings.scale, 1..=32).clamping(egui::SliderClamping::Always),
-                );
+                let mut fit = settings.fit_within.is_some();
+                if ui
+                    .checkbox(&mut fit, "Fit within size")
+                    .on_hover_text("Use the largest whole number scale that fits within the size, so pixels stay crisp")
+                    .changed()
+                {
+                    settings.fit_within = fit.then_some(DEFAULT_FIT_WITHIN);
+                }
+
+                if let Some(fit_within) = settings.fit_within.as_mut() {
+                    ui.label("Maximum width and height (pixels)");
+                    ui.add(DragValue::new(fit_within).range(1..=16384));
+                } else {
+                    ui.label("Scale factor");
+                    ui.add(
+                        Slider::new(&mut settings.scale, 1..=32)
+                            .clamping(egui::SliderClamping::Always),
+                    );
+                }
 
                 ui.add_space(DEFAULT_THEME.modal_spacing);
                 ui.separator();
@@ -3,7 +3,7 @@ use egui::{Id, Modal, Ui};
 use crate::{
     app::App,
     data::modal::{ModalResult, ModalState},
-    ui::theme::DEFAULT_THEME,
+    ui::theme::{DEFAULT_THEME, Theme},
 };
 
 pub fn export_tiled_modal_ui(ui: &mut Ui, app: &mut App) {
@@ -13,16 +13,22 @@ pub fn export_tiled_modal_ui(ui: &mut Ui, app: &mut App) {
     } = app.edit.modal
     {
         Modal::new(Id::new("Export Tiled Modal"))
-            .frame(DEFAULT_THEME.modal_frame())
+            .frame(Theme::current(ui.ctx()).modal_frame())
             .show(ui.ctx(), |ui| {
                 ui.set_width(250.0);
 
                 ui.heading("Export Tiled Map");
 
                 ui.add_space(DEFAULT_THEME.modal_spacing);
-                ui.label("Exports the currently selected map as a .tmx file, with optional .tsx files for tilesets.");
+                if settings.export_all_maps {
+                    ui.label("Exports every map as a .tmx file in a chosen directory, with optional .tsx files for tilesets, shared by all maps.");
+                } else {
+                    ui.label("Exports the currently selected map as a .tmx file, with optional .tsx files for tilesets.");
+                }
                 ui.add_space(DEFAULT_THEME.modal_spacing);
 
+                ui.checkbox(&mut settings.export_all_maps, "Export all maps");
+
                 ui.checkbox(
                     &mut settings.include_layer_data_as_properties,
                     "Include layer data as properties",
@@ -59,6 +65,10 @@ pub fn export_tiled_modal_ui(ui: &mut Ui, app: &mut App) {
     // Note, to avoid issues calling app methods while still
     // holding mutable ref, we produce an optional setting, and apply it here
     if let Some(ModalState::ExportTiled { settings, .. }) = modal_to_apply {
-        app.show_export_tiled_file_modal(&settings);
+        if settings.export_all_maps {
+            app.show_export_tiled_project_folder_modal(&settings);
+        } else {
+            app.show_export_tiled_file_modal(&settings);
+        }
     }
 }
@@ -3,7 +3,7 @@ use egui::{Id, ImageSource, Key, KeyboardShortcut, Modal, Modifiers, Sense, Text
 use crate::{
     app::App,
     data::modal::{ModalResult, ModalState},
-    ui::theme::DEFAULT_THEME,
+    ui::theme::Theme,
 };
 
 const INSTRUCTIONS: ImageSource<'static> = egui::include_image!("../../../assets/instructions.png");
@@ -28,7 +28,7 @@ pub fn help_modal_ui(ui: &mut Ui, app: &mut App) {
 
         let modal_response =
             Modal::new(Id::new("Help Modal"))
-                .frame(DEFAULT_THEME.modal_frame())
+                .frame(Theme::current(ui.ctx()).modal_frame())
                 .show(ui.ctx(), |ui| {
                     ui.set_width(modal_size);
                     ui.set_height(modal_size);
@@ -41,6 +41,8 @@ pub fn help_modal_ui(ui: &mut Ui, app: &mut App) {
                     *result = ModalResult::Apply;
                 }
 
+                    ui.label("Keys 1-9 select one of the first nine layers. For maps with more layers, press 0 to move the keys on to the next nine layers (10-18 and so on), wrapping back to the first nine. Press I to show only the selected layer, and again to show all layers. Press shift+V to show or hide the selected layers. Press M to cycle through select, draw, line, ellipse and erase modes. Press L for line mode, then drag to draw the stamp along a line from where the drag starts to where it stops. Press O for ellipse mode, then drag to draw an ellipse outline, or hold shift when releasing to draw a filled ellipse. Press K to pick the palette color of the tile under the pointer, adding the color to the palette if it isn't already there. Press G to repeat the last drawing or layer action. Press F to select every cell containing a tile on the selected layer.");
+
                     ui.ctx().input_mut(|i| {
                         if i.consume_shortcut(&CLOSE_SHORTCUT)
                             || i.consume_shortcut(&CLOSE_SHORTCUT_ALT)
@@ -6,19 +6,24 @@ use crate::{
         action::Action,
         modal::{ModalResult, ModalState},
     },
-    ui::theme::DEFAULT_THEME,
+    ui::theme::{DEFAULT_THEME, Theme},
 };
 
 pub fn import_tiled_modal_ui(ui: &mut Ui, app: &mut App) {
+    let selected_map = app.selected_map();
+    let selected_map_id = selected_map.map(|map| map.id());
+    let selected_map_name = selected_map.map(|map| map.name());
     let modal_to_apply = if let ModalState::ImportTiled {
         tiled,
         squash_layers,
         prefer_relative_path,
+        use_layer_properties,
+        into_selected_map,
         result,
     } = &mut app.edit.modal
     {
         Modal::new(Id::new("Import Tiled Modal"))
-            .frame(DEFAULT_THEME.modal_frame())
+            .frame(Theme::current(ui.ctx()).modal_frame())
             .show(ui.ctx(), |ui| {
                 ui.set_width(250.0);
 
@@ -35,6 +40,17 @@ pub fn import_tiled_modal_ui(ui: &mut Ui, app: &mut App) {
 
                 ui.checkbox(prefer_relative_path, "Use relative paths for tilesets");
 
+                ui.checkbox(use_layer_properties, "Restore MountainTiles layers")
+                    .on_hover_text("Use the properties written when exporting from MountainTiles to merge the per-color Tiled layers back into the original layers");
+
+                ui.add_enabled_ui(selected_map_name.is_some(), |ui| {
+                    ui.checkbox(into_selected_map, "Add layers to selected map")
+                        .on_hover_text(format!(
+                            "Add the Tiled layers above the existing layers of '{}', instead of creating a new map. The map and tile sizes must match.",
+                            selected_map_name.unwrap_or_default()
+                        ));
+                });
+
                 ui.add_space(DEFAULT_THEME.modal_spacing);
                 ui.separator();
                 ui.add_space(DEFAULT_THEME.modal_spacing);
@@ -62,6 +78,8 @@ pub fn import_tiled_modal_ui(ui: &mut Ui, app: &mut App) {
         tiled,
         squash_layers,
         prefer_relative_path,
+        use_layer_properties,
+        into_selected_map,
         ..
     }) = modal_to_apply
     {
@@ -69,6 +87,8 @@ pub fn import_tiled_modal_ui(ui: &mut Ui, app: &mut App) {
             tiled,
             squash_layers,
             prefer_relative_path,
+            use_layer_properties,
+            into_map: selected_map_id.filter(|_| into_selected_map),
         });
     }
 }
@@ -4,20 +4,27 @@ use crate::{
     app::App,
     data::action::Action,
     data::modal::{ModalResult, ModalState},
+    data::tiles::layer_tiles::LayerKind,
     ui::egui_utils::singleline_focus_and_select,
-    ui::theme::DEFAULT_THEME,
+    ui::theme::{DEFAULT_THEME, Theme},
+    ui::utils::optional_color_ui,
 };
 
 pub fn layer_modal_ui(ui: &mut Ui, app: &mut App) {
     let modal_to_apply = if let ModalState::Layer {
         ref mut name,
         ref mut opacity,
+        ref mut tint,
+        ref mut default_tint,
+        ref mut default_tint_as_text,
+        ref mut kind,
+        ref mut sparse,
         ref mut result,
         ..
     } = app.edit.modal
     {
         Modal::new(Id::new("Layer Modal"))
-            .frame(DEFAULT_THEME.modal_frame())
+            .frame(Theme::current(ui.ctx()).modal_frame())
             .show(ui.ctx(), |ui| {
                 ui.set_width(250.0);
 
@@ -58,6 +65,26 @@ pub fn layer_modal_ui(ui: &mut Ui, app: &mut App) {
                     }
                 };
 
+                ui.add_space(DEFAULT_THEME.modal_spacing);
+
+                optional_color_ui(ui, tint, "Tint", default_tint, default_tint_as_text);
+
+                ui.add_space(DEFAULT_THEME.modal_spacing);
+
+                let mut guide = *kind == LayerKind::Guide;
+                ui.checkbox(&mut guide, "Guide layer").on_hover_text(
+                    "Guide layers can be used for reference while editing, they are shown faded and are not exported",
+                );
+                *kind = if guide {
+                    LayerKind::Guide
+                } else {
+                    LayerKind::Tile
+                };
+
+                ui.checkbox(sparse, "Sparse storage").on_hover_text(
+                    "Store only the cells containing tiles, using less memory for large layers that are mostly empty",
+                );
+
                 ui.add_space(DEFAULT_THEME.modal_spacing);
                 ui.separator();
                 ui.add_space(DEFAULT_THEME.modal_spacing);
@@ -86,6 +113,9 @@ pub fn layer_modal_ui(ui: &mut Ui, app: &mut App) {
         layer_id,
         name,
         opacity,
+        tint,
+        kind,
+        sparse,
         ..
     }) = modal_to_apply
     {
@@ -94,6 +124,9 @@ pub fn layer_modal_ui(ui: &mut Ui, app: &mut App) {
             layer_id,
             name: name.to_string(),
             opacity,
+            tint,
+            kind,
+            sparse,
         };
 
         app.act(action);
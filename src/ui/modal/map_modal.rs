@@ -4,15 +4,16 @@ use crate::{
     app::App,
     data::{
         action::Action,
-        maps::Map,
+        maps::{Map, metadata_from_entries},
         modal::{MapOperation, ModalResult, ModalState},
     },
     geom::u32size2::u32size2,
     ui::egui_utils::user_color_edit_button,
-    ui::theme::DEFAULT_THEME,
+    ui::theme::{DEFAULT_THEME, Theme},
 };
 
 pub fn map_modal_ui(ui: &mut Ui, app: &mut App) {
+    let tilesets = app.state.resources.tilesets();
     let modal_to_apply = if let ModalState::Map {
         ref mut name,
         ref mut width,
@@ -21,12 +22,16 @@ pub fn map_modal_ui(ui: &mut Ui, app: &mut App) {
         ref mut tile_height,
         ref mut background_color,
         ref mut background_color_as_text,
+        ref mut inherit_background,
+        ref mut default_tile,
+        ref brush_tile,
+        ref mut metadata,
         ref operation,
         ref mut result,
     } = app.edit.modal
     {
         Modal::new(Id::new("Map Settings Modal"))
-            .frame(DEFAULT_THEME.modal_frame())
+            .frame(Theme::current(ui.ctx()).modal_frame())
             .show(ui.ctx(), |ui| {
                 ui.set_width(250.0);
 
@@ -59,7 +64,61 @@ pub fn map_modal_ui(ui: &mut Ui, app: &mut App) {
                 ui.add_space(DEFAULT_THEME.modal_spacing);
 
                 ui.label("Background");
-                user_color_edit_button(ui, background_color, background_color_as_text);
+                ui.checkbox(inherit_background, "Use default from application settings");
+                ui.add_enabled_ui(!*inherit_background, |ui| {
+                    user_color_edit_button(ui, background_color, background_color_as_text);
+                });
+
+                ui.add_space(DEFAULT_THEME.modal_spacing);
+
+                ui.label("Default tile, shown in empty cells");
+                ui.horizontal(|ui| {
+                    let tile_text = match default_tile {
+                        Some(tile) => match tilesets.get_by_id(tile.source.tileset_id) {
+                            Some(tileset) => {
+                                format!("{}, tile {}", tileset, tile.source.tile_index.index())
+                            }
+                            None => format!("Tile {}", tile.source.tile_index.index()),
+                        },
+                        None => "None".to_string(),
+                    };
+                    ui.label(tile_text);
+                    if ui
+                        .add_enabled(brush_tile.is_some(), egui::Button::new("Use brush tile"))
+                        .clicked()
+                    {
+                        *default_tile = *brush_tile;
+                    }
+                    if ui
+                        .add_enabled(default_tile.is_some(), egui::Button::new("Clear"))
+                        .clicked()
+                    {
+                        *default_tile = None;
+                    }
+                });
+
+                ui.add_space(DEFAULT_THEME.modal_spacing);
+
+                ui.label("Metadata, as keys and values");
+                let mut to_remove = None;
+                egui::Grid::new("map_metadata_grid").show(ui, |ui| {
+                    for (i, (key, value)) in metadata.iter_mut().enumerate() {
+                        ui.add(egui::TextEdit::singleline(key).desired_width(80.0));
+                        ui.add(egui::TextEdit::singleline(value).desired_width(100.0));
+                        if ui.button("Remove").clicked() {
+                            to_remove = Some(i);
+                        }
+                        ui.end_row();
+                    }
+                });
+                if let Some(i) = to_remove
+                    && i < metadata.len()
+                {
+                    metadata.remove(i);
+                }
+                if ui.button("Add entry").clicked() {
+                    metadata.push((String::new(), String::new()));
+                }
 
                 ui.add_space(DEFAULT_THEME.modal_spacing);
                 ui.separator();
@@ -95,16 +154,41 @@ pub fn map_modal_ui(ui: &mut Ui, app: &mut App) {
         tile_width,
         tile_height,
         background_color,
+        inherit_background,
+        default_tile,
+        metadata,
         operation,
         ..
     }) = modal_to_apply
     {
         let map_size = u32size2(width, height);
         let tile_size = u32size2(tile_width, tile_height);
+        let background_color = (!inherit_background).then_some(background_color);
+
+        if let Err(e) = app.settings.check_map_size(map_size) {
+            app.show_error_modal(&e.to_string());
+            return;
+        }
+
+        let metadata = match metadata_from_entries(&metadata) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                app.show_error_modal(&e.to_string());
+                return;
+            }
+        };
 
         match operation {
             MapOperation::NewMap => {
-                let map = Map::new_with_layer(name, map_size, tile_size, background_color);
+                let mut map = Map::new_with_layer(
+                    name,
+                    map_size,
+                    tile_size,
+                    background_color.unwrap_or(app.settings.default_map_background),
+                );
+                map.tiles.background = background_color;
+                map.tiles.default_tile = default_tile;
+                map.metadata = metadata;
                 app.act(Action::AddMap { map });
             }
             MapOperation::UpdateExistingMap(map_id) => {
@@ -114,7 +198,9 @@ pub fn map_modal_ui(ui: &mut Ui, app: &mut App) {
                     map_size,
                     tile_size,
                     background_color,
+                    default_tile,
                 });
+                app.act(Action::SetMapMetadata { map_id, metadata });
             }
         };
     };
@@ -0,0 +1,109 @@
+use egui::{Id, Modal, Ui};
+
+use crate::{
+    app::App,
+    data::{
+        action::Action,
+        modal::{ModalResult, ModalState},
+        tilesets::parse_tile_index_map,
+    },
+    ui::theme::{DEFAULT_THEME, Theme},
+};
+
+pub fn merge_tilesets_modal_ui(ui: &mut Ui, app: &mut App) {
+    let tilesets = app.state.resources.tilesets();
+    let modal_to_apply = if let ModalState::MergeTilesets {
+        src,
+        dst,
+        index_map_as_text,
+        result,
+    } = &mut app.edit.modal
+    {
+        Modal::new(Id::new("Merge Tilesets Modal"))
+            .frame(Theme::current(ui.ctx()).modal_frame())
+            .show(ui.ctx(), |ui| {
+                ui.set_width(300.0);
+
+                ui.heading("Merge Tileset");
+                ui.add_space(DEFAULT_THEME.modal_spacing);
+
+                let src_name = tilesets
+                    .get_by_id(*src)
+                    .map(|t| format!("{t}"))
+                    .unwrap_or_default();
+                ui.label(format!(
+                    "Changes all tiles using '{}' to use the destination tileset instead, then deletes '{}'.",
+                    src_name, src_name
+                ));
+                ui.add_space(DEFAULT_THEME.modal_spacing);
+
+                ui.label("Destination tileset");
+                let selected_text = dst
+                    .and_then(|id| tilesets.get_by_id(id))
+                    .map(|t| format!("{t}"))
+                    .unwrap_or_default();
+                egui::ComboBox::from_id_salt("merge_tilesets_dst")
+                    .selected_text(selected_text)
+                    .truncate()
+                    .width(ui.available_width())
+                    .show_ui(ui, |ui| {
+                        for tileset in tilesets.iter().filter(|t| t.id() != *src) {
+                            ui.selectable_value(dst, Some(tileset.id()), format!("{tileset}"));
+                        }
+                    });
+
+                ui.add_space(DEFAULT_THEME.modal_spacing);
+
+                ui.label("Tile index mapping, e.g. \"0=4, 1=5\". Tile indices not listed are unchanged.");
+                ui.text_edit_singleline(index_map_as_text);
+                let index_map = parse_tile_index_map(index_map_as_text);
+                if let Err(e) = &index_map {
+                    ui.label(format!("󰀦 {}", e));
+                }
+
+                ui.add_space(DEFAULT_THEME.modal_spacing);
+                ui.separator();
+                ui.add_space(DEFAULT_THEME.modal_spacing);
+
+                egui::Sides::new().show(
+                    ui,
+                    |_ui| {},
+                    |ui| {
+                        if ui
+                            .add_enabled(
+                                dst.is_some() && index_map.is_ok(),
+                                egui::Button::new("Merge"),
+                            )
+                            .clicked()
+                        {
+                            *result = ModalResult::Apply;
+                        }
+                        if ui.button("Cancel").clicked() {
+                            *result = ModalResult::Cancel;
+                        }
+                    },
+                );
+            });
+
+        app.progress_modal_state()
+    } else {
+        None
+    };
+
+    if let Some(ModalState::MergeTilesets {
+        src,
+        dst: Some(dst),
+        index_map_as_text,
+        ..
+    }) = modal_to_apply
+    {
+        match parse_tile_index_map(&index_map_as_text) {
+            Ok(index_map) => app.act(Action::MergeTilesets {
+                src,
+                dst,
+                index_map,
+            }),
+            Err(e) => app.show_error_modal(&e.to_string()),
+        }
+    }
+}
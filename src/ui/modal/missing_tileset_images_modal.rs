@@ -0,0 +1,75 @@
+use egui::{Id, Modal, RichText, Ui};
+
+use crate::{
+    app::App,
+    data::{
+        modal::{ModalResult, ModalState},
+        tilesets::TilesetId,
+    },
+    ui::{
+        file_dialog,
+        theme::{DEFAULT_THEME, Theme},
+    },
+};
+
+pub fn missing_tileset_images_modal_ui(ui: &mut Ui, app: &mut App) {
+    let mut to_locate: Option<TilesetId> = None;
+
+    if let ModalState::MissingTilesetImages { missing, result } = &mut app.edit.modal {
+        Modal::new(Id::new("Missing Tileset Images Modal"))
+            .frame(Theme::current(ui.ctx()).modal_frame())
+            .show(ui.ctx(), |ui| {
+                ui.set_width(400.0);
+
+                ui.heading("Missing Tileset Images");
+                ui.add_space(DEFAULT_THEME.modal_spacing);
+                ui.label("Some tileset images could not be found, so their tiles will be shown as missing. Use \"Locate...\" to select the image for each tileset.");
+                ui.add_space(DEFAULT_THEME.modal_spacing);
+
+                egui::ScrollArea::vertical()
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        for image in missing.iter() {
+                            ui.separator();
+                            egui::Sides::new().show(
+                                ui,
+                                |ui| {
+                                    ui.label(RichText::new(&image.tileset_name).strong());
+                                },
+                                |ui| {
+                                    if ui.button("Locate...").clicked() {
+                                        to_locate = Some(image.tileset_id);
+                                    }
+                                },
+                            );
+                            ui.label(format!("{} at '{}'", image.problem, image.resolved_path));
+                            ui.label(&image.path_notes);
+                        }
+                    });
+
+                ui.add_space(DEFAULT_THEME.modal_spacing);
+                ui.separator();
+                ui.add_space(DEFAULT_THEME.modal_spacing);
+
+                egui::Sides::new().show(
+                    ui,
+                    |_ui| {},
+                    |ui| {
+                        if ui.button("Close").clicked() {
+                            *result = ModalResult::Apply;
+                        }
+                    },
+                );
+            });
+
+        app.progress_modal_state();
+    }
+
+    if let Some(tileset_id) = to_locate {
+        match file_dialog::pick_file() {
+            Ok(Some(path)) => app.locate_missing_tileset_image(ui.ctx(), tileset_id, path),
+            Ok(None) => {}
+            Err(e) => app.show_error_modal(&e.to_string()),
+        }
+    }
+}
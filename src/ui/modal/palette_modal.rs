@@ -7,7 +7,7 @@ use crate::{
         enabled_square_button, sized_main_dir_cross_align_center, square_button,
         unselectable_label, unselectable_label_strong, user_color_edit_button,
     },
-    ui::theme::DEFAULT_THEME,
+    ui::theme::{DEFAULT_THEME, Theme},
 };
 use egui::{Id, Modal, RichText, Ui, vec2};
 use egui_extras::{Column, TableBuilder};
@@ -26,7 +26,7 @@ pub fn palette_modal_ui(ui: &mut Ui, app: &mut App) {
         selected_index.limit_to_palette(palette);
 
         Modal::new(Id::new("Palette Modal"))
-            .frame(DEFAULT_THEME.modal_frame())
+            .frame(Theme::current(ui.ctx()).modal_frame())
             .show(ui.ctx(), |ui| {
                 ui.set_width(250.0);
 
@@ -77,7 +77,7 @@ pub fn palette_modal_ui(ui: &mut Ui, app: &mut App) {
                                             ui,
                                             RichText::new(text)
                                                 .text_style(egui::TextStyle::Monospace)
-                                                .color(DEFAULT_THEME.base_subcontent),
+                                                .color(Theme::current(ui.ctx()).base_subcontent),
                                         );
                                     });
                                     if row.response().clicked() {
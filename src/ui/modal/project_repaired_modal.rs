@@ -0,0 +1,45 @@
+use egui::{Id, Modal, Ui};
+
+use crate::{
+    app::App,
+    data::modal::{ModalResult, ModalState},
+    ui::theme::{DEFAULT_THEME, Theme},
+};
+
+pub fn project_repaired_modal_ui(ui: &mut Ui, app: &mut App) {
+    if let ModalState::ProjectRepaired { repairs, result } = &mut app.edit.modal {
+        Modal::new(Id::new("Project Repaired Modal"))
+            .frame(Theme::current(ui.ctx()).modal_frame())
+            .show(ui.ctx(), |ui| {
+                ui.set_width(400.0);
+
+                ui.heading("Project Repaired");
+                ui.add_space(DEFAULT_THEME.modal_spacing);
+                ui.label("The project contained data that could not be used, and has been repaired. Save the project to keep the repairs.");
+                ui.add_space(DEFAULT_THEME.modal_spacing);
+
+                for repair in repairs.iter() {
+                    ui.label(format!("• {}", repair));
+                }
+
+                ui.add_space(DEFAULT_THEME.modal_spacing);
+                ui.separator();
+                ui.add_space(DEFAULT_THEME.modal_spacing);
+
+                egui::Sides::new().show(
+                    ui,
+                    |_ui| {},
+                    |ui| {
+                        if ui.button("Ok").clicked() {
+                            *result = ModalResult::Apply;
+                        }
+                    },
+                );
+            });
+
+        // Once closed, check for missing images, since this modal replaced that check
+        if app.progress_modal_state().is_some() {
+            app.check_tileset_images();
+        }
+    }
+}
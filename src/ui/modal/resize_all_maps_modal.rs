@@ -0,0 +1,108 @@
+use egui::{Id, Modal, Slider, Ui};
+
+use crate::{
+    app::App,
+    data::{
+        action::Action,
+        modal::{ModalResult, ModalState},
+        tiles::layer_tiles::ResizeAnchor,
+    },
+    geom::u32size2::u32size2,
+    ui::theme::{DEFAULT_THEME, Theme},
+};
+
+pub fn resize_all_maps_modal_ui(ui: &mut Ui, app: &mut App) {
+    let maps = &app.state.maps;
+    let modal_to_apply = if let ModalState::ResizeAllMaps {
+        width,
+        height,
+        anchor,
+        clipped,
+        result,
+    } = &mut app.edit.modal
+    {
+        Modal::new(Id::new("Resize All Maps Modal"))
+            .frame(Theme::current(ui.ctx()).modal_frame())
+            .show(ui.ctx(), |ui| {
+                ui.set_width(300.0);
+
+                ui.heading("Resize All Maps");
+                ui.add_space(DEFAULT_THEME.modal_spacing);
+
+                ui.label("Map width (in tiles)");
+                ui.add(Slider::new(width, 1..=256).clamping(egui::SliderClamping::Never));
+
+                ui.label("Map height (in tiles)");
+                ui.add(Slider::new(height, 1..=256).clamping(egui::SliderClamping::Never));
+
+                ui.add_space(DEFAULT_THEME.modal_spacing);
+
+                ui.label("Anchor existing contents at");
+                egui::ComboBox::from_id_salt("resize_all_maps_anchor")
+                    .selected_text(anchor.name())
+                    .show_ui(ui, |ui| {
+                        for a in ResizeAnchor::ALL {
+                            ui.selectable_value(anchor, a, a.name());
+                        }
+                    });
+
+                // Only check the maps again when the size or anchor change
+                let map_size = u32size2(*width, *height);
+                if !matches!(clipped, Some((size, a, _)) if (*size, *a) == (map_size, *anchor)) {
+                    let names = maps
+                        .iter()
+                        .filter(|map| map.tiles.resize_clips(map_size, *anchor))
+                        .map(|map| map.name())
+                        .collect();
+                    *clipped = Some((map_size, *anchor, names));
+                }
+                let clipped = clipped
+                    .as_ref()
+                    .map(|(_, _, names)| names.as_slice())
+                    .unwrap_or_default();
+                if !clipped.is_empty() {
+                    ui.add_space(DEFAULT_THEME.modal_spacing);
+                    ui.label(format!(
+                        "󰀦 Tiles outside the new size will be lost from: {}",
+                        clipped.join(", ")
+                    ));
+                }
+
+                ui.add_space(DEFAULT_THEME.modal_spacing);
+                ui.separator();
+                ui.add_space(DEFAULT_THEME.modal_spacing);
+
+                egui::Sides::new().show(
+                    ui,
+                    |_ui| {},
+                    |ui| {
+                        if ui.button("Resize").clicked() {
+                            *result = ModalResult::Apply;
+                        }
+                        if ui.button("Cancel").clicked() {
+                            *result = ModalResult::Cancel;
+                        }
+                    },
+                );
+            });
+
+        app.progress_modal_state()
+    } else {
+        None
+    };
+
+    if let Some(ModalState::ResizeAllMaps {
+        width,
+        height,
+        anchor,
+        ..
+    }) = modal_to_apply
+    {
+        let map_size = u32size2(width, height);
+        if let Err(e) = app.settings.check_map_size(map_size) {
+            app.show_error_modal(&e.to_string());
+            return;
+        }
+        app.act(Action::ResizeAllMaps { map_size, anchor });
+    }
+}
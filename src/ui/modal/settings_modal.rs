@@ -2,18 +2,31 @@ use egui::{Id, Modal, Ui};
 
 use crate::{
     app::App,
-    data::modal::{ModalResult, ModalState},
-    ui::theme::DEFAULT_THEME,
+    data::{
+        modal::{ModalResult, ModalState},
+        tiles::tile_color::UserColor,
+    },
+    ui::{
+        theme::{DEFAULT_THEME, Theme, ThemeChoice},
+        tiles::HighlightStyle,
+        utils::user_color_edit_button,
+    },
 };
 
+/// Initial color when choosing custom checkerboard colors, close to the theme's selected
+/// fill color (which can't be represented exactly, since it's brighter than its alpha)
+const DEFAULT_CHECKERBOARD_COLOR: UserColor = UserColor::new(204, 51, 255, 120);
+
 pub fn settings_modal_ui(ui: &mut Ui, app: &mut App) {
     let modal_to_apply = if let ModalState::Settings {
         ref mut settings,
+        ref mut default_map_background_as_text,
+        ref mut checkerboard_colors_as_text,
         ref mut result,
     } = app.edit.modal
     {
         Modal::new(Id::new("Application Settings Modal"))
-            .frame(DEFAULT_THEME.modal_frame())
+            .frame(Theme::current(ui.ctx()).modal_frame())
             .show(ui.ctx(), |ui| {
                 ui.set_width(250.0);
 
@@ -21,11 +34,122 @@ pub fn settings_modal_ui(ui: &mut Ui, app: &mut App) {
 
                 ui.add_space(DEFAULT_THEME.modal_spacing);
 
+                ui.horizontal(|ui| {
+                    ui.label("Theme");
+                    egui::ComboBox::from_id_salt("Theme")
+                        .selected_text(settings.theme.name())
+                        .show_ui(ui, |ui| {
+                            for choice in ThemeChoice::ALL {
+                                ui.selectable_value(&mut settings.theme, choice, choice.name());
+                            }
+                        });
+                });
+
+                let mut pattern = settings.highlight_style == HighlightStyle::Pattern;
+                if ui
+                    .checkbox(&mut pattern, "Striped highlights")
+                    .on_hover_text(
+                        "Show selected cells and cells to be erased with stripes sloping in opposite directions, so they can be told apart without relying on color",
+                    )
+                    .changed()
+                {
+                    settings.highlight_style = if pattern {
+                        HighlightStyle::Pattern
+                    } else {
+                        HighlightStyle::Fill
+                    };
+                }
+
+                ui.add_space(DEFAULT_THEME.modal_spacing);
+
                 ui.checkbox(
                     &mut settings.tileset_grid_spacing_enabled,
                     "Show gridlines in tilesets",
                 );
 
+                ui.checkbox(
+                    &mut settings.reset_transform_on_change,
+                    "Clear brush transform on change",
+                )
+                .on_hover_text(
+                    "Clear any rotation or mirroring of the brush when changing mode, tileset, tiles or layer",
+                );
+
+                ui.checkbox(
+                    &mut settings.snap_scene_to_pixels,
+                    "Align map to screen pixels",
+                )
+                .on_hover_text(
+                    "After panning or zooming, move the map slightly so it lines up with screen pixels, keeping tiles crisp",
+                );
+
+                #[cfg(not(target_arch = "wasm32"))]
+                ui.checkbox(
+                    &mut settings.watch_tileset_images,
+                    "Reload tileset images when changed",
+                )
+                .on_hover_text(
+                    "Watch tileset image files, and reload each image when it is saved, e.g. from an image editor",
+                );
+
+                ui.add_space(DEFAULT_THEME.modal_spacing);
+
+                ui.label("Maximum tiles in a map (width x height)");
+                ui.add(
+                    egui::DragValue::new(&mut settings.max_map_tiles)
+                        .range(1..=u32::MAX as u64)
+                        .speed(1000.0),
+                );
+
+                ui.add_space(DEFAULT_THEME.modal_spacing);
+
+                ui.label("Undo merge time (seconds)")
+                    .on_hover_text(
+                        "Changes made in quick succession, before an edit is finished, are undone in one step",
+                    );
+                ui.add(
+                    egui::DragValue::new(&mut settings.undo_coalesce_seconds)
+                        .range(0.0..=5.0)
+                        .speed(0.05),
+                );
+
+                ui.add_space(DEFAULT_THEME.modal_spacing);
+
+                ui.label("Default map background");
+                user_color_edit_button(
+                    ui,
+                    &mut settings.default_map_background,
+                    default_map_background_as_text,
+                );
+
+                ui.add_space(DEFAULT_THEME.modal_spacing);
+
+                let mut custom_checkerboard = settings.checkerboard_colors.is_some();
+                ui.checkbox(
+                    &mut custom_checkerboard,
+                    "Custom tileset checkerboard colors",
+                );
+                match (custom_checkerboard, settings.checkerboard_colors.as_mut()) {
+                    (true, Some(colors)) => {
+                        ui.horizontal(|ui| {
+                            for (color, as_text) in colors
+                                .iter_mut()
+                                .zip(checkerboard_colors_as_text.iter_mut())
+                            {
+                                user_color_edit_button(ui, color, as_text);
+                            }
+                        });
+                    }
+                    (true, None) => {
+                        settings.checkerboard_colors =
+                            Some([DEFAULT_CHECKERBOARD_COLOR, UserColor::TRANSPARENT]);
+                    }
+                    (false, _) => settings.checkerboard_colors = None,
+                }
+
+                ui.label("Checkerboard square size (in tiles)");
+                ui.add(egui::Slider::new(&mut settings.checkerboard_size, 1..=8));
+
                 ui.add_space(DEFAULT_THEME.modal_spacing);
                 ui.separator();
                 ui.add_space(DEFAULT_THEME.modal_spacing);
@@ -52,6 +176,8 @@ pub fn settings_modal_ui(ui: &mut Ui, app: &mut App) {
     // Note, to avoid issues calling app methods while still
     // holding mutable ref, we produce an optional setting, and apply it here
     if let Some(ModalState::Settings { settings, .. }) = modal_to_apply {
+        ui.ctx().set_theme(settings.theme.preference());
         app.settings = settings;
+        app.update_undo_settings();
     }
 }
@@ -0,0 +1,150 @@
+use egui::{Id, Modal, Ui};
+
+use crate::{
+    app::App,
+    data::{
+        action::Action,
+        modal::{ModalResult, ModalState},
+        tiles::{Tiles, tile_color::UserColor, tileset_tiles::TilesetTiles},
+        tilesets::Tilesets,
+    },
+    geom::u32size2::U32Size2,
+    ui::{
+        theme::{DEFAULT_THEME, Theme},
+        tiles::{Overlay, tiles},
+        tileset::tileset_message,
+    },
+};
+
+const PREVIEW_SIZE: f32 = 256.0;
+
+pub fn split_tileset_modal_ui(ui: &mut Ui, app: &mut App) {
+    let tilesets = app.state.resources.tilesets();
+    let modal_to_apply = if let ModalState::SplitTileset {
+        tileset_id,
+        selection,
+        regions,
+        result,
+    } = &mut app.edit.modal
+    {
+        let tileset = tilesets.get_by_id(*tileset_id);
+        Modal::new(Id::new("Split Tileset Modal"))
+            .frame(Theme::current(ui.ctx()).modal_frame())
+            .show(ui.ctx(), |ui| {
+                ui.set_width(300.0);
+
+                ui.heading("Split Tileset");
+                ui.add_space(DEFAULT_THEME.modal_spacing);
+
+                ui.label("Drag to select part of the image, then add it as a region. Each region becomes a new tileset using the same image.");
+                ui.add_space(DEFAULT_THEME.modal_spacing);
+
+                if let Some(tileset) = tileset {
+                    // Show the whole image, even if the tileset itself only uses part of it
+                    let mut whole_image = tileset.clone();
+                    whole_image.region = None;
+                    whole_image.columns = None;
+                    whole_image.tilecount = None;
+                    let mut preview_tilesets = Tilesets::default();
+                    let preview_id = preview_tilesets.push_tileset(whole_image.clone());
+
+                    if app.textures.texture_for_tileset(ui.ctx(), &whole_image).is_ok() {
+                        let mut tileset_tiles = TilesetTiles {
+                            foreground: tileset.foreground.unwrap_or(UserColor::WHITE),
+                            background: tileset.background.unwrap_or(UserColor::BLACK),
+                            tileset_id: preview_id,
+                            tile_size: tileset.tile_size,
+                            map_size: whole_image.grid_size(),
+                            tile_count: whole_image.tile_count(),
+                            scale: 1.0,
+                            gap: U32Size2::ZERO,
+                        };
+                        tileset_tiles.scale = tileset_tiles.scale_for_square_size(PREVIEW_SIZE);
+
+                        let response = ui.add(tiles(
+                            &tileset_tiles,
+                            app.state.resources.palette(),
+                            &preview_tilesets,
+                            &app.textures,
+                            Some(selection),
+                            app.settings.highlight_style,
+                            Overlay::None,
+                        ));
+                        if let Some(event) = tileset_tiles.event(&response) {
+                            selection.apply_tile_event(&event, false, false);
+                        }
+                    } else {
+                        tileset_message(ui, "Tileset image could not be loaded", PREVIEW_SIZE);
+                    }
+
+                    let selected_rect = selection.range_rect();
+                    if ui
+                        .add_enabled(selected_rect.is_some(), egui::Button::new("Add region"))
+                        .clicked()
+                        && let Some(rect) = selected_rect
+                    {
+                        regions.push((format!("{} {}", tileset.name, regions.len() + 1), rect));
+                        selection.clear();
+                    }
+                }
+
+                ui.add_space(DEFAULT_THEME.modal_spacing);
+
+                let mut to_remove = None;
+                egui::Grid::new("split_tileset_regions_grid").show(ui, |ui| {
+                    for (i, (name, rect)) in regions.iter_mut().enumerate() {
+                        ui.add(egui::TextEdit::singleline(name).desired_width(120.0));
+                        let size = rect.size();
+                        ui.label(format!("{}, {} ({}x{})", rect.min.x, rect.min.y, size.w, size.h));
+                        if ui.button("Remove").clicked() {
+                            to_remove = Some(i);
+                        }
+                        ui.end_row();
+                    }
+                });
+                if let Some(i) = to_remove
+                    && i < regions.len()
+                {
+                    regions.remove(i);
+                }
+
+                ui.add_space(DEFAULT_THEME.modal_spacing);
+                ui.separator();
+                ui.add_space(DEFAULT_THEME.modal_spacing);
+
+                egui::Sides::new().show(
+                    ui,
+                    |_ui| {},
+                    |ui| {
+                        if ui
+                            .add_enabled(
+                                tileset.is_some() && !regions.is_empty(),
+                                egui::Button::new("Split"),
+                            )
+                            .clicked()
+                        {
+                            *result = ModalResult::Apply;
+                        }
+                        if ui.button("Cancel").clicked() {
+                            *result = ModalResult::Cancel;
+                        }
+                    },
+                );
+            });
+
+        app.progress_modal_state()
+    } else {
+        None
+    };
+
+    if let Some(ModalState::SplitTileset {
+        tileset_id,
+        regions,
+        ..
+    }) = modal_to_apply
+        && let Some(tileset) = app.state.resources.tilesets().get_by_id(tileset_id)
+    {
+        let tilesets = tileset.split(&regions);
+        app.act(Action::AddSplitTilesets { tilesets });
+    }
+}
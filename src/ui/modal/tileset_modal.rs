@@ -3,35 +3,85 @@ use crate::{
     data::{
         action::Action,
         modal::{ModalResult, ModalState, TilesetOperation},
-        tiles::{Tiles, tile_color::UserColor, tileset_tiles::TilesetTiles},
-        tilesets::TilesetMode,
+        settings::Settings,
+        tiles::{TileIndex, Tiles, tile_color::UserColor, tileset_tiles::TilesetTiles},
+        tilesets::{Tileset, TilesetMode},
+        wang_set::{BOTTOM_LEFT, BOTTOM_RIGHT, TOP_LEFT, TOP_RIGHT, WangSet},
     },
     geom::u32size2::U32Size2,
     ui::{
         file_dialog,
-        theme::DEFAULT_THEME,
+        theme::{DEFAULT_THEME, Theme},
         tiles::{Overlay, tiles},
         tileset::tileset_message,
+        tileset_textures::TilesetTextures,
         utils::{optional_color_ui, user_color_edit_button},
     },
 };
-use egui::{Color32, Id, Label, Modal, Slider, Ui};
+use egui::{
+    Color32, DragValue, Id, Key, KeyboardShortcut, Label, Modal, Modifiers, Rect, RichText, Sense,
+    Slider, Ui, vec2,
+};
 
 const PREVIEW_SIZE: f32 = 256.0;
-const OVERLAY_COLOR: Color32 = DEFAULT_THEME.selected_fill;
-const OVERLAY: Overlay = Overlay::Checkerboard {
-    color: OVERLAY_COLOR,
+
+const SWAP_COLORS_SHORTCUT: KeyboardShortcut = KeyboardShortcut {
+    modifiers: Modifiers {
+        alt: false,
+        ctrl: false,
+        shift: true,
+        mac_cmd: false,
+        command: true,
+    },
+    logical_key: Key::X,
 };
 
+/// The checkerboard overlay for the preview, using the colors and size from settings,
+/// or the theme's selected fill where there are no colors set
+fn overlay(settings: &Settings, theme: &Theme) -> Overlay {
+    Overlay::Checkerboard {
+        colors: settings
+            .checkerboard_colors
+            .map(|colors| colors.map(|color| color.as_premultiplied_color32()))
+            .unwrap_or([theme.selected_fill, Color32::TRANSPARENT]),
+        size: settings.checkerboard_size,
+    }
+}
+
+/// The size in tiles of the image for `tileset`, [`None`] while the image is still loading,
+/// or an error message if the image failed to load or is too small for a single tile
+fn image_size_in_tiles(
+    ctx: &egui::Context,
+    textures: &TilesetTextures,
+    tileset: &Tileset,
+) -> Result<Option<U32Size2>, String> {
+    let texture_poll = textures
+        .texture_for_tileset(ctx, tileset)
+        .map_err(|e| e.to_string())?;
+    match texture_poll.size() {
+        Some(image_size) => {
+            let size_in_tiles = U32Size2::lossy_from_vec2(&image_size) / tileset.tile_size;
+            if size_in_tiles.area() == 0 {
+                Err("Image is smaller than a single tile, check the tile size".to_string())
+            } else {
+                Ok(Some(size_in_tiles))
+            }
+        }
+        None => Ok(None),
+    }
+}
+
 pub fn tileset_settings_ui(ui: &mut Ui, app: &mut App) {
     if let ModalState::Tileset {
         ref mut tilesets,
+        ref operation,
         ref mut default_foreground,
         ref mut default_foreground_as_text,
         ref mut default_background,
         ref mut default_background_as_text,
         ref mut default_transparent,
         ref mut default_transparent_as_text,
+        ref mut lock_square,
         ..
     } = app.edit.modal
     {
@@ -58,6 +108,18 @@ pub fn tileset_settings_ui(ui: &mut Ui, app: &mut App) {
                 }
                 ui.add_space(6.0);
 
+                // Unlike browsing, keep the existing name, and respect the preference for
+                // relative paths, for fixing up paths after the project or image is moved
+                if matches!(operation, TilesetOperation::UpdateExistingTileset(_))
+                    && ui.button("Locate image...").clicked()
+                {
+                    if let Ok(Some(path)) = file_dialog::pick_file() {
+                        tileset.relocate(path, app.textures.base_dir());
+                        app.textures.refresh_tileset(ui.ctx(), tileset);
+                    }
+                }
+                ui.add_space(6.0);
+
                 if ui.button("󰑐 Reload image").clicked() {
                     app.textures.refresh_tileset(ui.ctx(), tileset);
                 }
@@ -72,8 +134,16 @@ pub fn tileset_settings_ui(ui: &mut Ui, app: &mut App) {
 
             let selected_text = tileset.mode.description();
             let direct = TilesetMode::Direct;
+            let tolerance = match tileset.mode {
+                TilesetMode::TransparentBackground { tolerance, .. } => tolerance,
+                _ => 0,
+            };
             let transparent = TilesetMode::TransparentBackground {
                 background: *default_transparent,
+                tolerance,
+            };
+            let foreground_background = TilesetMode::ForegroundBackground {
+                background: *default_transparent,
             };
             egui::ComboBox::from_id_salt("tileset_mode")
                 .selected_text(selected_text)
@@ -83,11 +153,29 @@ pub fn tileset_settings_ui(ui: &mut Ui, app: &mut App) {
                 .show_ui(ui, |ui| {
                     ui.selectable_value(&mut tileset.mode, direct, direct.description());
                     ui.selectable_value(&mut tileset.mode, transparent, transparent.description());
+                    ui.selectable_value(
+                        &mut tileset.mode,
+                        foreground_background,
+                        foreground_background.description(),
+                    );
                 });
 
+            let previous_tileset = tileset.clone();
             match tileset.mode {
                 TilesetMode::Direct => {}
-                TilesetMode::TransparentBackground { ref mut background } => {
+                TilesetMode::TransparentBackground {
+                    ref mut background,
+                    ref mut tolerance,
+                } => {
+                    user_color_edit_button(ui, background, default_transparent_as_text);
+                    *default_transparent = *background;
+                    ui.label("Tolerance");
+                    if ui.add(Slider::new(tolerance, 0..=255)).changed() {
+                        // Release the texture loaded with the previous tolerance
+                        app.textures.refresh_tileset(ui.ctx(), &previous_tileset);
+                    }
+                }
+                TilesetMode::ForegroundBackground { ref mut background } => {
                     user_color_edit_button(ui, background, default_transparent_as_text);
                     *default_transparent = *background;
                 }
@@ -95,15 +183,62 @@ pub fn tileset_settings_ui(ui: &mut Ui, app: &mut App) {
 
             ui.add_space(DEFAULT_THEME.modal_spacing);
 
+            if ui
+                .checkbox(lock_square, "Square tiles")
+                .on_hover_text("Keep tile width and height equal")
+                .changed()
+                && *lock_square
+            {
+                tileset.tile_size.h = tileset.tile_size.w;
+            }
+
             ui.label("Tile width (in pixels)");
-            ui.add(
-                Slider::new(&mut tileset.tile_size.w, 4..=32).clamping(egui::SliderClamping::Never),
-            );
+            if ui
+                .add(
+                    Slider::new(&mut tileset.tile_size.w, 4..=32)
+                        .clamping(egui::SliderClamping::Never),
+                )
+                .changed()
+                && *lock_square
+            {
+                tileset.tile_size.h = tileset.tile_size.w;
+            }
 
             ui.label("Tile height (in pixels)");
-            ui.add(
-                Slider::new(&mut tileset.tile_size.h, 4..=32).clamping(egui::SliderClamping::Never),
-            );
+            if ui
+                .add(
+                    Slider::new(&mut tileset.tile_size.h, 4..=32)
+                        .clamping(egui::SliderClamping::Never),
+                )
+                .changed()
+                && *lock_square
+            {
+                tileset.tile_size.w = tileset.tile_size.h;
+            }
+
+            ui.add_space(DEFAULT_THEME.modal_spacing);
+
+            ui.horizontal(|ui| {
+                let mut override_columns = tileset.columns.is_some();
+                if ui
+                    .checkbox(&mut override_columns, "Columns")
+                    .on_hover_text(
+                        "Use fewer columns of tiles than fit in the image, for sheets with unused space on the right",
+                    )
+                    .changed()
+                {
+                    tileset.columns = override_columns.then_some(tileset.size_in_tiles.w);
+                }
+                let max_columns = tileset.size_in_tiles.w.max(1);
+                match tileset.columns {
+                    Some(ref mut columns) => {
+                        ui.add(DragValue::new(columns).range(1..=max_columns));
+                    }
+                    None => {
+                        ui.label(format!("All ({})", tileset.size_in_tiles.w));
+                    }
+                }
+            });
 
             ui.add_space(DEFAULT_THEME.modal_spacing);
             optional_color_ui(
@@ -123,10 +258,86 @@ pub fn tileset_settings_ui(ui: &mut Ui, app: &mut App) {
                 default_background,
                 default_background_as_text,
             );
+
+            ui.add_space(DEFAULT_THEME.modal_spacing);
+
+            let swap_shortcut = ui.ctx().format_shortcut(&SWAP_COLORS_SHORTCUT);
+            let swap_clicked = ui
+                .button("󰓡 Swap foreground/background")
+                .on_hover_text(format!("Exchange the preview colors ({swap_shortcut})"))
+                .clicked();
+            if swap_clicked || ui.input_mut(|i| i.consume_shortcut(&SWAP_COLORS_SHORTCUT)) {
+                tileset.swap_foreground_background();
+            }
+
+            ui.add_space(DEFAULT_THEME.modal_spacing);
+
+            let tile_count = tileset.tile_count();
+            wang_set_ui(ui, &mut tileset.wang_set, tile_count);
         }
     }
 }
 
+/// Draw a small icon showing which corners are set in a [`WangSet`] corner mask
+fn corners_icon(ui: &mut Ui, corners: u8) {
+    let (rect, _response) = ui.allocate_exact_size(vec2(16.0, 16.0), Sense::hover());
+    let painter = ui.painter();
+    let theme = Theme::current(ui.ctx());
+    painter.rect_filled(rect, 0.0, theme.base300);
+    let half = rect.size() / 2.0;
+    for (bit, offset) in [
+        (TOP_LEFT, vec2(0.0, 0.0)),
+        (TOP_RIGHT, vec2(half.x, 0.0)),
+        (BOTTOM_RIGHT, half),
+        (BOTTOM_LEFT, vec2(0.0, half.y)),
+    ] {
+        if corners & bit != 0 {
+            painter.rect_filled(
+                Rect::from_min_size(rect.min + offset, half),
+                0.0,
+                theme.selected,
+            );
+        }
+    }
+}
+
+/// Edit the optional [`WangSet`] for a tileset, choosing a tile index for each corner mask
+fn wang_set_ui(ui: &mut Ui, wang_set: &mut Option<Box<WangSet>>, tile_count: u32) {
+    let mut enabled = wang_set.is_some();
+    ui.checkbox(&mut enabled, "Auto-tiling (corners)");
+    if enabled && wang_set.is_none() {
+        *wang_set = Some(Box::new(WangSet::new()));
+    } else if !enabled {
+        *wang_set = None;
+    }
+
+    if let Some(wang_set) = wang_set {
+        ui.label("Tile index to use for each set of terrain corners:");
+        let max_index = tile_count.saturating_sub(1);
+        egui::Grid::new("wang_set_grid").show(ui, |ui| {
+            for (corners, tile) in wang_set.tiles.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    corners_icon(ui, corners as u8);
+                    let mut tile_enabled = tile.is_some();
+                    ui.checkbox(&mut tile_enabled, "");
+                    match (tile_enabled, tile.as_mut()) {
+                        (true, Some(tile_index)) => {
+                            let mut index = tile_index.index();
+                            ui.add(DragValue::new(&mut index).range(0..=max_index));
+                            *tile_index = TileIndex::new(index);
+                        }
+                        (true, None) => *tile = Some(TileIndex::new(0)),
+                        (false, _) => *tile = None,
+                    }
+                });
+                if corners % 2 == 1 {
+                    ui.end_row();
+                }
+            }
+        });
+    }
+}
+
 pub fn tileset_preview_ui(ui: &mut Ui, app: &mut App) {
     if let ModalState::Tileset {
         ref mut tilesets, ..
@@ -148,7 +359,8 @@ pub fn tileset_preview_ui(ui: &mut Ui, app: &mut App) {
                             background: tileset.background.unwrap_or(UserColor::BLACK),
                             tileset_id: tileset.id(),
                             tile_size: tileset.tile_size,
-                            map_size: tileset.size_in_tiles,
+                            map_size: tileset.grid_size(),
+                            tile_count: tileset.tile_count(),
                             scale: 1.0,
                             gap: U32Size2::ZERO,
                         };
@@ -162,7 +374,8 @@ pub fn tileset_preview_ui(ui: &mut Ui, app: &mut App) {
                             tilesets,
                             textures,
                             None,
-                            OVERLAY,
+                            app.settings.highlight_style,
+                            overlay(&app.settings, Theme::current(ui.ctx())),
                         ));
                         ui.label(format!(
                             "Preview ({}x{} px 󰁔 {}x{} tiles)",
@@ -171,19 +384,26 @@ pub fn tileset_preview_ui(ui: &mut Ui, app: &mut App) {
                             size_in_tiles.w,
                             size_in_tiles.h
                         ));
+                        if size_in_tiles.area() == 0 {
+                            ui.label(
+                                RichText::new("Image is smaller than a single tile")
+                                    .color(ui.visuals().error_fg_color),
+                            );
+                        }
                     }
                     None => {
                         tileset_message(ui, "Loading image...", PREVIEW_SIZE);
                         ui.label("Preview");
                     }
                 },
-                Err(_) => {
+                Err(e) => {
                     tileset_message(
                         ui,
                         "Select a valid image file (e.g. png) above",
                         PREVIEW_SIZE,
                     );
                     ui.label("Preview");
+                    ui.label(RichText::new(e.to_string()).color(ui.visuals().error_fg_color));
                 }
             }
         }
@@ -192,11 +412,24 @@ pub fn tileset_preview_ui(ui: &mut Ui, app: &mut App) {
 
 pub fn tileset_buttons_ui(ui: &mut Ui, app: &mut App) {
     if let ModalState::Tileset {
+        ref tilesets,
         ref operation,
         ref mut result,
         ..
     } = app.edit.modal
     {
+        // Only allow applying once the image has loaded and the preview has
+        // updated the tileset size to match it, so a stale size can't be used
+        let image_status = match tilesets.last() {
+            Some(tileset) => {
+                image_size_in_tiles(ui.ctx(), &app.textures, tileset).and_then(|size| match size {
+                    Some(size) if size == tileset.size_in_tiles => Ok(()),
+                    _ => Err("Waiting for image to load".to_string()),
+                })
+            }
+            None => Err("No tileset".to_string()),
+        };
+
         egui::Sides::new().show(
             ui,
             |_ui| {},
@@ -205,7 +438,12 @@ pub fn tileset_buttons_ui(ui: &mut Ui, app: &mut App) {
                     TilesetOperation::NewTileset => "Create",
                     TilesetOperation::UpdateExistingTileset(_) => "Apply",
                 };
-                if ui.button(confirm_text).clicked() {
+                let confirm = ui.add_enabled(image_status.is_ok(), egui::Button::new(confirm_text));
+                let confirm = match &image_status {
+                    Err(message) => confirm.on_disabled_hover_text(message),
+                    Ok(()) => confirm,
+                };
+                if confirm.clicked() {
                     *result = ModalResult::Apply;
                 }
                 if ui.button("Cancel").clicked() {
@@ -231,7 +469,7 @@ pub fn tileset_modal_ui(ui: &mut Ui, app: &mut App) {
     }
 
     Modal::new(Id::new("Tileset Modal"))
-        .frame(DEFAULT_THEME.modal_frame())
+        .frame(Theme::current(ui.ctx()).modal_frame())
         .show(ui.ctx(), |ui| {
             ui.set_height(430.0);
 
@@ -304,7 +542,7 @@ pub fn tileset_modal_ui(ui: &mut Ui, app: &mut App) {
                     app.act(Action::AddTileset { tileset });
                 }
                 TilesetOperation::UpdateExistingTileset(id) => {
-                    app.act(Action::UpdateTileset { id, tileset });
+                    app.prompt_to_update_tileset(id, tileset);
                 }
             }
         }
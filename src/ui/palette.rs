@@ -1,6 +1,5 @@
 use crate::{
-    app::App, data::palette::palette_index, data::tiles::tile_color::UserColor,
-    ui::theme::DEFAULT_THEME,
+    app::App, data::palette::palette_index, data::tiles::tile_color::UserColor, ui::theme::Theme,
 };
 use egui::{Response, Sense, StrokeKind, Ui, Vec2, vec2};
 
@@ -21,7 +20,7 @@ pub fn color_edit_ui(ui: &mut Ui, color: &UserColor, selected: bool) -> Response
             ui.painter().rect_stroke(
                 rect,
                 corner_radius,
-                DEFAULT_THEME.selected_stroke,
+                Theme::current(ui.ctx()).selected_stroke,
                 StrokeKind::Inside,
             );
         }
@@ -29,6 +28,43 @@ pub fn color_edit_ui(ui: &mut Ui, color: &UserColor, selected: bool) -> Response
     response
 }
 
+/// A compact indicator of the selected palette color, between the previous and next
+/// colors, which can be clicked to select them (as for the `[` and `]` shortcuts)
+pub fn palette_index_indicator_ui(ui: &mut Ui, app: &mut App) {
+    let palette = app.state.resources.palette();
+    let selected_index = app.edit.selected_palette_index();
+    let previous_index = selected_index.previous_within_palette(palette);
+    let next_index = selected_index.next_within_palette(palette);
+
+    let mut new_index = None;
+    ui.scope(|ui| {
+        ui.style_mut().spacing.item_spacing.x = 4.0;
+        for (index, selected, hover_text) in [
+            (previous_index, false, "Previous color ( [ )"),
+            (selected_index, true, "Selected color"),
+            (next_index, false, "Next color ( ] )"),
+        ] {
+            // With a single color there are no neighbours to show
+            if !selected && palette.len() < 2 {
+                continue;
+            }
+            if let Some(color) = palette.color_option(index) {
+                if color_edit_ui(ui, &color, selected)
+                    .on_hover_text(hover_text)
+                    .clicked()
+                {
+                    new_index = Some(index);
+                }
+            }
+        }
+        ui.label(format!("{}", selected_index.index()));
+    });
+
+    if let Some(index) = new_index {
+        app.select_palette_index(index);
+    }
+}
+
 pub fn palette_ui(ui: &mut Ui, app: &mut App) {
     let selected_index = app.edit.selected_palette_index();
     ui.horizontal_wrapped(|ui| {
@@ -47,5 +83,16 @@ pub fn palette_ui(ui: &mut Ui, app: &mut App) {
         if ui.button("󰏫...").clicked() {
             app.show_current_palette_modal();
         }
+        if ui
+            .add_enabled(
+                app.selected_map_edit_state()
+                    .is_some_and(|edit| !edit.selection().is_empty()),
+                egui::Button::new("󰸌 Recolor"),
+            )
+            .on_hover_text("Recolor the selected tiles with the selected color")
+            .clicked()
+        {
+            app.recolor_selection();
+        }
     });
 }
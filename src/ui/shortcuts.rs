@@ -1,7 +1,8 @@
 use egui::{Context, Key, KeyboardShortcut, Modifiers};
 
 use crate::{
-    app::App, geom::i32pos2::i32pos2, geom::transform::Transform, selection::ShiftDirection,
+    app::App, data::edit_state::LAYER_SHORTCUT_BANK_SIZE, geom::i32pos2::i32pos2,
+    geom::transform::Transform, selection::ShiftDirection,
 };
 
 const NEW_SHORTCUT: KeyboardShortcut = KeyboardShortcut {
@@ -32,6 +33,19 @@ const EXPORT_PNG_SHORTCUT: KeyboardShortcut = KeyboardShortcut {
     logical_key: Key::E,
 };
 
+// Note that this must be consumed before the reset zoom shortcut, since that
+// will also match when shift is held.
+pub const RELOAD_TILESETS_SHORTCUT: KeyboardShortcut = KeyboardShortcut {
+    modifiers: Modifiers {
+        alt: false,
+        ctrl: false,
+        shift: true,
+        mac_cmd: false,
+        command: true,
+    },
+    logical_key: Key::R,
+};
+
 const RESET_ZOOM_SHORTCUT: KeyboardShortcut = KeyboardShortcut {
     modifiers: Modifiers::COMMAND,
     logical_key: Key::R,
@@ -72,6 +86,12 @@ const CUT_SHORTCUT: KeyboardShortcut = KeyboardShortcut {
     modifiers: Modifiers::NONE,
     logical_key: Key::X,
 };
+// Note that this must be consumed before the paste shortcut, since that
+// will also match when shift is held.
+const TOGGLE_LAYER_VISIBLE_SHORTCUT: KeyboardShortcut = KeyboardShortcut {
+    modifiers: Modifiers::SHIFT,
+    logical_key: Key::V,
+};
 const PASTE_SHORTCUT: KeyboardShortcut = KeyboardShortcut {
     modifiers: Modifiers::NONE,
     logical_key: Key::V,
@@ -137,6 +157,14 @@ const DRAW_MODE_SHORTCUT: KeyboardShortcut = KeyboardShortcut {
     modifiers: Modifiers::NONE,
     logical_key: Key::D,
 };
+const LINE_MODE_SHORTCUT: KeyboardShortcut = KeyboardShortcut {
+    modifiers: Modifiers::NONE,
+    logical_key: Key::L,
+};
+const ELLIPSE_MODE_SHORTCUT: KeyboardShortcut = KeyboardShortcut {
+    modifiers: Modifiers::NONE,
+    logical_key: Key::O,
+};
 const SELECT_MODE_SHORTCUT: KeyboardShortcut = KeyboardShortcut {
     modifiers: Modifiers::NONE,
     logical_key: Key::S,
@@ -145,6 +173,23 @@ const ERASE_MODE_SHORTCUT: KeyboardShortcut = KeyboardShortcut {
     modifiers: Modifiers::NONE,
     logical_key: Key::E,
 };
+// Note we don't use tab for this, since egui uses it to move keyboard focus
+const CYCLE_MODE_SHORTCUT: KeyboardShortcut = KeyboardShortcut {
+    modifiers: Modifiers::NONE,
+    logical_key: Key::M,
+};
+const EYEDROP_PALETTE_COLOR_SHORTCUT: KeyboardShortcut = KeyboardShortcut {
+    modifiers: Modifiers::NONE,
+    logical_key: Key::K,
+};
+const REPEAT_LAST_ACTION_SHORTCUT: KeyboardShortcut = KeyboardShortcut {
+    modifiers: Modifiers::NONE,
+    logical_key: Key::G,
+};
+const SELECT_NONEMPTY_SHORTCUT: KeyboardShortcut = KeyboardShortcut {
+    modifiers: Modifiers::NONE,
+    logical_key: Key::F,
+};
 const DELETE_SHORTCUT: KeyboardShortcut = KeyboardShortcut {
     modifiers: Modifiers::NONE,
     logical_key: Key::Delete,
@@ -159,7 +204,12 @@ const TOGGLE_SELECT_ALL_LAYERS_SHORTCUT: KeyboardShortcut = KeyboardShortcut {
     logical_key: Key::A,
 };
 
-const LAYER_SHORTCUTS: [KeyboardShortcut; 9] = [
+const TOGGLE_SOLO_LAYER_SHORTCUT: KeyboardShortcut = KeyboardShortcut {
+    modifiers: Modifiers::NONE,
+    logical_key: Key::I,
+};
+
+const LAYER_SHORTCUTS: [KeyboardShortcut; LAYER_SHORTCUT_BANK_SIZE] = [
     KeyboardShortcut {
         modifiers: Modifiers::NONE,
         logical_key: Key::Num1,
@@ -198,6 +248,11 @@ const LAYER_SHORTCUTS: [KeyboardShortcut; 9] = [
     },
 ];
 
+const NEXT_LAYER_SHORTCUT_BANK_SHORTCUT: KeyboardShortcut = KeyboardShortcut {
+    modifiers: Modifiers::NONE,
+    logical_key: Key::Num0,
+};
+
 const TILESET_LEFT_SHORTCUT: KeyboardShortcut = KeyboardShortcut {
     modifiers: Modifiers::NONE,
     logical_key: Key::ArrowLeft,
@@ -215,12 +270,38 @@ const TILESET_DOWN_SHORTCUT: KeyboardShortcut = KeyboardShortcut {
     logical_key: Key::ArrowDown,
 };
 
+// Note that these must be consumed before the tileset navigation shortcuts,
+// since those will also match when shift is held.
+const CURSOR_LEFT_SHORTCUT: KeyboardShortcut = KeyboardShortcut {
+    modifiers: Modifiers::SHIFT,
+    logical_key: Key::ArrowLeft,
+};
+const CURSOR_RIGHT_SHORTCUT: KeyboardShortcut = KeyboardShortcut {
+    modifiers: Modifiers::SHIFT,
+    logical_key: Key::ArrowRight,
+};
+const CURSOR_UP_SHORTCUT: KeyboardShortcut = KeyboardShortcut {
+    modifiers: Modifiers::SHIFT,
+    logical_key: Key::ArrowUp,
+};
+const CURSOR_DOWN_SHORTCUT: KeyboardShortcut = KeyboardShortcut {
+    modifiers: Modifiers::SHIFT,
+    logical_key: Key::ArrowDown,
+};
+const CURSOR_APPLY_SHORTCUT: KeyboardShortcut = KeyboardShortcut {
+    modifiers: Modifiers::NONE,
+    logical_key: Key::Space,
+};
+
 const HELP_SHORTCUT: KeyboardShortcut = KeyboardShortcut {
     modifiers: Modifiers::NONE,
     logical_key: Key::H,
 };
 
 pub fn consume_shortcuts(ctx: &Context, app: &mut App) {
+    // Reloading needs the context, so can't be done while we are consuming input
+    let mut reload_tilesets = false;
+
     ctx.input_mut(|i| {
         if i.consume_shortcut(&NEW_SHORTCUT) {
             app.check_data_loss_then_new_document();
@@ -239,6 +320,9 @@ pub fn consume_shortcuts(ctx: &Context, app: &mut App) {
             app.show_export_png_modal();
         }
 
+        if i.consume_shortcut(&RELOAD_TILESETS_SHORTCUT) {
+            reload_tilesets = true;
+        }
         if i.consume_shortcut(&RESET_ZOOM_SHORTCUT) {
             app.reset_selected_map_zoom();
         }
@@ -249,6 +333,9 @@ pub fn consume_shortcuts(ctx: &Context, app: &mut App) {
         if i.consume_shortcut(&COPY_SHORTCUT) {
             app.copy();
         }
+        if i.consume_shortcut(&TOGGLE_LAYER_VISIBLE_SHORTCUT) {
+            app.toggle_selected_layer_visible();
+        }
         if i.consume_shortcut(&PASTE_SHORTCUT) || i.consume_shortcut(&PASTE_SHORTCUT_ALT) {
             app.draw_mode();
         }
@@ -301,12 +388,30 @@ pub fn consume_shortcuts(ctx: &Context, app: &mut App) {
             app.shift_layer_selection(ShiftDirection::IncreaseIndex);
         }
 
+        if i.consume_shortcut(&LINE_MODE_SHORTCUT) {
+            app.line_mode();
+        }
+        if i.consume_shortcut(&ELLIPSE_MODE_SHORTCUT) {
+            app.ellipse_mode();
+        }
         if i.consume_shortcut(&SELECT_MODE_SHORTCUT) {
             app.select_mode();
         }
         if i.consume_shortcut(&ERASE_MODE_SHORTCUT) {
             app.erase_mode();
         }
+        if i.consume_shortcut(&CYCLE_MODE_SHORTCUT) {
+            app.cycle_mode();
+        }
+        if i.consume_shortcut(&EYEDROP_PALETTE_COLOR_SHORTCUT) {
+            app.eyedrop_palette_color();
+        }
+        if i.consume_shortcut(&REPEAT_LAST_ACTION_SHORTCUT) {
+            app.repeat_last_action();
+        }
+        if i.consume_shortcut(&SELECT_NONEMPTY_SHORTCUT) {
+            app.select_nonempty_on_selected_layer();
+        }
         if i.consume_shortcut(&DELETE_SHORTCUT) || i.consume_shortcut(&BACKSPACE_SHORTCUT) {
             app.delete_and_clear_selection();
         }
@@ -315,12 +420,35 @@ pub fn consume_shortcuts(ctx: &Context, app: &mut App) {
             app.selected_map_toggle_select_all_layers();
         }
 
-        for (layer_index, layer_shortcut) in LAYER_SHORTCUTS.iter().enumerate() {
+        if i.consume_shortcut(&TOGGLE_SOLO_LAYER_SHORTCUT) {
+            app.toggle_solo_selected_layer();
+        }
+
+        if i.consume_shortcut(&NEXT_LAYER_SHORTCUT_BANK_SHORTCUT) {
+            app.next_layer_shortcut_bank();
+        }
+        for (key_index, layer_shortcut) in LAYER_SHORTCUTS.iter().enumerate() {
             if i.consume_shortcut(layer_shortcut) {
-                app.select_layer(layer_index)
+                app.select_layer(app.edit.layer_shortcut_index(key_index))
             }
         }
 
+        if i.consume_shortcut(&CURSOR_UP_SHORTCUT) {
+            app.move_map_cursor(i32pos2(0, -1));
+        }
+        if i.consume_shortcut(&CURSOR_DOWN_SHORTCUT) {
+            app.move_map_cursor(i32pos2(0, 1));
+        }
+        if i.consume_shortcut(&CURSOR_LEFT_SHORTCUT) {
+            app.move_map_cursor(i32pos2(-1, 0));
+        }
+        if i.consume_shortcut(&CURSOR_RIGHT_SHORTCUT) {
+            app.move_map_cursor(i32pos2(1, 0));
+        }
+        if i.consume_shortcut(&CURSOR_APPLY_SHORTCUT) {
+            app.apply_at_map_cursor();
+        }
+
         if i.consume_shortcut(&TILESET_UP_SHORTCUT) {
             app.shift_tileset_selection(i32pos2(0, -1));
         }
@@ -337,4 +465,8 @@ pub fn consume_shortcuts(ctx: &Context, app: &mut App) {
             app.show_help_modal();
         }
     });
+
+    if reload_tilesets {
+        app.reload_all_tilesets(ctx);
+    }
 }
@@ -2,6 +2,8 @@ use egui::containers::Frame;
 use egui::style::{Selection, Widgets};
 use egui::{Color32, CornerRadius, Margin, Shadow, Stroke};
 
+pub use crate::data::settings::ThemeChoice;
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Theme {
     pub base50: Color32,
@@ -14,9 +16,13 @@ pub struct Theme {
     pub selected_content: Color32,
     pub selected_border: Color32,
     pub selected_fill: Color32,
+    /// Stripes drawn for selection highlights, with [`crate::ui::tiles::HighlightStyle::Pattern`]
+    pub selected_stripe: Color32,
     pub selected_tile: Color32,
     pub unselected_tile: Color32,
     pub erase_fill: Color32,
+    /// Stripes drawn for erase highlights, with [`crate::ui::tiles::HighlightStyle::Pattern`]
+    pub erase_stripe: Color32,
     pub inactive_widget_bg: Color32,
     pub hovered_widget_bg: Color32,
     pub active_widget_bg: Color32,
@@ -54,8 +60,24 @@ impl Theme {
     // pub fn base_300_frame(&self, margin: i8) -> Frame {
     //     Self::frame(self.base300, margin)
     // }
+
+    /// The colors to use for an egui theme
+    pub fn for_egui_theme(theme: egui::Theme) -> &'static Theme {
+        match theme {
+            egui::Theme::Dark => &DEFAULT_THEME,
+            egui::Theme::Light => &LIGHT_THEME,
+        }
+    }
+
+    /// The colors for the theme currently in use, which depends on the theme
+    /// preference, and the system theme when following the system
+    pub fn current(ctx: &egui::Context) -> &'static Theme {
+        Self::for_egui_theme(ctx.theme())
+    }
 }
 
+/// The dark theme. Sizes and spacing are the same for all themes, so are
+/// taken from this theme directly.
 pub const DEFAULT_THEME: Theme = Theme {
     // Note - these are taken from mountain configurator theme in
     // lightbox-picow-rs, converted using https://oklch.com
@@ -84,6 +106,7 @@ pub const DEFAULT_THEME: Theme = Theme {
     selected_border: Color32::from_rgb(196, 179, 255),
     // selected_fill: Color32::from_rgba_premultiplied(96, 24, 192, 100),
     selected_fill: Color32::from_rgba_premultiplied(96, 24, 192, 120),
+    selected_stripe: Color32::from_rgb(176, 128, 255),
     // White for slightly higher contrast where used
     selected_content: Color32::from_rgb(255, 255, 255),
 
@@ -92,6 +115,7 @@ pub const DEFAULT_THEME: Theme = Theme {
     unselected_tile: Color32::from_rgb(79, 83, 92),
 
     erase_fill: Color32::from_rgba_premultiplied(192, 24, 24, 50),
+    erase_stripe: Color32::from_rgb(255, 96, 96),
 
     // These are taken from the tailwind theme generator, for the "Draft" button, unhovered and hovered
     inactive_widget_bg: Color32::from_rgb(26, 32, 40),
@@ -111,8 +135,55 @@ pub const DEFAULT_THEME: Theme = Theme {
     },
 };
 
-pub fn apply_theme(ctx: &egui::Context, colors: Theme) {
-    let mut widgets = Widgets::dark().clone();
+/// The light theme, using the same hue as [`DEFAULT_THEME`], with lightness reversed.
+/// Selection colors are kept, since they are drawn over maps and tilesets rather than
+/// the theme background, with the erase fill made stronger to stand out from light tiles.
+pub const LIGHT_THEME: Theme = Theme {
+    base50: Color32::from_rgb(196, 203, 212),
+    base100: Color32::from_rgb(238, 241, 245),
+    base200: Color32::from_rgb(222, 227, 233),
+    base300: Color32::from_rgb(202, 208, 216),
+    base_content: Color32::from_rgb(25, 32, 40),
+    base_subcontent: Color32::from_rgb(92, 98, 108),
+
+    selected: Color32::from_rgb(127, 33, 254),
+    selected_border: Color32::from_rgb(91, 33, 182),
+    selected_fill: Color32::from_rgba_premultiplied(96, 24, 192, 120),
+    selected_stripe: Color32::from_rgb(110, 40, 210),
+    selected_content: Color32::from_rgb(255, 255, 255),
+
+    selected_tile: Color32::from_rgb(217, 119, 6),
+    unselected_tile: Color32::from_rgb(168, 174, 184),
+
+    erase_fill: Color32::from_rgba_premultiplied(192, 24, 24, 90),
+    erase_stripe: Color32::from_rgb(200, 16, 16),
+
+    inactive_widget_bg: Color32::from_rgb(222, 227, 233),
+    hovered_widget_bg: Color32::from_rgb(210, 216, 223),
+    active_widget_bg: Color32::from_rgb(198, 205, 214),
+
+    modal_spacing: DEFAULT_THEME.modal_spacing,
+    separator_spacing: DEFAULT_THEME.separator_spacing,
+    control_height: DEFAULT_THEME.control_height,
+    row_height: DEFAULT_THEME.row_height,
+    selected_stroke: Stroke {
+        width: 1.0,
+        color: Color32::from_rgb(25, 32, 40),
+    },
+};
+
+/// Apply the visuals of [`DEFAULT_THEME`] and [`LIGHT_THEME`] to egui's dark and light
+/// themes, which one is used is then set by the theme preference, see [`ThemeChoice`]
+pub fn apply_themes(ctx: &egui::Context) {
+    apply_theme(ctx, egui::Theme::Dark, &DEFAULT_THEME);
+    apply_theme(ctx, egui::Theme::Light, &LIGHT_THEME);
+}
+
+fn apply_theme(ctx: &egui::Context, theme: egui::Theme, colors: &Theme) {
+    let mut widgets = match theme {
+        egui::Theme::Dark => Widgets::dark(),
+        egui::Theme::Light => Widgets::light(),
+    };
 
     // The style of an interactive widget, such as a button, at rest.
     widgets.inactive.bg_fill = colors.inactive_widget_bg;
@@ -128,10 +199,10 @@ pub fn apply_theme(ctx: &egui::Context, colors: Theme) {
     widgets.active.weak_bg_fill = colors.active_widget_bg;
     // widgets.active.bg_stroke = widgets.inactive.bg_stroke;
 
-    widgets.noninteractive.bg_stroke = Stroke::new(1.0, colors.base50);
+    widgets.noninteractive.bg_stroke = Stroke::new(1.0_f32, colors.base50);
 
     ctx.set_visuals_of(
-        egui::Theme::Dark,
+        theme,
         egui::Visuals {
             window_fill: colors.base100,
             window_corner_radius: CornerRadius::ZERO,
@@ -143,22 +214,14 @@ pub fn apply_theme(ctx: &egui::Context, colors: Theme) {
             widgets,
             selection: Selection {
                 bg_fill: colors.selected,
-                stroke: Stroke::new(1.0, colors.selected_content),
+                stroke: Stroke::new(1.0_f32, colors.selected_content),
             },
 
             // bg_fill: Color32::from_rgb(0, 92, 128),
-            // stroke: Stroke::new(1.0, Color32::from_rgb(192, 222, 255)),
-            ..Default::default()
+            // stroke: Stroke::new(1.0_f32, Color32::from_rgb(192, 222, 255)),
+            ..theme.default_visuals()
         },
     );
 
-    // ctx.set_visuals_of(
-    //     egui::Theme::Light,
-    //     egui::Visuals {
-    //         panel_fill: egui::Color32::GREEN,
-    //         ..Default::default()
-    //     },
-    // );
-
     // ctx.set_visuals(theme.visuals(ctx.style().visuals.clone()));
 }
@@ -1,34 +1,111 @@
 use crate::{
+    app::export_workspace::{flat_tile_index, tileset_firstgids},
     data::palette::Palette,
     data::tiles::{
         Tiles,
+        tile_mesh_cache::{TileMeshCache, TileMeshInputs},
         tile_selection::{SelectionType, TileSelection},
     },
     data::tilesets::{Tileset, Tilesets},
     geom::transform::Transform,
-    geom::u32pos2::u32pos2,
-    ui::theme::DEFAULT_THEME,
+    geom::u32pos2::{U32Pos2, u32pos2},
+    geom::u32rect::U32Rect,
+    ui::theme::Theme,
     ui::tile_mesh::TileMesh,
     ui::tileset_textures::{ErrorTexture, TilesetTextures},
 };
 use egui::{
-    Color32, Context, Mesh, Pos2, Rect, Sense, Shape, Vec2,
+    Align2, Color32, Context, FontId, Mesh, Pos2, Rect, Sense, Shape, Vec2,
+    ahash::{HashSet, HashSetExt},
     load::{SizedTexture, TexturePoll},
     pos2, vec2,
 };
+use std::sync::Arc;
 
-const SELECTION_FILL_COLOR: Color32 = DEFAULT_THEME.selected_fill;
-// const SELECTION_DRAG_COLOR: Color32 = DEFAULT_THEME.selected_drag;
-const ERASE_FILL_COLOR: Color32 = DEFAULT_THEME.erase_fill;
-// const SELECTION_DRAG_STROKE: Stroke = Stroke {
-//     width: 1.0,
-//     color: DEFAULT_THEME.selected_border,
-// };
+pub use crate::data::settings::HighlightStyle;
+
+/// The spacing between stripes for [`HighlightStyle::Pattern`], in points
+const STRIPE_SPACING: f32 = 6.0;
+
+/// The width of stripes for [`HighlightStyle::Pattern`], in points
+const STRIPE_WIDTH: f32 = 2.0;
+
+/// Fill `rect` with diagonal stripes, which line up across neighbouring rects.
+/// Stripes fall from top right to bottom left, or with `rising`, from top left
+/// to bottom right.
+fn striped_rect(painter: &egui::Painter, rect: Rect, color: Color32, rising: bool) {
+    let painter = painter.with_clip_rect(rect.intersect(painter.clip_rect()));
+    let stroke = egui::Stroke::new(STRIPE_WIDTH, color);
+    // Each stripe is the line x + sign * y = offset
+    let sign = if rising { -1.0 } else { 1.0 };
+    let (min_offset, max_offset) = if rising {
+        (rect.min.x - rect.max.y, rect.max.x - rect.min.y)
+    } else {
+        (rect.min.x + rect.min.y, rect.max.x + rect.max.y)
+    };
+    // Start stripes from a multiple of the spacing, so stripes are continuous
+    // between cells
+    let mut offset = (min_offset / STRIPE_SPACING).floor() * STRIPE_SPACING;
+    while offset <= max_offset {
+        painter.line_segment(
+            [
+                pos2(offset - sign * rect.min.y, rect.min.y),
+                pos2(offset - sign * rect.max.y, rect.max.y),
+            ],
+            stroke,
+        );
+        offset += STRIPE_SPACING;
+    }
+}
 
 #[derive(Debug, Clone, Copy)]
 pub enum Overlay {
     None,
-    Checkerboard { color: Color32 },
+    /// Alternate between two colors in squares of `size` cells
+    Checkerboard {
+        colors: [Color32; 2],
+        size: u32,
+    },
+    /// Color each cell by the number of layers with a tile in that cell
+    Heatmap,
+    /// Show the flat tile index of the topmost tile in each cell, as used in exported
+    /// tile data, see [`crate::app::export_workspace::flat_tile_index`]. Indices are only
+    /// drawn when zoomed in far enough to read them.
+    TileIndices,
+}
+
+/// The font size of [`Overlay::TileIndices`] text, as a proportion of the cell size
+const TILE_INDEX_FONT_SCALE: f32 = 0.3;
+
+/// The smallest font size [`Overlay::TileIndices`] text is drawn at, in screen
+/// pixels. When zoomed out so text would be smaller than this, it isn't drawn.
+const MIN_TILE_INDEX_FONT_SIZE: f32 = 8.0;
+
+/// The background drawn behind [`Overlay::TileIndices`] text, so it can be read over tiles
+const TILE_INDEX_BACKGROUND: Color32 = Color32::from_rgba_premultiplied(0, 0, 0, 160);
+
+/// Colors used for the [`Overlay::Heatmap`] overlay, for cells with 1, 2, 3 and
+/// 4 or more layers containing a tile. Cells with no tiles are not colored.
+const HEATMAP_COLORS: [Color32; 4] = [
+    Color32::from_rgba_premultiplied(0, 64, 0, 64),
+    Color32::from_rgba_premultiplied(64, 64, 0, 64),
+    Color32::from_rgba_premultiplied(80, 32, 0, 80),
+    Color32::from_rgba_premultiplied(96, 0, 0, 96),
+];
+
+/// The number of layers with a tile at `pos`
+fn layers_with_tile_at<T: Tiles>(tiles: &T, pos: U32Pos2) -> usize {
+    (0..tiles.layer_count())
+        .filter(|layer_index| tiles.tile(*layer_index, pos).is_some())
+        .count()
+}
+
+/// The [`Overlay::Heatmap`] color for a cell containing tiles in `count` layers
+fn heatmap_color(count: usize) -> Option<Color32> {
+    count
+        .checked_sub(1)
+        .map(|i| i.min(HEATMAP_COLORS.len() - 1))
+        .and_then(|i| HEATMAP_COLORS.get(i).copied())
 }
 
 fn load_texture(
@@ -46,6 +123,131 @@ fn load_texture(
     }
 }
 
+/// The opacity to display a layer with, see
+/// [`crate::data::tiles::layer_tiles::LayerKind::display_opacity`]
+fn display_opacity<T: Tiles>(tiles: &T, layer_index: usize) -> Option<f32> {
+    tiles
+        .layer_kind(layer_index)
+        .display_opacity(tiles.layer_opacity(layer_index))
+}
+
+/// Find the range of grid positions that are at least partly visible within `visible_rect`,
+/// when drawing `tiles` in `rect`, or [`None`] if no positions are visible.
+/// The range includes an extra cell around each edge, so that cells at the edges are
+/// still drawn when the visible rect doesn't exactly match the grid (e.g. when it has
+/// been transformed by a [`egui::Scene`], with rounding errors).
+fn visible_grid_rect<T: Tiles>(tiles: &T, rect: Rect, visible_rect: Rect) -> Option<U32Rect> {
+    let map_size = tiles.map_size();
+    let visible = rect.intersect(visible_rect);
+    if map_size.w == 0 || map_size.h == 0 || !visible.is_positive() {
+        return None;
+    }
+
+    let cell_size = Vec2::from(tiles.tile_size() + tiles.gap()) * tiles.scale();
+    let min = ((visible.min - rect.min) / cell_size).floor() - Vec2::splat(1.0);
+    let max = ((visible.max - rect.min) / cell_size).ceil();
+
+    // Clamp to the map, note that NaN and negative values convert to 0
+    let clamp = |value: f32, size: u32| (value as u32).min(size - 1);
+    Some(U32Rect {
+        min: u32pos2(clamp(min.x, map_size.w), clamp(min.y, map_size.h)),
+        max: u32pos2(clamp(max.x, map_size.w), clamp(max.y, map_size.h)),
+    })
+}
+
+/// Build the mesh to draw the tiles from one tileset in a layer, or for a layer index
+/// of [`None`], the default tile in cells where all layers are empty. Only the tiles
+/// in `grid_rect` are included.
+#[allow(clippy::too_many_arguments)]
+fn layer_mesh<T: Tiles>(
+    tiles: &T,
+    layer_index: Option<usize>,
+    tileset: &Tileset,
+    texture: SizedTexture,
+    success: bool,
+    palette: &Palette,
+    origin: Pos2,
+    grid_rect: U32Rect,
+) -> Mesh {
+    let tile_and_gap_size = tiles.tile_size() + tiles.gap();
+
+    let mut half_gap_size: Vec2 = tiles.gap().into();
+    half_gap_size /= 2.0;
+
+    let default_tile = tiles.default_tile();
+    // Layer opacity is applied to each tile's color. A layer has at most one tile in each
+    // cell, and tiles don't overlap, so this gives the same result as drawing the layer
+    // fully opaque and then compositing it with the layer opacity.
+    let opacity = layer_index.and_then(|layer_index| display_opacity(tiles, layer_index));
+    // Likewise the layer tint is multiplied with each tile's color
+    let tint = layer_index.and_then(|layer_index| tiles.layer_tint(layer_index));
+
+    let mut mesh = Mesh::with_texture(texture.id);
+    let tile_set_size = tileset.size_in_tiles;
+    let tile_set_size_f: Vec2 = tile_set_size.into();
+    let tile_uv_size = vec2(1.0, 1.0) / tile_set_size_f;
+
+    let grid_positions = (grid_rect.min.y..=grid_rect.max.y)
+        .flat_map(|y| (grid_rect.min.x..=grid_rect.max.x).map(move |x| u32pos2(x, y)));
+    for grid_pos in grid_positions {
+        let screen_pos = (Pos2::from(grid_pos * tile_and_gap_size) + half_gap_size) * tiles.scale()
+            + origin.to_vec2();
+        let screen_size = Vec2::from(tiles.tile_size()) * tiles.scale();
+
+        let tile = match layer_index {
+            Some(layer_index) => tiles.tile(layer_index, grid_pos),
+            None => default_tile.filter(|_| {
+                (0..tiles.layer_count())
+                    .all(|layer_index| tiles.tile(layer_index, grid_pos).is_none())
+            }),
+        };
+
+        if let Some(ref tile) = tile {
+            if tile.source.tileset_id == tileset.id() {
+                let tile_uv_pos = (Vec2::from(tileset.image_pos(tile.source.tile_index.index()))
+                    / tile_set_size_f)
+                    .to_pos2();
+                let uv = if success {
+                    Rect::from_min_size(tile_uv_pos, tile_uv_size)
+                } else {
+                    Rect::from_min_size(pos2(0.0, 0.0), vec2(1.0, 1.0))
+                };
+                let transform = if success {
+                    tile.transform
+                } else {
+                    Transform::None
+                };
+
+                // Combine with any opacity for just this tile, e.g. for a preview
+                let tile_opacity =
+                    layer_index.and_then(|layer_index| tiles.tile_opacity(layer_index, grid_pos));
+                let tile_opacity = match (opacity, tile_opacity) {
+                    (Some(a), Some(b)) => Some(a * b),
+                    (a, b) => a.or(b),
+                };
+
+                let color = match (tile_opacity, tint) {
+                    (None, None) => tile.color.as_color32_premultiplied(palette),
+                    _ => tile
+                        .color
+                        .as_user_color(palette)
+                        .with_optional_tint(tint)
+                        .with_optional_opacity(tile_opacity)
+                        .as_premultiplied_color32(),
+                };
+
+                mesh.add_rect_with_transform(
+                    Rect::from_min_size(screen_pos, screen_size),
+                    uv,
+                    transform,
+                    color,
+                );
+            }
+        }
+    }
+    mesh
+}
+
 #[allow(clippy::too_many_arguments)]
 fn tiles_ui<T: Tiles>(
     ui: &mut egui::Ui,
@@ -54,20 +256,23 @@ fn tiles_ui<T: Tiles>(
     tilesets: &Tilesets,
     textures: &TilesetTextures,
     selection: Option<&TileSelection>,
+    highlight: HighlightStyle,
     overlay: Overlay,
     sense: Sense,
+    mut mesh_cache: Option<&mut TileMeshCache>,
 ) -> egui::Response {
     let desired_size = tiles.pixel_size();
 
     // let (rect, response) = ui.allocate_exact_size(desired_size, egui::Sense::click());
     let (rect, response) = ui.allocate_exact_size(desired_size, sense);
 
-    if ui.is_rect_visible(rect) {
+    if let Some(grid_rect) = ui
+        .is_rect_visible(rect)
+        .then(|| visible_grid_rect(tiles, rect, ui.clip_rect()))
+        .flatten()
+    {
         let tile_and_gap_size = tiles.tile_size() + tiles.gap();
 
-        let mut half_gap_size: Vec2 = tiles.gap().into();
-        half_gap_size /= 2.0;
-
         let ctx = ui.ctx();
         ui.painter()
             .rect_filled(rect, 0.0, tiles.background().as_premultiplied_color32());
@@ -75,78 +280,91 @@ fn tiles_ui<T: Tiles>(
         // the last layer is "at the bottom".
         // This is done so that the order of layers in the data vec and list UI are as expected by the user, with earlier
         // layers higher in the "stack", and we only need to reverse the vec here when drawing.
-        for layer_index in (0..tiles.layer_count()).rev() {
-            let opacity = tiles.layer_opacity(layer_index);
+        // If there is a default tile, it is drawn first (below all layers), represented by
+        // a layer index of `None`.
+        let default_layer = tiles.default_tile().map(|_| None);
+
+        // Track the cached meshes we use, so we can discard the rest
+        let mut used_mesh_keys = HashSet::new();
+
+        for layer_index in default_layer
+            .into_iter()
+            .chain((0..tiles.layer_count()).rev().map(Some))
+        {
+            let version = layer_index.and_then(|layer_index| tiles.layer_version(layer_index));
             for tileset in tilesets.iter() {
                 if let Some((texture, success)) = load_texture(textures, ctx, tileset) {
-                    let mut mesh = Mesh::with_texture(texture.id);
-                    let tile_set_size = tileset.size_in_tiles;
-                    let tile_set_size_f: Vec2 = tile_set_size.into();
-                    let tile_uv_size = vec2(1.0, 1.0) / tile_set_size_f;
-
-                    for grid_pos in tiles.map_positions() {
-                        let screen_pos = (Pos2::from(grid_pos * tile_and_gap_size) + half_gap_size)
-                            * tiles.scale()
-                            + rect.min.to_vec2();
-                        let screen_size = Vec2::from(tiles.tile_size()) * tiles.scale();
-
-                        if let Some(ref tile) = tiles.tile(layer_index, grid_pos) {
-                            if tile.source.tileset_id == tileset.id() {
-                                let tile_uv_pos = (Vec2::from(
-                                    tile_set_size
-                                        .pos_from_linear_index(tile.source.tile_index.index()),
-                                ) / tile_set_size_f)
-                                    .to_pos2();
-                                let uv = if success {
-                                    Rect::from_min_size(tile_uv_pos, tile_uv_size)
-                                } else {
-                                    Rect::from_min_size(pos2(0.0, 0.0), vec2(1.0, 1.0))
-                                };
-                                let transform = if success {
-                                    tile.transform
-                                } else {
-                                    Transform::None
-                                };
-
-                                let color = match opacity {
-                                    Some(opacity) => tile
-                                        .color
-                                        .as_user_color(palette)
-                                        .with_opacity(opacity)
-                                        .as_premultiplied_color32(),
-                                    // TODO: Rename to as_premultiplied_color32
-                                    None => tile.color.as_color32_premultiplied(palette),
-                                };
-
-                                mesh.add_rect_with_transform(
-                                    Rect::from_min_size(screen_pos, screen_size),
-                                    uv,
-                                    transform,
-                                    color,
-                                );
-                            }
+                    let build = || {
+                        layer_mesh(
+                            tiles,
+                            layer_index,
+                            tileset,
+                            texture,
+                            success,
+                            palette,
+                            rect.min,
+                            grid_rect,
+                        )
+                    };
+                    let mesh = match (version, mesh_cache.as_deref_mut()) {
+                        (Some(version), Some(mesh_cache)) => {
+                            let key = (version.id, tileset.id());
+                            used_mesh_keys.insert(key);
+                            let inputs = TileMeshInputs {
+                                version,
+                                texture_id: texture.id,
+                                texture_loaded: success,
+                                tileset_size: tileset.size_in_tiles,
+                                tileset_columns: tileset.column_count(),
+                                tileset_region: tileset.region,
+                                opacity: layer_index
+                                    .and_then(|layer_index| display_opacity(tiles, layer_index)),
+                                tint: layer_index
+                                    .and_then(|layer_index| tiles.layer_tint(layer_index)),
+                                tile_size: tiles.tile_size(),
+                                gap: tiles.gap(),
+                                scale: tiles.scale(),
+                                origin: rect.min,
+                                grid_rect,
+                            };
+                            mesh_cache.mesh(key, inputs, palette, build)
                         }
-                    }
+                        _ => Arc::new(build()),
+                    };
                     ui.painter().add(Shape::mesh(mesh));
                 }
             }
         }
 
+        if let Some(mesh_cache) = mesh_cache {
+            mesh_cache.retain(&used_mesh_keys);
+        }
+
         if let Some(selection) = selection {
             // Draw selection
-            let selection_fill_color = match selection.selection_type() {
-                SelectionType::Selection => SELECTION_FILL_COLOR,
-                SelectionType::Erase => ERASE_FILL_COLOR,
-            };
+            let theme = Theme::current(ui.ctx());
+            let highlight_rect =
+                |highlight_rect: Rect| match (selection.selection_type(), highlight) {
+                    (SelectionType::Selection, HighlightStyle::Fill) => {
+                        ui.painter()
+                            .rect_filled(highlight_rect, 0.0, theme.selected_fill);
+                    }
+                    (SelectionType::Selection, HighlightStyle::Pattern) => {
+                        striped_rect(ui.painter(), highlight_rect, theme.selected_stripe, true);
+                    }
+                    (SelectionType::Erase, HighlightStyle::Fill) => {
+                        ui.painter()
+                            .rect_filled(highlight_rect, 0.0, theme.erase_fill);
+                    }
+                    (SelectionType::Erase, HighlightStyle::Pattern) => {
+                        striped_rect(ui.painter(), highlight_rect, theme.erase_stripe, false);
+                    }
+                };
             for pos in selection.iter() {
                 let screen_pos =
                     Pos2::from(*pos * tile_and_gap_size) * tiles.scale() + rect.min.to_vec2();
                 let screen_size = Vec2::from(tile_and_gap_size) * tiles.scale();
-                ui.painter().rect_filled(
-                    Rect::from_min_size(screen_pos, screen_size),
-                    0.0,
-                    selection_fill_color,
-                );
+                highlight_rect(Rect::from_min_size(screen_pos, screen_size));
             }
 
             // Draw selection drag box
@@ -161,28 +379,90 @@ fn tiles_ui<T: Tiles>(
                 //     SELECTION_DRAG_STROKE,
                 //     StrokeKind::Inside,
                 // );
-                ui.painter().rect_filled(
-                    Rect::from_min_size(screen_pos, screen_size),
-                    0.0,
-                    selection_fill_color,
-                );
+                highlight_rect(Rect::from_min_size(screen_pos, screen_size));
             }
         }
 
         // Draw overlay
-        if let Overlay::Checkerboard { color } = overlay {
-            for y in 0..tiles.map_size().h {
-                for x in 0..tiles.map_size().w {
-                    if (x + y) % 2 == 0 {
+        match overlay {
+            Overlay::None => {}
+            Overlay::Checkerboard { colors, size } => {
+                let size = size.max(1);
+                for y in 0..tiles.map_size().h {
+                    for x in 0..tiles.map_size().w {
+                        let color = if (x / size + y / size) % 2 == 0 {
+                            colors[0]
+                        } else {
+                            colors[1]
+                        };
+                        if color != Color32::TRANSPARENT {
+                            let pos = u32pos2(x, y);
+                            let screen_pos = Pos2::from(pos * tile_and_gap_size) * tiles.scale()
+                                + rect.min.to_vec2();
+                            let screen_size = Vec2::from(tile_and_gap_size) * tiles.scale();
+                            ui.painter().rect_filled(
+                                Rect::from_min_size(screen_pos, screen_size),
+                                0.0,
+                                color,
+                            );
+                        }
+                    }
+                }
+            }
+            Overlay::Heatmap => {
+                for y in grid_rect.min.y..=grid_rect.max.y {
+                    for x in grid_rect.min.x..=grid_rect.max.x {
                         let pos = u32pos2(x, y);
-                        let screen_pos = Pos2::from(pos * tile_and_gap_size) * tiles.scale()
-                            + rect.min.to_vec2();
-                        let screen_size = Vec2::from(tile_and_gap_size) * tiles.scale();
-                        ui.painter().rect_filled(
-                            Rect::from_min_size(screen_pos, screen_size),
-                            0.0,
-                            color,
-                        );
+                        if let Some(color) = heatmap_color(layers_with_tile_at(tiles, pos)) {
+                            let screen_pos = Pos2::from(pos * tile_and_gap_size) * tiles.scale()
+                                + rect.min.to_vec2();
+                            let screen_size = Vec2::from(tile_and_gap_size) * tiles.scale();
+                            ui.painter().rect_filled(
+                                Rect::from_min_size(screen_pos, screen_size),
+                                0.0,
+                                color,
+                            );
+                        }
+                    }
+                }
+            }
+            Overlay::TileIndices => {
+                let cell_size = Vec2::from(tiles.tile_size()) * tiles.scale();
+                let font_size = cell_size.min_elem() * TILE_INDEX_FONT_SCALE;
+                // The map may be drawn zoomed in a scene, so check the size on screen
+                let zoom = ctx
+                    .layer_transform_to_global(ui.layer_id())
+                    .map(|transform| transform.scaling)
+                    .unwrap_or(1.0);
+                if font_size * zoom >= MIN_TILE_INDEX_FONT_SIZE {
+                    let firstgids = tileset_firstgids(tilesets);
+                    let font = FontId::monospace(font_size);
+                    for y in grid_rect.min.y..=grid_rect.max.y {
+                        for x in grid_rect.min.x..=grid_rect.max.x {
+                            let pos = u32pos2(x, y);
+                            let Some(index) = (0..tiles.layer_count())
+                                .find_map(|layer_index| tiles.tile(layer_index, pos))
+                                .and_then(|tile| flat_tile_index(&tile, &firstgids))
+                            else {
+                                continue;
+                            };
+                            let screen_pos = Pos2::from(pos * tile_and_gap_size) * tiles.scale()
+                                + rect.min.to_vec2();
+                            let center = Rect::from_min_size(screen_pos, cell_size).center();
+                            let galley = ui.painter().layout_no_wrap(
+                                index.to_string(),
+                                font.clone(),
+                                Color32::WHITE,
+                            );
+                            let text_rect =
+                                Align2::CENTER_CENTER.anchor_size(center, galley.size());
+                            ui.painter().rect_filled(
+                                text_rect.expand(font_size * 0.1),
+                                0.0,
+                                TILE_INDEX_BACKGROUND,
+                            );
+                            ui.painter().galley(text_rect.min, galley, Color32::WHITE);
+                        }
                     }
                 }
             }
@@ -199,6 +479,7 @@ pub fn tiles<'a, T: Tiles>(
     tilesets: &'a Tilesets,
     textures: &'a TilesetTextures,
     selection: Option<&'a TileSelection>,
+    highlight: HighlightStyle,
     overlay: Overlay,
 ) -> impl egui::Widget + 'a {
     tiles_with_sense(
@@ -207,19 +488,24 @@ pub fn tiles<'a, T: Tiles>(
         tilesets,
         textures,
         selection,
+        highlight,
         overlay,
         egui::Sense::drag(),
+        None,
     )
 }
 
-/// Tile map
+/// Tile map, caching the meshes used to draw each layer in `mesh_cache` if present
+#[allow(clippy::too_many_arguments)]
 pub fn tiles_no_sense<'a, T: Tiles>(
     tiles: &'a T,
     palette: &'a Palette,
     tilesets: &'a Tilesets,
     textures: &'a TilesetTextures,
     selection: Option<&'a TileSelection>,
+    highlight: HighlightStyle,
     overlay: Overlay,
+    mesh_cache: Option<&'a mut TileMeshCache>,
 ) -> impl egui::Widget + 'a {
     tiles_with_sense(
         tiles,
@@ -227,24 +513,100 @@ pub fn tiles_no_sense<'a, T: Tiles>(
         tilesets,
         textures,
         selection,
+        highlight,
         overlay,
         egui::Sense::empty(),
+        mesh_cache,
     )
 }
 
-/// Tile map, specifying the [`egui::Sense`] to use
+/// Tile map, specifying the [`egui::Sense`] to use, and optionally a [`TileMeshCache`]
+#[allow(clippy::too_many_arguments)]
 pub fn tiles_with_sense<'a, T: Tiles>(
     tiles: &'a T,
     palette: &'a Palette,
     tilesets: &'a Tilesets,
     textures: &'a TilesetTextures,
     selection: Option<&'a TileSelection>,
+    highlight: HighlightStyle,
     overlay: Overlay,
     sense: Sense,
+    mesh_cache: Option<&'a mut TileMeshCache>,
 ) -> impl egui::Widget + 'a {
     move |ui: &mut egui::Ui| {
         tiles_ui(
-            ui, tiles, palette, tilesets, textures, selection, overlay, sense,
+            ui, tiles, palette, tilesets, textures, selection, highlight, overlay, sense,
+            mesh_cache,
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        data::tiles::{layer_tiles::LayerTiles, tile_color::UserColor},
+        geom::u32size2::u32size2,
+    };
+
+    use super::*;
+
+    #[test]
+    fn visible_grid_rect_includes_partly_visible_cells() {
+        let tiles = LayerTiles::new(UserColor::BLACK, u32size2(8, 8), u32size2(10, 10));
+        let rect = Rect::from_min_size(pos2(100.0, 100.0), vec2(80.0, 80.0));
+
+        // Whole map visible
+        assert_eq!(
+            visible_grid_rect(&tiles, rect, Rect::EVERYTHING),
+            Some(U32Rect::from_two_pos(u32pos2(0, 0), u32pos2(9, 9)))
+        );
+
+        // Cells 2 to 4 partly visible, plus a cell of margin each side
+        let visible = Rect::from_min_max(pos2(120.0, 120.0), pos2(135.0, 135.0));
+        assert_eq!(
+            visible_grid_rect(&tiles, rect, visible),
+            Some(U32Rect::from_two_pos(u32pos2(1, 1), u32pos2(5, 5)))
+        );
+
+        // Margin is clamped to the map
+        let visible = Rect::from_min_max(pos2(0.0, 170.0), pos2(105.0, 400.0));
+        assert_eq!(
+            visible_grid_rect(&tiles, rect, visible),
+            Some(U32Rect::from_two_pos(u32pos2(0, 7), u32pos2(1, 9)))
+        );
+
+        // Nothing visible
+        let visible = Rect::from_min_max(pos2(0.0, 0.0), pos2(50.0, 50.0));
+        assert_eq!(visible_grid_rect(&tiles, rect, visible), None);
+    }
+
+    #[test]
+    fn heatmap_counts_layers_with_tiles() {
+        use crate::data::tiles::{Tile, TileIndex, TileSource, tile_color::TileColor};
+        use crate::data::tilesets::TilesetId;
+
+        let mut tiles = LayerTiles::new(UserColor::BLACK, u32size2(8, 8), u32size2(4, 4));
+        tiles.insert_layer(0, "Top", true, None);
+        tiles.insert_layer(0, "Bottom", true, None);
+        let tile = Tile {
+            source: TileSource {
+                tileset_id: TilesetId::ONE,
+                tile_index: TileIndex::new(0),
+            },
+            color: TileColor::Default,
+            transform: Transform::None,
+        };
+        tiles.set_tile(0, u32pos2(1, 1), Some(tile));
+        tiles.set_tile(1, u32pos2(1, 1), Some(tile));
+        tiles.set_tile(1, u32pos2(2, 1), Some(tile));
+
+        assert_eq!(layers_with_tile_at(&tiles, u32pos2(0, 0)), 0);
+        assert_eq!(layers_with_tile_at(&tiles, u32pos2(2, 1)), 1);
+        assert_eq!(layers_with_tile_at(&tiles, u32pos2(1, 1)), 2);
+
+        assert_eq!(heatmap_color(0), None);
+        assert_eq!(heatmap_color(1), HEATMAP_COLORS.first().copied());
+        assert_eq!(heatmap_color(4), HEATMAP_COLORS.last().copied());
+        assert_eq!(heatmap_color(10), HEATMAP_COLORS.last().copied());
+    }
+}
@@ -2,9 +2,9 @@
 use crate::{
     app::App,
     data::tiles::{Tiles, tile_color::UserColor, tileset_tiles::TilesetTiles},
-    geom::u32size2::U32Size2,
-    ui::egui_utils::square_button,
-    ui::theme::DEFAULT_THEME,
+    geom::{transform::Transform, u32size2::U32Size2},
+    ui::egui_utils::{enabled_square_button, square_button},
+    ui::theme::{DEFAULT_THEME, Theme},
     ui::tiles::{Overlay, tiles},
 };
 use egui::{Layout, Ui, WidgetText, vec2};
@@ -13,7 +13,7 @@ fn tileset_tiles_ui(ui: &mut Ui, app: &mut App) {
     if let Some(tileset) = app.selected_tileset() {
         match app.textures.texture_for_tileset(ui.ctx(), tileset) {
             Ok(_) => {
-                let map_size = tileset.size_in_tiles;
+                let map_size = tileset.grid_size();
                 let tile_size = tileset.tile_size;
                 let scene_size = ui.available_width();
                 let gap = if app.settings.tileset_grid_spacing_enabled {
@@ -28,6 +28,7 @@ fn tileset_tiles_ui(ui: &mut Ui, app: &mut App) {
                     tileset_id: tileset.id(),
                     tile_size,
                     map_size,
+                    tile_count: tileset.tile_count(),
                     scale: 1.0,
                     gap,
                 };
@@ -46,6 +47,7 @@ fn tileset_tiles_ui(ui: &mut Ui, app: &mut App) {
                         tilesets,
                         textures,
                         app.edit.selected_tileset_tile_selection(),
+                        app.settings.highlight_style,
                         Overlay::None,
                     ));
 
@@ -76,7 +78,7 @@ fn tileset_tiles_ui(ui: &mut Ui, app: &mut App) {
 }
 
 pub fn tileset_message(ui: &mut Ui, text: impl Into<WidgetText>, size: f32) {
-    DEFAULT_THEME.base_200_frame(0).show(ui, |ui| {
+    Theme::current(ui.ctx()).base_200_frame(0).show(ui, |ui| {
         ui.add_sized(vec2(size, size), tileset_label(text));
     });
 }
@@ -94,9 +96,31 @@ pub fn tileset_label(text: impl Into<WidgetText>) -> impl egui::Widget {
     }
 }
 
+/// Show the transform of the stamp if it has one, so it's clear that tiles will be
+/// drawn rotated or mirrored
+fn stamp_transform_ui(ui: &mut Ui, app: &mut App) {
+    let transform = app.edit.stamp.transform;
+    if transform != Transform::None {
+        ui.add_space(4.0);
+        ui.horizontal(|ui| {
+            ui.label(format!("󰑧 Brush is {}", transform.description()))
+                .on_hover_text("Tiles will be drawn with this transform");
+            if ui
+                .add_enabled(app.edit.mode.draws_stamp(), egui::Button::new("Clear"))
+                .on_hover_text("Clear the transform (U)")
+                .clicked()
+            {
+                app.clear_transform();
+            }
+        });
+    }
+}
+
 pub fn tileset_ui(ui: &mut Ui, app: &mut App) {
     tileset_tiles_ui(ui, app);
 
+    stamp_transform_ui(ui, app);
+
     ui.add_space(8.0);
 
     ui.horizontal(|ui| {
@@ -117,6 +141,20 @@ pub fn tileset_ui(ui: &mut Ui, app: &mut App) {
                 app.show_edit_tileset_modal();
             }
 
+            if enabled_square_button(ui, app.state.resources.tilesets().len() > 1, "󰘭")
+                .on_hover_text("Merge into another tileset...")
+                .clicked()
+            {
+                app.show_merge_selected_tileset_modal();
+            }
+
+            if square_button(ui, "󰃐")
+                .on_hover_text("Split into tilesets using parts of the image...")
+                .clicked()
+            {
+                app.show_split_selected_tileset_modal();
+            }
+
             let selected_text = app
                 .selected_tileset()
                 .map(|t| format!("{}", t))
@@ -1,14 +1,25 @@
-use crate::data::tilesets::{Tileset, TilesetMode};
+use crate::{
+    data::{
+        modal::MissingTilesetImage,
+        tilesets::{Tileset, TilesetMode, Tilesets},
+    },
+    geom::u32size2::{U32Size2, u32size2},
+};
 use camino::Utf8PathBuf;
 use egui::{
     Context, ImageSource, SizeHint, TextureOptions,
     emath::OrderedFloat,
-    load::{LoadError, TexturePoll},
+    load::{Bytes, LoadError, TexturePoll},
 };
-use std::fmt::Display;
+use image::{GenericImageView, ImageReader, RgbaImage};
+use std::{borrow::Cow, fmt::Display};
+
+const MOUNTAIN_TILES_PNG: &[u8] = include_bytes!("../../assets/mountain-tiles.png");
 
-const MOUNTAIN_TILES: ImageSource<'static> =
-    egui::include_image!("../../assets/mountain-tiles.png");
+const MOUNTAIN_TILES: ImageSource<'static> = ImageSource::Bytes {
+    uri: Cow::Borrowed("bytes://../../assets/mountain-tiles.png"),
+    bytes: Bytes::Static(MOUNTAIN_TILES_PNG),
+};
 
 const MISSING_PALETTE: ImageSource<'static> =
     egui::include_image!("../../assets/missing_palette.png");
@@ -23,7 +34,7 @@ pub enum ErrorTexture {
     MissingTileset,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum TextureSource {
     Builtin,
     File { base_dir: Option<Utf8PathBuf> },
@@ -55,25 +66,26 @@ pub struct TilesetError {
     load_error: LoadError,
 }
 
-impl TilesetError {
-    pub fn path_notes(&self) -> String {
-        match &self.source {
+impl TextureSource {
+    /// Notes on how a tileset image path is resolved, to help the user fix a missing image
+    fn path_notes(&self, tileset_path: &Utf8PathBuf) -> String {
+        match self {
             TextureSource::Builtin => "".to_string(),
             TextureSource::File { base_dir } => {
-                if self.tileset_path.is_absolute() {
+                if tileset_path.is_absolute() {
                     format!(
                         "Image has absolute path '{}', is this correct for your system? You might want to use a relative path.",
-                        self.tileset_path
+                        tileset_path
                     )
                 } else {
                     match base_dir {
                         None => format!(
                             "Image has relative path '{}', but project has not been saved, try saving.",
-                            self.tileset_path
+                            tileset_path
                         ),
                         Some(base_dir) => format!(
                             "Image is at relative path '{}', project saved at '{}'.",
-                            self.tileset_path, base_dir
+                            tileset_path, base_dir
                         ),
                     }
                 }
@@ -82,6 +94,12 @@ impl TilesetError {
     }
 }
 
+impl TilesetError {
+    pub fn path_notes(&self) -> String {
+        self.source.path_notes(&self.tileset_path)
+    }
+}
+
 impl Display for TilesetError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match &self.source {
@@ -93,7 +111,7 @@ impl Display for TilesetError {
             TextureSource::File { base_dir: _ } => match &self.path_status {
                 PathStatus::ExistsAsFile => write!(
                     f,
-                    "Image file at '{}' failed to load ({}). If you have just created the file, try reloading the software (this is a known issue, we aim to improve this in future).",
+                    "Image file at '{}' failed to load ({}). If you have just created or edited the file, try \"View -> Reload tileset images\".",
                     self.resolved_path, self.load_error
                 ),
                 PathStatus::ExistsNotFile => write!(
@@ -156,6 +174,30 @@ impl TilesetTextures {
         format!("tileset://{}//file://{}", mode_json, path)
     }
 
+    /// Where tileset images are loaded from
+    pub fn source(&self) -> &TextureSource {
+        &self.source
+    }
+
+    /// The directory relative tileset paths are resolved from, if any
+    pub fn base_dir(&self) -> Option<&Utf8PathBuf> {
+        match &self.source {
+            TextureSource::Builtin => None,
+            TextureSource::File { base_dir } => base_dir.as_ref(),
+        }
+    }
+
+    /// Read the size of the image for a tileset, in tiles, without decoding the image
+    pub fn tileset_image_size_in_tiles(&self, tileset: &Tileset) -> eyre::Result<U32Size2> {
+        let (w, h) = match self.path_for_tileset(tileset) {
+            Some(path) => ImageReader::open(path)?
+                .with_guessed_format()?
+                .into_dimensions()?,
+            None => image::load_from_memory(MOUNTAIN_TILES_PNG)?.dimensions(),
+        };
+        Ok(u32size2(w, h) / tileset.tile_size)
+    }
+
     pub fn path_for_tileset(&self, tileset: &Tileset) -> Option<Utf8PathBuf> {
         match &self.source {
             TextureSource::Builtin => None,
@@ -165,6 +207,25 @@ impl TilesetTextures {
         }
     }
 
+    /// Decode the image for a tileset directly to a CPU image buffer, with the
+    /// tileset's mode applied (e.g. making the background transparent).
+    /// This doesn't use an egui [`Context`] or GPU textures, so can be used for
+    /// rendering without a window, e.g. for exports.
+    pub fn decode_tileset_image(&self, tileset: &Tileset) -> eyre::Result<RgbaImage> {
+        let dynamic_image = match &self.source {
+            TextureSource::Builtin => image::load_from_memory(MOUNTAIN_TILES_PNG)?,
+            TextureSource::File { base_dir } => {
+                let path = self.path_for_tileset_from_base_dir(base_dir, tileset);
+                ImageReader::open(path)?.decode()?
+            }
+        };
+        let mut image = dynamic_image.into_rgba8();
+        for pixel in image.pixels_mut() {
+            tileset.mode.transform_color_slice(&mut pixel.0);
+        }
+        Ok(image)
+    }
+
     pub fn texture_for_tileset(
         &self,
         ctx: &Context,
@@ -235,6 +296,47 @@ impl TilesetTextures {
         image_source.load(ctx, TextureOptions::NEAREST, Self::SIZE_HINT)
     }
 
+    /// Check the image file for a tileset exists, without loading it.
+    /// Returns [`None`] if the image is present (or built in), otherwise details
+    /// of the problem for display to the user.
+    pub fn missing_tileset_image(&self, tileset: &Tileset) -> Option<MissingTilesetImage> {
+        let path = self.path_for_tileset(tileset)?;
+        let problem = match Self::path_status(&path) {
+            PathStatus::ExistsAsFile => return None,
+            PathStatus::ExistsNotFile => "Image is not a file (may be a directory?)".to_string(),
+            PathStatus::DoesNotExist => "Image not found".to_string(),
+            PathStatus::Errored(e) => format!("Error checking file status ({})", e),
+        };
+        Some(MissingTilesetImage {
+            tileset_id: tileset.id(),
+            tileset_name: tileset.name.clone(),
+            resolved_path: path,
+            problem,
+            path_notes: self.source.path_notes(&tileset.path),
+        })
+    }
+
+    /// Check the image file for each tileset, see [`TilesetTextures::missing_tileset_image`]
+    pub fn missing_tileset_images(&self, tilesets: &Tilesets) -> Vec<MissingTilesetImage> {
+        tilesets
+            .iter()
+            .filter_map(|tileset| self.missing_tileset_image(tileset))
+            .collect()
+    }
+
+    /// Forget the cached image of every tileset, so each image is loaded again from
+    /// its file when next used, along with the error textures.
+    pub fn refresh_all_tilesets(&self, ctx: &Context, tilesets: &Tilesets) {
+        for tileset in tilesets.iter() {
+            self.refresh_tileset(ctx, tileset);
+        }
+        for error_image in [MISSING_PALETTE, MISSING_IMAGE, MISSING_TILESET] {
+            if let Some(uri) = error_image.uri() {
+                ctx.forget_image(uri);
+            }
+        }
+    }
+
     pub fn refresh_tileset(&self, ctx: &Context, tileset: &Tileset) {
         match &self.source {
             TextureSource::Builtin => {}
@@ -246,3 +348,51 @@ impl TilesetTextures {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use eyre::eyre;
+
+    use super::*;
+
+    fn tileset(name: &str, path: &str) -> Tileset {
+        let mut tileset = Tileset::default();
+        tileset.name = name.to_string();
+        tileset.path = path.into();
+        tileset
+    }
+
+    #[test]
+    fn missing_tileset_images_are_found() -> eyre::Result<()> {
+        let temp = assert_fs::TempDir::new()?;
+        let dir = Utf8PathBuf::from_path_buf(temp.path().to_path_buf())
+            .map_err(|_| eyre!("Can't convert temp dir to utf8"))?;
+        std::fs::write(dir.join("present.png"), [])?;
+
+        let mut textures = TilesetTextures::default();
+        textures.update_base_dir_from_file_path(Some(dir.join("project.mtp")));
+
+        let present = tileset("present", "present.png");
+        assert!(textures.missing_tileset_image(&present).is_none());
+
+        let absent = tileset("absent", "absent.png");
+        let missing = textures
+            .missing_tileset_image(&absent)
+            .ok_or_else(|| eyre!("Expected missing image"))?;
+        assert_eq!(missing.tileset_name, "absent");
+        assert_eq!(missing.resolved_path, dir.join("absent.png"));
+        assert!(missing.path_notes.contains("relative path 'absent.png'"));
+
+        // Directories aren't images
+        let directory = tileset("directory", ".");
+        assert!(textures.missing_tileset_image(&directory).is_some());
+
+        // Builtin tilesets are never missing
+        assert!(
+            TilesetTextures::builtin_tileset_textures()
+                .missing_tileset_image(&absent)
+                .is_none()
+        );
+        Ok(())
+    }
+}
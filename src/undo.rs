@@ -7,11 +7,24 @@ pub struct Settings {
     ///
     /// Default: `200`
     pub max_undos: usize,
+
+    /// Changes fed within this many seconds of the previous revision are merged into
+    /// that revision, rather than creating a new one, so that a rapid series of changes
+    /// during an edit can be undone in one step. A new revision is created once changes
+    /// have been idle for at least this long, or once the edit is complete, see
+    /// [`Undoable::is_complete`]. Use `0.0` to create a revision for every change.
+    ///
+    /// Default: `0.0`
+    #[serde(default)]
+    pub coalesce_seconds: f64,
 }
 
 impl Default for Settings {
     fn default() -> Self {
-        Self { max_undos: 200 }
+        Self {
+            max_undos: 200,
+            coalesce_seconds: 0.0,
+        }
     }
 }
 
@@ -19,6 +32,13 @@ impl Default for Settings {
 pub trait Undoable: Clone {
     /// Check whether this data has changed from a previous state.
     fn has_changed_from(&self, previous: &Self) -> bool;
+
+    /// True if this data is at the end of an edit, e.g. after a drag has stopped, rather
+    /// than part way through. Later changes are never merged into a revision of complete
+    /// data, see [`Settings::coalesce_seconds`].
+    fn is_complete(&self) -> bool {
+        true
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -89,6 +109,11 @@ pub struct Undo<D: Undoable, V: Clone> {
     /// to be unique for revisions within this [`Undo`], no other properties
     /// are needed/guaranteed.
     next_revision_index: RevisionIndex,
+
+    /// The time the most recent revision was created by [`Undo::feed_state`], if
+    /// it can still have changes coalesced into it. This is cleared by undo/redo,
+    /// since the most recent revision is then an earlier state.
+    last_revision_time: Option<f64>,
 }
 
 impl<D: Undoable, V: Clone> std::fmt::Debug for Undo<D, V> {
@@ -109,6 +134,7 @@ impl<D: Undoable, V: Clone> Default for Undo<D, V> {
             undos: VecDeque::new(),
             redos: Vec::new(),
             next_revision_index: RevisionIndex::FIRST,
+            last_revision_time: None,
         }
     }
 }
@@ -202,6 +228,12 @@ impl<D: Undoable, V: Clone> Undo<D, V> {
         }
     }
 
+    /// Replace the [`Settings`], e.g. after the user changes them. This applies to
+    /// revisions created from now on.
+    pub fn set_settings(&mut self, settings: Settings) {
+        self.settings = settings;
+    }
+
     /// Do we have an undo point different from the given state?
     pub fn has_undo(&self, current_data: &D) -> bool {
         match self.undos.len() {
@@ -215,6 +247,24 @@ impl<D: Undoable, V: Clone> Undo<D, V> {
         !self.redos.is_empty() && !self.has_changed_from_last_undo(current_data)
     }
 
+    /// The number of times [`Undo::undo`] can be called in succession, from the given state
+    pub fn available_undos(&self, current_data: &D) -> usize {
+        if self.has_changed_from_last_undo(current_data) {
+            self.undos.len()
+        } else {
+            self.undos.len().saturating_sub(1)
+        }
+    }
+
+    /// The number of times [`Undo::redo`] can be called in succession, from the given state
+    pub fn available_redos(&self, current_data: &D) -> usize {
+        if self.has_redo(current_data) {
+            self.redos.len()
+        } else {
+            0
+        }
+    }
+
     fn new_revision(&mut self, current_data: &D, current_view: &V) -> Revision<D, V> {
         let index = self.next_revision_index;
         self.next_revision_index = self.next_revision_index.next();
@@ -227,6 +277,7 @@ impl<D: Undoable, V: Clone> Undo<D, V> {
 
     pub fn undo(&mut self, current_data: &D, current_view: &V) -> Option<(D, V)> {
         if self.has_undo(current_data) {
+            self.last_revision_time = None;
             // Always use the view from the most recent revision, even though we
             // are usually undoing to the previous revision of the data - this is so
             // that we display the context in which the most recent revision was created,
@@ -256,6 +307,7 @@ impl<D: Undoable, V: Clone> Undo<D, V> {
             self.redos.clear();
             None
         } else if let Some(revision) = self.redos.pop() {
+            self.last_revision_time = None;
             self.undos.push_back(revision);
             self.undos.back().map(|r| (r.data.clone(), r.view.clone()))
         } else {
@@ -274,11 +326,34 @@ impl<D: Undoable, V: Clone> Undo<D, V> {
         }
     }
 
+    /// Apply `update` to the data of every undo and redo revision, for changes that
+    /// must apply to all revisions rather than being undoable themselves, e.g. to keep
+    /// data valid after a change to the context it is interpreted in. The update must
+    /// not be considered a change by [`Undoable::has_changed_from`].
+    pub fn update_all_data(&mut self, mut update: impl FnMut(&mut D)) {
+        for revision in self.undos.iter_mut().chain(self.redos.iter_mut()) {
+            update(&mut revision.data);
+        }
+    }
+
+    /// True if a change at `current_time` should be merged into the most recent
+    /// revision, see [`Settings::coalesce_seconds`]. The first revision is never
+    /// merged into, so there is always an initial state to undo back to, and neither
+    /// is a revision at the end of an edit, so separate edits are undone separately.
+    fn should_coalesce(&self, current_time: f64) -> bool {
+        self.settings.coalesce_seconds > 0.0
+            && self.undos.len() > 1
+            && self.undos.back().is_some_and(|r| !r.data.is_complete())
+            && self
+                .last_revision_time
+                .is_some_and(|time| current_time - time < self.settings.coalesce_seconds)
+    }
+
     /// Call this as often as you want (e.g. every frame)
-    /// and [`Undoer`] will determine if a new undo point should be created.
+    /// and [`Undo`] will determine if a new undo point should be created.
     ///
     /// * `current_time`: current time in seconds.
-    pub fn feed_state(&mut self, current_data: &D, current_view: &V) {
+    pub fn feed_state(&mut self, current_time: f64, current_data: &D, current_view: &V) {
         match self.undos.back() {
             None => {
                 // First time feed_state is called.
@@ -289,9 +364,181 @@ impl<D: Undoable, V: Clone> Undo<D, V> {
                 // Otherwise create an undo only if state has changed
                 if current_data.has_changed_from(&latest_revision.data) {
                     self.redos.clear();
+                    // Replace the most recent revision if we're coalescing changes into it
+                    if self.should_coalesce(current_time) {
+                        self.undos.pop_back();
+                    }
                     self.add_undo(current_data, current_view);
+                    self.last_revision_time = Some(current_time);
                 }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Counter(u32);
+
+    impl Undoable for Counter {
+        fn has_changed_from(&self, previous: &Self) -> bool {
+            self.0 != previous.0
+        }
+    }
+
+    /// A value edited in strokes, which are complete when `complete` is true
+    #[derive(Clone, Debug, PartialEq)]
+    struct Stroke {
+        value: u32,
+        complete: bool,
+    }
+
+    impl Undoable for Stroke {
+        fn has_changed_from(&self, previous: &Self) -> bool {
+            self != previous
+        }
+
+        fn is_complete(&self) -> bool {
+            self.complete
+        }
+    }
+
+    fn stroke(value: u32, complete: bool) -> Stroke {
+        Stroke { value, complete }
+    }
+
+    fn undo_count(undo: &mut Undo<Counter, ()>, mut current: Counter) -> usize {
+        let mut count = 0;
+        while let Some((data, _)) = undo.undo(&current, &()) {
+            current = data;
+            count += 1;
+        }
+        count
+    }
+
+    #[test]
+    fn rapid_changes_coalesce() {
+        let mut undo: Undo<Stroke, ()> = Undo::with_settings(Settings {
+            coalesce_seconds: 0.5,
+            ..Default::default()
+        });
+        undo.feed_state(0.0, &stroke(0, true), &());
+
+        // A stroke after a pause, with many rapid changes, then completed
+        for i in 1..=20 {
+            undo.feed_state(1.0 + i as f64 * 0.1, &stroke(i, false), &());
+        }
+        undo.feed_state(3.05, &stroke(20, true), &());
+
+        // A change after the changes have been idle
+        undo.feed_state(10.0, &stroke(21, false), &());
+
+        let values = |undo: &Undo<Stroke, ()>| {
+            undo.undos
+                .iter()
+                .map(|r| (r.data.value, r.data.complete))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(values(&undo), vec![(0, true), (20, true), (21, false)]);
+
+        // A pause part way through a stroke also starts a new revision
+        undo.feed_state(12.0, &stroke(22, false), &());
+        assert_eq!(undo.undos.len(), 4);
+    }
+
+    #[test]
+    fn complete_edits_do_not_coalesce() {
+        let mut undo: Undo<Stroke, ()> = Undo::with_settings(Settings {
+            coalesce_seconds: 0.5,
+            ..Default::default()
+        });
+        undo.feed_state(0.0, &stroke(0, true), &());
+
+        // Separate edits in quick succession are still undone separately
+        for i in 1..=5 {
+            undo.feed_state(1.0 + i as f64 * 0.01, &stroke(i, true), &());
+        }
+        assert_eq!(undo.undos.len(), 6);
+
+        // As is an edit that completes without changing after being started
+        undo.feed_state(1.1, &stroke(6, false), &());
+        undo.feed_state(1.2, &stroke(6, true), &());
+        undo.feed_state(1.3, &stroke(7, true), &());
+        let values: Vec<_> = undo.undos.iter().map(|r| r.data.value).collect();
+        assert_eq!(values, vec![0, 1, 2, 3, 4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn no_coalescing_by_default() {
+        let mut undo: Undo<Counter, ()> = Undo::default();
+        undo.feed_state(0.0, &Counter(0), &());
+        for i in 1..=5 {
+            undo.feed_state(0.0, &Counter(i), &());
+        }
+        assert_eq!(undo_count(&mut undo, Counter(5)), 5);
+    }
+
+    #[test]
+    fn change_after_undo_does_not_coalesce() {
+        let mut undo: Undo<Stroke, ()> = Undo::with_settings(Settings {
+            coalesce_seconds: 0.5,
+            ..Default::default()
+        });
+        undo.feed_state(0.0, &stroke(0, false), &());
+        undo.feed_state(1.0, &stroke(1, false), &());
+        let undone = undo.undo(&stroke(1, false), &()).map(|(data, _)| data);
+        assert_eq!(undone, Some(stroke(0, false)));
+
+        // Changing straight after the undo must not replace the state we undid to
+        undo.feed_state(1.1, &stroke(2, false), &());
+        assert_eq!(
+            undo.undos.iter().map(|r| r.data.value).collect::<Vec<_>>(),
+            vec![0, 2]
+        );
+    }
+
+    #[test]
+    fn available_undos_and_redos() {
+        let mut undo: Undo<Counter, ()> = Undo::default();
+        undo.feed_state(0.0, &Counter(0), &());
+        undo.feed_state(0.0, &Counter(1), &());
+        undo.feed_state(0.0, &Counter(2), &());
+        assert_eq!(undo.available_undos(&Counter(2)), 2);
+        assert_eq!(undo.available_redos(&Counter(2)), 0);
+
+        // An unrecorded change can also be undone
+        assert_eq!(undo.available_undos(&Counter(3)), 3);
+
+        let undone = undo.undo(&Counter(2), &()).map(|(data, _)| data);
+        assert_eq!(undone, Some(Counter(1)));
+        assert_eq!(undo.available_undos(&Counter(1)), 1);
+        assert_eq!(undo.available_redos(&Counter(1)), 1);
+
+        // Changing after an undo means there is nothing to redo
+        assert_eq!(undo.available_redos(&Counter(5)), 0);
+
+        // Counts match the number of undos actually possible
+        assert_eq!(undo_count(&mut undo, Counter(1)), 1);
+    }
+
+    #[test]
+    fn update_all_data_updates_undos_and_redos() {
+        let mut undo: Undo<Counter, ()> = Undo::default();
+        undo.feed_state(0.0, &Counter(0), &());
+        undo.feed_state(0.0, &Counter(1), &());
+        undo.feed_state(0.0, &Counter(2), &());
+        let undone = undo.undo(&Counter(2), &()).map(|(data, _)| data);
+        assert_eq!(undone, Some(Counter(1)));
+
+        undo.update_all_data(|counter| counter.0 += 10);
+        let undone = undo.undo(&Counter(11), &()).map(|(data, _)| data);
+        assert_eq!(undone, Some(Counter(10)));
+        let redone = undo.redo(&Counter(10)).map(|(data, _)| data);
+        assert_eq!(redone, Some(Counter(11)));
+        let redone = undo.redo(&Counter(11)).map(|(data, _)| data);
+        assert_eq!(redone, Some(Counter(12)));
+    }
+}
@@ -1,4 +1,4 @@
-use camino::{Utf8Path, Utf8PathBuf};
+use camino::{Utf8Component, Utf8Path, Utf8PathBuf};
 use eyre::eyre;
 
 pub fn tmx_parent_dir(tmx_path: &Utf8PathBuf) -> eyre::Result<&Utf8Path> {
@@ -34,3 +34,47 @@ pub fn path_with_suffix_and_extension(
     path.set_extension(extension);
     path
 }
+
+/// Normalize a path lexically, without accessing the filesystem, by removing `.`
+/// components and resolving `..` components against the preceding component where
+/// possible. Leading `..` components of relative paths are kept, and `..` at the
+/// root of an absolute path is dropped.
+pub fn normalize_path(path: &Utf8Path) -> Utf8PathBuf {
+    let mut normalized = Utf8PathBuf::new();
+    for component in path.components() {
+        match component {
+            Utf8Component::CurDir => {}
+            Utf8Component::ParentDir => match normalized.components().next_back() {
+                Some(Utf8Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                Some(Utf8Component::RootDir) | Some(Utf8Component::Prefix(_)) => {}
+                _ => normalized.push(".."),
+            },
+            _ => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// True if two paths refer to the same file, as far as we can tell without accessing
+/// the filesystem. Relative paths are resolved against `base_dir` if known, and paths
+/// are compared after [`normalize_path`]. If one path is absolute and the other is
+/// relative with no `base_dir`, we can't tell, so they are not equivalent.
+pub fn paths_equivalent(a: &Utf8Path, b: &Utf8Path, base_dir: Option<&Utf8Path>) -> bool {
+    let resolve = |path: &Utf8Path| match base_dir {
+        Some(base_dir) if path.is_relative() => normalize_path(&base_dir.join(path)),
+        _ => normalize_path(path),
+    };
+    resolve(a) == resolve(b)
+}
+
+/// Read a text file, skipping any UTF-8 byte order mark and whitespace at the start,
+/// since some tools write these before the content, and parsers may reject them
+/// (e.g. before `<?xml` in Tiled files).
+pub fn read_text_file(path: &Utf8Path) -> eyre::Result<String> {
+    let text = std::fs::read_to_string(path)?;
+    Ok(text
+        .trim_start_matches(['\u{feff}', ' ', '\t', '\r', '\n'])
+        .to_string())
+}
@@ -0,0 +1,140 @@
+use camino::Utf8PathBuf;
+use egui::ahash::{HashMap, HashMapExt};
+
+use std::{
+    fs,
+    sync::{Arc, Mutex, mpsc},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+/// How often watched files are checked for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The state of a file used to detect changes, the modification time and length,
+/// or [`None`] if the file can't be read (e.g. it doesn't exist yet)
+type FileStamp = Option<(SystemTime, u64)>;
+
+fn file_stamp(path: &Utf8PathBuf) -> FileStamp {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Tracks the state of a set of files, to find which have changed since last checked
+#[derive(Debug, Default)]
+pub struct FileStamps {
+    stamps: HashMap<Utf8PathBuf, FileStamp>,
+}
+
+impl FileStamps {
+    /// Check each of `paths`, returning those that have changed (including being
+    /// created or deleted) since the last check. Paths that weren't included in the
+    /// last check are recorded, but not returned, and paths not in `paths` are forgotten.
+    pub fn changed(&mut self, paths: &[Utf8PathBuf]) -> Vec<Utf8PathBuf> {
+        let mut stamps = HashMap::with_capacity(paths.len());
+        let mut changed = vec![];
+        for path in paths {
+            let stamp = file_stamp(path);
+            if let Some(previous) = self.stamps.get(path)
+                && *previous != stamp
+            {
+                changed.push(path.clone());
+            }
+            stamps.insert(path.clone(), stamp);
+        }
+        self.stamps = stamps;
+        changed
+    }
+}
+
+/// Watches a set of files on a background thread, by polling for changes, and
+/// delivers the path of each file when it changes
+pub struct FileWatcher {
+    _thread: thread::JoinHandle<()>,
+    paths: Arc<Mutex<Vec<Utf8PathBuf>>>,
+    receiver: mpsc::Receiver<Utf8PathBuf>,
+}
+
+impl FileWatcher {
+    /// Replace the watched files with `paths`
+    pub fn watch(&self, paths: Vec<Utf8PathBuf>) {
+        if let Ok(mut watched) = self.paths.lock() {
+            *watched = paths;
+        }
+    }
+
+    // Attempt to receive a changed path without blocking, returning None if none is available
+    pub fn poll_recv(&mut self) -> Option<Utf8PathBuf> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+/// Start watching files, initially none, see [`FileWatcher::watch`].
+/// The UI is repainted when a file changes, so the change can be handled promptly.
+/// The thread stops once the watcher is dropped.
+pub fn create_file_watcher(ctx: egui::Context) -> FileWatcher {
+    let (sender, receiver) = mpsc::channel();
+    let paths = Arc::new(Mutex::new(vec![]));
+    let thread_paths = Arc::clone(&paths);
+
+    let thread = thread::spawn(move || {
+        let mut stamps = FileStamps::default();
+        // The watcher holds the only other reference to the paths
+        while Arc::strong_count(&thread_paths) > 1 {
+            let paths = match thread_paths.lock() {
+                Ok(paths) => paths.clone(),
+                Err(_) => return,
+            };
+            let changed = stamps.changed(&paths);
+            if !changed.is_empty() {
+                for path in changed {
+                    log::trace!("Watched file changed: {}", path);
+                    // An error just indicates the watcher has been dropped
+                    if sender.send(path).is_err() {
+                        return;
+                    }
+                }
+                ctx.request_repaint();
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    });
+
+    FileWatcher {
+        _thread: thread,
+        paths,
+        receiver,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_stamps_find_changed_files() -> eyre::Result<()> {
+        let temp = assert_fs::TempDir::new()?;
+        let path = |name: &str| Utf8PathBuf::try_from(temp.path().join(name));
+        let existing = path("existing.png")?;
+        let created = path("created.png")?;
+        fs::write(&existing, [0])?;
+
+        // The first check only records the files
+        let mut stamps = FileStamps::default();
+        let paths = vec![existing.clone(), created.clone()];
+        assert!(stamps.changed(&paths).is_empty());
+        assert!(stamps.changed(&paths).is_empty());
+
+        // Changes, including creating a file, are found once
+        fs::write(&existing, [0, 1])?;
+        fs::write(&created, [0])?;
+        assert_eq!(stamps.changed(&paths), paths);
+        assert!(stamps.changed(&paths).is_empty());
+
+        // Files that are no longer watched are forgotten
+        assert!(stamps.changed(std::slice::from_ref(&created)).is_empty());
+        fs::write(&existing, [0, 1, 2])?;
+        assert!(stamps.changed(&paths).is_empty());
+        Ok(())
+    }
+}
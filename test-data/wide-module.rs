@@ -0,0 +1,86 @@
+pub mod wide_tile {
+    /// A size, in tiles
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Size {
+        pub width: u32,
+        pub height: u32,
+    }
+
+    impl Size {
+        pub const fn new(width: u32, height: u32) -> Self {
+            Self { width, height }
+        }
+    }
+
+    /// A tile, with the Tiled gid (flat tile index, with flip bits in bits 29 to 31)
+    /// in the low 32 bits, and the palette index in the high 32 bits
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct WideTile(pub u64);
+
+    impl WideTile {
+        /// The Tiled gid, including flip bits
+        pub const fn gid(&self) -> u32 {
+            self.0 as u32
+        }
+
+        /// The flat tile index, where 0 is the empty tile
+        pub const fn flat_tile_index(&self) -> u32 {
+            self.gid() & 0x1FFF_FFFF
+        }
+
+        /// The Tiled flip bits, moved to the low 3 bits
+        pub const fn flip_bits(&self) -> u32 {
+            self.gid() >> 29
+        }
+
+        pub const fn palette_index(&self) -> u32 {
+            (self.0 >> 32) as u32
+        }
+
+        pub const fn is_empty(&self) -> bool {
+            self.flat_tile_index() == 0
+        }
+    }
+
+    pub struct WideLayerData<const N: usize> {
+        pub name: &'static str,
+        pub visible: bool,
+        pub size: Size,
+        pub tiles: [WideTile; N],
+        pub opacity: Option<f32>,
+    }
+
+    pub struct WideMapData<const L: usize, const N: usize> {
+        pub name: &'static str,
+        pub size: Size,
+        pub layers: [&'static WideLayerData<N>; L],
+    }
+}
+
+pub mod large_palette {
+    pub mod layers {
+
+        use super::super::wide_tile::{Size, WideLayerData, WideTile};
+
+        pub const NEW_LAYER: WideLayerData<2> = WideLayerData {
+            name: "New Layer",
+            visible: true,
+            size: Size::new(2, 1),
+            tiles: [
+                WideTile(0),
+                WideTile(1286342705156),
+            ],
+            opacity: None,
+        };
+    }
+
+    use super::wide_tile::{Size, WideMapData};
+
+    pub const MAP: WideMapData<1, 2> = WideMapData {
+        name: "Large Palette",
+        size: Size::new(2, 1),
+        layers: [
+            &layers::NEW_LAYER
+        ]
+    };
+}
@@ -0,0 +1,28 @@
+//! Compiles a module exported with the "Wide" tile format, which must only use the
+//! definitions it contains. The module is checked against the exporter's output by
+//! `wide_tile_format_exports_large_palette_indices` in `src/app/export_workspace.rs`.
+
+#[allow(dead_code)]
+mod maps {
+    include!("../test-data/wide-module.rs");
+}
+
+use maps::{large_palette, wide_tile::Size};
+
+#[test]
+fn wide_module_tiles() {
+    let map = &large_palette::MAP;
+    assert_eq!(map.name, "Large Palette");
+    assert_eq!(map.size, Size::new(2, 1));
+
+    let layer = map.layers.first().copied();
+    let tiles: Vec<_> = layer.iter().flat_map(|layer| layer.tiles.iter()).collect();
+    assert_eq!(tiles.len(), 2);
+
+    // An empty tile, then tile 3 of the first tileset mirrored in x, with palette index 299
+    assert!(tiles.first().is_some_and(|tile| tile.is_empty()));
+    let tile = tiles.get(1).copied();
+    assert_eq!(tile.map(|tile| tile.flat_tile_index()), Some(4));
+    assert_eq!(tile.map(|tile| tile.flip_bits()), Some(0b100));
+    assert_eq!(tile.map(|tile| tile.palette_index()), Some(299));
+}